@@ -0,0 +1,25 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "im_ai_conversation")]
+#[serde(rename_all = "camelCase")]
+pub struct Model {
+    /// 服务端会话ID
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    #[serde(skip)]
+    pub login_uid: String,
+    pub title: String,
+    pub role_id: Option<String>,
+    pub model_id: Option<String>,
+    pub pinned: bool,
+    pub create_time: i64,
+    /// 最后一次对话或同步的时间（毫秒）
+    pub update_time: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,31 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "im_ai_message")]
+#[serde(rename_all = "camelCase")]
+pub struct Model {
+    /// 服务端消息ID，本地流式写入的消息使用请求ID
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    #[serde(skip)]
+    pub login_uid: String,
+    pub conversation_id: String,
+    /// 消息类型: user, assistant
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    pub content: String,
+    pub reasoning_content: Option<String>,
+    pub model: Option<String>,
+    /// 消息状态: streaming, done, cancelled, error, interrupted
+    pub status: String,
+    /// 是否由本地流式请求写入，同步到服务端记录后会被替换
+    pub local: bool,
+    pub create_time: i64,
+    pub update_time: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
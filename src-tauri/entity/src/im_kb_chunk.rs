@@ -0,0 +1,26 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "im_kb_chunk")]
+#[serde(rename_all = "camelCase")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub source_id: String,
+    #[serde(skip)]
+    pub login_uid: String,
+    /// 片段在文档中的序号
+    pub seq: i32,
+    /// 所在章节标题，多级标题以 " > " 连接
+    pub heading: Option<String>,
+    pub content: String,
+    /// 片段在文档中的起止行号，从 1 开始
+    pub start_line: i32,
+    pub end_line: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
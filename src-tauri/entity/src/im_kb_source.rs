@@ -0,0 +1,30 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "im_kb_source")]
+#[serde(rename_all = "camelCase")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    #[serde(skip)]
+    pub login_uid: String,
+    /// 本地文件的绝对路径
+    pub path: String,
+    /// 文件名
+    pub name: String,
+    /// 文档类型: markdown, text, code
+    pub kind: String,
+    pub size: i64,
+    /// 建立索引时文件的修改时间（毫秒）
+    pub modified_time: Option<i64>,
+    pub chunk_count: i32,
+    pub create_time: i64,
+    /// 最后一次建立索引的时间（毫秒）
+    pub update_time: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -23,6 +23,15 @@ pub struct Model {
     /// 消息发送状态: pending, success, fail
     pub send_status: String,
     pub time_block: Option<i64>,
+    /// 自动重发的次数
+    #[serde(skip)]
+    pub send_attempts: i32,
+    /// 下一次允许自动重发的时间戳（毫秒）
+    #[serde(skip)]
+    pub next_retry_time: Option<i64>,
+    /// 最近一次开始发送的时间戳（毫秒）
+    #[serde(skip)]
+    pub last_attempt_time: Option<i64>,
 }
 
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,46 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "im_upload_task")]
+#[serde(rename_all = "camelCase")]
+pub struct Model {
+    /// 上传任务ID
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    #[serde(skip)]
+    pub login_uid: String,
+    pub file_path: String,
+    pub file_size: i64,
+    /// 对象存储类型: qiniu, s3
+    pub provider: String,
+    /// 文件指纹，恢复上传前用于确认文件未被修改
+    pub fingerprint: String,
+    /// 上传地址
+    pub domain: String,
+    /// 七牛上传凭证，S3 使用本地配置签名时为空
+    #[serde(skip)]
+    pub token: String,
+    /// S3 分片上传的 UploadId
+    pub session_id: Option<String>,
+    /// 最终保存的对象 key
+    pub object_key: String,
+    pub block_size: i64,
+    /// 已上传的字节数
+    pub uploaded: i64,
+    /// 已上传分片的凭证列表（JSON 数组），七牛为 ctx，S3 为 ETag
+    #[serde(skip)]
+    pub contexts: String,
+    /// 已上传分片中最早失效的凭证过期时间（秒）
+    pub ctx_expire_at: Option<i64>,
+    /// 任务状态: uploading, paused, failed
+    pub status: String,
+    pub last_error: Option<String>,
+    pub create_time: i64,
+    pub update_time: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,26 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "im_ws_outbox")]
+#[serde(rename_all = "camelCase")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    /// 客户端生成的消息ID，用于去重
+    pub client_id: String,
+    #[serde(skip)]
+    pub login_uid: String,
+    /// 待发送的 JSON 消息体
+    pub payload: String,
+    pub retry_count: i32,
+    pub last_error: Option<String>,
+    pub create_time: i64,
+    /// 过期时间戳（毫秒），过期后不再投递
+    pub expire_time: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,7 +1,13 @@
+pub mod im_ai_conversation;
+pub mod im_ai_message;
 pub mod im_config;
 pub mod im_contact;
+pub mod im_kb_chunk;
+pub mod im_kb_source;
 pub mod im_message;
 pub mod im_room;
 pub mod im_room_member;
+pub mod im_upload_task;
 pub mod im_user;
+pub mod im_ws_outbox;
 pub mod prelude;
@@ -5,6 +5,13 @@ mod m20241220_000002_add_token_field;
 mod m20241220_000003_add_refresh_token_field;
 mod m20250917_000001_update_msg_table;
 mod m20250917_000002_add_thumbnail_path;
+mod m20261018_000001_create_message_fts;
+mod m20261018_000002_create_ws_outbox;
+mod m20261018_000003_add_message_send_retry;
+mod m20261018_000004_create_upload_task;
+mod m20261018_000006_add_message_local_path;
+mod m20261018_000007_create_ai_chat;
+mod m20261018_000008_create_knowledge_base;
 
 pub struct Migrator;
 
@@ -17,6 +24,13 @@ impl MigratorTrait for Migrator {
             Box::new(m20241220_000003_add_refresh_token_field::Migration),
             Box::new(m20250917_000001_update_msg_table::Migration),
             Box::new(m20250917_000002_add_thumbnail_path::Migration),
+            Box::new(m20261018_000001_create_message_fts::Migration),
+            Box::new(m20261018_000002_create_ws_outbox::Migration),
+            Box::new(m20261018_000003_add_message_send_retry::Migration),
+            Box::new(m20261018_000004_create_upload_task::Migration),
+            Box::new(m20261018_000006_add_message_local_path::Migration),
+            Box::new(m20261018_000007_create_ai_chat::Migration),
+            Box::new(m20261018_000008_create_knowledge_base::Migration),
         ]
     }
 }
@@ -0,0 +1,39 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // 创建消息全文索引表，rowid 与 im_message 的 rowid 保持一致
+        // trigram 分词器按三字符切分，可以直接匹配中日韩文本的子串
+        db.execute_unprepared(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS im_message_fts USING fts5(
+                content,
+                id UNINDEXED,
+                login_uid UNINDEXED,
+                room_id UNINDEXED,
+                tokenize = 'trigram'
+            )",
+        )
+        .await?;
+
+        // 已有消息的索引由应用在打开数据库后按 extract_search_text 的规则在后台回填
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP TABLE IF EXISTS im_message_fts")
+            .await?;
+        // 清除回填完成标记，重新建表后再次回填
+        db.execute_unprepared("DELETE FROM im_config WHERE config_key = 'searchIndexBackfilled'")
+            .await?;
+
+        Ok(())
+    }
+}
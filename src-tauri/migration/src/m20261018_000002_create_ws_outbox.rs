@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 创建 WebSocket 离线发件箱表，自增 id 决定投递顺序
+        manager
+            .create_table(
+                Table::create()
+                    .table(ImWsOutbox::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ImWsOutbox::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ImWsOutbox::ClientId).string().not_null())
+                    .col(ColumnDef::new(ImWsOutbox::LoginUid).string().not_null())
+                    .col(ColumnDef::new(ImWsOutbox::Payload).text().not_null())
+                    .col(
+                        ColumnDef::new(ImWsOutbox::RetryCount)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(ImWsOutbox::LastError).string())
+                    .col(
+                        ColumnDef::new(ImWsOutbox::CreateTime)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImWsOutbox::ExpireTime)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 同一用户下按 client_id 去重
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_im_ws_outbox_client_id")
+                    .table(ImWsOutbox::Table)
+                    .col(ImWsOutbox::ClientId)
+                    .col(ImWsOutbox::LoginUid)
+                    .unique()
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ImWsOutbox::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ImWsOutbox {
+    Table,
+    Id,
+    ClientId,
+    LoginUid,
+    Payload,
+    RetryCount,
+    LastError,
+    CreateTime,
+    ExpireTime,
+}
@@ -0,0 +1,85 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 记录消息自动重发的次数
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ImMessage::Table)
+                    .add_column(
+                        ColumnDef::new(ImMessage::SendAttempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 下一次允许重发的时间戳（毫秒）
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ImMessage::Table)
+                    .add_column(ColumnDef::new(ImMessage::NextRetryTime).big_integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        // 最近一次开始发送的时间戳（毫秒），用于识别中断的发送
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ImMessage::Table)
+                    .add_column(ColumnDef::new(ImMessage::LastAttemptTime).big_integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ImMessage::Table)
+                    .drop_column(ImMessage::LastAttemptTime)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ImMessage::Table)
+                    .drop_column(ImMessage::NextRetryTime)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ImMessage::Table)
+                    .drop_column(ImMessage::SendAttempts)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ImMessage {
+    Table,
+    SendAttempts,
+    NextRetryTime,
+    LastAttemptTime,
+}
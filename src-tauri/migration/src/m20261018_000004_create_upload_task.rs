@@ -0,0 +1,121 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 创建分片上传任务表，记录所用对象存储与已上传块的 ctx 以便断点续传
+        manager
+            .create_table(
+                Table::create()
+                    .table(ImUploadTask::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ImUploadTask::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ImUploadTask::LoginUid).string().not_null())
+                    .col(ColumnDef::new(ImUploadTask::FilePath).string().not_null())
+                    .col(
+                        ColumnDef::new(ImUploadTask::FileSize)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImUploadTask::Provider)
+                            .string()
+                            .not_null()
+                            .default("qiniu"),
+                    )
+                    .col(
+                        ColumnDef::new(ImUploadTask::Fingerprint)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ImUploadTask::Domain).string().not_null())
+                    .col(ColumnDef::new(ImUploadTask::Token).text().not_null())
+                    .col(ColumnDef::new(ImUploadTask::SessionId).string())
+                    .col(ColumnDef::new(ImUploadTask::ObjectKey).string().not_null())
+                    .col(
+                        ColumnDef::new(ImUploadTask::BlockSize)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImUploadTask::Uploaded)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(ImUploadTask::Contexts)
+                            .text()
+                            .not_null()
+                            .default("[]"),
+                    )
+                    .col(ColumnDef::new(ImUploadTask::CtxExpireAt).big_integer())
+                    .col(ColumnDef::new(ImUploadTask::Status).string().not_null())
+                    .col(ColumnDef::new(ImUploadTask::LastError).string())
+                    .col(
+                        ColumnDef::new(ImUploadTask::CreateTime)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImUploadTask::UpdateTime)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_im_upload_task_login_uid")
+                    .table(ImUploadTask::Table)
+                    .col(ImUploadTask::LoginUid)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ImUploadTask::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ImUploadTask {
+    Table,
+    Id,
+    LoginUid,
+    FilePath,
+    FileSize,
+    Provider,
+    Fingerprint,
+    Domain,
+    Token,
+    SessionId,
+    ObjectKey,
+    BlockSize,
+    Uploaded,
+    Contexts,
+    CtxExpireAt,
+    Status,
+    LastError,
+    CreateTime,
+    UpdateTime,
+}
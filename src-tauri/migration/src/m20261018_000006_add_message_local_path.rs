@@ -0,0 +1,40 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 消息中媒体文件下载到本地缓存后的路径
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ImMessage::Table)
+                    .add_column(ColumnDef::new(ImMessage::LocalPath).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ImMessage::Table)
+                    .drop_column(ImMessage::LocalPath)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ImMessage {
+    Table,
+    LocalPath,
+}
@@ -0,0 +1,168 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 创建 AI 会话表，缓存服务端会话列表以便离线浏览
+        manager
+            .create_table(
+                Table::create()
+                    .table(ImAiConversation::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ImAiConversation::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ImAiConversation::LoginUid)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImAiConversation::Title)
+                            .string()
+                            .not_null()
+                            .default(""),
+                    )
+                    .col(ColumnDef::new(ImAiConversation::RoleId).string())
+                    .col(ColumnDef::new(ImAiConversation::ModelId).string())
+                    .col(
+                        ColumnDef::new(ImAiConversation::Pinned)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(ImAiConversation::CreateTime)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImAiConversation::UpdateTime)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_im_ai_conversation_login_uid")
+                    .table(ImAiConversation::Table)
+                    .col(ImAiConversation::LoginUid)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        // 创建 AI 消息表，流式回答在接收过程中逐步写入
+        manager
+            .create_table(
+                Table::create()
+                    .table(ImAiMessage::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ImAiMessage::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ImAiMessage::LoginUid).string().not_null())
+                    .col(
+                        ColumnDef::new(ImAiMessage::ConversationId)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ImAiMessage::MsgType).string().not_null())
+                    .col(
+                        ColumnDef::new(ImAiMessage::Content)
+                            .text()
+                            .not_null()
+                            .default(""),
+                    )
+                    .col(ColumnDef::new(ImAiMessage::ReasoningContent).text())
+                    .col(ColumnDef::new(ImAiMessage::Model).string())
+                    .col(ColumnDef::new(ImAiMessage::Status).string().not_null())
+                    .col(
+                        ColumnDef::new(ImAiMessage::Local)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(ImAiMessage::CreateTime)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImAiMessage::UpdateTime)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_im_ai_message_conversation")
+                    .table(ImAiMessage::Table)
+                    .col(ImAiMessage::LoginUid)
+                    .col(ImAiMessage::ConversationId)
+                    .col(ImAiMessage::CreateTime)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ImAiMessage::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ImAiConversation::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ImAiConversation {
+    Table,
+    Id,
+    LoginUid,
+    Title,
+    RoleId,
+    ModelId,
+    Pinned,
+    CreateTime,
+    UpdateTime,
+}
+
+#[derive(DeriveIden)]
+enum ImAiMessage {
+    Table,
+    Id,
+    LoginUid,
+    ConversationId,
+    MsgType,
+    Content,
+    ReasoningContent,
+    Model,
+    Status,
+    Local,
+    CreateTime,
+    UpdateTime,
+}
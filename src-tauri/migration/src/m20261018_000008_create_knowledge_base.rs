@@ -0,0 +1,161 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 创建本地知识库来源表，每个本地文件对应一条记录
+        manager
+            .create_table(
+                Table::create()
+                    .table(ImKbSource::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ImKbSource::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ImKbSource::LoginUid).string().not_null())
+                    .col(ColumnDef::new(ImKbSource::Path).string().not_null())
+                    .col(ColumnDef::new(ImKbSource::Name).string().not_null())
+                    .col(ColumnDef::new(ImKbSource::Kind).string().not_null())
+                    .col(
+                        ColumnDef::new(ImKbSource::Size)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(ImKbSource::ModifiedTime).big_integer())
+                    .col(
+                        ColumnDef::new(ImKbSource::ChunkCount)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(ImKbSource::CreateTime)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImKbSource::UpdateTime)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_im_kb_source_path")
+                    .table(ImKbSource::Table)
+                    .col(ImKbSource::LoginUid)
+                    .col(ImKbSource::Path)
+                    .unique()
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        // 创建知识片段表，rowid 与全文索引表的 rowid 保持一致
+        manager
+            .create_table(
+                Table::create()
+                    .table(ImKbChunk::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ImKbChunk::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ImKbChunk::SourceId).string().not_null())
+                    .col(ColumnDef::new(ImKbChunk::LoginUid).string().not_null())
+                    .col(ColumnDef::new(ImKbChunk::Seq).integer().not_null())
+                    .col(ColumnDef::new(ImKbChunk::Heading).string())
+                    .col(ColumnDef::new(ImKbChunk::Content).text().not_null())
+                    .col(ColumnDef::new(ImKbChunk::StartLine).integer().not_null())
+                    .col(ColumnDef::new(ImKbChunk::EndLine).integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_im_kb_chunk_source")
+                    .table(ImKbChunk::Table)
+                    .col(ImKbChunk::SourceId)
+                    .col(ImKbChunk::Seq)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        // 知识片段全文索引，检索时用 bm25() 排序
+        // 与消息索引一样使用 trigram 分词器，可以直接匹配中日韩文本
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE VIRTUAL TABLE IF NOT EXISTS im_kb_chunk_fts USING fts5(
+                    content,
+                    heading,
+                    source_id UNINDEXED,
+                    login_uid UNINDEXED,
+                    tokenize = 'trigram'
+                )",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DROP TABLE IF EXISTS im_kb_chunk_fts")
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ImKbChunk::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ImKbSource::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ImKbSource {
+    Table,
+    Id,
+    LoginUid,
+    Path,
+    Name,
+    Kind,
+    Size,
+    ModifiedTime,
+    ChunkCount,
+    CreateTime,
+    UpdateTime,
+}
+
+#[derive(DeriveIden)]
+enum ImKbChunk {
+    Table,
+    Id,
+    SourceId,
+    LoginUid,
+    Seq,
+    Heading,
+    Content,
+    StartLine,
+    EndLine,
+}
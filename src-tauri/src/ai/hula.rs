@@ -0,0 +1,72 @@
+//! 经 HuLa 服务端的 AI 对话，SSE 事件的 data 即为回答片段
+
+use super::{AiMessageRequest, AiProvider, AiProviderKind, AiStreamEvent, ChatTurn};
+use crate::error::CommonError;
+use crate::im_request_client::{ImRequestClient, ImUrl};
+use crate::utils::sse::SseEvent;
+use std::borrow::Cow;
+
+#[derive(Debug, Clone)]
+pub struct HulaProvider {
+    rc: ImRequestClient,
+}
+
+impl HulaProvider {
+    pub fn new(rc: ImRequestClient) -> Self {
+        Self { rc }
+    }
+}
+
+impl AiProvider for HulaProvider {
+    fn kind(&self) -> AiProviderKind {
+        AiProviderKind::Hula
+    }
+
+    fn needs_history(&self) -> bool {
+        false
+    }
+
+    async fn send(
+        &self,
+        request: &AiMessageRequest,
+        _history: &[ChatTurn],
+        last_event_id: Option<&str>,
+    ) -> Result<reqwest::Response, CommonError> {
+        // 服务端不支持单独传递参考资料，放在提问前面
+        let request = match &request.knowledge_context {
+            Some(context) => Cow::Owned(AiMessageRequest {
+                content: format!("{}\n问题：{}", context, request.content),
+                ..request.clone()
+            }),
+            None => Cow::Borrowed(request),
+        };
+        let (method, path) = ImUrl::MessageSendStream.get_url();
+        let response = self
+            .rc
+            .request_stream(
+                method,
+                path,
+                Some(request.as_ref()),
+                None::<serde_json::Value>,
+                last_event_id,
+            )
+            .await?;
+        Ok(response)
+    }
+
+    fn decode(&self, event: SseEvent) -> AiStreamEvent {
+        match event.event.as_str() {
+            "error" => AiStreamEvent::Error(event.data),
+            "message" => AiStreamEvent::Delta {
+                content: event.data,
+                event: None,
+                id: event.id,
+            },
+            _ => AiStreamEvent::Delta {
+                content: event.data,
+                event: Some(event.event),
+                id: event.id,
+            },
+        }
+    }
+}
@@ -0,0 +1,443 @@
+//! 本地知识库
+//!
+//! 本地文档按类型切分为片段后写入 SQLite 全文索引，对话时用提问检索最相关的片段，
+//! 作为参考资料随请求发送。Markdown 按章节和顶层块切分，代码按空行分隔的顶层定义切分，
+//! 纯文本按段落切分，过长的块再按行拆开。
+
+use crate::error::CommonError;
+use crate::repository::im_knowledge_repository::{self, KnowledgeHit};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use sea_orm::DatabaseConnection;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::Path;
+
+/// 单个片段的长度上限（字符）
+pub const MAX_CHUNK_CHARS: usize = 1200;
+/// 提问生成的检索词上限，避免 MATCH 表达式过大
+const MAX_QUERY_TERMS: usize = 64;
+/// trigram 分词器能匹配的最短字符数
+const TRIGRAM_CHARS: usize = 3;
+/// 默认检索的片段数
+pub const DEFAULT_TOP_K: usize = 4;
+/// 检索片段数上限，避免参考资料占满上下文
+const MAX_TOP_K: usize = 10;
+/// 多级标题的连接符
+const HEADING_SEPARATOR: &str = " > ";
+
+/// 按代码切分的文件扩展名
+const CODE_EXTENSIONS: &[&str] = &[
+    "rs", "ts", "tsx", "js", "jsx", "mjs", "cjs", "vue", "py", "go", "java", "kt", "kts", "swift",
+    "c", "h", "cc", "cpp", "hpp", "cs", "rb", "php", "lua", "dart", "scala", "sh", "bash", "zsh",
+    "ps1", "sql", "toml", "yaml", "yml", "json", "xml", "html", "css", "scss", "less", "gradle",
+];
+/// 按纯文本切分的文件扩展名
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "text", "log", "csv", "tsv", "ini", "conf", "rst", "adoc",
+];
+
+/// 文档类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    Markdown,
+    Text,
+    Code,
+}
+
+impl DocumentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentKind::Markdown => "markdown",
+            DocumentKind::Text => "text",
+            DocumentKind::Code => "code",
+        }
+    }
+
+    /// 按扩展名判断文档类型，无扩展名的文件（如 README、LICENSE）视为纯文本
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let Some(extension) = path.extension().and_then(|ext| ext.to_str()) else {
+            return Some(DocumentKind::Text);
+        };
+        let extension = extension.to_ascii_lowercase();
+        if matches!(extension.as_str(), "md" | "markdown" | "mdx") {
+            Some(DocumentKind::Markdown)
+        } else if CODE_EXTENSIONS.contains(&extension.as_str()) {
+            Some(DocumentKind::Code)
+        } else if TEXT_EXTENSIONS.contains(&extension.as_str()) {
+            Some(DocumentKind::Text)
+        } else {
+            None
+        }
+    }
+}
+
+/// 文档片段
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentChunk {
+    /// 所在章节标题，多级标题以 " > " 连接
+    pub heading: Option<String>,
+    pub content: String,
+    /// 起止行号，从 1 开始
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// 切分文档
+pub fn chunk_document(kind: DocumentKind, text: &str) -> Vec<DocumentChunk> {
+    let mut chunker = Chunker::new(text);
+    match kind {
+        DocumentKind::Markdown => {
+            for (heading, blocks) in markdown_sections(text) {
+                chunker.pack(heading, blocks);
+            }
+        }
+        DocumentKind::Text => chunker.pack(None, paragraph_ranges(text)),
+        DocumentKind::Code => chunker.pack(None, code_ranges(text)),
+    }
+    chunker.chunks
+}
+
+/// 把提问拆成 trigram 检索词，按出现顺序去重
+pub fn retrieval_terms(query: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut terms = Vec::new();
+    for word in query.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = word.to_lowercase().chars().collect();
+        for window in chars.windows(TRIGRAM_CHARS) {
+            let term: String = window.iter().collect();
+            if seen.insert(term.clone()) {
+                terms.push(term);
+                if terms.len() >= MAX_QUERY_TERMS {
+                    return terms;
+                }
+            }
+        }
+    }
+    terms
+}
+
+/// trigram 无法匹配的 1~2 个字符的词，提问中没有更长的词时用于 LIKE 检索
+pub fn short_terms(query: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| !word.is_empty() && word.chars().count() < TRIGRAM_CHARS)
+        .filter(|word| seen.insert(word.clone()))
+        .take(MAX_QUERY_TERMS)
+        .collect()
+}
+
+/// 用提问检索最相关的片段
+pub async fn retrieve(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    question: &str,
+    top_k: Option<usize>,
+) -> Result<Vec<KnowledgeHit>, CommonError> {
+    let top_k = top_k.unwrap_or(DEFAULT_TOP_K).clamp(1, MAX_TOP_K) as u64;
+    let terms = retrieval_terms(question);
+    if terms.is_empty() {
+        return im_knowledge_repository::search_chunks_like(
+            db,
+            login_uid,
+            &short_terms(question),
+            top_k,
+        )
+        .await;
+    }
+    im_knowledge_repository::search_chunks(db, login_uid, &terms, top_k).await
+}
+
+/// 把检索结果整理为随请求发送的参考资料
+pub fn build_context(hits: &[KnowledgeHit]) -> Option<String> {
+    if hits.is_empty() {
+        return None;
+    }
+    let mut context =
+        String::from("以下是本地知识库中与问题相关的资料，回答时请优先参考，资料不相关时忽略：\n");
+    for (index, hit) in hits.iter().enumerate() {
+        let location = match &hit.chunk.heading {
+            Some(heading) => format!("{}{}{}", hit.source_name, HEADING_SEPARATOR, heading),
+            None => hit.source_name.clone(),
+        };
+        context.push_str(&format!(
+            "\n[{}] {}（第 {}-{} 行）\n{}\n",
+            index + 1,
+            location,
+            hit.chunk.start_line,
+            hit.chunk.end_line,
+            hit.chunk.content
+        ));
+    }
+    Some(context)
+}
+
+/// 按章节拆分 Markdown，返回每个章节的标题和顶层块
+fn markdown_sections(text: &str) -> Vec<(Option<String>, Vec<Range<usize>>)> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+    options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+
+    let mut sections = vec![(None, Vec::new())];
+    let mut outline: Vec<(HeadingLevel, String)> = Vec::new();
+    let mut heading: Option<(HeadingLevel, String)> = None;
+    let mut depth = 0usize;
+
+    for (event, range) in Parser::new_ext(text, options).into_offset_iter() {
+        match event {
+            Event::Start(tag) => {
+                if depth == 0 {
+                    match tag {
+                        Tag::Heading { level, .. } => heading = Some((level, String::new())),
+                        // 文档开头的元数据不作为内容
+                        Tag::MetadataBlock(_) => {}
+                        _ => sections.last_mut().unwrap().1.push(range),
+                    }
+                }
+                depth += 1;
+            }
+            Event::End(tag) => {
+                depth = depth.saturating_sub(1);
+                if depth == 0
+                    && matches!(tag, TagEnd::Heading(_))
+                    && let Some((level, title)) = heading.take()
+                {
+                    outline.retain(|(parent, _)| *parent < level);
+                    outline.push((level, title.trim().to_string()));
+                    let path: Vec<&str> = outline
+                        .iter()
+                        .map(|(_, title)| title.as_str())
+                        .filter(|title| !title.is_empty())
+                        .collect();
+                    let path = (!path.is_empty()).then(|| path.join(HEADING_SEPARATOR));
+                    sections.push((path, Vec::new()));
+                }
+            }
+            Event::Text(content) | Event::Code(content) => {
+                if let Some((_, title)) = heading.as_mut() {
+                    title.push_str(&content);
+                }
+            }
+            _ => {}
+        }
+    }
+    sections
+}
+
+/// 以空行分隔的段落
+fn paragraph_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut current: Option<Range<usize>> = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let end = offset + line.len();
+        if line.trim().is_empty() {
+            ranges.extend(current.take());
+        } else {
+            match current.as_mut() {
+                Some(range) => range.end = end,
+                None => current = Some(offset..end),
+            }
+        }
+        offset = end;
+    }
+    ranges.extend(current);
+    ranges
+}
+
+/// 代码按顶层定义分块：缩进开头的段落属于上一段（函数体中的空行不会把函数拆开）
+fn code_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for range in paragraph_ranges(text) {
+        let indented = text[range.clone()].starts_with([' ', '\t']);
+        match ranges.last_mut() {
+            Some(last) if indented => last.end = range.end,
+            _ => ranges.push(range),
+        }
+    }
+    ranges
+}
+
+/// 把相邻的块合并为不超过长度上限的片段
+struct Chunker<'a> {
+    text: &'a str,
+    /// 每行起始的字节偏移
+    line_starts: Vec<usize>,
+    chunks: Vec<DocumentChunk>,
+}
+
+impl<'a> Chunker<'a> {
+    fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self {
+            text,
+            line_starts,
+            chunks: Vec::new(),
+        }
+    }
+
+    fn pack(&mut self, heading: Option<String>, blocks: Vec<Range<usize>>) {
+        let mut current: Option<Range<usize>> = None;
+        for block in blocks {
+            for piece in self.split_oversized(block) {
+                current = match current {
+                    Some(range) if self.chars(range.start..piece.end) <= MAX_CHUNK_CHARS => {
+                        Some(range.start..piece.end)
+                    }
+                    Some(range) => {
+                        self.emit(&heading, range);
+                        Some(piece)
+                    }
+                    None => Some(piece),
+                };
+            }
+        }
+        if let Some(range) = current {
+            self.emit(&heading, range);
+        }
+    }
+
+    /// 超过上限的块按行拆分，单行仍超过上限时按字符拆分
+    fn split_oversized(&self, range: Range<usize>) -> Vec<Range<usize>> {
+        if self.chars(range.clone()) <= MAX_CHUNK_CHARS {
+            return vec![range];
+        }
+        let mut pieces = Vec::new();
+        let mut current: Option<Range<usize>> = None;
+        let mut offset = range.start;
+        for line in self.text[range].split_inclusive('\n') {
+            let line_range = offset..offset + line.len();
+            offset = line_range.end;
+            for part in self.split_line(line_range) {
+                current = match current {
+                    Some(range) if self.chars(range.start..part.end) <= MAX_CHUNK_CHARS => {
+                        Some(range.start..part.end)
+                    }
+                    Some(range) => {
+                        pieces.push(range);
+                        Some(part)
+                    }
+                    None => Some(part),
+                };
+            }
+        }
+        pieces.extend(current);
+        pieces
+    }
+
+    fn split_line(&self, range: Range<usize>) -> Vec<Range<usize>> {
+        let mut parts = Vec::new();
+        let mut start = range.start;
+        let mut count = 0;
+        for (index, _) in self.text[range.clone()].char_indices() {
+            if count == MAX_CHUNK_CHARS {
+                parts.push(start..range.start + index);
+                start = range.start + index;
+                count = 0;
+            }
+            count += 1;
+        }
+        parts.push(start..range.end);
+        parts
+    }
+
+    fn emit(&mut self, heading: &Option<String>, range: Range<usize>) {
+        let raw = &self.text[range.clone()];
+        let content = raw.trim();
+        if content.is_empty() {
+            return;
+        }
+        let start = range.start + (raw.len() - raw.trim_start().len());
+        let end = start + content.len();
+        self.chunks.push(DocumentChunk {
+            heading: heading.clone(),
+            content: content.to_string(),
+            start_line: self.line_of(start),
+            end_line: self.line_of(end - 1),
+        });
+    }
+
+    fn chars(&self, range: Range<usize>) -> usize {
+        self.text[range].chars().count()
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_chunks_follow_sections() {
+        let text = "---\ntitle: 说明\n---\n\n简介段落\n\n# 安装\n\n## 代理\n\n设置 `HTTP_PROXY`。\n\n```sh\n# 不是标题\nexport HTTP_PROXY=x\n```\n\n# 使用\n\n- 第一项\n- 第二项\n";
+        let chunks = chunk_document(DocumentKind::Markdown, text);
+        let summary: Vec<(Option<&str>, usize, usize)> = chunks
+            .iter()
+            .map(|c| (c.heading.as_deref(), c.start_line, c.end_line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (None, 5, 5),
+                (Some("安装 > 代理"), 11, 16),
+                (Some("使用"), 20, 21),
+            ]
+        );
+        assert!(chunks[1].content.contains("# 不是标题"));
+    }
+
+    #[test]
+    fn test_splits_long_text_and_keeps_code_bodies() {
+        let paragraph = "字".repeat(MAX_CHUNK_CHARS / 2 + 10);
+        let text = format!(
+            "{paragraph}\n\n{paragraph}\n\n{}",
+            "长".repeat(MAX_CHUNK_CHARS * 2)
+        );
+        let chunks = chunk_document(DocumentKind::Text, &text);
+        assert_eq!(chunks.len(), 4);
+        assert!(
+            chunks
+                .iter()
+                .all(|c| c.content.chars().count() <= MAX_CHUNK_CHARS)
+        );
+        assert_eq!((chunks[1].start_line, chunks[2].start_line), (3, 5));
+
+        let code = "fn a() {\n    let x = 1;\n\n    x + 1\n}\n\nfn b() {}\n";
+        let ranges: Vec<&str> = code_ranges(code)
+            .into_iter()
+            .map(|range| code[range].trim_end())
+            .collect();
+        assert_eq!(
+            ranges,
+            vec!["fn a() {\n    let x = 1;\n\n    x + 1\n}", "fn b() {}"]
+        );
+    }
+
+    #[test]
+    fn test_retrieval_terms() {
+        assert_eq!(
+            retrieval_terms("如何配置代理 HTTP? 配置"),
+            vec!["如何配", "何配置", "配置代", "置代理", "htt", "ttp"]
+        );
+        assert!(retrieval_terms("a b 你好").is_empty());
+        assert_eq!(
+            short_terms("AI 报销 ai, go 配置代理"),
+            vec!["ai", "报销", "go"]
+        );
+        assert_eq!(
+            DocumentKind::from_path(Path::new("docs/README.MD")),
+            Some(DocumentKind::Markdown)
+        );
+        assert_eq!(DocumentKind::from_path(Path::new("logo.png")), None);
+    }
+}
@@ -0,0 +1,160 @@
+//! AI 对话服务
+//!
+//! 对话请求通过 [`AiProvider::send`] 发出并返回 SSE 响应，每条 SSE 事件由 [`AiProvider::decode`]
+//! 转换为 [`AiStreamEvent`]。流式读取、断线重连和本地记录由调用方统一处理，
+//! 因此无论使用哪个服务，前端收到的事件格式都相同。
+
+use crate::error::CommonError;
+use crate::utils::sse::SseEvent;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+pub mod hula;
+pub mod knowledge;
+pub mod openai;
+pub mod stream_manager;
+
+pub use hula::HulaProvider;
+pub use openai::OpenAiProvider;
+pub use stream_manager::{AiStreamManager, StopReason, StreamLimits};
+
+/// 对话服务类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AiProviderKind {
+    Hula,
+    OpenAi,
+}
+
+impl AiProviderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AiProviderKind::Hula => "hula",
+            AiProviderKind::OpenAi => "openai",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "hula" => Some(AiProviderKind::Hula),
+            "openai" | "openai-compatible" => Some(AiProviderKind::OpenAi),
+            _ => None,
+        }
+    }
+}
+
+/// AI 消息发送请求参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiMessageRequest {
+    pub conversation_id: String,
+    pub content: String,
+    pub use_context: Option<bool>,
+    pub reasoning_enabled: Option<bool>,
+    /// 是否检索本地知识库作为参考资料，仅在客户端使用
+    #[serde(default, skip_serializing)]
+    pub use_knowledge: Option<bool>,
+    /// 检索的片段数，默认 `knowledge::DEFAULT_TOP_K`
+    #[serde(default, skip_serializing)]
+    pub knowledge_top_k: Option<usize>,
+    /// 检索到的参考资料，发送前由客户端填充
+    #[serde(skip)]
+    pub knowledge_context: Option<String>,
+}
+
+/// 会话中的一条历史消息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatTurn {
+    /// user 或 assistant
+    pub role: String,
+    pub content: String,
+}
+
+/// SSE 事件解析后的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AiStreamEvent {
+    /// 回答片段，`event` 和 `id` 为原始 SSE 事件的字段
+    Delta {
+        content: String,
+        event: Option<String>,
+        id: Option<String>,
+    },
+    /// 服务端声明回答已结束
+    Done,
+    /// 服务端返回的错误
+    Error(String),
+    /// 服务端统计的 token 用量
+    Usage(TokenUsage),
+    /// 与回答内容无关的事件，如角色声明、思考过程
+    Skip,
+}
+
+/// 单次回答的 token 用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub total_tokens: Option<u64>,
+}
+
+/// AI 对话服务接口
+pub trait AiProvider: Send + Sync {
+    fn kind(&self) -> AiProviderKind;
+
+    /// 是否需要客户端附带会话历史，HuLa 服务端自行维护上下文
+    fn needs_history(&self) -> bool;
+
+    /// 发送对话请求，`history` 按时间正序且不包含本次提问
+    /// 断线重连时 `last_event_id` 为最后收到的事件 id
+    fn send(
+        &self,
+        request: &AiMessageRequest,
+        history: &[ChatTurn],
+        last_event_id: Option<&str>,
+    ) -> impl Future<Output = Result<reqwest::Response, CommonError>> + Send;
+
+    /// 解析一条 SSE 事件
+    fn decode(&self, event: SseEvent) -> AiStreamEvent;
+}
+
+/// 运行时选择的对话服务
+#[derive(Debug, Clone)]
+pub enum AiBackend {
+    Hula(HulaProvider),
+    OpenAi(OpenAiProvider),
+}
+
+impl AiProvider for AiBackend {
+    fn kind(&self) -> AiProviderKind {
+        match self {
+            AiBackend::Hula(provider) => provider.kind(),
+            AiBackend::OpenAi(provider) => provider.kind(),
+        }
+    }
+
+    fn needs_history(&self) -> bool {
+        match self {
+            AiBackend::Hula(provider) => provider.needs_history(),
+            AiBackend::OpenAi(provider) => provider.needs_history(),
+        }
+    }
+
+    async fn send(
+        &self,
+        request: &AiMessageRequest,
+        history: &[ChatTurn],
+        last_event_id: Option<&str>,
+    ) -> Result<reqwest::Response, CommonError> {
+        match self {
+            AiBackend::Hula(provider) => provider.send(request, history, last_event_id).await,
+            AiBackend::OpenAi(provider) => provider.send(request, history, last_event_id).await,
+        }
+    }
+
+    fn decode(&self, event: SseEvent) -> AiStreamEvent {
+        match self {
+            AiBackend::Hula(provider) => provider.decode(event),
+            AiBackend::OpenAi(provider) => provider.decode(event),
+        }
+    }
+}
@@ -0,0 +1,378 @@
+//! OpenAI 兼容的 `/chat/completions` 流式接口，适用于 llama.cpp、vLLM、Ollama 等自建服务
+
+use super::{AiMessageRequest, AiProvider, AiProviderKind, AiStreamEvent, ChatTurn, TokenUsage};
+use crate::configuration::OpenAiSettings;
+use crate::error::CommonError;
+use crate::utils::sse::SseEvent;
+use serde_json::{Value, json};
+
+/// 流结束标记
+const DONE_MARKER: &str = "[DONE]";
+
+#[derive(Debug, Clone)]
+pub struct OpenAiProvider {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    system_prompt: Option<String>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    context_messages: usize,
+}
+
+impl OpenAiProvider {
+    /// `api_key` 保存在敏感信息存储中，不写入配置文件
+    pub fn new(
+        client: reqwest::Client,
+        settings: &OpenAiSettings,
+        api_key: Option<String>,
+    ) -> Self {
+        Self {
+            client,
+            base_url: settings.base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.filter(|key| !key.is_empty()),
+            model: settings.model.clone(),
+            system_prompt: settings
+                .system_prompt
+                .clone()
+                .filter(|prompt| !prompt.is_empty()),
+            temperature: settings.temperature,
+            max_tokens: settings.max_tokens,
+            context_messages: settings.context_messages(),
+        }
+    }
+
+    /// 构建请求体，历史按问答对保留最近 `context_messages` 条
+    /// 没有回答的提问和不成对的回答不会发送，避免上下文以 assistant 开头或连续出现两条提问
+    fn request_body(&self, request: &AiMessageRequest, history: &[ChatTurn]) -> Value {
+        let mut messages = Vec::with_capacity(history.len() + 2);
+        if let Some(prompt) = &self.system_prompt {
+            messages.push(json!({ "role": "system", "content": prompt }));
+        }
+        if let Some(context) = &request.knowledge_context {
+            messages.push(json!({ "role": "system", "content": context }));
+        }
+        let mut pairs = Vec::new();
+        let mut turns = history.iter().peekable();
+        while let Some(turn) = turns.next() {
+            if turn.role == "user"
+                && let Some(answer) = turns.next_if(|next| next.role == "assistant")
+            {
+                pairs.push((turn, answer));
+            }
+        }
+        let skip = pairs.len().saturating_sub(self.context_messages / 2);
+        for (question, answer) in &pairs[skip..] {
+            messages.push(json!({ "role": question.role, "content": question.content }));
+            messages.push(json!({ "role": answer.role, "content": answer.content }));
+        }
+        messages.push(json!({ "role": "user", "content": request.content }));
+
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": true,
+            // 要求在最后一个片段中附带 token 用量
+            "stream_options": { "include_usage": true },
+        });
+        if let Some(temperature) = self.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        body
+    }
+}
+
+impl AiProvider for OpenAiProvider {
+    fn kind(&self) -> AiProviderKind {
+        AiProviderKind::OpenAi
+    }
+
+    fn needs_history(&self) -> bool {
+        true
+    }
+
+    async fn send(
+        &self,
+        request: &AiMessageRequest,
+        history: &[ChatTurn],
+        _last_event_id: Option<&str>,
+    ) -> Result<reqwest::Response, CommonError> {
+        let mut builder = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .header(reqwest::header::ACCEPT, "text/event-stream")
+            .json(&self.request_body(request, history));
+        if let Some(api_key) = &self.api_key {
+            builder = builder.bearer_auth(api_key);
+        }
+
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("请求AI服务失败: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let message = serde_json::from_str::<Value>(&body)
+                .ok()
+                .as_ref()
+                .and_then(error_message)
+                .unwrap_or(body);
+            return Err(anyhow::anyhow!("AI服务返回错误，状态码: {}, {}", status, message).into());
+        }
+        Ok(response)
+    }
+
+    fn decode(&self, event: SseEvent) -> AiStreamEvent {
+        let data = event.data.trim();
+        if data == DONE_MARKER {
+            return AiStreamEvent::Done;
+        }
+        let Ok(chunk) = serde_json::from_str::<Value>(data) else {
+            return AiStreamEvent::Skip;
+        };
+        if let Some(message) = error_message(&chunk) {
+            return AiStreamEvent::Error(message);
+        }
+        // 只转发正文，思考过程（reasoning_content）和仅声明角色的片段不计入回答
+        match chunk
+            .pointer("/choices/0/delta/content")
+            .and_then(Value::as_str)
+        {
+            Some(content) if !content.is_empty() => AiStreamEvent::Delta {
+                content: content.to_string(),
+                event: None,
+                id: None,
+            },
+            _ => match chunk.get("usage") {
+                Some(usage) if usage.is_object() => AiStreamEvent::Usage(TokenUsage {
+                    prompt_tokens: usage.get("prompt_tokens").and_then(Value::as_u64),
+                    completion_tokens: usage.get("completion_tokens").and_then(Value::as_u64),
+                    total_tokens: usage.get("total_tokens").and_then(Value::as_u64),
+                }),
+                _ => AiStreamEvent::Skip,
+            },
+        }
+    }
+}
+
+/// 读取 `{"error": {"message": ...}}` 或 `{"error": "..."}` 形式的错误信息
+fn error_message(value: &Value) -> Option<String> {
+    match value.get("error")? {
+        Value::String(message) => Some(message.clone()),
+        error => error
+            .get("message")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .or_else(|| Some(error.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sse::SseDecoder;
+    use futures::StreamExt;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn settings(base_url: String) -> OpenAiSettings {
+        OpenAiSettings {
+            base_url,
+            model: "qwen2.5".to_string(),
+            system_prompt: Some("你是助手".to_string()),
+            temperature: None,
+            max_tokens: Some(256),
+            context_messages: Some(2),
+        }
+    }
+
+    fn request(content: &str) -> AiMessageRequest {
+        AiMessageRequest {
+            conversation_id: "1".to_string(),
+            content: content.to_string(),
+            use_context: Some(true),
+            reasoning_enabled: None,
+            use_knowledge: None,
+            knowledge_top_k: None,
+            knowledge_context: None,
+        }
+    }
+
+    fn turn(role: &str, content: &str) -> ChatTurn {
+        ChatTurn {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    /// 按 chunks 分段返回 SSE 响应，记录收到的原始请求
+    async fn serve(
+        status: &'static str,
+        chunks: Vec<&'static str>,
+        requests: Arc<Mutex<Vec<String>>>,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut raw = Vec::new();
+            let mut buf = vec![0u8; 4096];
+            // 读完请求头和 Content-Length 指定的请求体
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                raw.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&raw).to_string();
+                if let Some(pos) = text.find("\r\n\r\n") {
+                    let length: usize = text
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(str::to_string)
+                        })
+                        .and_then(|len| len.trim().parse().ok())
+                        .unwrap_or(0);
+                    if raw.len() >= pos + 4 + length {
+                        break;
+                    }
+                }
+            }
+            requests
+                .lock()
+                .unwrap()
+                .push(String::from_utf8_lossy(&raw).to_string());
+
+            let head = format!(
+                "HTTP/1.1 {status}\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n"
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            for chunk in chunks {
+                socket.write_all(chunk.as_bytes()).await.unwrap();
+                socket.flush().await.unwrap();
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        });
+        format!("http://{addr}/v1/")
+    }
+
+    async fn collect(provider: &OpenAiProvider, response: reqwest::Response) -> Vec<AiStreamEvent> {
+        let mut decoder = SseDecoder::new();
+        let mut events = Vec::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            for event in decoder.feed(&chunk.unwrap()) {
+                match provider.decode(event) {
+                    AiStreamEvent::Skip => {}
+                    event => events.push(event),
+                }
+            }
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_streams_completion_from_mock_server() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base_url = serve(
+            "200 OK",
+            vec![
+                "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{\"content\":\"你\"}}]}\n\ndata: {\"choices\":[{\"delta\":",
+                "{\"content\":\"好\"}}]}\r\n\r\n: keep-alive\n\n",
+                "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+                "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":2,\"total_tokens\":14}}\n\ndata: [DONE]\n\n",
+            ],
+            requests.clone(),
+        )
+        .await;
+        let provider = OpenAiProvider::new(
+            reqwest::Client::new(),
+            &settings(base_url),
+            Some("sk-local".to_string()),
+        );
+        // 多出的回答没有对应的提问，第二问还没有回答
+        let history = vec![
+            turn("user", "第零问"),
+            turn("assistant", "第零答"),
+            turn("user", "第一问"),
+            turn("assistant", "第一答"),
+            turn("assistant", "多出的回答"),
+            turn("user", "第二问"),
+        ];
+
+        let mut request = request("第三问");
+        request.knowledge_context = Some("参考资料".to_string());
+        let response = provider.send(&request, &history, None).await.unwrap();
+        let events = collect(&provider, response).await;
+        let content: String = events
+            .iter()
+            .filter_map(|event| match event {
+                AiStreamEvent::Delta { content, .. } => Some(content.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(content, "你好");
+        assert!(events.contains(&AiStreamEvent::Usage(TokenUsage {
+            prompt_tokens: Some(12),
+            completion_tokens: Some(2),
+            total_tokens: Some(14),
+        })));
+        assert_eq!(events.last(), Some(&AiStreamEvent::Done));
+
+        let raw = requests.lock().unwrap()[0].clone();
+        assert!(raw.starts_with("POST /v1/chat/completions "));
+        assert!(
+            raw.to_lowercase()
+                .contains("authorization: bearer sk-local")
+        );
+        let body: Value = serde_json::from_str(&raw[raw.find("\r\n\r\n").unwrap() + 4..]).unwrap();
+        assert_eq!(body["model"], "qwen2.5");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["max_tokens"], 256);
+        assert_eq!(body["stream_options"]["include_usage"], true);
+        // 系统提示词 + 参考资料 + 最近 1 组问答 + 本次提问
+        let contents: Vec<&str> = body["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            contents,
+            vec!["你是助手", "参考资料", "第一问", "第一答", "第三问"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reports_server_errors() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base_url = serve(
+            "404 Not Found",
+            vec!["{\"error\":{\"message\":\"model 'qwen2.5' not found\"}}"],
+            requests,
+        )
+        .await;
+        let provider = OpenAiProvider::new(reqwest::Client::new(), &settings(base_url), None);
+        let err = provider
+            .send(&request("你好"), &[], None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("model 'qwen2.5' not found"));
+
+        let event = provider.decode(SseEvent {
+            event: "message".to_string(),
+            data: "{\"error\":\"context length exceeded\"}".to_string(),
+            id: None,
+        });
+        assert_eq!(
+            event,
+            AiStreamEvent::Error("context length exceeded".to_string())
+        );
+    }
+}
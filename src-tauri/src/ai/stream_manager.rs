@@ -0,0 +1,372 @@
+//! AI 流式任务管理
+//!
+//! 每个流式回答注册为一个 [`StreamHandle`]，任务通过 [`StreamHandle::guard`] 等待网络操作，
+//! 收到取消信号或超时时返回 [`StopReason`]，由任务自行收尾（保存已收到的内容、发送结束事件）。
+//! 句柄被丢弃时自动从管理器中移除。
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use super::AiProviderKind;
+
+/// 流式任务被停止的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// 用户主动取消
+    Cancelled,
+    /// 退出登录
+    Logout,
+    /// 超过空闲时间没有收到数据
+    IdleTimeout,
+    /// 超过单次回答的总时长
+    TotalTimeout,
+}
+
+impl StopReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopReason::Cancelled => "cancelled",
+            StopReason::Logout => "logout",
+            StopReason::IdleTimeout => "idleTimeout",
+            StopReason::TotalTimeout => "totalTimeout",
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, StopReason::IdleTimeout | StopReason::TotalTimeout)
+    }
+}
+
+/// 流式任务的并发和超时限制
+#[derive(Debug, Clone, Copy)]
+pub struct StreamLimits {
+    /// 每个用户同时进行的流式回答数量上限
+    pub max_per_user: usize,
+    pub idle_timeout: Duration,
+    pub total_timeout: Duration,
+}
+
+/// 正在进行的流式任务信息
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveStreamInfo {
+    pub request_id: String,
+    pub conversation_id: String,
+    pub provider: String,
+    /// 开始时间（毫秒）
+    pub started_at: i64,
+    pub elapsed_ms: u64,
+    /// 距离最后一次收到数据的时间
+    pub idle_ms: u64,
+    /// 已收到的回答片段数
+    pub chunks: u64,
+    /// 已收到的回答字符数
+    pub chars: u64,
+    /// 已发出停止信号，等待任务收尾
+    pub stopping: bool,
+}
+
+#[derive(Debug)]
+struct StreamStats {
+    chunks: AtomicU64,
+    chars: AtomicU64,
+    /// 最后一次收到数据的时间（毫秒）
+    last_activity: AtomicI64,
+}
+
+#[derive(Debug)]
+struct ActiveStream {
+    login_uid: String,
+    conversation_id: String,
+    provider: AiProviderKind,
+    started_at: i64,
+    stats: Arc<StreamStats>,
+    stop: watch::Sender<Option<StopReason>>,
+}
+
+/// 流式任务管理器
+#[derive(Debug, Default)]
+pub struct AiStreamManager {
+    streams: Mutex<HashMap<String, ActiveStream>>,
+}
+
+impl AiStreamManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册新的流式任务，超过用户并发上限或请求ID重复时返回错误
+    pub fn register(
+        self: &Arc<Self>,
+        request_id: &str,
+        login_uid: &str,
+        conversation_id: &str,
+        provider: AiProviderKind,
+        limits: StreamLimits,
+    ) -> Result<StreamHandle, String> {
+        let mut streams = self.streams.lock().unwrap();
+        if streams.contains_key(request_id) {
+            return Err(format!("请求ID已存在: {}", request_id));
+        }
+        let running = streams
+            .values()
+            .filter(|stream| stream.login_uid == login_uid)
+            .count();
+        if running >= limits.max_per_user {
+            return Err(format!(
+                "同时进行的 AI 对话已达上限（{}），请稍后再试",
+                limits.max_per_user
+            ));
+        }
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let stats = Arc::new(StreamStats {
+            chunks: AtomicU64::new(0),
+            chars: AtomicU64::new(0),
+            last_activity: AtomicI64::new(now),
+        });
+        let (stop, stop_rx) = watch::channel(None);
+        streams.insert(
+            request_id.to_string(),
+            ActiveStream {
+                login_uid: login_uid.to_string(),
+                conversation_id: conversation_id.to_string(),
+                provider,
+                started_at: now,
+                stats: stats.clone(),
+                stop,
+            },
+        );
+
+        let started = Instant::now();
+        Ok(StreamHandle {
+            manager: self.clone(),
+            request_id: request_id.to_string(),
+            stats,
+            stop_rx,
+            limits,
+            started,
+            last_activity: started,
+        })
+    }
+
+    /// 通知用户的指定任务停止，任务不存在或不属于该用户时返回 false
+    pub fn cancel(&self, request_id: &str, login_uid: &str, reason: StopReason) -> bool {
+        let streams = self.streams.lock().unwrap();
+        match streams.get(request_id) {
+            Some(stream) if stream.login_uid == login_uid => {
+                stream.stop.send_replace(Some(reason));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 通知所有任务停止，返回通知的任务数
+    pub fn cancel_all(&self, reason: StopReason) -> usize {
+        let streams = self.streams.lock().unwrap();
+        for stream in streams.values() {
+            stream.stop.send_replace(Some(reason));
+        }
+        streams.len()
+    }
+
+    /// 列出用户正在进行的流式任务，按开始时间排序
+    pub fn list(&self, login_uid: &str) -> Vec<ActiveStreamInfo> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let streams = self.streams.lock().unwrap();
+        let mut list: Vec<ActiveStreamInfo> = streams
+            .iter()
+            .filter(|(_, stream)| stream.login_uid == login_uid)
+            .map(|(request_id, stream)| ActiveStreamInfo {
+                request_id: request_id.clone(),
+                conversation_id: stream.conversation_id.clone(),
+                provider: stream.provider.as_str().to_string(),
+                started_at: stream.started_at,
+                elapsed_ms: (now - stream.started_at).max(0) as u64,
+                idle_ms: (now - stream.stats.last_activity.load(Ordering::Relaxed)).max(0) as u64,
+                chunks: stream.stats.chunks.load(Ordering::Relaxed),
+                chars: stream.stats.chars.load(Ordering::Relaxed),
+                stopping: stream.stop.borrow().is_some(),
+            })
+            .collect();
+        list.sort_by_key(|info| info.started_at);
+        list
+    }
+
+    fn remove(&self, request_id: &str) {
+        self.streams.lock().unwrap().remove(request_id);
+    }
+}
+
+/// 单个流式任务的句柄，丢弃时注销任务
+#[derive(Debug)]
+pub struct StreamHandle {
+    manager: Arc<AiStreamManager>,
+    request_id: String,
+    stats: Arc<StreamStats>,
+    stop_rx: watch::Receiver<Option<StopReason>>,
+    limits: StreamLimits,
+    started: Instant,
+    last_activity: Instant,
+}
+
+impl StreamHandle {
+    /// 等待一个网络操作完成，期间收到停止信号或超时则返回停止原因
+    /// 操作完成即视为有数据往来，重新开始计算空闲时间
+    pub async fn guard<F: Future>(&mut self, fut: F) -> Result<F::Output, StopReason> {
+        if let Some(reason) = *self.stop_rx.borrow() {
+            return Err(reason);
+        }
+
+        let idle_deadline = self.last_activity + self.limits.idle_timeout;
+        let total_deadline = self.started + self.limits.total_timeout;
+        let (deadline, timeout_reason) = if total_deadline <= idle_deadline {
+            (total_deadline, StopReason::TotalTimeout)
+        } else {
+            (idle_deadline, StopReason::IdleTimeout)
+        };
+
+        let stop_rx = &mut self.stop_rx;
+        let stopped = async {
+            loop {
+                if stop_rx.changed().await.is_err() {
+                    // 管理器中的记录已被移除，不会再收到停止信号
+                    std::future::pending::<()>().await;
+                }
+                if let Some(reason) = *stop_rx.borrow() {
+                    return reason;
+                }
+            }
+        };
+
+        let output = tokio::select! {
+            biased;
+            reason = stopped => return Err(reason),
+            _ = tokio::time::sleep_until(deadline) => return Err(timeout_reason),
+            output = fut => output,
+        };
+        self.last_activity = Instant::now();
+        self.stats
+            .last_activity
+            .store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
+        Ok(output)
+    }
+
+    /// 记录收到的回答片段
+    pub fn record_chunk(&self, content: &str) {
+        self.stats.chunks.fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .chars
+            .fetch_add(content.chars().count() as u64, Ordering::Relaxed);
+    }
+
+    pub fn chunks(&self) -> u64 {
+        self.stats.chunks.load(Ordering::Relaxed)
+    }
+
+    pub fn chars(&self) -> u64 {
+        self.stats.chars.load(Ordering::Relaxed)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+impl Drop for StreamHandle {
+    fn drop(&mut self) {
+        self.manager.remove(&self.request_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max_per_user: usize) -> StreamLimits {
+        StreamLimits {
+            max_per_user,
+            idle_timeout: Duration::from_secs(60),
+            total_timeout: Duration::from_secs(600),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limits_per_user_and_unregisters_on_drop() {
+        let manager = Arc::new(AiStreamManager::new());
+        let first = manager
+            .register("r1", "u1", "c1", AiProviderKind::Hula, limits(1))
+            .unwrap();
+        assert!(
+            manager
+                .register("r2", "u1", "c1", AiProviderKind::Hula, limits(1))
+                .is_err()
+        );
+        // 其他用户不受影响，重复的请求ID被拒绝
+        let _other = manager
+            .register("r3", "u2", "c2", AiProviderKind::OpenAi, limits(1))
+            .unwrap();
+        assert!(
+            manager
+                .register("r3", "u3", "c3", AiProviderKind::Hula, limits(1))
+                .is_err()
+        );
+
+        first.record_chunk("你好");
+        let list = manager.list("u1");
+        assert_eq!(list.len(), 1);
+        assert_eq!((list[0].chunks, list[0].chars), (1, 2));
+
+        drop(first);
+        assert!(manager.list("u1").is_empty());
+        assert!(
+            manager
+                .register("r2", "u1", "c1", AiProviderKind::Hula, limits(1))
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_guard_stops_on_cancel_and_timeout() {
+        let manager = Arc::new(AiStreamManager::new());
+        let mut handle = manager
+            .register("r1", "u1", "c1", AiProviderKind::Hula, limits(2))
+            .unwrap();
+        assert_eq!(handle.guard(async { 1 }).await, Ok(1));
+
+        let canceller = manager.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert_eq!(canceller.cancel_all(StopReason::Logout), 1);
+        });
+        let result = handle.guard(std::future::pending::<()>()).await;
+        assert_eq!(result, Err(StopReason::Logout));
+        assert!(manager.list("u1")[0].stopping);
+
+        let mut idle = manager
+            .register(
+                "r2",
+                "u1",
+                "c1",
+                AiProviderKind::Hula,
+                StreamLimits {
+                    idle_timeout: Duration::from_millis(20),
+                    ..limits(2)
+                },
+            )
+            .unwrap();
+        let result = idle.guard(std::future::pending::<()>()).await;
+        assert_eq!(result, Err(StopReason::IdleTimeout));
+        assert!(!manager.cancel("missing", "u1", StopReason::Cancelled));
+        // 不能取消其他用户的任务
+        assert!(!manager.cancel("r2", "u2", StopReason::Cancelled));
+        assert!(manager.cancel("r2", "u1", StopReason::Cancelled));
+    }
+}
@@ -0,0 +1,243 @@
+use crate::error::CommonError;
+use crate::im_request_client::ImUrl;
+use crate::repository::im_ai_chat_repository::{self, STATUS_DONE};
+use chrono::Utc;
+use entity::{im_ai_conversation, im_ai_message};
+use sea_orm::DatabaseConnection;
+use serde_json::Value;
+
+/// 需要同步到本地 AI 会话缓存的接口请求
+#[derive(Debug, Clone, PartialEq)]
+pub enum AiChatSync {
+    /// 会话列表或分页
+    Conversations,
+    /// 单个会话详情
+    Conversation,
+    /// 会话的消息列表
+    Messages {
+        conversation_id: String,
+    },
+    DeleteConversations {
+        ids: Vec<String>,
+    },
+    DeleteUnpinnedConversations,
+    ClearMessages {
+        conversation_ids: Vec<String>,
+    },
+    DeleteMessage {
+        id: String,
+    },
+}
+
+impl AiChatSync {
+    /// 根据请求地址和参数判断请求成功后是否需要更新本地缓存
+    pub fn from_request(url: &ImUrl, body: Option<&Value>, params: Option<&Value>) -> Option<Self> {
+        match url {
+            ImUrl::ConversationMyList | ImUrl::ConversationPage => Some(Self::Conversations),
+            ImUrl::ConversationGetMy => Some(Self::Conversation),
+            ImUrl::MessageListByConversationId => Some(Self::Messages {
+                conversation_id: json_string(params?, "conversationId")?,
+            }),
+            ImUrl::ConversationDeleteMy => Some(Self::DeleteConversations {
+                ids: id_list(body?, "conversationIdList"),
+            }),
+            ImUrl::ConversationDeleteByUnpinned => Some(Self::DeleteUnpinnedConversations),
+            ImUrl::MessageDeleteByConversationId => Some(Self::ClearMessages {
+                conversation_ids: id_list(body?, "conversationIdList"),
+            }),
+            ImUrl::MessageDelete => Some(Self::DeleteMessage {
+                id: json_string(params?, "id")?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// 用接口返回的数据更新本地缓存
+    pub async fn apply(
+        self,
+        db: &DatabaseConnection,
+        login_uid: &str,
+        data: Option<&Value>,
+    ) -> Result<(), CommonError> {
+        match self {
+            Self::Conversations => {
+                // 分页接口返回 { list, total }，列表接口直接返回数组
+                let items = data
+                    .map(|data| data.get("list").unwrap_or(data))
+                    .and_then(Value::as_array);
+                let conversations = items
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|item| parse_conversation(login_uid, item))
+                    .collect();
+                im_ai_chat_repository::save_conversations(db, conversations).await
+            }
+            Self::Conversation => {
+                let conversations = data
+                    .and_then(|item| parse_conversation(login_uid, item))
+                    .into_iter()
+                    .collect();
+                im_ai_chat_repository::save_conversations(db, conversations).await
+            }
+            Self::Messages { conversation_id } => {
+                let messages = data
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|item| parse_message(login_uid, &conversation_id, item))
+                    .collect();
+                im_ai_chat_repository::save_server_messages(
+                    db,
+                    login_uid,
+                    &conversation_id,
+                    messages,
+                )
+                .await
+            }
+            Self::DeleteConversations { ids } => {
+                im_ai_chat_repository::delete_conversations(db, login_uid, &ids).await
+            }
+            Self::DeleteUnpinnedConversations => {
+                im_ai_chat_repository::delete_unpinned_conversations(db, login_uid).await
+            }
+            Self::ClearMessages { conversation_ids } => {
+                im_ai_chat_repository::clear_conversation_messages(db, login_uid, &conversation_ids)
+                    .await
+            }
+            Self::DeleteMessage { id } => {
+                im_ai_chat_repository::delete_message(db, login_uid, &id).await
+            }
+        }
+    }
+}
+
+/// 读取字符串字段，服务端的 ID 可能以数字返回
+fn json_string(value: &Value, key: &str) -> Option<String> {
+    match value.get(key)? {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// 读取毫秒时间戳，兼容数字和数字字符串
+fn json_time(value: &Value, key: &str) -> Option<i64> {
+    match value.get(key)? {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn id_list(body: &Value, key: &str) -> Vec<String> {
+    body.get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|id| match id {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_conversation(login_uid: &str, item: &Value) -> Option<im_ai_conversation::Model> {
+    let id = json_string(item, "id")?;
+    let create_time =
+        json_time(item, "createTime").unwrap_or_else(|| Utc::now().timestamp_millis());
+    Some(im_ai_conversation::Model {
+        id,
+        login_uid: login_uid.to_string(),
+        title: json_string(item, "title").unwrap_or_default(),
+        role_id: json_string(item, "roleId"),
+        model_id: json_string(item, "modelId"),
+        pinned: item.get("pinned").and_then(Value::as_bool).unwrap_or(false),
+        create_time,
+        update_time: json_time(item, "updateTime").unwrap_or(create_time),
+    })
+}
+
+fn parse_message(
+    login_uid: &str,
+    conversation_id: &str,
+    item: &Value,
+) -> Option<im_ai_message::Model> {
+    let id = json_string(item, "id")?;
+    let create_time =
+        json_time(item, "createTime").unwrap_or_else(|| Utc::now().timestamp_millis());
+    Some(im_ai_message::Model {
+        id,
+        login_uid: login_uid.to_string(),
+        conversation_id: json_string(item, "conversationId")
+            .unwrap_or_else(|| conversation_id.to_string()),
+        msg_type: json_string(item, "type").unwrap_or_else(|| "assistant".to_string()),
+        content: json_string(item, "content").unwrap_or_default(),
+        reasoning_content: json_string(item, "reasoningContent"),
+        model: json_string(item, "model"),
+        status: STATUS_DONE.to_string(),
+        local: false,
+        create_time,
+        update_time: json_time(item, "updateTime").unwrap_or(create_time),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn detects_requests_to_sync() {
+        let params = json!({ "conversationId": 12, "pageNo": 1 });
+        assert_eq!(
+            AiChatSync::from_request(&ImUrl::MessageListByConversationId, None, Some(&params)),
+            Some(AiChatSync::Messages {
+                conversation_id: "12".to_string()
+            })
+        );
+
+        let body = json!({ "conversationIdList": ["1", 2] });
+        assert_eq!(
+            AiChatSync::from_request(&ImUrl::ConversationDeleteMy, Some(&body), None),
+            Some(AiChatSync::DeleteConversations {
+                ids: vec!["1".to_string(), "2".to_string()]
+            })
+        );
+
+        assert_eq!(
+            AiChatSync::from_request(&ImUrl::MessageDelete, None, None),
+            None
+        );
+        assert_eq!(
+            AiChatSync::from_request(&ImUrl::ConversationCreateMy, Some(&body), None),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_server_records() {
+        let conversation = parse_conversation(
+            "u1",
+            &json!({ "id": 7, "title": "周报", "pinned": true, "roleId": 3, "createTime": "1700000000000" }),
+        )
+        .unwrap();
+        assert_eq!(conversation.id, "7");
+        assert_eq!(conversation.role_id.as_deref(), Some("3"));
+        assert!(conversation.pinned);
+        assert_eq!(conversation.update_time, 1_700_000_000_000);
+
+        let message = parse_message(
+            "u1",
+            "7",
+            &json!({ "id": "m1", "type": "user", "content": "你好", "createTime": 1 }),
+        )
+        .unwrap();
+        assert_eq!(message.conversation_id, "7");
+        assert_eq!(message.msg_type, "user");
+        assert_eq!(message.status, STATUS_DONE);
+        assert!(!message.local);
+
+        assert!(parse_message("u1", "7", &json!({ "content": "缺少ID" })).is_none());
+    }
+}
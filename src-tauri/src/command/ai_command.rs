@@ -1,190 +1,668 @@
 use crate::AppData;
-use crate::im_request_client::ImUrl;
+use crate::ai::knowledge;
+use crate::ai::stream_manager::ActiveStreamInfo;
+use crate::ai::{
+    AiBackend, AiMessageRequest, AiProvider, AiProviderKind, AiStreamEvent, ChatTurn, HulaProvider,
+    OpenAiProvider, StopReason, StreamLimits, TokenUsage,
+};
+use crate::command::message_command::run_with_write_lock;
+use crate::repository::im_ai_chat_repository::{
+    self, STATUS_CANCELLED, STATUS_DONE, STATUS_ERROR, STATUS_STREAMING,
+};
+use crate::secret_store;
+use crate::utils::sse::SseDecoder;
+use chrono::Utc;
+use entity::{im_ai_conversation, im_ai_message};
 use futures::StreamExt;
+use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 use tauri::{State, ipc::Channel};
-use tracing::{error, info};
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+/// 流式传输中断后基于 `Last-Event-ID` 连续重连的最大次数
+const MAX_RECONNECTS: u32 = 3;
+/// 服务端未通过 `retry` 指定时的重连间隔
+const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// 流式回答写入本地的间隔
+const TRANSCRIPT_FLUSH_INTERVAL: Duration = Duration::from_millis(300);
+/// 本地新建会话时取提问内容的前若干字符作为标题
+const CONVERSATION_TITLE_CHARS: usize = 30;
+/// 本地搜索默认返回的条数
+const AI_SEARCH_LIMIT: u64 = 50;
+
+/// 请求 OpenAI 兼容接口的 HTTP 客户端，复用连接池
+static AI_HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// 推送给前端的事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SseEventType {
+    Chunk,
+    Done,
+    Error,
+    Cancelled,
+    Usage,
+}
 
 /// SSE 流式数据事件
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SseStreamEvent {
-    /// 事件类型: "chunk" | "done" | "error"
-    pub event_type: String,
+    /// 事件类型: "chunk" | "done" | "error" | "cancelled" | "usage"
+    pub event_type: SseEventType,
+    /// 服务端 `event` 字段，默认的 message 事件为空
+    pub event: Option<String>,
+    /// 服务端事件 id
+    pub id: Option<String>,
     /// 数据内容
     pub data: Option<String>,
     /// 错误信息
     pub error: Option<String>,
     /// 请求ID，用于区分不同的请求
     pub request_id: String,
+    /// 用量统计，仅 usage 事件携带
+    pub usage: Option<StreamUsage>,
 }
 
-/// AI 消息发送请求参数
+/// 流式任务结束时的用量统计，无论回答以何种方式结束都会作为最后一个事件发送
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct AiMessageRequest {
-    pub conversation_id: String,
-    pub content: String,
-    pub use_context: Option<bool>,
-    pub reasoning_enabled: Option<bool>,
+pub struct StreamUsage {
+    /// 回答的最终状态: "done" | "cancelled" | "error"
+    pub status: String,
+    /// 被停止的原因: "cancelled" | "logout" | "idleTimeout" | "totalTimeout"
+    pub stop_reason: Option<String>,
+    /// 收到的回答片段数
+    pub chunks: u64,
+    /// 收到的回答字符数
+    pub chars: u64,
+    pub duration_ms: u64,
+    /// 服务端统计的 token 用量，服务端未提供时为空
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub total_tokens: Option<u64>,
+}
+
+impl SseStreamEvent {
+    fn chunk(request_id: &str, content: String, event: Option<String>, id: Option<String>) -> Self {
+        Self {
+            event_type: SseEventType::Chunk,
+            event,
+            id,
+            data: Some(content),
+            error: None,
+            request_id: request_id.to_string(),
+            usage: None,
+        }
+    }
+
+    fn done(request_id: &str, content: String) -> Self {
+        Self {
+            event_type: SseEventType::Done,
+            event: None,
+            id: None,
+            data: Some(content),
+            error: None,
+            request_id: request_id.to_string(),
+            usage: None,
+        }
+    }
+
+    fn error(request_id: &str, error: String) -> Self {
+        Self {
+            event_type: SseEventType::Error,
+            event: None,
+            id: None,
+            data: None,
+            error: Some(error),
+            request_id: request_id.to_string(),
+            usage: None,
+        }
+    }
+
+    fn cancelled(request_id: &str, content: String) -> Self {
+        Self {
+            event_type: SseEventType::Cancelled,
+            event: None,
+            id: None,
+            data: Some(content),
+            error: None,
+            request_id: request_id.to_string(),
+            usage: None,
+        }
+    }
+
+    fn usage(request_id: &str, usage: StreamUsage) -> Self {
+        Self {
+            event_type: SseEventType::Usage,
+            event: None,
+            id: None,
+            data: None,
+            error: None,
+            request_id: request_id.to_string(),
+            usage: Some(usage),
+        }
+    }
 }
 
 /// 发送 AI 消息并监听 SSE 流式响应
+///
+/// 按配置经 HuLa 服务端或自建的 OpenAI 兼容接口对话，推送给前端的事件格式相同。
+/// 传输中断时，如果服务端下发过事件 id，会携带 `Last-Event-ID` 重连并继续接收。
+/// 每个用户同时进行的回答数量有上限，长时间没有数据或超过总时长的回答会被停止，
+/// 被取消时发送携带部分回答的 cancelled 事件，无论以何种方式结束，最后都会发送一个 usage 事件。
+/// `useKnowledge` 为 true 时先检索本地知识库，命中的片段作为参考资料随请求发送
 #[tauri::command]
 pub async fn ai_message_send_stream(
     state: State<'_, AppData>,
-    body: AiMessageRequest,
+    mut body: AiMessageRequest,
     request_id: String,
     on_event: Channel<SseStreamEvent>,
 ) -> Result<(), String> {
     info!("开始发送 AI 流式消息请求, body: {:?}", body);
 
-    // 使用 ImRequestClient 发送流式请求
-    let response = {
-        let mut rc = state.rc.lock().await;
-        let (method, path) = ImUrl::MessageSendStream.get_url();
-
-        rc.request_stream(method, path, Some(body), None::<serde_json::Value>)
-            .await
-            .map_err(|e| {
-                error!("发送流式请求失败: {}", e);
-                let error_event = SseStreamEvent {
-                    event_type: "error".to_string(),
-                    data: None,
-                    error: Some(e.to_string()),
-                    request_id: request_id.clone(),
-                };
-                let _ = on_event.send(error_event);
-                e.to_string()
-            })?
-    }; // 锁在这里释放
+    let send_error = |e: String| {
+        let _ = on_event.send(SseStreamEvent::error(&request_id, e.clone()));
+        e
+    };
+
+    let provider = ai_backend(&state).await.map_err(send_error)?;
+    let login_uid = state.user_info.lock().await.uid.clone();
+    let db = state.db_conn.read().await.clone();
+    let limits = stream_limits(&state).await;
+
+    let mut handle = state
+        .ai_streams
+        .register(
+            &request_id,
+            &login_uid,
+            &body.conversation_id,
+            provider.kind(),
+            limits,
+        )
+        .map_err(send_error)?;
+
+    let history = if provider.needs_history() && body.use_context != Some(false) {
+        load_history(&db, &login_uid, &body.conversation_id).await
+    } else {
+        Vec::new()
+    };
+
+    if body.use_knowledge == Some(true) {
+        match knowledge::retrieve(&db, &login_uid, &body.content, body.knowledge_top_k).await {
+            Ok(hits) => {
+                info!("本地知识库命中 {} 个片段", hits.len());
+                body.knowledge_context = knowledge::build_context(&hits);
+            }
+            Err(e) => warn!("检索本地知识库失败: {}", e),
+        }
+    }
+
+    let response = match handle.guard(provider.send(&body, &history, None)).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => {
+            error!("发送流式请求失败: {}", e);
+            return Err(send_error(e.to_string()));
+        }
+        Err(reason) => {
+            info!("AI 流式请求在连接前被停止: {}", reason.as_str());
+            return Err(send_error(stop_message(reason, &limits)));
+        }
+    };
 
-    info!("SSE 连接已建立，开始监听流式数据...");
+    info!(
+        "SSE 连接已建立（{}），开始监听流式数据...",
+        provider.kind().as_str()
+    );
 
-    // 在后台任务中处理 SSE 事件流
-    let request_id_clone = request_id.clone();
+    let mut transcript =
+        TranscriptWriter::start(db, state.write_lock.clone(), login_uid, &body, &request_id).await;
 
-    let join_handle = tokio::spawn(async move {
-        let mut stream = response.bytes_stream();
+    // 在后台任务中处理 SSE 事件流，任务结束时句柄被丢弃并从管理器中移除
+    tokio::spawn(async move {
+        let mut response = response;
+        let mut decoder = SseDecoder::new();
         let mut full_content = String::new();
-        let mut buffer = String::new();
-
-        while let Some(chunk_result) = stream.next().await {
-            match chunk_result {
-                Ok(chunk) => {
-                    // 将字节转换为字符串
-                    if let Ok(text) = String::from_utf8(chunk.to_vec()) {
-                        info!("收到原始数据块 (长度: {}): {:?}", text.len(), text);
-                        buffer.push_str(&text);
-
-                        // 处理 SSE 格式的数据
-                        // SSE 格式: data: <content>\n\n
-                        while let Some(pos) = buffer.find("\n\n") {
-                            let message = buffer[..pos].to_string();
-                            buffer = buffer[pos + 2..].to_string();
-
-                            info!("处理SSE消息: {:?}", message);
-
-                            // 解析 SSE 消息
-                            for line in message.lines() {
-                                info!("处理行: {:?}", line);
-
-                                if let Some(data) = line.strip_prefix("data: ") {
-                                    info!("收到 SSE 数据: {}", data);
-
-                                    // 累积内容
-                                    full_content.push_str(data);
-
-                                    // 发送数据块事件到前端
-                                    let chunk_event = SseStreamEvent {
-                                        event_type: "chunk".to_string(),
-                                        data: Some(data.to_string()),
-                                        error: None,
-                                        request_id: request_id_clone.clone(),
-                                    };
-
-                                    if let Err(e) = on_event.send(chunk_event) {
-                                        error!("发送 chunk 事件失败: {}", e);
-                                    }
-                                } else if line.starts_with("data:") {
-                                    // 处理没有空格的情况: data:<content>
-                                    let data = &line[5..];
-                                    info!("收到 SSE 数据 (无空格): {}", data);
-
-                                    // 累积内容
-                                    full_content.push_str(data);
-
-                                    // 发送数据块事件到前端
-                                    let chunk_event = SseStreamEvent {
-                                        event_type: "chunk".to_string(),
-                                        data: Some(data.to_string()),
-                                        error: None,
-                                        request_id: request_id_clone.clone(),
-                                    };
-
-                                    if let Err(e) = on_event.send(chunk_event) {
-                                        error!("发送 chunk 事件失败: {}", e);
-                                    }
-                                }
+        let mut token_usage = TokenUsage::default();
+        let mut reconnects = 0;
+
+        let end = 'stream: loop {
+            let mut stream = response.bytes_stream();
+            let mut failure = None;
+
+            loop {
+                let chunk = match handle.guard(stream.next()).await {
+                    Ok(Some(Ok(chunk))) => chunk,
+                    Ok(Some(Err(e))) => {
+                        failure = Some(e);
+                        break;
+                    }
+                    Ok(None) => break,
+                    Err(reason) => break 'stream StreamEnd::Stopped(reason),
+                };
+                for event in decoder.feed(&chunk) {
+                    reconnects = 0;
+                    match provider.decode(event) {
+                        AiStreamEvent::Delta { content, event, id } => {
+                            handle.record_chunk(&content);
+                            full_content.push_str(&content);
+                            transcript.push(&content).await;
+                            let chunk_event =
+                                SseStreamEvent::chunk(&request_id, content, event, id);
+                            if let Err(e) = on_event.send(chunk_event) {
+                                error!("发送 chunk 事件失败: {}", e);
                             }
                         }
-                    } else {
-                        error!("无法将字节转换为UTF-8字符串");
+                        AiStreamEvent::Done => break 'stream StreamEnd::Done,
+                        AiStreamEvent::Error(message) => {
+                            error!("服务端返回错误事件: {}", message);
+                            break 'stream StreamEnd::Failed(message);
+                        }
+                        AiStreamEvent::Usage(usage) => token_usage = usage,
+                        AiStreamEvent::Skip => {}
                     }
                 }
-                Err(e) => {
+            }
+
+            let Some(e) = failure else {
+                break 'stream StreamEnd::Done;
+            };
+            // 断开时未以空行结束的事件不完整，直接丢弃
+            decoder.reset();
+            let last_event_id = match decoder.last_event_id() {
+                Some(id) if reconnects < MAX_RECONNECTS => id.to_string(),
+                _ => {
                     error!("读取流数据失败: {}", e);
-                    let error_event = SseStreamEvent {
-                        event_type: "error".to_string(),
-                        data: None,
-                        error: Some(e.to_string()),
-                        request_id: request_id_clone.clone(),
-                    };
-
-                    if let Err(e) = on_event.send(error_event) {
-                        error!("发送 error 事件失败: {}", e);
-                    }
-                    break;
+                    break 'stream StreamEnd::Failed(e.to_string());
+                }
+            };
+            reconnects += 1;
+            warn!(
+                "SSE 连接中断: {}，第 {} 次重连，Last-Event-ID: {}",
+                e, reconnects, last_event_id
+            );
+            let delay = decoder.retry().unwrap_or(DEFAULT_RECONNECT_DELAY);
+            if let Err(reason) = handle.guard(tokio::time::sleep(delay)).await {
+                break 'stream StreamEnd::Stopped(reason);
+            }
+            response = match handle
+                .guard(provider.send(&body, &history, Some(&last_event_id)))
+                .await
+            {
+                Ok(Ok(response)) => response,
+                Ok(Err(e)) => {
+                    error!("SSE 重连失败: {}", e);
+                    break 'stream StreamEnd::Failed(e.to_string());
                 }
+                Err(reason) => break 'stream StreamEnd::Stopped(reason),
+            };
+        };
+
+        let (status, stop_reason) = match end {
+            StreamEnd::Done => {
+                info!("SSE 流正常结束，总内容长度: {}", full_content.len());
+                transcript.finish(STATUS_DONE).await;
+                if let Err(e) = on_event.send(SseStreamEvent::done(&request_id, full_content)) {
+                    error!("发送 done 事件失败: {}", e);
+                }
+                (STATUS_DONE, None)
+            }
+            StreamEnd::Failed(message) => {
+                transcript.finish(STATUS_ERROR).await;
+                let _ = on_event.send(SseStreamEvent::error(&request_id, message));
+                (STATUS_ERROR, None)
             }
+            StreamEnd::Stopped(reason) if reason.is_timeout() => {
+                warn!("AI 流式任务超时: {}, {}", request_id, reason.as_str());
+                transcript.finish(STATUS_ERROR).await;
+                let message = stop_message(reason, &limits);
+                let _ = on_event.send(SseStreamEvent::error(&request_id, message));
+                (STATUS_ERROR, Some(reason))
+            }
+            StreamEnd::Stopped(reason) => {
+                info!("AI 流式任务已停止: {}, {}", request_id, reason.as_str());
+                // 保留已收到的部分回答
+                transcript.finish(STATUS_CANCELLED).await;
+                if let Err(e) = on_event.send(SseStreamEvent::cancelled(&request_id, full_content))
+                {
+                    error!("发送 cancelled 事件失败: {}", e);
+                }
+                (STATUS_CANCELLED, Some(reason))
+            }
+        };
+
+        let usage = StreamUsage {
+            status: status.to_string(),
+            stop_reason: stop_reason.map(|reason| reason.as_str().to_string()),
+            chunks: handle.chunks(),
+            chars: handle.chars(),
+            duration_ms: handle.elapsed().as_millis() as u64,
+            prompt_tokens: token_usage.prompt_tokens,
+            completion_tokens: token_usage.completion_tokens,
+            total_tokens: token_usage.total_tokens,
+        };
+        if let Err(e) = on_event.send(SseStreamEvent::usage(&request_id, usage)) {
+            error!("发送 usage 事件失败: {}", e);
         }
+    });
 
-        // 流结束，发送完成事件
-        info!("SSE 流正常结束，总内容长度: {}", full_content.len());
-        let done_event = SseStreamEvent {
-            event_type: "done".to_string(),
-            data: Some(full_content),
-            error: None,
-            request_id: request_id_clone.clone(),
+    Ok(())
+}
+
+/// 流式任务的结束方式
+enum StreamEnd {
+    Done,
+    Failed(String),
+    Stopped(StopReason),
+}
+
+/// 流式任务被停止时返回给前端的提示
+fn stop_message(reason: StopReason, limits: &StreamLimits) -> String {
+    match reason {
+        StopReason::Cancelled => "AI 回答已取消".to_string(),
+        StopReason::Logout => "已退出登录，AI 回答已停止".to_string(),
+        StopReason::IdleTimeout => format!(
+            "AI 回答超时：{} 秒内未收到数据",
+            limits.idle_timeout.as_secs()
+        ),
+        StopReason::TotalTimeout => {
+            format!("AI 回答超时：超过 {} 秒", limits.total_timeout.as_secs())
+        }
+    }
+}
+
+/// 把流式回答逐步写入本地消息表
+/// 按间隔批量追加，任务被取消时最多丢失最后一个间隔内收到的内容
+struct TranscriptWriter {
+    db: DatabaseConnection,
+    write_lock: Arc<Mutex<()>>,
+    login_uid: String,
+    message_id: String,
+    pending: String,
+    last_flush: Instant,
+}
+
+impl TranscriptWriter {
+    /// 写入提问和一条接收中的空白回答，回答使用请求ID作为消息ID
+    async fn start(
+        db: DatabaseConnection,
+        write_lock: Arc<Mutex<()>>,
+        login_uid: String,
+        body: &AiMessageRequest,
+        request_id: &str,
+    ) -> Self {
+        let now = Utc::now().timestamp_millis();
+        let title: String = body
+            .content
+            .chars()
+            .take(CONVERSATION_TITLE_CHARS)
+            .collect();
+        let question = im_ai_message::Model {
+            id: format!("{}-user", request_id),
+            login_uid: login_uid.clone(),
+            conversation_id: body.conversation_id.clone(),
+            msg_type: "user".to_string(),
+            content: body.content.clone(),
+            reasoning_content: None,
+            model: None,
+            status: STATUS_DONE.to_string(),
+            local: true,
+            create_time: now,
+            update_time: now,
+        };
+        let answer = im_ai_message::Model {
+            id: request_id.to_string(),
+            msg_type: "assistant".to_string(),
+            content: String::new(),
+            status: STATUS_STREAMING.to_string(),
+            create_time: now + 1,
+            update_time: now + 1,
+            ..question.clone()
         };
 
-        if let Err(e) = on_event.send(done_event) {
-            error!("发送 done 事件失败: {}", e);
+        let result = run_with_write_lock(write_lock.clone(), "start_ai_transcript", || async {
+            im_ai_chat_repository::touch_conversation(
+                &db,
+                &login_uid,
+                &body.conversation_id,
+                &title,
+            )
+            .await?;
+            im_ai_chat_repository::insert_message(&db, question.clone()).await?;
+            im_ai_chat_repository::insert_message(&db, answer.clone()).await
+        })
+        .await;
+        if let Err(e) = result {
+            warn!("保存 AI 对话记录失败: {}", e);
         }
 
-        info!("SSE 流处理完成");
-    });
+        Self {
+            db,
+            write_lock,
+            login_uid,
+            message_id: request_id.to_string(),
+            pending: String::new(),
+            last_flush: Instant::now(),
+        }
+    }
 
-    {
-        let mut tasks = state.stream_tasks.lock().await;
-        tasks.insert(request_id.clone(), join_handle);
+    async fn push(&mut self, delta: &str) {
+        self.pending.push_str(delta);
+        if self.last_flush.elapsed() >= TRANSCRIPT_FLUSH_INTERVAL {
+            self.flush().await;
+        }
     }
 
-    Ok(())
+    async fn flush(&mut self) {
+        self.last_flush = Instant::now();
+        if self.pending.is_empty() {
+            return;
+        }
+        let delta = std::mem::take(&mut self.pending);
+        if let Err(e) = run_with_write_lock(self.write_lock.clone(), "append_ai_transcript", || {
+            im_ai_chat_repository::append_message_content(
+                &self.db,
+                &self.message_id,
+                &self.login_uid,
+                &delta,
+            )
+        })
+        .await
+        {
+            warn!("写入 AI 回答失败: {}", e);
+        }
+    }
+
+    async fn finish(&mut self, status: &str) {
+        self.flush().await;
+        if let Err(e) = run_with_write_lock(self.write_lock.clone(), "finish_ai_transcript", || {
+            im_ai_chat_repository::finish_streaming_message(
+                &self.db,
+                &self.message_id,
+                &self.login_uid,
+                status,
+            )
+        })
+        .await
+        {
+            warn!("更新 AI 回答状态失败: {}", e);
+        }
+    }
+}
+
+/// 按配置选择对话服务，未配置时经 HuLa 服务端
+async fn ai_backend(state: &AppData) -> Result<AiBackend, String> {
+    let config = state.config.lock().await;
+    let settings = config.ai.clone().unwrap_or_default();
+    let kind = match settings.provider.as_deref().filter(|p| !p.is_empty()) {
+        Some(provider) => AiProviderKind::parse(provider)
+            .ok_or_else(|| format!("不支持的AI服务类型: {}", provider))?,
+        None => AiProviderKind::Hula,
+    };
+
+    match kind {
+        AiProviderKind::Hula => Ok(AiBackend::Hula(HulaProvider::new(state.rc.clone()))),
+        AiProviderKind::OpenAi => {
+            let openai = settings
+                .openai
+                .ok_or_else(|| "未配置 OpenAI 兼容接口".to_string())?;
+            let client = match AI_HTTP_CLIENT.get() {
+                Some(client) => client.clone(),
+                None => {
+                    // 流式回答持续时间不定，不设置整体请求超时
+                    let client = config
+                        .http
+                        .clone()
+                        .unwrap_or_default()
+                        .client_builder()
+                        .and_then(|builder| builder.build().map_err(anyhow::Error::from))
+                        .map_err(|e| format!("Failed to create HTTP client: {e}"))?;
+                    AI_HTTP_CLIENT.get_or_init(|| client).clone()
+                }
+            };
+            let api_key = match state.rc.secret_store() {
+                Some(store) => secret_store::load_ai_api_key(store.as_ref())
+                    .map_err(|e| format!("读取 API Key 失败: {e}"))?,
+                None => None,
+            };
+            Ok(AiBackend::OpenAi(OpenAiProvider::new(
+                client, &openai, api_key,
+            )))
+        }
+    }
+}
+
+/// 读取流式任务的并发和超时配置
+async fn stream_limits(state: &AppData) -> StreamLimits {
+    let settings = state.config.lock().await.ai.clone().unwrap_or_default();
+    StreamLimits {
+        max_per_user: settings.max_concurrent_streams(),
+        idle_timeout: settings.idle_timeout(),
+        total_timeout: settings.total_timeout(),
+    }
 }
 
-/// 取消指定请求ID的 AI 流式任务
+/// 从本地记录读取会话历史，只使用已完整结束且有内容的消息
+async fn load_history(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    conversation_id: &str,
+) -> Vec<ChatTurn> {
+    match im_ai_chat_repository::list_messages(db, login_uid, conversation_id).await {
+        Ok(messages) => messages
+            .into_iter()
+            .filter(|message| message.status == STATUS_DONE && !message.content.is_empty())
+            .map(|message| ChatTurn {
+                role: message.msg_type,
+                content: message.content,
+            })
+            .collect(),
+        Err(e) => {
+            warn!("读取 AI 会话历史失败: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// 取消当前用户指定请求ID的 AI 流式任务，任务会保存已收到的部分回答并发送 cancelled 事件
 #[tauri::command]
 pub async fn ai_message_cancel_stream(
     state: State<'_, AppData>,
     request_id: String,
 ) -> Result<(), String> {
     info!("尝试取消 AI 流式任务: {}", request_id);
-    let mut tasks = state.stream_tasks.lock().await;
-    if let Some(handle) = tasks.remove(&request_id) {
-        handle.abort();
+    let login_uid = state.user_info.lock().await.uid.clone();
+    if state
+        .ai_streams
+        .cancel(&request_id, &login_uid, StopReason::Cancelled)
+    {
         info!("AI 流式任务已取消: {}", request_id);
         return Ok(());
     }
     Err(format!("未找到指定请求ID的任务: {}", request_id))
 }
+
+/// 保存 OpenAI 兼容接口的 API Key，传空时清除
+#[tauri::command]
+pub async fn save_ai_api_key(
+    state: State<'_, AppData>,
+    api_key: Option<String>,
+) -> Result<(), String> {
+    let store = state
+        .rc
+        .secret_store()
+        .ok_or_else(|| "敏感信息存储不可用".to_string())?;
+    secret_store::save_ai_api_key(store.as_ref(), api_key.as_deref()).map_err(|e| e.to_string())
+}
+
+/// 获取当前用户正在进行的 AI 流式任务
+#[tauri::command]
+pub async fn list_active_streams(
+    state: State<'_, AppData>,
+) -> Result<Vec<ActiveStreamInfo>, String> {
+    let login_uid = state.user_info.lock().await.uid.clone();
+    Ok(state.ai_streams.list(&login_uid))
+}
+
+/// 获取本地缓存的 AI 会话列表，离线时使用
+#[tauri::command]
+pub async fn list_local_ai_conversations(
+    state: State<'_, AppData>,
+) -> Result<Vec<im_ai_conversation::Model>, String> {
+    let login_uid = state.user_info.lock().await.uid.clone();
+    let db = state.db_conn.read().await.clone();
+    Ok(im_ai_chat_repository::list_conversations(&db, &login_uid).await?)
+}
+
+/// 获取本地缓存的会话消息，包含被取消或中断的部分回答
+#[tauri::command]
+pub async fn list_local_ai_messages(
+    state: State<'_, AppData>,
+    conversation_id: String,
+) -> Result<Vec<im_ai_message::Model>, String> {
+    let login_uid = state.user_info.lock().await.uid.clone();
+    let db = state.db_conn.read().await.clone();
+    Ok(im_ai_chat_repository::list_messages(&db, &login_uid, &conversation_id).await?)
+}
+
+/// 本地 AI 消息搜索结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiMessageSearchResult {
+    #[serde(flatten)]
+    pub message: im_ai_message::Model,
+    pub conversation_title: Option<String>,
+    /// 命中关键词附近的内容摘要
+    pub snippet: Option<String>,
+}
+
+/// 搜索本地缓存的 AI 消息，可限定在单个会话内
+#[tauri::command]
+pub async fn search_local_ai_messages(
+    state: State<'_, AppData>,
+    keyword: String,
+    conversation_id: Option<String>,
+    limit: Option<u64>,
+) -> Result<Vec<AiMessageSearchResult>, String> {
+    let login_uid = state.user_info.lock().await.uid.clone();
+    let db = state.db_conn.read().await.clone();
+    let hits = im_ai_chat_repository::search_messages(
+        &db,
+        &login_uid,
+        &keyword,
+        conversation_id.as_deref(),
+        limit.unwrap_or(AI_SEARCH_LIMIT),
+    )
+    .await?;
+    Ok(hits
+        .into_iter()
+        .map(|hit| AiMessageSearchResult {
+            message: hit.message,
+            conversation_title: hit.conversation_title,
+            snippet: hit.snippet,
+        })
+        .collect())
+}
@@ -1,5 +1,6 @@
 use crate::AppData;
 use crate::command::message_command::MessageResp;
+use crate::pojo::common::{CursorPageParam, CursorPageResp};
 use crate::repository::im_message_repository;
 
 use serde::{Deserialize, Serialize};
@@ -12,7 +13,7 @@ pub struct ChatHistoryQueryParam {
     pub room_id: String,
     pub message_type: Option<String>, // "all", "image", "file"
     pub search_keyword: Option<String>,
-    pub sort_order: Option<String>, // "asc", "desc"
+    pub sort_order: Option<String>, // "asc", "desc", "relevance"
     pub date_range: Option<DateRange>,
     pub pagination: PaginationParam,
 }
@@ -37,6 +38,18 @@ pub struct ChatHistoryResponse {
     pub messages: Vec<MessageResp>,
     pub has_more: bool,
     pub current_page: u32,
+    /// 关键词搜索时的命中信息，顺序与 messages 一致
+    pub highlights: Vec<SearchHighlight>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHighlight {
+    pub message_id: String,
+    /// 命中片段，关键词使用 `<mark>` 标签包裹
+    pub snippet: Option<String>,
+    /// BM25 相关度得分，越大越相关
+    pub score: Option<f64>,
 }
 
 /// 查询聊天历史记录的Tauri命令
@@ -72,7 +85,7 @@ pub async fn query_chat_history(
     };
 
     // 查询数据库
-    let messages =
+    let hits =
         im_message_repository::query_chat_history(&*state.db_conn.read().await, query_condition)
             .await
             .map_err(|e| {
@@ -81,10 +94,24 @@ pub async fn query_chat_history(
             })?;
 
     // 转换为响应格式
-    let message_resps: Vec<MessageResp> = messages
-        .into_iter()
-        .map(|msg| crate::command::message_command::convert_message_to_resp(msg, None))
-        .collect();
+    let has_keyword = param
+        .search_keyword
+        .as_deref()
+        .is_some_and(|keyword| !keyword.trim().is_empty());
+    let mut highlights = Vec::new();
+    let mut message_resps: Vec<MessageResp> = Vec::with_capacity(hits.len());
+    for hit in hits {
+        if has_keyword {
+            highlights.push(SearchHighlight {
+                message_id: hit.record.message.id.clone(),
+                snippet: hit.snippet,
+                score: hit.score,
+            });
+        }
+        message_resps.push(crate::command::message_command::convert_message_to_resp(
+            hit.record, None,
+        ));
+    }
 
     // 根据返回的消息数量判断是否还有更多数据
     let has_more = message_resps.len() >= param.pagination.page_size as usize;
@@ -93,11 +120,104 @@ pub async fn query_chat_history(
         messages: message_resps,
         has_more,
         current_page: param.pagination.page,
+        highlights,
     };
 
     Ok(response)
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GlobalSearchParam {
+    pub keyword: String,
+    pub message_type: Option<String>, // "all", "image", "file"
+    /// 每个房间返回的命中消息条数，默认 3
+    pub hits_per_room: Option<u32>,
+    #[serde(flatten)]
+    pub cursor_page_param: CursorPageParam,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomSearchResult {
+    pub room_id: String,
+    pub room_name: Option<String>,
+    pub avatar: Option<String>,
+    pub hit_count: u64,
+    pub last_hit_time: i64,
+    pub messages: Vec<MessageResp>,
+    pub highlights: Vec<SearchHighlight>,
+}
+
+/// 跨房间搜索本地消息的Tauri命令，结果按房间分组
+#[tauri::command]
+pub async fn search_all_messages(
+    param: GlobalSearchParam,
+    state: State<'_, AppData>,
+) -> Result<CursorPageResp<Vec<RoomSearchResult>>, String> {
+    info!(
+        "跨房间搜索消息 - 关键词: {}, 消息类型: {:?}, 游标: {}",
+        param.keyword, param.message_type, param.cursor_page_param.cursor
+    );
+
+    let login_uid = {
+        let user_info = state.user_info.lock().await;
+        user_info.uid.clone()
+    };
+
+    let message_type = parse_message_type(&param.message_type);
+    let db_result = im_message_repository::search_all_messages(
+        &*state.db_conn.read().await,
+        &login_uid,
+        &param.keyword,
+        message_type.as_deref(),
+        param.hits_per_room.unwrap_or(3),
+        param.cursor_page_param,
+    )
+    .await
+    .map_err(|e| {
+        error!("跨房间搜索消息失败: {}", e);
+        e.to_string()
+    })?;
+
+    let list = db_result
+        .list
+        .unwrap_or_default()
+        .into_iter()
+        .map(|group| {
+            let mut messages = Vec::with_capacity(group.hits.len());
+            let mut highlights = Vec::with_capacity(group.hits.len());
+            for hit in group.hits {
+                highlights.push(SearchHighlight {
+                    message_id: hit.record.message.id.clone(),
+                    snippet: hit.snippet,
+                    score: hit.score,
+                });
+                messages.push(crate::command::message_command::convert_message_to_resp(
+                    hit.record, None,
+                ));
+            }
+
+            RoomSearchResult {
+                room_id: group.room_id,
+                room_name: group.room_name,
+                avatar: group.avatar,
+                hit_count: group.hit_count,
+                last_hit_time: group.last_hit_time,
+                messages,
+                highlights,
+            }
+        })
+        .collect();
+
+    Ok(CursorPageResp {
+        cursor: db_result.cursor,
+        is_last: db_result.is_last,
+        list: Some(list),
+        total: db_result.total,
+    })
+}
+
 /// 内部查询条件结构
 #[derive(Debug, Clone)]
 pub struct ChatHistoryQueryCondition {
@@ -114,6 +234,8 @@ pub struct ChatHistoryQueryCondition {
 pub enum SortOrder {
     Asc,
     Desc,
+    /// 按关键词相关度排序，无关键词时等同于 Desc
+    Relevance,
 }
 
 /// 解析消息类型筛选条件
@@ -140,6 +262,7 @@ fn parse_sort_order(sort_order: &Option<String>) -> SortOrder {
     match sort_order {
         Some(order) => match order.as_str() {
             "asc" => SortOrder::Asc,
+            "relevance" => SortOrder::Relevance,
             "desc" | _ => SortOrder::Desc,
         },
         None => SortOrder::Desc, // 默认降序（最新的在前）
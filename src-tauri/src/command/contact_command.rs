@@ -1,5 +1,5 @@
 use crate::AppData;
-use crate::command::token_helper::{capture_token_snapshot_arc, persist_token_if_refreshed_arc};
+use crate::command::token_helper::{capture_token_snapshot, persist_token_if_refreshed};
 use crate::error::CommonError;
 use crate::im_request_client::{ImRequestClient, ImUrl};
 use crate::repository::im_contact_repository::{
@@ -11,7 +11,7 @@ use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tauri::State;
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::RwLock;
 use tracing::{error, info};
 
 #[tauri::command]
@@ -69,14 +69,12 @@ pub async fn list_contacts_command(
 /// 获取并更新联系人数据
 async fn fetch_and_update_contacts(
     db_conn: Arc<RwLock<DatabaseConnection>>,
-    request_client: Arc<Mutex<ImRequestClient>>,
+    request_client: ImRequestClient,
     login_uid: String,
 ) -> Result<Vec<im_contact::Model>, CommonError> {
-    let old_tokens = capture_token_snapshot_arc(&request_client).await;
+    let old_tokens = capture_token_snapshot(&request_client);
 
     let resp: Option<Vec<im_contact::Model>> = request_client
-        .lock()
-        .await
         .im_request(
             ImUrl::GetContactList,
             None::<serde_json::Value>,
@@ -84,7 +82,7 @@ async fn fetch_and_update_contacts(
         )
         .await?;
 
-    persist_token_if_refreshed_arc(&old_tokens, &request_client, &db_conn, &login_uid).await;
+    persist_token_if_refreshed(&old_tokens, &request_client, &login_uid);
 
     if let Some(data) = resp {
         // 保存到本地数据库
@@ -127,12 +125,10 @@ pub async fn hide_contact_command(
             user_info.uid.clone()
         };
 
-        let old_tokens = capture_token_snapshot_arc(&state.rc).await;
+        let old_tokens = capture_token_snapshot(&state.rc);
 
         let resp: Option<bool> = state
             .rc
-            .lock()
-            .await
             .im_request(
                 ImUrl::SetHide,
                 Some(data.clone()),
@@ -140,7 +136,7 @@ pub async fn hide_contact_command(
             )
             .await?;
 
-        persist_token_if_refreshed_arc(&old_tokens, &state.rc, &state.db_conn, &login_uid).await;
+        persist_token_if_refreshed(&old_tokens, &state.rc, &login_uid);
 
         if let Some(_) = resp {
             // 更新本地数据库
@@ -1,8 +1,13 @@
 use crate::AppData;
-use crate::configuration::get_configuration;
+use crate::command::message_command::spawn_search_index_backfill;
+use crate::command::token_helper::migrate_legacy_tokens;
+use crate::configuration::{DatabaseSettings, get_configuration};
 use crate::error::CommonError;
+use crate::repository::im_ai_chat_repository;
 use crate::repository::im_message_repository::reset_table_initialization_flags;
+use crate::utils::db_crypto::{self, DbKeyStore};
 use migration::{Migrator, MigratorTrait};
+use sea_orm::DatabaseConnection;
 use tauri::{AppHandle, State};
 use tracing::info;
 
@@ -30,10 +35,10 @@ pub async fn switch_user_database(
         let configuration = get_configuration(&app_handle)
             .map_err(|e| anyhow::anyhow!("Failed to load configuration: {}", e))?;
 
-        // 创建新的数据库连接
+        // 创建新的数据库连接，旧版本遗留的明文数据库会在打开前迁移为加密数据库
         let new_db = configuration
             .database
-            .connection_string(&app_handle, Some(&uid))
+            .connect_or_fallback(&app_handle, Some(&uid))
             .await?;
 
         // 执行数据库迁移
@@ -49,6 +54,29 @@ pub async fn switch_user_database(
         // 重置表初始化标志，确保新数据库会创建必要的表
         reset_table_initialization_flags();
 
+        // 旧版本把 token 保存在用户数据库中，切换时迁移到凭证存储
+        if let Some(store) = state.rc.secret_store() {
+            if let Err(e) = migrate_legacy_tokens(&new_db, store.as_ref(), &uid).await {
+                tracing::warn!("Failed to migrate legacy tokens for user {}: {}", uid, e);
+            }
+        }
+
+        // 上次退出时仍在接收的 AI 回答不会再更新，标记为已中断
+        let active_ids: Vec<String> = state
+            .ai_streams
+            .list(&uid)
+            .into_iter()
+            .map(|stream| stream.request_id)
+            .collect();
+        if let Err(e) =
+            im_ai_chat_repository::mark_interrupted_messages(&new_db, &uid, &active_ids).await
+        {
+            tracing::warn!("Failed to mark interrupted AI messages for {}: {}", uid, e);
+        }
+
+        // 为迁移前已有的消息回填全文索引
+        spawn_search_index_backfill(new_db.clone(), state.write_lock.clone(), uid.to_string());
+
         // 替换数据库连接
         {
             let mut db_guard = state.db_conn.write().await;
@@ -62,3 +90,80 @@ pub async fn switch_user_database(
 
     result.map_err(|e| e.to_string())
 }
+
+/// 重新加密当前用户的数据库
+/// 生成新的用户盐值并派生新密钥，使用新密钥导出整个数据库后替换原文件
+///
+/// # 参数
+/// * `state` - 应用状态
+/// * `app_handle` - Tauri应用句柄
+///
+/// # 返回值
+/// * `Ok(())` - 重新加密成功
+/// * `Err(String)` - 重新加密失败时返回错误信息，此时数据库仍使用旧密钥
+#[tauri::command]
+pub async fn rekey_user_database(
+    state: State<'_, AppData>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let uid = state.user_info.lock().await.uid.clone();
+    let uid = Some(uid.as_str()).filter(|id| !id.is_empty());
+    info!("Re-keying database for uid: {:?}", uid);
+
+    let result: Result<(), CommonError> = async {
+        let configuration = get_configuration(&app_handle)
+            .map_err(|e| anyhow::anyhow!("Failed to load configuration: {}", e))?;
+        let db_path = DatabaseSettings::database_path(&app_handle, uid)?;
+        let store = DbKeyStore::for_database(&db_path);
+
+        // 先准备好新旧密钥，之后的任何失败都不能让连接停留在断开状态
+        let current_key = store.keys(uid)?.current;
+        let new_key = store.begin_rekey(uid)?;
+
+        // 重新加密期间阻止所有读写，导出前必须关闭连接池中的所有连接
+        let _write_guard = state.write_lock.lock().await;
+        let mut db_guard = state.db_conn.write().await;
+        let old_db = std::mem::replace(&mut *db_guard, DatabaseConnection::Disconnected);
+        if let Err(e) = old_db.close().await {
+            tracing::warn!("Failed to close database before re-keying: {}", e);
+        }
+
+        let exported = db_crypto::export_database(&db_path, Some(&current_key), &new_key).await;
+        let key_saved = match &exported {
+            Ok(_) => store.commit_rekey(uid),
+            Err(e) => {
+                tracing::error!("Failed to re-key database: {}", e);
+                store.abort_rekey(uid)
+            }
+        };
+
+        // 无论是否成功都重新打开数据库：优先使用文件实际对应的密钥，再尝试另一个
+        let (first, second) = if exported.is_ok() {
+            (&new_key, &current_key)
+        } else {
+            (&current_key, &new_key)
+        };
+        let reopened = match DatabaseSettings::connect_encrypted(&db_path, first).await {
+            Ok(db) => Ok(db),
+            Err(e) => {
+                tracing::warn!("Failed to reopen database after re-keying: {}", e);
+                DatabaseSettings::connect_encrypted(&db_path, second).await
+            }
+        };
+        *db_guard = match reopened {
+            Ok(db) => db,
+            Err(e) => {
+                tracing::error!("Failed to reopen database with either key: {}", e);
+                configuration
+                    .database
+                    .connect_or_fallback(&app_handle, uid)
+                    .await?
+            }
+        };
+        key_saved?;
+        exported
+    }
+    .await;
+
+    result.map_err(|e| e.to_string())
+}
@@ -0,0 +1,156 @@
+use crate::AppData;
+use crate::configuration::{DownloadSettings, HttpSettings};
+use crate::download::{self, DownloadProgress, MediaCache};
+use crate::repository::im_message_repository;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, ipc::Channel};
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+/// 进度事件的最小发送间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+static MEDIA_CACHE: OnceCell<MediaCache> = OnceCell::const_new();
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgressPayload {
+    pub downloaded: u64,
+    /// 文件总大小，服务端未返回长度时为空
+    pub total: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadedMedia {
+    pub path: String,
+    /// 文件内容的 SHA-256
+    pub hash: String,
+    pub size: u64,
+    /// 是否直接命中本地缓存
+    pub cached: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaCacheUsage {
+    pub file_count: u64,
+    pub total_size: u64,
+    pub max_size: u64,
+}
+
+/// 下载消息中的媒体文件到本地缓存，相同内容只保存一份，中断后再次调用会继续下载
+/// 传入 `message_id` 时记录到消息的本地路径，之后查询消息时消息体中会带上 `localPath`
+#[tauri::command]
+pub async fn download_media(
+    app_handle: AppHandle,
+    url: String,
+    message_id: Option<String>,
+    on_progress: Channel<DownloadProgressPayload>,
+) -> Result<DownloadedMedia, String> {
+    let cache = media_cache(&app_handle).await?;
+    let client = download_client(&app_handle).await?;
+
+    let mut last_emit: Option<Instant> = None;
+    let (media, cached) = download::fetch(&client, cache, &url, |progress: DownloadProgress| {
+        let finished = progress.total == Some(progress.downloaded);
+        if !finished && last_emit.is_some_and(|t| t.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        last_emit = Some(Instant::now());
+        let _ = on_progress.send(DownloadProgressPayload {
+            downloaded: progress.downloaded,
+            total: progress.total,
+        });
+    })
+    .await?;
+
+    let path = media.path.to_string_lossy().to_string();
+    if let Some(message_id) = message_id {
+        let state = app_handle
+            .try_state::<AppData>()
+            .ok_or_else(|| "App state not ready".to_string())?;
+        let login_uid = state.user_info.lock().await.uid.clone();
+        let db = state.db_conn.read().await.clone();
+        if let Err(e) = im_message_repository::update_message_local_path(
+            &db,
+            &message_id,
+            &login_uid,
+            Some(&path),
+        )
+        .await
+        {
+            warn!("Failed to save local path of message {}: {}", message_id, e);
+        }
+    }
+
+    Ok(DownloadedMedia {
+        path,
+        hash: media.hash,
+        size: media.size,
+        cached,
+    })
+}
+
+/// 获取下载缓存的占用情况
+#[tauri::command]
+pub async fn get_media_cache_usage(app_handle: AppHandle) -> Result<MediaCacheUsage, String> {
+    let cache = media_cache(&app_handle).await?;
+    let (file_count, total_size) = cache.usage().await;
+    Ok(MediaCacheUsage {
+        file_count,
+        total_size,
+        max_size: cache.max_size(),
+    })
+}
+
+/// 清空下载缓存，同时清除所有账号消息中记录的本地路径
+#[tauri::command]
+pub async fn clear_media_cache(app_handle: AppHandle) -> Result<(), String> {
+    let cache = media_cache(&app_handle).await?;
+    cache.clear().await?;
+
+    if let Some(state) = app_handle.try_state::<AppData>() {
+        let db = state.db_conn.read().await.clone();
+        im_message_repository::clear_message_local_paths(&db).await?;
+    }
+    Ok(())
+}
+
+/// 下载缓存保存在应用缓存目录，所有账号共用，首次使用时按配置的容量打开
+async fn media_cache(app_handle: &AppHandle) -> Result<&'static MediaCache, String> {
+    MEDIA_CACHE
+        .get_or_try_init(|| async {
+            let root = app_handle
+                .path()
+                .app_cache_dir()
+                .map_err(|e| format!("Failed to get app_cache_dir: {e}"))?
+                .join("media");
+            let settings = match app_handle.try_state::<AppData>() {
+                Some(state) => state
+                    .config
+                    .lock()
+                    .await
+                    .download
+                    .clone()
+                    .unwrap_or_default(),
+                None => DownloadSettings::default(),
+            };
+            MediaCache::open(root, settings.cache_max_size())
+                .await
+                .map_err(String::from)
+        })
+        .await
+}
+
+/// 下载使用的 HTTP 客户端，沿用配置中的代理和证书，不设置整体请求超时
+async fn download_client(app_handle: &AppHandle) -> Result<reqwest::Client, String> {
+    let http = match app_handle.try_state::<AppData>() {
+        Some(state) => state.config.lock().await.http.clone().unwrap_or_default(),
+        None => HttpSettings::default(),
+    };
+    http.client_builder()
+        .and_then(|builder| builder.build().map_err(anyhow::Error::from))
+        .map_err(|e| format!("Failed to create HTTP client: {e}"))
+}
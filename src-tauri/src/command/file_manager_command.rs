@@ -250,6 +250,7 @@ fn convert_message_to_file_info(record: MessageWithThumbnail) -> Option<FileInfo
     let MessageWithThumbnail {
         message,
         thumbnail_path,
+        local_path,
     } = record;
 
     // 解析消息体中的文件信息
@@ -306,7 +307,7 @@ fn convert_message_to_file_info(record: MessageWithThumbnail) -> Option<FileInfo
                         .as_str()
                         .or_else(|| file_data["downloadUrl"].as_str())
                         .map(|s| s.to_string()),
-                    is_downloaded: Some(false),
+                    is_downloaded: Some(local_path.is_some()),
                     status: "completed".to_string(),
                     thumbnail_url: thumbnail_path
                         .clone()
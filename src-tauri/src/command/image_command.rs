@@ -0,0 +1,471 @@
+use crate::AppData;
+use crate::repository::im_message_repository;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{ColorType, DynamicImage, ImageDecoder, ImageEncoder, ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+/// 缩略图默认最长边
+const DEFAULT_THUMBNAIL_SIZE: u32 = 300;
+/// 有损编码的默认质量
+const DEFAULT_QUALITY: u8 = 85;
+/// 压缩到目标大小时允许降到的最低质量
+const MIN_QUALITY: u8 = 40;
+/// 降低质量仍超出目标大小时，每次缩小到原来的比例
+const DOWNSCALE_RATIO: f64 = 0.8;
+/// 最多缩小的次数
+const MAX_DOWNSCALE_STEPS: usize = 6;
+/// EXIF IFD0 中指向 GPS 信息的标签
+const EXIF_GPS_IFD_TAG: u16 = 0x8825;
+
+/// 输出图片格式，WebP 只支持无损编码
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl OutputFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageProcessOptions {
+    /// 缩略图最长边，默认 `DEFAULT_THUMBNAIL_SIZE`
+    pub thumbnail_size: Option<u32>,
+    /// 缩略图格式，默认 JPEG
+    pub thumbnail_format: Option<OutputFormat>,
+    /// 有损编码质量（1-100）
+    pub quality: Option<u8>,
+    /// 上传图片的最长边，超出时等比缩小
+    pub max_dimension: Option<u32>,
+    /// 上传图片的目标大小（字节），超出时降低质量或缩小尺寸
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessedImage {
+    /// 用于上传的图片，不需要处理时为原文件路径
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    pub size: u64,
+    pub thumbnail_path: String,
+    pub thumbnail_width: u32,
+    pub thumbnail_height: u32,
+    /// 是否移除了包含定位信息的 EXIF
+    pub gps_removed: bool,
+}
+
+/// 处理待发送的图片：按 EXIF 方向旋转，移除定位信息，按需压缩到目标大小并生成缩略图
+/// 传入 `message_id` 时缩略图路径会保存到消息的 thumbnail_path
+#[tauri::command]
+pub async fn process_outgoing_image(
+    app_handle: AppHandle,
+    path: String,
+    options: Option<ImageProcessOptions>,
+    message_id: Option<String>,
+) -> Result<ProcessedImage, String> {
+    let output_dir = app_handle
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("Failed to get app_cache_dir: {e}"))?
+        .join("images");
+    let options = options.unwrap_or_default();
+
+    let processed =
+        tokio::task::spawn_blocking(move || process_image(Path::new(&path), &output_dir, &options))
+            .await
+            .map_err(|e| format!("Image processing task failed: {e}"))??;
+
+    if let Some(message_id) = message_id {
+        let state = app_handle
+            .try_state::<AppData>()
+            .ok_or_else(|| "App state not ready".to_string())?;
+        let login_uid = state.user_info.lock().await.uid.clone();
+        let db = state.db_conn.read().await.clone();
+        if let Err(e) = im_message_repository::update_message_thumbnail_path(
+            &db,
+            &message_id,
+            &login_uid,
+            Some(&processed.thumbnail_path),
+        )
+        .await
+        {
+            warn!(
+                "Failed to save thumbnail path of message {}: {}",
+                message_id, e
+            );
+        }
+    }
+
+    Ok(processed)
+}
+
+fn process_image(
+    source: &Path,
+    output_dir: &Path,
+    options: &ImageProcessOptions,
+) -> Result<ProcessedImage, String> {
+    let data = std::fs::read(source).map_err(|e| format!("Failed to read image: {e}"))?;
+    let format =
+        image::guess_format(&data).map_err(|e| format!("Unsupported image format: {e}"))?;
+    let mut decoder = ImageReader::with_format(Cursor::new(&data), format)
+        .into_decoder()
+        .map_err(|e| format!("Failed to decode image: {e}"))?;
+    let mut exif = decoder.exif_metadata().ok().flatten();
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img =
+        DynamicImage::from_decoder(decoder).map_err(|e| format!("Failed to decode image: {e}"))?;
+    img.apply_orientation(orientation);
+
+    // 包含定位信息时不保留任何 EXIF，其余情况只清除方向标记，避免查看器再次旋转
+    let gps_removed = exif.as_deref().is_some_and(exif_has_gps);
+    if gps_removed {
+        exif = None;
+    } else if let Some(exif) = exif.as_mut() {
+        let _ = Orientation::remove_from_exif_chunk(exif);
+    }
+
+    let quality = options.quality.unwrap_or(DEFAULT_QUALITY).clamp(1, 100);
+    let oversized = options
+        .max_dimension
+        .is_some_and(|max| img.width() > max || img.height() > max);
+    let too_large = options.max_bytes.is_some_and(|max| data.len() as u64 > max);
+    // 动图重新编码会丢失动画，只生成缩略图
+    let reencode = format != ImageFormat::Gif
+        && (gps_removed || orientation != Orientation::NoTransforms || oversized || too_large);
+
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create output directory: {e}"))?;
+    let file_id = uuid::Uuid::new_v4().to_string();
+
+    let (path, size) = if reencode {
+        if let Some(max) = options.max_dimension.filter(|_| oversized) {
+            img = img.resize(max, max, FilterType::Lanczos3);
+        }
+        let output_format = match format {
+            ImageFormat::Png => OutputFormat::Png,
+            ImageFormat::WebP => OutputFormat::Webp,
+            _ => OutputFormat::Jpeg,
+        };
+        let (encoded, resized) = encode_to_size(
+            &img,
+            output_format,
+            quality,
+            options.max_bytes,
+            exif.as_deref(),
+        )?;
+        if let Some(resized) = resized {
+            img = resized;
+        }
+        let path = output_dir.join(format!("{file_id}.{}", output_format.extension()));
+        std::fs::write(&path, &encoded).map_err(|e| format!("Failed to write image: {e}"))?;
+        (path, encoded.len() as u64)
+    } else {
+        (source.to_path_buf(), data.len() as u64)
+    };
+
+    let thumbnail_size = options
+        .thumbnail_size
+        .unwrap_or(DEFAULT_THUMBNAIL_SIZE)
+        .max(1);
+    let thumbnail_format = options.thumbnail_format.unwrap_or_default();
+    let thumbnail = if img.width() > thumbnail_size || img.height() > thumbnail_size {
+        Cow::Owned(img.thumbnail(thumbnail_size, thumbnail_size))
+    } else {
+        Cow::Borrowed(&img)
+    };
+    let thumbnail_data = encode(&thumbnail, thumbnail_format, quality, None)?;
+    let thumbnail_path: PathBuf =
+        output_dir.join(format!("{file_id}_thumb.{}", thumbnail_format.extension()));
+    std::fs::write(&thumbnail_path, &thumbnail_data)
+        .map_err(|e| format!("Failed to write thumbnail: {e}"))?;
+
+    info!(
+        "Processed image {:?}: {}x{}, {} bytes, reencoded: {}, gps removed: {}",
+        source,
+        img.width(),
+        img.height(),
+        size,
+        reencode,
+        gps_removed
+    );
+    Ok(ProcessedImage {
+        path: path.to_string_lossy().to_string(),
+        width: img.width(),
+        height: img.height(),
+        size,
+        thumbnail_path: thumbnail_path.to_string_lossy().to_string(),
+        thumbnail_width: thumbnail.width(),
+        thumbnail_height: thumbnail.height(),
+        gps_removed,
+    })
+}
+
+/// 编码图片并尽量压缩到 `max_bytes` 以内：先逐步降低质量，仍然超出时缩小尺寸
+/// 缩小过尺寸时一并返回缩小后的图片
+fn encode_to_size(
+    img: &DynamicImage,
+    format: OutputFormat,
+    quality: u8,
+    max_bytes: Option<u64>,
+    exif: Option<&[u8]>,
+) -> Result<(Vec<u8>, Option<DynamicImage>), String> {
+    let Some(max_bytes) = max_bytes else {
+        return Ok((encode(img, format, quality, exif)?, None));
+    };
+
+    // 只有 JPEG 可以调整质量
+    let qualities: Vec<u8> = match format {
+        OutputFormat::Jpeg => (MIN_QUALITY..=quality.max(MIN_QUALITY))
+            .rev()
+            .step_by(10)
+            .collect(),
+        _ => vec![quality],
+    };
+
+    let mut resized: Option<DynamicImage> = None;
+    let mut encoded = Vec::new();
+    for step in 0..=MAX_DOWNSCALE_STEPS {
+        let current = resized.as_ref().unwrap_or(img);
+        for &q in &qualities {
+            encoded = encode(current, format, q, exif)?;
+            if encoded.len() as u64 <= max_bytes {
+                return Ok((encoded, resized));
+            }
+        }
+        if step == MAX_DOWNSCALE_STEPS {
+            break;
+        }
+        let width = (current.width() as f64 * DOWNSCALE_RATIO) as u32;
+        let height = (current.height() as f64 * DOWNSCALE_RATIO) as u32;
+        if width == 0 || height == 0 {
+            break;
+        }
+        resized = Some(current.resize_exact(width, height, FilterType::Triangle));
+    }
+
+    // 达不到目标大小时使用压缩程度最高的结果
+    warn!(
+        "Image could not be compressed below {} bytes, using {} bytes",
+        max_bytes,
+        encoded.len()
+    );
+    Ok((encoded, resized))
+}
+
+fn encode(
+    img: &DynamicImage,
+    format: OutputFormat,
+    quality: u8,
+    exif: Option<&[u8]>,
+) -> Result<Vec<u8>, String> {
+    // PNG 和 WebP 编码器只接受 8 位图像
+    let img = match img.color() {
+        ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8 => Cow::Borrowed(img),
+        color if color.has_alpha() => Cow::Owned(DynamicImage::ImageRgba8(img.to_rgba8())),
+        _ => Cow::Owned(DynamicImage::ImageRgb8(img.to_rgb8())),
+    };
+
+    let mut buf = Vec::new();
+    let result = match format {
+        OutputFormat::Jpeg => {
+            let mut encoder = JpegEncoder::new_with_quality(&mut buf, quality);
+            if let Some(exif) = exif {
+                let _ = encoder.set_exif_metadata(exif.to_vec());
+            }
+            img.write_with_encoder(encoder)
+        }
+        OutputFormat::Png => {
+            let mut encoder = PngEncoder::new(&mut buf);
+            if let Some(exif) = exif {
+                let _ = encoder.set_exif_metadata(exif.to_vec());
+            }
+            img.write_with_encoder(encoder)
+        }
+        OutputFormat::Webp => {
+            let mut encoder = WebPEncoder::new_lossless(&mut buf);
+            if let Some(exif) = exif {
+                let _ = encoder.set_exif_metadata(exif.to_vec());
+            }
+            img.write_with_encoder(encoder)
+        }
+    };
+    result.map_err(|e| format!("Failed to encode image: {e}"))?;
+    Ok(buf)
+}
+
+/// EXIF 的 IFD0 中是否有指向 GPS 信息的条目
+fn exif_has_gps(exif: &[u8]) -> bool {
+    let tiff = exif.strip_prefix(b"Exif\0\0").unwrap_or(exif);
+    let read_u16 = |offset: usize, little: bool| {
+        let bytes: [u8; 2] = tiff.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if little {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let read_u32 = |offset: usize, little: bool| {
+        let bytes: [u8; 4] = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+
+    let little = match tiff.get(..4) {
+        Some([0x49, 0x49, 42, 0]) => true,
+        Some([0x4d, 0x4d, 0, 42]) => false,
+        _ => return false,
+    };
+    let Some(ifd) = read_u32(4, little).map(|offset| offset as usize) else {
+        return false;
+    };
+    let Some(count) = read_u16(ifd, little) else {
+        return false;
+    };
+    (0..count as usize)
+        .filter_map(|i| read_u16(ifd + 2 + i * 12, little))
+        .any(|tag| tag == EXIF_GPS_IFD_TAG)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造小端 EXIF，包含方向标记，可选包含 GPS 指针
+    fn build_exif(orientation: u16, with_gps: bool) -> Vec<u8> {
+        let entries: Vec<(u16, u16, u32, u32)> = [
+            Some((0x0112, 3, 1, orientation as u32)),
+            with_gps.then_some((EXIF_GPS_IFD_TAG, 4, 1, 0)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        let mut exif = b"II*\0".to_vec();
+        exif.extend_from_slice(&8u32.to_le_bytes());
+        exif.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (tag, kind, count, value) in entries {
+            exif.extend_from_slice(&tag.to_le_bytes());
+            exif.extend_from_slice(&kind.to_le_bytes());
+            exif.extend_from_slice(&count.to_le_bytes());
+            exif.extend_from_slice(&value.to_le_bytes());
+        }
+        exif.extend_from_slice(&0u32.to_le_bytes());
+        exif
+    }
+
+    fn write_jpeg(path: &Path, width: u32, height: u32, exif: Vec<u8>) {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([
+                (x * 7 % 256) as u8,
+                (y * 13 % 256) as u8,
+                ((x ^ y) % 256) as u8,
+            ])
+        }));
+        let mut buf = Vec::new();
+        let mut encoder = JpegEncoder::new_with_quality(&mut buf, 95);
+        encoder.set_exif_metadata(exif).unwrap();
+        img.write_with_encoder(encoder).unwrap();
+        std::fs::write(path, buf).unwrap();
+    }
+
+    fn read_exif(path: &Path) -> Option<Vec<u8>> {
+        let mut decoder = ImageReader::open(path)
+            .unwrap()
+            .with_guessed_format()
+            .unwrap()
+            .into_decoder()
+            .unwrap();
+        decoder.exif_metadata().unwrap()
+    }
+
+    #[test]
+    fn test_rotates_and_strips_gps() {
+        let dir = std::env::temp_dir().join(format!("hula-image-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("photo.jpg");
+        // 方向 6 表示需要顺时针旋转 90 度
+        write_jpeg(&source, 640, 480, build_exif(6, true));
+
+        let options = ImageProcessOptions {
+            thumbnail_format: Some(OutputFormat::Webp),
+            ..Default::default()
+        };
+        let result = process_image(&source, &dir, &options).unwrap();
+        assert!(result.gps_removed);
+        assert_ne!(Path::new(&result.path), source);
+        assert_eq!((result.width, result.height), (480, 640));
+        assert_eq!(
+            (result.thumbnail_width, result.thumbnail_height),
+            (225, 300)
+        );
+        assert!(result.thumbnail_path.ends_with(".webp"));
+        assert!(read_exif(Path::new(&result.path)).is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_compresses_to_target_size() {
+        let dir = std::env::temp_dir().join(format!("hula-image-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("large.jpg");
+        write_jpeg(&source, 1200, 900, build_exif(1, false));
+        let original_size = std::fs::metadata(&source).unwrap().len();
+
+        let options = ImageProcessOptions {
+            max_bytes: Some(original_size / 4),
+            ..Default::default()
+        };
+        let result = process_image(&source, &dir, &options).unwrap();
+        assert!(!result.gps_removed);
+        assert!(result.size <= original_size / 4);
+        assert_eq!(std::fs::metadata(&result.path).unwrap().len(), result.size);
+        // 没有定位信息时保留 EXIF
+        let exif = read_exif(Path::new(&result.path)).unwrap();
+        assert_eq!(
+            Orientation::from_exif_chunk(&exif),
+            Some(Orientation::NoTransforms)
+        );
+
+        // 不需要处理时直接使用原图
+        let result = process_image(&source, &dir, &ImageProcessOptions::default()).unwrap();
+        assert_eq!(Path::new(&result.path), source);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_exif_has_gps() {
+        assert!(exif_has_gps(&build_exif(1, true)));
+        assert!(!exif_has_gps(&build_exif(1, false)));
+        let mut prefixed = b"Exif\0\0".to_vec();
+        prefixed.extend(build_exif(1, true));
+        assert!(exif_has_gps(&prefixed));
+        assert!(!exif_has_gps(b"not exif"));
+    }
+}
@@ -0,0 +1,316 @@
+use crate::AppData;
+use crate::ai::knowledge::{self, DocumentChunk, DocumentKind};
+use crate::command::message_command::run_with_write_lock;
+use crate::repository::im_knowledge_repository;
+use chrono::Utc;
+use entity::{im_kb_chunk, im_kb_source};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::State;
+use tracing::{info, warn};
+
+/// 单个文件的大小上限 5MB
+const MAX_SOURCE_FILE_SIZE: u64 = 5 * 1024 * 1024;
+/// 添加目录时最多索引的文件数
+const MAX_SOURCE_FILES: usize = 500;
+/// 扫描目录时跳过的依赖和构建产物目录，隐藏目录同样跳过
+const IGNORED_DIRS: &[&str] = &[
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    "vendor",
+    "__pycache__",
+];
+
+/// 读取并切分后的本地文件
+struct LoadedDocument {
+    path: String,
+    name: String,
+    kind: DocumentKind,
+    size: u64,
+    modified_time: Option<i64>,
+    chunks: Vec<DocumentChunk>,
+}
+
+/// 未能加入知识库的文件
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedKnowledgeFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/// 添加知识库来源的结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddKnowledgeSourceResult {
+    pub sources: Vec<im_kb_source::Model>,
+    pub skipped: Vec<SkippedKnowledgeFile>,
+    /// 重新添加目录时，因文件已删除而移除的来源ID
+    pub removed: Vec<String>,
+}
+
+/// 本地知识库检索结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnowledgeSearchResult {
+    #[serde(flatten)]
+    pub chunk: im_kb_chunk::Model,
+    pub source_name: String,
+    pub source_path: String,
+    /// bm25 分数，越小越相关
+    pub rank: f64,
+}
+
+/// 把本地文件或目录加入知识库，已添加过的文件重新建立索引
+///
+/// 支持 Markdown、纯文本和常见代码文件，目录会递归扫描，
+/// 目录中无法读取或不支持的文件会被跳过并在结果中说明，
+/// 目录下文件已被删除的来源会一并移除
+#[tauri::command]
+pub async fn add_knowledge_source(
+    state: State<'_, AppData>,
+    path: String,
+) -> Result<AddKnowledgeSourceResult, String> {
+    info!("添加知识库来源: {}", path);
+    let root = std::path::absolute(&path).map_err(|e| format!("无效的路径: {}", e))?;
+    let load_root = root.clone();
+    let (documents, skipped) = tokio::task::spawn_blocking(move || load_documents(&load_root))
+        .await
+        .map_err(|e| format!("读取知识库文件失败: {}", e))??;
+
+    let login_uid = state.user_info.lock().await.uid.clone();
+    let db = state.db_conn.read().await.clone();
+    let mut sources = Vec::with_capacity(documents.len());
+    for document in documents {
+        let existing =
+            im_knowledge_repository::find_source_by_path(&db, &login_uid, &document.path).await?;
+        let now = Utc::now().timestamp_millis();
+        let source = im_kb_source::Model {
+            id: existing
+                .as_ref()
+                .map(|source| source.id.clone())
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            login_uid: login_uid.clone(),
+            path: document.path,
+            name: document.name,
+            kind: document.kind.as_str().to_string(),
+            size: document.size as i64,
+            modified_time: document.modified_time,
+            chunk_count: document.chunks.len() as i32,
+            create_time: existing.map(|source| source.create_time).unwrap_or(now),
+            update_time: now,
+        };
+        run_with_write_lock(state.write_lock.clone(), "save_knowledge_source", || {
+            im_knowledge_repository::save_source(&db, source.clone(), &document.chunks)
+        })
+        .await?;
+        sources.push(source);
+    }
+
+    let removed = if root.is_dir() {
+        prune_missing_sources(&state, &db, &login_uid, &root).await?
+    } else {
+        Vec::new()
+    };
+
+    info!(
+        "知识库来源已添加: {} 个文件，跳过 {} 个，移除 {} 个",
+        sources.len(),
+        skipped.len(),
+        removed.len()
+    );
+    Ok(AddKnowledgeSourceResult {
+        sources,
+        skipped,
+        removed,
+    })
+}
+
+/// 移除目录下文件已不存在的来源，返回被移除的来源ID
+async fn prune_missing_sources(
+    state: &State<'_, AppData>,
+    db: &DatabaseConnection,
+    login_uid: &str,
+    root: &Path,
+) -> Result<Vec<String>, String> {
+    let mut removed = Vec::new();
+    for source in im_knowledge_repository::list_sources(db, login_uid).await? {
+        let path = Path::new(&source.path);
+        // 无法确认文件是否存在时保留来源
+        if !path.starts_with(root) || tokio::fs::try_exists(path).await.unwrap_or(true) {
+            continue;
+        }
+        run_with_write_lock(state.write_lock.clone(), "prune_knowledge_source", || {
+            im_knowledge_repository::delete_source(db, login_uid, &source.id)
+        })
+        .await?;
+        info!("知识库来源文件已删除，移除索引: {}", source.path);
+        removed.push(source.id);
+    }
+    Ok(removed)
+}
+
+/// 从知识库中移除来源及其索引
+#[tauri::command]
+pub async fn remove_knowledge_source(
+    state: State<'_, AppData>,
+    source_id: String,
+) -> Result<(), String> {
+    let login_uid = state.user_info.lock().await.uid.clone();
+    let db = state.db_conn.read().await.clone();
+    let removed = run_with_write_lock(state.write_lock.clone(), "remove_knowledge_source", || {
+        im_knowledge_repository::delete_source(&db, &login_uid, &source_id)
+    })
+    .await?;
+    if !removed {
+        return Err(format!("未找到知识库来源: {}", source_id));
+    }
+    Ok(())
+}
+
+/// 获取知识库中的全部来源
+#[tauri::command]
+pub async fn list_knowledge_sources(
+    state: State<'_, AppData>,
+) -> Result<Vec<im_kb_source::Model>, String> {
+    let login_uid = state.user_info.lock().await.uid.clone();
+    let db = state.db_conn.read().await.clone();
+    Ok(im_knowledge_repository::list_sources(&db, &login_uid).await?)
+}
+
+/// 检索知识库，返回与对话时相同的片段，便于预览参考资料
+#[tauri::command]
+pub async fn search_knowledge(
+    state: State<'_, AppData>,
+    query: String,
+    top_k: Option<usize>,
+) -> Result<Vec<KnowledgeSearchResult>, String> {
+    let login_uid = state.user_info.lock().await.uid.clone();
+    let db = state.db_conn.read().await.clone();
+    let hits = knowledge::retrieve(&db, &login_uid, &query, top_k).await?;
+    Ok(hits
+        .into_iter()
+        .map(|hit| KnowledgeSearchResult {
+            chunk: hit.chunk,
+            source_name: hit.source_name,
+            source_path: hit.source_path,
+            rank: hit.rank,
+        })
+        .collect())
+}
+
+/// 读取单个文件，或递归读取目录中支持的文件
+fn load_documents(root: &Path) -> Result<(Vec<LoadedDocument>, Vec<SkippedKnowledgeFile>), String> {
+    let root = std::path::absolute(root).map_err(|e| format!("无效的路径: {}", e))?;
+    let metadata =
+        std::fs::metadata(&root).map_err(|e| format!("无法读取 {}: {}", root.display(), e))?;
+    if metadata.is_file() {
+        let document = load_document(&root)?;
+        return Ok((vec![document], Vec::new()));
+    }
+
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    if collect_files(&root, &mut files) {
+        warn!(
+            "目录 {} 中的文件超过 {} 个，只索引前 {} 个",
+            root.display(),
+            MAX_SOURCE_FILES,
+            MAX_SOURCE_FILES
+        );
+        skipped.push(SkippedKnowledgeFile {
+            path: root.to_string_lossy().to_string(),
+            reason: format!(
+                "文件超过单次添加的上限 {}，只索引了前 {} 个",
+                MAX_SOURCE_FILES, MAX_SOURCE_FILES
+            ),
+        });
+    }
+
+    let mut documents = Vec::with_capacity(files.len());
+    for path in files {
+        match load_document(&path) {
+            Ok(document) => documents.push(document),
+            Err(reason) => skipped.push(SkippedKnowledgeFile {
+                path: path.to_string_lossy().to_string(),
+                reason,
+            }),
+        }
+    }
+    Ok((documents, skipped))
+}
+
+/// 按路径排序收集目录中支持的文件，不跟随符号链接
+///
+/// 收集到 `MAX_SOURCE_FILES` 个文件后停止扫描，仍有未收集的文件时返回 true
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> bool {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("无法读取目录 {}: {}", dir.display(), e);
+            return false;
+        }
+    };
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            if !IGNORED_DIRS.contains(&name.as_ref()) && collect_files(&path, files) {
+                return true;
+            }
+        } else if file_type.is_file() && DocumentKind::from_path(&path).is_some() {
+            if files.len() >= MAX_SOURCE_FILES {
+                return true;
+            }
+            files.push(path);
+        }
+    }
+    false
+}
+
+fn load_document(path: &Path) -> Result<LoadedDocument, String> {
+    let kind = DocumentKind::from_path(path).ok_or_else(|| "不支持的文件类型".to_string())?;
+    let metadata = std::fs::metadata(path).map_err(|e| format!("无法读取文件: {}", e))?;
+    if metadata.len() > MAX_SOURCE_FILE_SIZE {
+        return Err(format!("文件超过 {}MB", MAX_SOURCE_FILE_SIZE / 1024 / 1024));
+    }
+    let bytes = std::fs::read(path).map_err(|e| format!("无法读取文件: {}", e))?;
+    let text = String::from_utf8(bytes)
+        .ok()
+        .filter(|text| !text.contains('\0'))
+        .ok_or_else(|| "不是 UTF-8 文本文件".to_string())?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+
+    let chunks = knowledge::chunk_document(kind, text);
+    if chunks.is_empty() {
+        return Err("没有可索引的内容".to_string());
+    }
+    Ok(LoadedDocument {
+        path: path.to_string_lossy().to_string(),
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        kind,
+        size: metadata.len(),
+        modified_time: metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_millis() as i64),
+        chunks,
+    })
+}
@@ -1,34 +1,33 @@
 use crate::AppData;
-use crate::command::token_helper::{
-    capture_token_snapshot_arc, capture_token_snapshot_direct, persist_token_if_refreshed_arc,
-    persist_token_if_refreshed_direct,
-};
+use crate::command::token_helper::{capture_token_snapshot, persist_token_if_refreshed};
+use crate::command::{message_retry_command, message_sync};
 use crate::error::CommonError;
-use crate::im_request_client::{ImRequestClient, ImUrl};
-use crate::pojo::common::{CursorPageParam, CursorPageResp};
+use crate::im_request_client::ImRequestClient;
+use crate::pojo::common::{CursorPageParam, CursorPageResp, deserialize_id};
 use crate::repository::im_message_repository::MessageWithThumbnail;
 use crate::repository::{im_message_repository, im_user_repository};
+use crate::vo::message::MsgListReq;
 use crate::vo::vo::ChatMessageReq;
 
 use entity::im_user::Entity as ImUserEntity;
-use entity::{im_message, im_user};
+use entity::{im_contact, im_message, im_room_member, im_user};
 use once_cell::sync::Lazy;
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
-use sea_orm::{DatabaseConnection, TransactionTrait};
+use sea_orm::{ConnectionTrait, DatabaseConnection, TransactionTrait};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::future::Future;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicI64, Ordering};
-use tauri::{State, ipc::Channel};
-use tokio::sync::Mutex;
+use tauri::{AppHandle, Manager, State, ipc::Channel};
+use tokio::sync::{Mutex, RwLock};
 use tokio::time::{Duration, sleep};
 use tracing::{debug, error, info, warn};
 
 const WRITE_RETRY_LIMIT: usize = 3; // 写操作最多重试 3 次
 const WRITE_RETRY_DELAY_MS: u64 = 80; // 重试基础延迟 80ms
 
-async fn run_with_write_lock<T, F, Fut>(
+pub(crate) async fn run_with_write_lock<T, F, Fut>(
     lock: Arc<Mutex<()>>, // 传入全局写锁，保证串行执行
     op_name: &str,        // 当前操作名用于日志
     mut operation: F,     // 实际写入逻辑
@@ -72,6 +71,31 @@ where
     }
 }
 
+/// 在后台为建立全文索引之前的消息回填索引，每批写入都经过全局写锁
+pub(crate) fn spawn_search_index_backfill(
+    db: DatabaseConnection,
+    write_lock: Arc<Mutex<()>>,
+    login_uid: String,
+) {
+    tokio::spawn(async move {
+        let mut after_id: Option<String> = None;
+        loop {
+            let result = run_with_write_lock(write_lock.clone(), "backfill_search_index", || {
+                im_message_repository::backfill_search_index(&db, &login_uid, after_id.as_deref())
+            })
+            .await;
+            match result {
+                Ok(Some(last_id)) => after_id = Some(last_id),
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Failed to backfill search index for {}: {}", login_uid, e);
+                    break;
+                }
+            }
+        }
+    });
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct MessageResp {
@@ -217,6 +241,7 @@ pub fn convert_message_to_resp(
     let MessageWithThumbnail {
         message: msg,
         thumbnail_path,
+        local_path,
     } = record;
 
     // 解析消息体 - 安全地处理 JSON 解析
@@ -240,7 +265,8 @@ pub fn convert_message_to_resp(
         }
     });
 
-    inject_thumbnail_path(&mut body, thumbnail_path.as_deref());
+    inject_body_path(&mut body, "thumbnailPath", thumbnail_path.as_deref());
+    inject_body_path(&mut body, "localPath", local_path.as_deref());
 
     // 解析消息标记 - 支持从 message_marks 字段解析
     let message_marks = msg.message_marks.as_ref().and_then(|marks_str| {
@@ -291,8 +317,9 @@ pub fn convert_message_to_resp(
 
 /// 检查用户初始化状态并获取消息
 pub async fn check_user_init_and_fetch_messages(
-    client: &mut ImRequestClient,
+    client: &ImRequestClient,
     db_conn: &DatabaseConnection,
+    write_lock: &Arc<Mutex<()>>,
     uid: &str,
     async_data: bool,
     force_full: bool,
@@ -350,12 +377,17 @@ pub async fn check_user_init_and_fetch_messages(
                     error!("Failed to fetch all messages: {}", e);
                     return Err(e);
                 }
+                // 全量同步后本地消息以服务端为准，旧的同步游标不再有效
+                if let Err(e) = message_sync::reset_sync_cursors(db_conn, uid).await {
+                    warn!("Failed to reset message sync cursors: {}", e);
+                }
             } else {
                 info!(
                     "User {} incremental/offline message update, async_data: {:?}",
                     uid, async_data
                 );
-                fetch_all_messages(client, db_conn, uid, async_data)
+                // 按房间补齐断线期间缺失的消息，只拉取本地缺失的区间
+                message_sync::sync_rooms_incrementally(client, db_conn, write_lock, uid)
                     .await
                     .map_err(|e| {
                         error!("Failed to update offline messages: {}", e);
@@ -371,7 +403,7 @@ pub async fn check_user_init_and_fetch_messages(
 
 // 获取所有消息并保存到数据库
 pub async fn fetch_all_messages(
-    client: &mut ImRequestClient,
+    client: &ImRequestClient,
     db_conn: &DatabaseConnection,
     uid: &str,
     async_data: bool,
@@ -381,18 +413,16 @@ pub async fn fetch_all_messages(
         uid, async_data
     );
     // 调用后端接口 /chat/msg/list 获取所有消息，传递 async_data 参数
-    let body = match async_data {
-        true => Some(serde_json::json!({ "async": async_data })),
-        false => None,
+    let req = MsgListReq {
+        async_data: async_data.then_some(true),
+        ..Default::default()
     };
 
-    let old_tokens = capture_token_snapshot_direct(client);
+    let old_tokens = capture_token_snapshot(client);
 
-    let messages: Option<Vec<MessageResp>> = client
-        .im_request(ImUrl::GetMsgList, body, None::<serde_json::Value>)
-        .await?;
+    let messages = client.call(&req).await?;
 
-    persist_token_if_refreshed_direct(&old_tokens, client, db_conn, uid).await;
+    persist_token_if_refreshed(&old_tokens, client, uid);
 
     if let Some(mut messages) = messages {
         // 排序消息（按发送时间）
@@ -507,10 +537,10 @@ pub async fn sync_messages(
         _ => state.user_info.lock().await.uid.clone(),
     };
 
-    let mut client = state.rc.lock().await;
     check_user_init_and_fetch_messages(
-        &mut client,
+        &state.rc,
         &*state.db_conn.read().await,
+        &state.write_lock,
         &uid,
         async_data,
         full_sync,
@@ -521,7 +551,10 @@ pub async fn sync_messages(
 }
 
 /// 将 MessageResp 转换为数据库模型（用于 fetch_all_messages）
-fn convert_resp_to_record_for_fetch(msg_resp: MessageResp, uid: String) -> MessageWithThumbnail {
+pub(crate) fn convert_resp_to_record_for_fetch(
+    msg_resp: MessageResp,
+    uid: String,
+) -> MessageWithThumbnail {
     use serde_json;
 
     // 序列化消息体为 JSON 字符串
@@ -552,6 +585,9 @@ fn convert_resp_to_record_for_fetch(msg_resp: MessageResp, uid: String) -> Messa
         login_uid: uid.to_string(),
         send_status: "success".to_string(),
         time_block: msg_resp.time_block,
+        send_attempts: 0,
+        next_retry_time: None,
+        last_attempt_time: None,
     };
 
     let thumbnail_path = extract_thumbnail_path_from_body(&msg_resp.message.body);
@@ -568,7 +604,8 @@ fn extract_thumbnail_path_from_body(body: &Option<serde_json::Value>) -> Option<
     })
 }
 
-fn inject_thumbnail_path(body: &mut Option<serde_json::Value>, path: Option<&str>) {
+/// 把本地保存的文件路径写入消息体的 `field` 字段，消息体中已有非空值时保持不变
+fn inject_body_path(body: &mut Option<serde_json::Value>, field: &str, path: Option<&str>) {
     let Some(path) = path else {
         return;
     };
@@ -580,13 +617,13 @@ fn inject_thumbnail_path(body: &mut Option<serde_json::Value>, path: Option<&str
     if let Some(val) = body {
         if let Some(map) = val.as_object_mut() {
             let exists = map
-                .get("thumbnailPath")
+                .get(field)
                 .and_then(|v| v.as_str())
                 .map(|s| !s.is_empty())
                 .unwrap_or(false);
             if !exists {
                 map.insert(
-                    "thumbnailPath".to_string(),
+                    field.to_string(),
                     serde_json::Value::String(path.to_string()),
                 );
             }
@@ -635,6 +672,9 @@ pub async fn send_msg(
         login_uid: login_uid.clone(),
         send_status: "pending".to_string(), // 初始状态为pending
         time_block: None,
+        send_attempts: 0,
+        next_retry_time: None,
+        last_attempt_time: None,
     };
 
     let mut message_record = MessageWithThumbnail::new(message_model, thumbnail_path);
@@ -663,59 +703,20 @@ pub async fn send_msg(
     // 异步发送到后端接口
     let db_conn = state.db_conn.clone();
     let request_client = state.rc.clone();
-    let mut record_for_send = message_record.clone();
-    let uid_for_token = login_uid.clone();
 
     tokio::spawn(async move {
-        let old_tokens = capture_token_snapshot_arc(&request_client).await;
-
-        // 发送到后端接口
-        let result: Result<Option<MessageResp>, anyhow::Error> = {
-            let mut client = request_client.lock().await;
-            client
-                .im_request(ImUrl::SendMsg, Some(send_data), None::<serde_json::Value>)
-                .await
-        };
-
-        persist_token_if_refreshed_arc(&old_tokens, &request_client, &db_conn, &uid_for_token)
-            .await;
-
-        let mut id = None;
-
-        // 根据发送结果更新消息状态
-        let status = match result {
-            Ok(Some(mut resp)) => {
-                resp.old_msg_id = Some(msg_id.clone());
-                id = resp.message.id.clone();
-                record_for_send.message.body = resp.message.body.as_ref().and_then(|body| {
-                    if body.is_null() {
-                        None
-                    } else {
-                        serde_json::to_string(body).ok()
-                    }
-                });
-                if let Some(path) = extract_thumbnail_path_from_body(&resp.message.body) {
-                    record_for_send.thumbnail_path = Some(path);
-                }
-                "success"
-            }
-            _ => "fail",
-        };
-
-        // 更新消息状态
-        let model = im_message_repository::update_message_status(
-            &*db_conn.read().await,
-            record_for_send,
-            status,
-            id,
-            login_uid.clone(),
+        let outcome = deliver_message(
+            db_conn,
+            request_client,
+            message_record,
+            send_data,
+            login_uid,
         )
         .await;
 
-        match model {
-            Ok(model) => {
-                let resp = convert_message_to_resp(model, Some(msg_id));
-                success_channel.send(resp).unwrap();
+        match outcome {
+            Ok(outcome) => {
+                success_channel.send(outcome.resp).unwrap();
             }
             Err(e) => {
                 error!("{:?}", e);
@@ -727,6 +728,276 @@ pub async fn send_msg(
     Ok(())
 }
 
+/// 单次投递结果
+pub(crate) struct DeliveryOutcome {
+    pub resp: MessageResp,
+    pub success: bool,
+    /// 累计自动重发次数
+    pub attempts: i32,
+    /// 下一次自动重发时间，达到上限时为空
+    pub next_retry_time: Option<i64>,
+}
+
+/// 调用后端接口发送消息，并根据结果更新本地消息状态
+/// 发送失败时按退避策略安排下一次自动重发
+pub(crate) async fn deliver_message(
+    db_conn: Arc<RwLock<DatabaseConnection>>,
+    request_client: ImRequestClient,
+    mut record: MessageWithThumbnail,
+    send_data: ChatMessageReq,
+    login_uid: String,
+) -> Result<DeliveryOutcome, CommonError> {
+    let msg_id = record.message.id.clone();
+    let old_tokens = capture_token_snapshot(&request_client);
+
+    // 记录开始发送的时间，发送过程中中断的消息据此由后台任务重发
+    im_message_repository::mark_send_started(
+        &*db_conn.read().await,
+        &msg_id,
+        &login_uid,
+        chrono::Utc::now().timestamp_millis(),
+    )
+    .await?;
+
+    // 发送到后端接口
+    let result = request_client.call(&send_data).await;
+
+    persist_token_if_refreshed(&old_tokens, &request_client, &login_uid);
+
+    let mut id = None;
+
+    // 根据发送结果更新消息状态
+    let status = match result {
+        Ok(Some(mut resp)) => {
+            resp.old_msg_id = Some(msg_id.clone());
+            id = resp.message.id.clone();
+            record.message.body = resp.message.body.as_ref().and_then(|body| {
+                if body.is_null() {
+                    None
+                } else {
+                    serde_json::to_string(body).ok()
+                }
+            });
+            if let Some(path) = extract_thumbnail_path_from_body(&resp.message.body) {
+                record.thumbnail_path = Some(path);
+            }
+            "success"
+        }
+        Ok(None) => {
+            warn!("SendMsg returned empty data, message id: {}", msg_id);
+            "fail"
+        }
+        Err(e) => {
+            warn!("SendMsg failed, message id: {}, error: {}", msg_id, e);
+            "fail"
+        }
+    };
+
+    // 更新消息状态
+    let db = db_conn.read().await;
+    let model =
+        im_message_repository::update_message_status(&db, record, status, id, login_uid.clone())
+            .await?;
+
+    let success = status == "success";
+    let (attempts, next_retry_time) = if success {
+        (0, None)
+    } else {
+        message_retry_command::schedule_retry(&db, &msg_id, &login_uid).await?
+    };
+
+    Ok(DeliveryOutcome {
+        resp: convert_message_to_resp(model, Some(msg_id)),
+        success,
+        attempts,
+        next_retry_time,
+    })
+}
+
+/// 保存 WebSocket 推送的新消息，在转发到前端之前写入本地数据库
+pub(crate) async fn save_received_message(
+    app_handle: &AppHandle,
+    data: &serde_json::Value,
+) -> Result<(), String> {
+    let Some(state) = app_handle.try_state::<AppData>() else {
+        return Ok(());
+    };
+    let login_uid = state.user_info.lock().await.uid.clone();
+    if login_uid.is_empty() {
+        return Ok(());
+    }
+
+    let msg_resp: MessageResp = serde_json::from_value(data.clone())
+        .map_err(|e| format!("Failed to decode received message: {}", e))?;
+    let record = convert_resp_to_record_for_fetch(msg_resp, login_uid.clone());
+    if record.message.id.is_empty() || record.message.room_id.is_empty() {
+        return Err("Received message has no id or room_id".to_string());
+    }
+
+    let record = run_with_write_lock(state.write_lock.clone(), "save_received_message", || {
+        let db_conn = state.db_conn.clone();
+        let mut record = record.clone();
+        let login_uid = login_uid.clone();
+        async move {
+            let db = db_conn.read().await;
+            let tx = db.begin().await?;
+            // 与 fetch_all_messages 保持一致：以房间内上一条消息计算 time_block
+            if let Some(send_time) = record.message.send_time {
+                record.message.time_block = im_message_repository::calculate_time_block(
+                    &tx,
+                    &record.message.room_id,
+                    &record.message.id,
+                    send_time,
+                    &login_uid,
+                )
+                .await?;
+            }
+            im_message_repository::save_all(&tx, vec![record.clone()]).await?;
+            tx.commit().await?;
+            Ok(record)
+        }
+    })
+    .await?;
+
+    debug!(
+        "Saved received message {} in room {}",
+        record.message.id, record.message.room_id
+    );
+    Ok(())
+}
+
+/// 撤回消息的消息类型
+const RECALL_MESSAGE_TYPE: u8 = 2;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct MsgRecallPayload {
+    #[serde(deserialize_with = "deserialize_id")]
+    msg_id: String,
+    room_id: Option<String>,
+    /// 撤回人ID，缺省时视为发送者本人撤回
+    recall_uid: Option<String>,
+}
+
+/// 将 WebSocket 推送的撤回事件写入本地数据库
+pub(crate) async fn apply_message_recall(
+    app_handle: &AppHandle,
+    data: &serde_json::Value,
+) -> Result<(), String> {
+    let Some(state) = app_handle.try_state::<AppData>() else {
+        return Ok(());
+    };
+    let login_uid = state.user_info.lock().await.uid.clone();
+    if login_uid.is_empty() {
+        return Ok(());
+    }
+
+    let payload: MsgRecallPayload = serde_json::from_value(data.clone())
+        .map_err(|e| format!("Failed to decode recall event: {}", e))?;
+
+    run_with_write_lock(state.write_lock.clone(), "apply_message_recall", || {
+        let db_conn = state.db_conn.clone();
+        let payload = payload.clone();
+        let login_uid = login_uid.clone();
+        async move {
+            let db = db_conn.read().await;
+            let tx = db.begin().await?;
+
+            // 本地不存在或已经撤回的消息无需处理
+            let message = im_message::Entity::find_by_id((payload.msg_id, login_uid.clone()))
+                .one(&tx)
+                .await?;
+            let Some(message) = message.filter(|m| m.message_type != Some(RECALL_MESSAGE_TYPE))
+            else {
+                return Ok(());
+            };
+
+            let recall_uid = payload.recall_uid.unwrap_or_else(|| message.uid.clone());
+            let room_id = payload.room_id.unwrap_or_else(|| message.room_id.clone());
+            let recall_text =
+                build_recall_text(&tx, &room_id, &message, &recall_uid, &login_uid).await?;
+
+            im_message_repository::update_message_recall_status(
+                &tx,
+                &message.id,
+                RECALL_MESSAGE_TYPE,
+                &recall_text,
+                &login_uid,
+            )
+            .await?;
+            tx.commit().await?;
+            Ok(())
+        }
+    })
+    .await
+}
+
+/// 生成撤回提示文案，与前端 chatStore.updateRecallMsg 保持一致
+async fn build_recall_text<C: ConnectionTrait>(
+    db: &C,
+    room_id: &str,
+    message: &im_message::Model,
+    recall_uid: &str,
+    login_uid: &str,
+) -> Result<String, CommonError> {
+    let sender_uid = message.uid.as_str();
+
+    if recall_uid == login_uid {
+        if recall_uid == sender_uid {
+            return Ok("你撤回了一条消息".to_string());
+        }
+        let sender_name = member_display_name(db, room_id, sender_uid, login_uid)
+            .await?
+            .or_else(|| message.nickname.clone())
+            .unwrap_or_else(|| sender_uid.to_string());
+        return Ok(format!("你撤回了{}的一条消息", sender_name));
+    }
+
+    let is_group = im_contact::Entity::find()
+        .filter(im_contact::Column::RoomId.eq(room_id))
+        .filter(im_contact::Column::LoginUid.eq(login_uid))
+        .one(db)
+        .await?
+        .is_some_and(|contact| contact.contact_type == Some(1));
+
+    let text = match (is_group, sender_uid == login_uid) {
+        (true, is_self) => {
+            let recaller_name = member_display_name(db, room_id, recall_uid, login_uid)
+                .await?
+                .unwrap_or_else(|| recall_uid.to_string());
+            if is_self {
+                format!("{}撤回了你的一条消息", recaller_name)
+            } else {
+                format!("{}撤回了一条消息", recaller_name)
+            }
+        }
+        (false, true) => "对方撤回了你的一条消息".to_string(),
+        (false, false) => "对方撤回了一条消息".to_string(),
+    };
+    Ok(text)
+}
+
+/// 获取成员在房间中的显示名称，优先使用群昵称
+async fn member_display_name<C: ConnectionTrait>(
+    db: &C,
+    room_id: &str,
+    uid: &str,
+    login_uid: &str,
+) -> Result<Option<String>, CommonError> {
+    let member = im_room_member::Entity::find()
+        .filter(im_room_member::Column::RoomId.eq(room_id))
+        .filter(im_room_member::Column::Uid.eq(uid))
+        .filter(im_room_member::Column::LoginUid.eq(login_uid))
+        .one(db)
+        .await?;
+
+    Ok(member.and_then(|m| {
+        m.my_name
+            .filter(|name| !name.is_empty())
+            .or_else(|| Some(m.name).filter(|name| !name.is_empty()))
+    }))
+}
+
 #[tauri::command]
 pub async fn save_msg(data: MessageResp, state: State<'_, AppData>) -> Result<(), String> {
     // 创建 im_message::Model
@@ -1,21 +1,24 @@
 use std::collections::HashMap;
-use std::collections::hash_map::Entry;
 
+use crate::AppData;
+use crate::command::message_command::{MessageMark, run_with_write_lock};
 use crate::error::CommonError;
-use crate::{AppData, command::message_command::MessageMark};
+use crate::pojo::common::deserialize_id;
 use entity::im_message;
 use sea_orm::ColumnTrait;
-use sea_orm::{EntityTrait, IntoActiveModel, QueryFilter, Set, TransactionTrait};
+use sea_orm::{ConnectionTrait, EntityTrait, IntoActiveModel, QueryFilter, Set, TransactionTrait};
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{AppHandle, Manager, State};
 use tracing::{error, info};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ChatMessageMarkReq {
+    #[serde(deserialize_with = "deserialize_id")]
     msg_id: String,
     mark_type: i32,
     act_type: u8,
+    #[serde(deserialize_with = "deserialize_id")]
     uid: String,
     mark_count: u32,
 }
@@ -28,36 +31,8 @@ pub async fn save_message_mark(
 ) -> Result<(), String> {
     let result: Result<(), CommonError> = async {
         let db = state.db_conn.read().await;
-        let messages: Vec<im_message::Model> = im_message::Entity::find()
-            .filter(im_message::Column::Id.eq(data.msg_id.clone()))
-            .all(&*db)
-            .await?;
-
-        for message in messages {
-            let message_marks = message.message_marks.clone();
-            if let Some(message_marks) = message_marks {
-                let new_message_marks = get_new_message_marks(
-                    &message_marks,
-                    data.mark_type.to_string(),
-                    data.mark_count,
-                    data.uid.clone(),
-                    &message.login_uid,
-                )?;
-
-                // 创建ActiveModel，只设置需要更新的字段
-                let mut active_message = message.into_active_model();
-                active_message.message_marks = Set(Some(new_message_marks));
-
-                // 更新数据库
-                im_message::Entity::update(active_message)
-                    .exec(&*db)
-                    .await?;
-            }
-        }
-
-        // 开启事务保存到数据库
         let tx = db.begin().await?;
-        // im_message_mark_repository::save_msg_mark(&tx, message_mark).await?;
+        apply_message_mark(&tx, &data).await?;
         tx.commit().await?;
 
         info!(
@@ -77,32 +52,104 @@ pub async fn save_message_mark(
     }
 }
 
+/// WebSocket 推送的消息标记事件，兼容 `{ markList: [...] }` 与单个标记两种格式
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MarkEventPayload {
+    List {
+        #[serde(rename = "markList")]
+        mark_list: Vec<ChatMessageMarkReq>,
+    },
+    Single(ChatMessageMarkReq),
+}
+
+/// 将 WebSocket 推送的消息标记事件写入本地数据库
+pub(crate) async fn apply_message_mark_event(
+    app_handle: &AppHandle,
+    data: &serde_json::Value,
+) -> Result<(), String> {
+    let Some(state) = app_handle.try_state::<AppData>() else {
+        return Ok(());
+    };
+
+    let marks = match serde_json::from_value(data.clone())
+        .map_err(|e| format!("Failed to decode mark event: {}", e))?
+    {
+        MarkEventPayload::List { mark_list } => mark_list,
+        MarkEventPayload::Single(mark) => vec![mark],
+    };
+
+    run_with_write_lock(state.write_lock.clone(), "apply_message_mark_event", || {
+        let db_conn = state.db_conn.clone();
+        let marks = marks.clone();
+        async move {
+            let db = db_conn.read().await;
+            let tx = db.begin().await?;
+            for mark in &marks {
+                apply_message_mark(&tx, mark).await?;
+            }
+            tx.commit().await?;
+            Ok(())
+        }
+    })
+    .await
+}
+
+/// 合并消息标记到本地消息
+async fn apply_message_mark<C: ConnectionTrait>(
+    db: &C,
+    data: &ChatMessageMarkReq,
+) -> Result<(), CommonError> {
+    let messages: Vec<im_message::Model> = im_message::Entity::find()
+        .filter(im_message::Column::Id.eq(data.msg_id.clone()))
+        .all(db)
+        .await?;
+
+    for message in messages {
+        let new_message_marks = get_new_message_marks(
+            message.message_marks.as_deref(),
+            data.mark_type.to_string(),
+            data.mark_count,
+            data.act_type,
+            &data.uid,
+            &message.login_uid,
+        )?;
+
+        // 创建ActiveModel，只设置需要更新的字段
+        let mut active_message = message.into_active_model();
+        active_message.message_marks = Set(Some(new_message_marks));
+
+        // 更新数据库
+        im_message::Entity::update(active_message).exec(db).await?;
+    }
+
+    Ok(())
+}
+
 fn get_new_message_marks(
-    message_marks: &str,
+    message_marks: Option<&str>,
     mark_type: String,
     mark_count: u32,
-    uid: String,
+    act_type: u8,
+    uid: &str,
     login_uid: &str,
 ) -> Result<String, CommonError> {
-    let mut message_marks: HashMap<String, MessageMark> =
-        serde_json::from_str::<HashMap<String, MessageMark>>(message_marks)
-            .map_err(|e| anyhow::anyhow!("Failed to parse message marks: {}", e))?;
-
-    match message_marks.entry(mark_type.clone()) {
-        Entry::Occupied(entry) => {
-            let message_mark = entry.into_mut();
-            message_mark.count = mark_count;
-            message_mark.user_marked = uid == login_uid;
-        }
-        Entry::Vacant(_) => {
-            message_marks.insert(
-                mark_type,
-                MessageMark {
-                    count: mark_count,
-                    user_marked: uid == login_uid,
-                },
-            );
+    let mut message_marks: HashMap<String, MessageMark> = match message_marks {
+        Some(marks) if !marks.trim().is_empty() => {
+            serde_json::from_str::<HashMap<String, MessageMark>>(marks)
+                .map_err(|e| anyhow::anyhow!("Failed to parse message marks: {}", e))?
         }
+        _ => HashMap::new(),
+    };
+
+    let message_mark = message_marks.entry(mark_type).or_insert(MessageMark {
+        count: 0,
+        user_marked: false,
+    });
+    message_mark.count = mark_count;
+    // 只有当前用户自己的操作才会改变 user_marked，actType: 1 确认 2 取消
+    if uid == login_uid {
+        message_mark.user_marked = act_type == 1;
     }
 
     let new_message_marks = serde_json::to_string(&message_marks)
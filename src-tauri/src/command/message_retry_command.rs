@@ -0,0 +1,282 @@
+use crate::AppData;
+use crate::command::message_command::{MessageResp, deliver_message};
+use crate::error::CommonError;
+use crate::repository::im_message_repository;
+use crate::vo::vo::ChatMessageReq;
+use crate::websocket::commands::get_websocket_client_container;
+
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Manager, State, ipc::Channel};
+use tokio::sync::Mutex;
+use tokio::time::{Duration, sleep};
+use tracing::{error, info, warn};
+
+/// 单条消息最多自动重发次数，超过后需要用户手动重试
+pub const MAX_SEND_ATTEMPTS: i32 = 5;
+/// 重发退避基础延迟
+const RETRY_BASE_DELAY_MS: i64 = 2_000;
+/// 重发退避最大延迟：5 分钟
+const RETRY_MAX_DELAY_MS: i64 = 5 * 60 * 1000;
+/// 开始发送后超过请求超时再加该时长仍为 pending，视为发送已中断（例如应用在发送过程中退出）
+const PENDING_SEND_GRACE_MS: i64 = 30_000;
+/// 每轮最多重发的消息数量
+const RETRY_BATCH_SIZE: u64 = 20;
+/// 后台任务轮询间隔
+const WORKER_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RetryStatus {
+    /// 正在重发
+    Retrying,
+    /// 重发成功
+    Success,
+    /// 重发失败，等待下一次重发
+    Failed,
+    /// 达到最大重发次数，停止自动重发
+    Exhausted,
+}
+
+/// 消息重发状态变化事件
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageRetryEvent {
+    /// 重发前的本地消息ID
+    pub msg_id: String,
+    pub status: RetryStatus,
+    pub attempts: i32,
+    pub next_retry_time: Option<i64>,
+    /// 重发完成后的消息，仅在 Success/Failed/Exhausted 时返回
+    pub message: Option<MessageResp>,
+}
+
+struct RetryWorker {
+    running: AtomicBool,
+    /// 保证同一时间只有一轮重发在执行
+    pass_lock: Mutex<()>,
+    /// 前端注册的状态通道
+    status_channel: Mutex<Option<Channel<MessageRetryEvent>>>,
+}
+
+static RETRY_WORKER: OnceLock<RetryWorker> = OnceLock::new();
+
+fn retry_worker() -> &'static RetryWorker {
+    RETRY_WORKER.get_or_init(|| RetryWorker {
+        running: AtomicBool::new(false),
+        pass_lock: Mutex::new(()),
+        status_channel: Mutex::new(None),
+    })
+}
+
+/// 计算第 `attempts` 次失败后的退避时长（毫秒）
+fn retry_backoff_ms(attempts: i32) -> i64 {
+    let steps = (attempts - 1).clamp(0, 16) as u32;
+    (RETRY_BASE_DELAY_MS * 2_i64.pow(steps)).min(RETRY_MAX_DELAY_MS)
+}
+
+/// 记录一次发送失败并安排下一次重发，返回累计次数与下一次重发时间
+pub(crate) async fn schedule_retry(
+    db: &DatabaseConnection,
+    msg_id: &str,
+    login_uid: &str,
+) -> Result<(i32, Option<i64>), CommonError> {
+    let attempts = im_message_repository::get_send_attempts(db, msg_id, login_uid).await? + 1;
+    let next_retry_time = if attempts < MAX_SEND_ATTEMPTS {
+        Some(chrono::Utc::now().timestamp_millis() + retry_backoff_ms(attempts))
+    } else {
+        None
+    };
+
+    im_message_repository::update_send_retry(db, msg_id, login_uid, attempts, next_retry_time)
+        .await?;
+    Ok((attempts, next_retry_time))
+}
+
+async fn report(event: MessageRetryEvent) {
+    let mut channel = retry_worker().status_channel.lock().await;
+    if let Some(ch) = channel.as_ref()
+        && ch.send(event).is_err()
+    {
+        // 前端页面已销毁，移除失效的通道
+        *channel = None;
+    }
+}
+
+async fn is_ws_connected() -> bool {
+    get_websocket_client_container()
+        .read()
+        .await
+        .as_ref()
+        .map(|client| client.is_connected())
+        .unwrap_or(false)
+}
+
+/// 启动后台重发任务，WebSocket 连接成功后调用；连接断开后任务自动退出
+pub fn start_retry_worker(app_handle: AppHandle) {
+    let worker = retry_worker();
+    if worker.running.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    info!("Starting message retry worker");
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = run_retry_pass(&app_handle).await {
+                warn!("Message retry pass failed: {}", e);
+            }
+
+            sleep(WORKER_POLL_INTERVAL).await;
+            if is_ws_connected().await {
+                continue;
+            }
+
+            // 先清除运行标记再复查连接，避免与重连时的启动调用交错导致没有任务在运行
+            let worker = retry_worker();
+            worker.running.store(false, Ordering::SeqCst);
+            if !is_ws_connected().await || worker.running.swap(true, Ordering::SeqCst) {
+                break;
+            }
+        }
+
+        info!("Message retry worker stopped");
+    });
+}
+
+/// 执行一轮重发，返回本轮处理的消息数量
+async fn run_retry_pass(app_handle: &AppHandle) -> Result<usize, CommonError> {
+    let Some(state) = app_handle.try_state::<AppData>() else {
+        return Ok(0);
+    };
+    let _guard = retry_worker().pass_lock.lock().await;
+
+    let login_uid = state.user_info.lock().await.uid.clone();
+    if login_uid.is_empty() {
+        return Ok(0);
+    }
+
+    let request_timeout_ms = state
+        .config
+        .lock()
+        .await
+        .http
+        .clone()
+        .unwrap_or_default()
+        .request_timeout()
+        .as_millis() as i64;
+    let now = chrono::Utc::now().timestamp_millis();
+    let candidates = im_message_repository::list_retryable_messages(
+        &*state.db_conn.read().await,
+        &login_uid,
+        now,
+        now - request_timeout_ms - PENDING_SEND_GRACE_MS,
+        MAX_SEND_ATTEMPTS,
+        RETRY_BATCH_SIZE,
+    )
+    .await?;
+
+    let count = candidates.len();
+    if count > 0 {
+        info!("Retrying {} undelivered messages", count);
+    }
+
+    for (record, attempts) in candidates {
+        let msg_id = record.message.id.clone();
+        report(MessageRetryEvent {
+            msg_id: msg_id.clone(),
+            status: RetryStatus::Retrying,
+            attempts,
+            next_retry_time: None,
+            message: None,
+        })
+        .await;
+
+        let send_data = ChatMessageReq {
+            id: msg_id.clone(),
+            room_id: Some(record.message.room_id.clone()),
+            msg_type: record.message.message_type,
+            body: record
+                .message
+                .body
+                .as_deref()
+                .and_then(|body| serde_json::from_str(body).ok()),
+            skip: None,
+            is_temp: None,
+            is_push_message: None,
+        };
+
+        // 单条消息失败不影响本轮其余消息的重发
+        let outcome = match deliver_message(
+            state.db_conn.clone(),
+            state.rc.clone(),
+            record,
+            send_data,
+            login_uid.clone(),
+        )
+        .await
+        {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                error!("Failed to retry message {}: {}", msg_id, e);
+                report(MessageRetryEvent {
+                    msg_id,
+                    status: RetryStatus::Failed,
+                    attempts,
+                    next_retry_time: None,
+                    message: None,
+                })
+                .await;
+                continue;
+            }
+        };
+
+        let status = if outcome.success {
+            RetryStatus::Success
+        } else if outcome.attempts >= MAX_SEND_ATTEMPTS {
+            warn!(
+                "Message {} reached max send attempts ({}), giving up",
+                msg_id, MAX_SEND_ATTEMPTS
+            );
+            RetryStatus::Exhausted
+        } else {
+            RetryStatus::Failed
+        };
+
+        report(MessageRetryEvent {
+            msg_id,
+            status,
+            attempts: outcome.attempts,
+            next_retry_time: outcome.next_retry_time,
+            message: Some(outcome.resp),
+        })
+        .await;
+    }
+
+    Ok(count)
+}
+
+/// 手动重发未成功的消息：重置重发次数后立即执行一轮重发
+/// `status_channel` 同时注册为后台重发任务的状态通道
+#[tauri::command]
+pub async fn retry_failed_messages(
+    message_ids: Option<Vec<String>>,
+    app_handle: AppHandle,
+    state: State<'_, AppData>,
+    status_channel: Channel<MessageRetryEvent>,
+) -> Result<usize, String> {
+    let login_uid = state.user_info.lock().await.uid.clone();
+
+    im_message_repository::reset_send_attempts(
+        &*state.db_conn.read().await,
+        &login_uid,
+        message_ids.as_deref(),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    *retry_worker().status_channel.lock().await = Some(status_channel);
+
+    run_retry_pass(&app_handle).await.map_err(|e| e.to_string())
+}
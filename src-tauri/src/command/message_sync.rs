@@ -0,0 +1,403 @@
+use crate::command::message_command::{
+    MessageResp, convert_resp_to_record_for_fetch, run_with_write_lock,
+};
+use crate::command::token_helper::{capture_token_snapshot, persist_token_if_refreshed};
+use crate::error::CommonError;
+use crate::im_request_client::{ImRequestClient, ImUrl};
+use crate::repository::{im_config_repository, im_contact_repository, im_message_repository};
+use crate::vo::message::MsgPageReq;
+
+use entity::im_contact;
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// 房间同步游标在 im_config 中的键前缀
+const SYNC_CURSOR_KEY_PREFIX: &str = "msgSyncCursor:";
+/// 单页拉取的消息数量
+const SYNC_PAGE_SIZE: u32 = 50;
+/// 单个房间单次同步最多拉取的页数，超出后记录缺口留待下次继续
+const MAX_PAGES_PER_ROOM: usize = 20;
+
+/// 房间的增量同步游标
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct RoomSyncCursor {
+    /// 已确认连续的最大消息ID，此ID及之前的消息本地都是完整的
+    synced_to: Option<String>,
+    /// 尚未补齐的缺口
+    gap: Option<SyncGap>,
+    update_time: i64,
+}
+
+/// 消息缺口：从 `resume_cursor` 继续向前翻页直到 `synced_to`，补齐后游标推进到 `top`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SyncGap {
+    resume_cursor: String,
+    top: String,
+}
+
+/// 一段区间的拉取结果
+enum RangeEnd {
+    /// 已到达下界或服务端没有更早的消息
+    Complete,
+    /// 达到页数上限，保存翻页游标以便继续
+    Exhausted(String),
+}
+
+fn parse_id(id: Option<&str>) -> Option<i64> {
+    id.and_then(|id| id.parse::<i64>().ok())
+}
+
+fn message_id(msg: &MessageResp) -> Option<i64> {
+    parse_id(msg.message.id.as_deref())
+}
+
+fn cursor_key(room_id: &str) -> String {
+    format!("{}{}", SYNC_CURSOR_KEY_PREFIX, room_id)
+}
+
+async fn load_cursor(
+    db: &DatabaseConnection,
+    room_id: &str,
+    login_uid: &str,
+) -> Result<Option<RoomSyncCursor>, CommonError> {
+    let config =
+        im_config_repository::get_config_by_key(db, &cursor_key(room_id), login_uid).await?;
+    Ok(config
+        .and_then(|c| c.config_value)
+        .and_then(|value| serde_json::from_str(&value).ok()))
+}
+
+async fn save_cursor(
+    db: &DatabaseConnection,
+    room_id: &str,
+    cursor: &RoomSyncCursor,
+    login_uid: &str,
+) -> Result<(), CommonError> {
+    let value = serde_json::to_string(cursor)
+        .map_err(|e| anyhow::anyhow!("Failed to serialize sync cursor: {}", e))?;
+    im_config_repository::save_or_update_config(db, &cursor_key(room_id), Some(value), login_uid)
+        .await
+}
+
+/// 清空所有房间的同步游标，全量同步后调用，下次增量同步以本地最大消息ID为起点
+pub async fn reset_sync_cursors(
+    db: &DatabaseConnection,
+    login_uid: &str,
+) -> Result<u64, CommonError> {
+    im_config_repository::delete_config_by_prefix(db, SYNC_CURSOR_KEY_PREFIX, login_uid).await
+}
+
+/// 从 `start_cursor` 开始向前翻页，收集 ID 大于 `stop_at` 的消息
+///
+/// 翻到 `stop_at` 所在的页时，先比较该页中不大于 `stop_at` 的消息数量与本地同一ID区间的消息数量，
+/// 一致即认为与本地消息衔接上；不一致时逐条比对，有缺失说明下界之前还有缺口，补上缺失的消息并继续向前翻页。
+/// `stop_at` 为 None 时表示本地没有可信的下界，一直拉取到房间最早的消息
+async fn fetch_range(
+    client: &ImRequestClient,
+    db: &DatabaseConnection,
+    room_id: &str,
+    login_uid: &str,
+    start_cursor: Option<String>,
+    stop_at: Option<i64>,
+) -> Result<(Vec<MessageResp>, RangeEnd), CommonError> {
+    let mut cursor = start_cursor;
+    let mut messages = Vec::new();
+
+    for _ in 0..MAX_PAGES_PER_ROOM {
+        let page = client
+            .call(&MsgPageReq {
+                room_id: room_id.to_string(),
+                page_size: SYNC_PAGE_SIZE,
+                cursor: cursor.clone(),
+            })
+            .await?;
+        let Some(page) = page else {
+            return Ok((messages, RangeEnd::Complete));
+        };
+
+        let list = page.list.unwrap_or_default();
+        // 服务端没有返回消息说明已经没有更早的消息，不再继续翻页
+        if list.is_empty() {
+            return Ok((messages, RangeEnd::Complete));
+        }
+
+        let mut overlap = Vec::new();
+        for msg in list {
+            match (message_id(&msg), stop_at) {
+                (Some(id), Some(stop)) if id <= stop => overlap.push(msg),
+                _ => messages.push(msg),
+            }
+        }
+
+        let mut reached = false;
+        if let (Some(stop), Some(lowest)) = (stop_at, overlap.iter().filter_map(message_id).min()) {
+            // 先比较服务端与本地在 [lowest, stop] 区间内的消息数量，一致说明本地在这段区间内是连续的
+            let local_count = im_message_repository::count_room_messages_in_id_range(
+                db, room_id, login_uid, lowest, stop,
+            )
+            .await?;
+            reached = local_count == overlap.len() as u64;
+        }
+        if !reached && !overlap.is_empty() {
+            // 数量不一致时逐条比对，找出真正缺失的消息
+            let ids: Vec<(String, Option<i64>)> = overlap
+                .iter()
+                .filter_map(|msg| msg.message.id.clone().map(|id| (id, msg.message.send_time)))
+                .collect();
+            let missing =
+                im_message_repository::find_missing_message_ids(db, room_id, login_uid, &ids)
+                    .await?;
+            reached = missing.is_empty();
+            if !reached {
+                warn!(
+                    "Room {} is missing {} messages below the synced boundary, continue paging",
+                    room_id,
+                    missing.len()
+                );
+                messages.extend(overlap.into_iter().filter(|msg| {
+                    msg.message
+                        .id
+                        .as_ref()
+                        .is_some_and(|id| missing.contains(id))
+                }));
+            }
+        }
+
+        let next_cursor = page.cursor.filter(|c| !c.is_empty());
+        match next_cursor {
+            Some(next) if !reached && !page.is_last => cursor = Some(next),
+            _ => return Ok((messages, RangeEnd::Complete)),
+        }
+    }
+
+    match cursor {
+        Some(cursor) => Ok((messages, RangeEnd::Exhausted(cursor))),
+        None => Ok((messages, RangeEnd::Complete)),
+    }
+}
+
+/// 按发送时间顺序写入消息，逐条计算 time_block 以便与本地已有消息正确衔接
+/// 写入经过全局写锁，避免与 WebSocket 推送的消息同时写库
+async fn save_fetched_messages(
+    db: &DatabaseConnection,
+    write_lock: &Arc<Mutex<()>>,
+    mut messages: Vec<MessageResp>,
+    login_uid: &str,
+) -> Result<usize, CommonError> {
+    messages.sort_by_key(|msg| msg.message.send_time.unwrap_or(0));
+    let count = messages.len();
+    let records: Vec<_> = messages
+        .into_iter()
+        .map(|msg| convert_resp_to_record_for_fetch(msg, login_uid.to_string()))
+        .filter(|record| !record.message.id.is_empty() && !record.message.room_id.is_empty())
+        .collect();
+    if records.is_empty() {
+        return Ok(count);
+    }
+
+    run_with_write_lock(write_lock.clone(), "save_synced_messages", || {
+        let records = records.clone();
+        async move {
+            let tx = db.begin().await?;
+            for mut record in records {
+                if let Some(send_time) = record.message.send_time {
+                    record.message.time_block = im_message_repository::calculate_time_block(
+                        &tx,
+                        &record.message.room_id,
+                        &record.message.id,
+                        send_time,
+                        login_uid,
+                    )
+                    .await?;
+                }
+                im_message_repository::save_all(&tx, vec![record]).await?;
+            }
+            tx.commit().await?;
+            Ok::<(), CommonError>(())
+        }
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!(e))?;
+
+    Ok(count)
+}
+
+/// 增量同步单个房间，返回新写入的消息数量
+///
+/// 本地最大消息ID之前可能存在缺口：WebSocket 推送的新消息会抬高最大ID，但断线期间的消息并未落库。
+/// 因此以游标中已确认连续的 `synced_to` 作为下界，从最新消息向前翻页直到下界，
+/// 并在下界处与本地消息比对确认是否衔接；本地没有该房间的消息时一直拉取到房间最早的消息。
+/// 翻页次数超出上限时把剩余区间记录为缺口，下次同步继续补齐。
+async fn sync_room(
+    client: &ImRequestClient,
+    db: &DatabaseConnection,
+    write_lock: &Arc<Mutex<()>>,
+    room_id: &str,
+    login_uid: &str,
+) -> Result<usize, CommonError> {
+    let mut cursor = load_cursor(db, room_id, login_uid).await?;
+    let lower = match cursor.as_ref() {
+        // 游标中的下界为空表示还没有确认过任何消息，需要从房间最早的消息开始补齐
+        Some(cursor) => parse_id(cursor.synced_to.as_deref()),
+        // 没有游标时以本地最大消息ID为下界，翻页到下界时再与本地消息比对
+        None => parse_id(
+            im_message_repository::get_room_max_message_id(db, room_id, login_uid)
+                .await?
+                .as_deref(),
+        ),
+    };
+    let previous_gap = cursor.as_mut().and_then(|c| c.gap.take());
+
+    // 先拉取最新的消息，直到上次缺口的顶部或已确认的下界
+    let top_stop = previous_gap
+        .as_ref()
+        .and_then(|gap| parse_id(Some(&gap.top)))
+        .or(lower);
+    let (mut messages, end) = fetch_range(client, db, room_id, login_uid, None, top_stop).await?;
+    let newest = messages.iter().filter_map(message_id).max();
+
+    let (synced_to, gap) = match (end, previous_gap) {
+        (RangeEnd::Exhausted(resume_cursor), _) => {
+            let top = newest.map(|id| id.to_string()).unwrap_or_default();
+            (lower, Some(SyncGap { resume_cursor, top }))
+        }
+        (RangeEnd::Complete, Some(gap)) => {
+            // 继续补齐上次未完成的缺口
+            let (older, end) = fetch_range(
+                client,
+                db,
+                room_id,
+                login_uid,
+                Some(gap.resume_cursor),
+                lower,
+            )
+            .await?;
+            messages.extend(older);
+            let top = newest.or_else(|| parse_id(Some(&gap.top)));
+            match end {
+                RangeEnd::Complete => (top.max(lower), None),
+                RangeEnd::Exhausted(resume_cursor) => (
+                    lower,
+                    Some(SyncGap {
+                        resume_cursor,
+                        top: top.map(|id| id.to_string()).unwrap_or_default(),
+                    }),
+                ),
+            }
+        }
+        (RangeEnd::Complete, None) => (newest.max(lower), None),
+    };
+
+    let saved = save_fetched_messages(db, write_lock, messages, login_uid).await?;
+    if gap.is_some() {
+        warn!(
+            "Room {} still has a message gap after syncing {} messages, will resume next time",
+            room_id, saved
+        );
+    }
+
+    let cursor = RoomSyncCursor {
+        synced_to: synced_to.map(|id| id.to_string()),
+        gap,
+        update_time: chrono::Utc::now().timestamp_millis(),
+    };
+    run_with_write_lock(write_lock.clone(), "save_sync_cursor", || {
+        save_cursor(db, room_id, &cursor, login_uid)
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!(e))?;
+
+    Ok(saved)
+}
+
+/// 判断房间是否需要同步：服务端有更新的会话活跃时间、本地最大ID超出已确认区间或存在未补齐的缺口
+async fn needs_sync(
+    db: &DatabaseConnection,
+    contact: &im_contact::Model,
+    login_uid: &str,
+) -> Result<bool, CommonError> {
+    let room_id = &contact.room_id;
+    let latest_send_time =
+        im_message_repository::get_room_latest_send_time(db, room_id, login_uid).await?;
+    if contact.active_time.unwrap_or(0) > latest_send_time.unwrap_or(0) {
+        return Ok(true);
+    }
+
+    let Some(cursor) = load_cursor(db, room_id, login_uid).await? else {
+        return Ok(false);
+    };
+    if cursor.gap.is_some() {
+        return Ok(true);
+    }
+
+    let max_id = parse_id(
+        im_message_repository::get_room_max_message_id(db, room_id, login_uid)
+            .await?
+            .as_deref(),
+    );
+    Ok(max_id > parse_id(cursor.synced_to.as_deref()))
+}
+
+/// 按房间增量同步离线消息，只拉取本地缺失的部分
+pub async fn sync_rooms_incrementally(
+    client: &ImRequestClient,
+    db_conn: &DatabaseConnection,
+    write_lock: &Arc<Mutex<()>>,
+    login_uid: &str,
+) -> Result<(), CommonError> {
+    let old_tokens = capture_token_snapshot(client);
+
+    // 先刷新会话列表，用会话的活跃时间判断哪些房间有新消息
+    let contacts: Option<Vec<im_contact::Model>> = client
+        .im_request(
+            ImUrl::GetContactList,
+            None::<serde_json::Value>,
+            None::<serde_json::Value>,
+        )
+        .await?;
+    let contacts = match contacts {
+        Some(contacts) => {
+            im_contact_repository::save_contact_batch(db_conn, contacts.clone(), login_uid).await?;
+            contacts
+        }
+        None => im_contact_repository::list_contact(db_conn, login_uid).await?,
+    };
+
+    let mut synced_rooms = 0;
+    let mut saved_messages = 0;
+    for contact in &contacts {
+        let room_id = &contact.room_id;
+        match needs_sync(db_conn, contact, login_uid).await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                warn!("Failed to check sync state of room {}: {}", room_id, e);
+                continue;
+            }
+        }
+
+        match sync_room(client, db_conn, write_lock, room_id, login_uid).await {
+            Ok(saved) => {
+                debug!("Synced {} messages for room {}", saved, room_id);
+                synced_rooms += 1;
+                saved_messages += saved;
+            }
+            Err(e) => warn!("Failed to sync messages for room {}: {}", room_id, e),
+        }
+    }
+
+    persist_token_if_refreshed(&old_tokens, client, login_uid);
+
+    info!(
+        "Incremental message sync finished, {} of {} rooms synced, {} messages saved",
+        synced_rooms,
+        contacts.len(),
+        saved_messages
+    );
+    Ok(())
+}
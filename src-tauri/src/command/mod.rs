@@ -2,15 +2,21 @@ use tauri::{AppHandle, State};
 
 use crate::AppData;
 
+pub mod ai_chat_sync;
 pub mod ai_command;
 pub mod app_state_command;
 pub mod chat_history_command;
 pub mod contact_command;
 pub mod database_command;
+pub mod download_command;
 pub mod file_manager_command;
+pub mod image_command;
+pub mod knowledge_command;
 pub mod markdown_command;
 pub mod message_command;
 pub mod message_mark_command;
+pub mod message_retry_command;
+pub mod message_sync;
 pub mod oauth_command;
 pub mod request_command;
 pub mod room_member_command;
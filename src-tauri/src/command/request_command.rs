@@ -3,11 +3,20 @@ use tauri::{AppHandle, Emitter, Manager, State};
 
 use crate::{
     AppData,
-    command::message_command::check_user_init_and_fetch_messages,
-    command::token_helper::capture_token_snapshot_direct,
+    command::ai_chat_sync::AiChatSync,
+    command::message_command::{
+        check_user_init_and_fetch_messages, run_with_write_lock, spawn_search_index_backfill,
+    },
+    command::token_helper::{
+        capture_token_snapshot, migrate_legacy_tokens, persist_token_if_refreshed,
+    },
     configuration::get_configuration,
     im_request_client::{ImRequest, ImUrl},
-    repository::{im_message_repository::reset_table_initialization_flags, im_user_repository},
+    repository::{
+        im_ai_chat_repository, im_message_repository::reset_table_initialization_flags,
+        im_user_repository,
+    },
+    secret_store::AuthTokens,
     vo::vo::{LoginReq, LoginResp},
 };
 
@@ -23,22 +32,22 @@ pub async fn login_command(
             // 先切换到用户专属数据库
             switch_to_user_database(&state, &app_handle, uid).await?;
 
-            // 从数据库获取用户的 refresh_token
-            let db_result =
-                im_user_repository::get_user_tokens(&*state.db_conn.read().await, uid).await;
-            match db_result {
-                Ok(Some((token, refresh_token))) => {
+            // 从凭证存储获取用户的 token
+            let restore_result = state.rc.restore_session(uid);
+            match restore_result {
+                Ok(Some(AuthTokens {
+                    token,
+                    refresh_token,
+                })) => {
                     if refresh_token.is_empty() {
-                        let check_result: Result<Option<serde_json::Value>, anyhow::Error> = {
-                            let mut rc = state.rc.lock().await;
-                            rc.token = Some(token.clone());
-                            rc.im_request(
+                        let check_result: Result<Option<serde_json::Value>, anyhow::Error> = state
+                            .rc
+                            .im_request(
                                 ImUrl::CheckToken,
                                 None::<serde_json::Value>,
                                 None::<serde_json::Value>,
                             )
-                            .await
-                        };
+                            .await;
 
                         if check_result.is_ok() {
                             let login_resp = LoginResp {
@@ -54,30 +63,14 @@ pub async fn login_command(
                     }
 
                     // 使用 start_refresh_token 刷新登录（不会添加 token 头，适合自动登录场景）
-                    let refresh_result = {
-                        let mut rc = state.rc.lock().await;
-                        // 设置 ImRequestClient 内部的 refresh_token
-                        rc.refresh_token = Some(refresh_token.clone());
-                        rc.start_refresh_token().await
-                    };
+                    // 会话已绑定，刷新后的 token 会自动写入凭证存储
+                    let refresh_result = state.rc.start_refresh_token().await;
 
                     match refresh_result {
                         Ok(()) => {
                             // 从 ImRequestClient 中获取刷新后的 token
-                            let rc = state.rc.lock().await;
-                            let new_token = rc.token.clone().unwrap_or_default();
-                            let new_refresh_token = rc.refresh_token.clone().unwrap_or_default();
-                            drop(rc);
-
-                            // 保存新的 token 信息到数据库
-                            im_user_repository::save_user_tokens(
-                                &*state.db_conn.read().await,
-                                uid,
-                                &new_token,
-                                &new_refresh_token,
-                            )
-                            .await
-                            .ok();
+                            let new_token = state.rc.token().unwrap_or_default();
+                            let new_refresh_token = state.rc.refresh_token().unwrap_or_default();
 
                             // 转换为 LoginResp 格式返回
                             let login_resp = LoginResp {
@@ -101,7 +94,9 @@ pub async fn login_command(
                     }
                 }
                 Ok(None) => {}
-                Err(_) => {}
+                Err(e) => {
+                    tracing::warn!("Failed to restore tokens for uid {}: {}", uid, e);
+                }
             };
             // 自动登录失败，返回错误让前端切换到手动登录
             return Err("自动登录失败，请手动登录".to_string());
@@ -111,10 +106,7 @@ pub async fn login_command(
     } else {
         // 手动登录逻辑
         let async_data = data.async_data;
-        let res = {
-            let mut rc = state.rc.lock().await;
-            rc.login(data).await.map_err(|e| e.to_string())?
-        }; // 锁在这里被释放
+        let res = state.rc.login(data).await.map_err(|e| e.to_string())?;
 
         // 登录成功后处理用户信息和token保存
         if let Some(login_resp) = &res {
@@ -140,7 +132,7 @@ async fn switch_to_user_database(
     // 创建新的数据库连接
     let new_db = configuration
         .database
-        .connection_string(app_handle, Some(uid))
+        .connect_or_fallback(app_handle, Some(uid))
         .await
         .map_err(|e| e.to_string())?;
 
@@ -152,6 +144,29 @@ async fn switch_to_user_database(
     // 重置表初始化标志，确保新数据库会创建必要的表
     reset_table_initialization_flags();
 
+    // 旧版本把 token 保存在用户数据库中，切换时迁移到凭证存储
+    if let Some(store) = state.rc.secret_store() {
+        if let Err(e) = migrate_legacy_tokens(&new_db, store.as_ref(), uid).await {
+            tracing::warn!("Failed to migrate legacy tokens for user {}: {}", uid, e);
+        }
+    }
+
+    // 上次退出时仍在接收的 AI 回答不会再更新，标记为已中断
+    let active_ids: Vec<String> = state
+        .ai_streams
+        .list(uid)
+        .into_iter()
+        .map(|stream| stream.request_id)
+        .collect();
+    if let Err(e) =
+        im_ai_chat_repository::mark_interrupted_messages(&new_db, uid, &active_ids).await
+    {
+        tracing::warn!("Failed to mark interrupted AI messages for {}: {}", uid, e);
+    }
+
+    // 为迁移前已有的消息回填全文索引
+    spawn_search_index_backfill(new_db.clone(), state.write_lock.clone(), uid.to_string());
+
     // 替换数据库连接
     {
         let mut db_guard = state.db_conn.write().await;
@@ -174,20 +189,20 @@ async fn handle_login_success(
     user_info.uid = login_resp.uid.clone();
     user_info.token = login_resp.token.clone();
     user_info.refresh_token = login_resp.refresh_token.clone();
-    // 保存 token 信息到数据库
-    im_user_repository::save_user_tokens(
-        &*state.db_conn.read().await,
-        uid,
-        &login_resp.token,
-        &login_resp.refresh_token,
-    )
-    .await
-    .map_err(|e| e.to_string())?;
+    drop(user_info);
+    im_user_repository::ensure_user(&*state.db_conn.read().await, uid)
+        .await
+        .map_err(|e| e.to_string())?;
 
-    let mut client = state.rc.lock().await;
+    // 保存 token 信息到凭证存储
+    state.rc.bind_session(uid);
+    if let Err(e) = state.rc.persist_tokens() {
+        tracing::warn!("Failed to persist tokens for {}: {}", uid, e);
+    }
     check_user_init_and_fetch_messages(
-        &mut client,
+        &state.rc,
         &*state.db_conn.read().await,
+        &state.write_lock,
         uid,
         async_data,
         false,
@@ -208,36 +223,37 @@ pub async fn im_request_command(
     app_handle: tauri::AppHandle,
 ) -> Result<Option<serde_json::Value>, String> {
     let user_uid = state.user_info.lock().await.uid.clone();
-    let mut rc = state.rc.lock().await;
+    let rc = &state.rc;
 
     // 记录请求前的 token，用于检测是否被刷新
-    let old_tokens = capture_token_snapshot_direct(&rc);
+    let old_tokens = capture_token_snapshot(rc);
 
     if let Ok(url) = url.parse::<ImUrl>() {
+        let ai_chat_sync = AiChatSync::from_request(&url, body.as_ref(), params.as_ref());
         let result: Result<Option<serde_json::Value>, anyhow::Error> =
             rc.im_request(url, body, params).await;
 
-        // 无论请求成功还是失败，都检查 token 是否被刷新，如果是则保存到数据库
+        // 无论请求成功还是失败，都检查 token 是否被刷新，如果是则保存到凭证存储
         // 这确保了即使请求重试后失败，刷新后的 token 也能被持久化
-        if let (Some(new_token), Some(new_refresh_token)) =
-            (rc.token.clone(), rc.refresh_token.clone())
-        {
-            if old_tokens.token != rc.token || old_tokens.refresh_token != rc.refresh_token {
-                if !user_uid.is_empty() {
-                    im_user_repository::save_user_tokens(
-                        &*state.db_conn.read().await,
-                        &user_uid,
-                        &new_token,
-                        &new_refresh_token,
-                    )
-                    .await
-                    .ok();
-                }
-            }
-        }
+        persist_token_if_refreshed(&old_tokens, rc, &user_uid);
 
         match result {
             Ok(data) => {
+                if let Some(sync) = ai_chat_sync {
+                    // AI 会话和消息同步到本地，供离线浏览和搜索
+                    let db = state.db_conn.read().await.clone();
+                    let write_lock = state.write_lock.clone();
+                    let data = data.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = run_with_write_lock(write_lock, "sync_ai_chat", || {
+                            sync.clone().apply(&db, &user_uid, data.as_ref())
+                        })
+                        .await
+                        {
+                            tracing::warn!("Failed to sync AI chat cache: {}", e);
+                        }
+                    });
+                }
                 return Ok(data);
             }
             Err(e) => {
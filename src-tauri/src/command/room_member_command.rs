@@ -1,20 +1,21 @@
 use crate::AppData;
-use crate::command::token_helper::{capture_token_snapshot_arc, persist_token_if_refreshed_arc};
+use crate::command::message_command::run_with_write_lock;
+use crate::command::token_helper::{capture_token_snapshot, persist_token_if_refreshed};
 use crate::error::CommonError;
-use crate::pojo::common::{CursorPageParam, CursorPageResp, Page, PageParam};
+use crate::pojo::common::{CursorPageParam, CursorPageResp, Page, PageParam, deserialize_id};
 use crate::repository::im_room_member_repository::update_my_room_info as update_my_room_info_db;
+use crate::vo::group::{GroupListMemberReq, GroupListReq};
 use crate::vo::vo::MyRoomInfoReq;
 
 use entity::{im_room, im_room_member};
 use tracing::{error, info};
 
-use crate::im_request_client::{ImRequestClient, ImUrl};
-use crate::repository::im_room_member_repository;
+use crate::im_request_client::ImRequestClient;
+use crate::repository::{im_contact_repository, im_room_member_repository};
+use sea_orm::TransactionTrait;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::sync::Arc;
-use tauri::State;
-use tokio::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -52,21 +53,12 @@ pub async fn update_my_room_info(
         let uid = user_info.uid.clone();
         drop(user_info);
 
-        let old_tokens = capture_token_snapshot_arc(&state.rc).await;
+        let old_tokens = capture_token_snapshot(&state.rc);
 
         // 调用后端接口更新房间信息
-        let _resp: Option<bool> = state
-            .rc
-            .lock()
-            .await
-            .im_request(
-                ImUrl::UpdateMyRoomInfo,
-                Some(my_room_info.clone()),
-                None::<serde_json::Value>,
-            )
-            .await?;
+        state.rc.call(&my_room_info).await?;
 
-        persist_token_if_refreshed_arc(&old_tokens, &state.rc, &state.db_conn, &uid).await;
+        persist_token_if_refreshed(&old_tokens, &state.rc, &uid);
 
         // 更新本地数据库
         update_my_room_info_db(
@@ -107,13 +99,7 @@ pub async fn get_room_members(
     info!("Calling to get all member list of room with room_id");
     let uid = state.user_info.lock().await.uid.clone();
     let result: Result<Vec<RoomMemberResponse>, CommonError> = async {
-        let mut members = fetch_and_update_room_members(
-            room_id.clone(),
-            state.rc.clone(),
-            state.db_conn.clone(),
-            &uid,
-        )
-        .await?;
+        let mut members = fetch_and_update_room_members(room_id.clone(), &state.rc, &uid).await?;
 
         sort_room_members(&mut members);
 
@@ -170,9 +156,7 @@ pub async fn page_room(
     let uid = state.user_info.lock().await.uid.clone();
     let result: Result<Page<im_room::Model>, CommonError> = async {
         // 直接调用后端接口获取数据，不保存到数据库
-        let data =
-            fetch_rooms_from_backend(page_param, state.rc.clone(), state.db_conn.clone(), &uid)
-                .await?;
+        let data = fetch_rooms_from_backend(page_param, &state.rc, &uid).await?;
 
         Ok(data)
     }
@@ -190,24 +174,19 @@ pub async fn page_room(
 /// 从后端获取房间数据（不保存到数据库）
 async fn fetch_rooms_from_backend(
     page_param: PageParam,
-    request_client: Arc<Mutex<ImRequestClient>>,
-    db_conn: Arc<tokio::sync::RwLock<sea_orm::DatabaseConnection>>,
+    request_client: &ImRequestClient,
     uid: &str,
 ) -> Result<Page<im_room::Model>, CommonError> {
-    let old_tokens = capture_token_snapshot_arc(&request_client).await;
-
-    let resp: Option<Page<im_room::Model>> = {
-        let mut client = request_client.lock().await;
-        client
-            .im_request(
-                ImUrl::GroupList,
-                None::<serde_json::Value>,
-                Some(page_param),
-            )
-            .await?
-    };
+    let old_tokens = capture_token_snapshot(request_client);
+
+    let resp = request_client
+        .call(&GroupListReq {
+            current: page_param.current,
+            size: page_param.size,
+        })
+        .await?;
 
-    persist_token_if_refreshed_arc(&old_tokens, &request_client, &db_conn, uid).await;
+    persist_token_if_refreshed(&old_tokens, request_client, uid);
 
     if let Some(data) = resp {
         Ok(data)
@@ -244,25 +223,14 @@ fn sort_room_members(members: &mut Vec<RoomMemberResponse>) {
 /// 异步更新房间成员数据
 async fn fetch_and_update_room_members(
     room_id: String,
-    request_client: Arc<Mutex<ImRequestClient>>,
-    db_conn: Arc<tokio::sync::RwLock<sea_orm::DatabaseConnection>>,
+    request_client: &ImRequestClient,
     uid: &str,
 ) -> Result<Vec<RoomMemberResponse>, CommonError> {
-    let old_tokens = capture_token_snapshot_arc(&request_client).await;
+    let old_tokens = capture_token_snapshot(request_client);
 
-    let resp: Option<Vec<RoomMemberResponse>> = request_client
-        .lock()
-        .await
-        .im_request(
-            ImUrl::GroupListMember,
-            None::<serde_json::Value>,
-            Some(serde_json::json!({
-                "roomId": room_id
-            })),
-        )
-        .await?;
+    let resp = request_client.call(&GroupListMemberReq { room_id }).await?;
 
-    persist_token_if_refreshed_arc(&old_tokens, &request_client, &db_conn, uid).await;
+    persist_token_if_refreshed(&old_tokens, request_client, uid);
 
     if let Some(data) = resp {
         return Ok(data);
@@ -270,3 +238,194 @@ async fn fetch_and_update_room_members(
 
     Ok(Vec::new())
 }
+
+/// 群成员变动类型：加入群组
+const CHANGE_TYPE_JOIN: u8 = 1;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ChangedMember {
+    #[serde(deserialize_with = "deserialize_id")]
+    uid: String,
+    account: Option<String>,
+    name: Option<String>,
+    avatar: Option<String>,
+    active_status: Option<u8>,
+    #[serde(rename = "roleId")]
+    group_role: Option<i64>,
+    loc_place: Option<String>,
+    last_opt_time: Option<i64>,
+    my_name: Option<String>,
+    user_state_id: Option<String>,
+}
+
+impl From<ChangedMember> for im_room_member::Model {
+    fn from(member: ChangedMember) -> Self {
+        Self {
+            id: String::new(),
+            room_id: None,
+            uid: Some(member.uid),
+            account: member.account,
+            my_name: member.my_name,
+            active_status: member.active_status,
+            group_role: member.group_role,
+            loc_place: member.loc_place,
+            last_opt_time: member.last_opt_time.unwrap_or_default(),
+            create_time: Some(chrono::Utc::now().timestamp_millis()),
+            name: member.name.unwrap_or_default(),
+            avatar: member.avatar,
+            user_state_id: member.user_state_id,
+            login_uid: String::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct MemberChangePayload {
+    #[serde(deserialize_with = "deserialize_id")]
+    room_id: String,
+    change_type: u8,
+    #[serde(default)]
+    user_list: Vec<ChangedMember>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RoomInfoChangePayload {
+    #[serde(deserialize_with = "deserialize_id")]
+    room_id: String,
+    name: Option<String>,
+    avatar: Option<String>,
+}
+
+/// 获取写入本地数据库所需的状态和当前登录用户，未登录时返回 None
+async fn event_context(app_handle: &AppHandle) -> Option<(State<'_, AppData>, String)> {
+    let state = app_handle.try_state::<AppData>()?;
+    let login_uid = state.user_info.lock().await.uid.clone();
+    (!login_uid.is_empty()).then_some((state, login_uid))
+}
+
+/// 将 WebSocket 推送的群成员变动写入本地数据库
+pub(crate) async fn apply_member_change(
+    app_handle: &AppHandle,
+    data: &serde_json::Value,
+) -> Result<(), String> {
+    let Some((state, login_uid)) = event_context(app_handle).await else {
+        return Ok(());
+    };
+    let payload: MemberChangePayload = serde_json::from_value(data.clone())
+        .map_err(|e| format!("Failed to decode member change event: {}", e))?;
+
+    run_with_write_lock(state.write_lock.clone(), "apply_member_change", || {
+        let db_conn = state.db_conn.clone();
+        let payload = payload.clone();
+        let login_uid = login_uid.clone();
+        async move {
+            let db = db_conn.read().await;
+            let tx = db.begin().await?;
+            let room_id = payload.room_id;
+
+            if payload.change_type == CHANGE_TYPE_JOIN {
+                let members = payload.user_list.into_iter().map(Into::into).collect();
+                im_room_member_repository::upsert_room_members(&tx, &room_id, members, &login_uid)
+                    .await?;
+            } else if payload.user_list.iter().any(|m| m.uid == login_uid) {
+                // 本人被移除或退出群聊，清理该群的本地数据
+                im_room_member_repository::delete_room(&tx, &room_id, &login_uid).await?;
+                im_contact_repository::delete_contact(&tx, &room_id, &login_uid).await?;
+            } else {
+                let uids: Vec<String> = payload.user_list.into_iter().map(|m| m.uid).collect();
+                im_room_member_repository::remove_room_members(&tx, &room_id, &uids, &login_uid)
+                    .await?;
+            }
+
+            tx.commit().await?;
+            Ok(())
+        }
+    })
+    .await
+}
+
+/// 将 WebSocket 推送的群信息变更写入本地数据库
+pub(crate) async fn apply_room_info_change(
+    app_handle: &AppHandle,
+    data: &serde_json::Value,
+) -> Result<(), String> {
+    let Some((state, login_uid)) = event_context(app_handle).await else {
+        return Ok(());
+    };
+    let payload: RoomInfoChangePayload = serde_json::from_value(data.clone())
+        .map_err(|e| format!("Failed to decode room info change event: {}", e))?;
+
+    run_with_write_lock(state.write_lock.clone(), "apply_room_info_change", || {
+        let db_conn = state.db_conn.clone();
+        let payload = payload.clone();
+        let login_uid = login_uid.clone();
+        async move {
+            let db = db_conn.read().await;
+            let tx = db.begin().await?;
+            let name = payload.name.as_deref();
+            let avatar = payload.avatar.as_deref();
+
+            im_room_member_repository::update_room_info(
+                &tx,
+                &payload.room_id,
+                name,
+                avatar,
+                &login_uid,
+            )
+            .await?;
+            im_contact_repository::update_contact_info(
+                &tx,
+                &payload.room_id,
+                name,
+                avatar,
+                &login_uid,
+            )
+            .await?;
+
+            tx.commit().await?;
+            Ok(())
+        }
+    })
+    .await
+}
+
+/// 将 WebSocket 推送的群聊解散事件写入本地数据库，保留历史消息
+pub(crate) async fn apply_room_dissolution(
+    app_handle: &AppHandle,
+    data: &serde_json::Value,
+) -> Result<(), String> {
+    let Some((state, login_uid)) = event_context(app_handle).await else {
+        return Ok(());
+    };
+    // 负载为房间ID，兼容 { roomId } 格式
+    let room_id = match data {
+        serde_json::Value::String(room_id) => room_id.clone(),
+        serde_json::Value::Number(room_id) => room_id.to_string(),
+        other => other
+            .get("roomId")
+            .map(|id| {
+                id.as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| id.to_string())
+            })
+            .ok_or_else(|| "Room dissolution event has no roomId".to_string())?,
+    };
+
+    run_with_write_lock(state.write_lock.clone(), "apply_room_dissolution", || {
+        let db_conn = state.db_conn.clone();
+        let room_id = room_id.clone();
+        let login_uid = login_uid.clone();
+        async move {
+            let db = db_conn.read().await;
+            let tx = db.begin().await?;
+            im_room_member_repository::delete_room(&tx, &room_id, &login_uid).await?;
+            im_contact_repository::delete_contact(&tx, &room_id, &login_uid).await?;
+            tx.commit().await?;
+            Ok(())
+        }
+    })
+    .await
+}
@@ -1,8 +1,8 @@
+use crate::error::CommonError;
 use crate::im_request_client::ImRequestClient;
 use crate::repository::im_user_repository;
+use crate::secret_store::{self, SecretStore};
 use sea_orm::DatabaseConnection;
-use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock};
 use tracing::{info, warn};
 
 #[derive(Clone, Debug)]
@@ -12,191 +12,100 @@ pub struct TokenSnapshot {
 }
 
 /// Token 刷新检测器
-/// 用于在 im_request 调用前后检测 token 是否被刷新，并自动持久化到数据库
+/// 用于在 im_request 调用前后检测 token 是否被刷新，并自动持久化到凭证存储
 pub struct TokenRefreshGuard {
     old_tokens: TokenSnapshot,
 }
 
 impl TokenRefreshGuard {
     /// 在 im_request 调用前创建，记录当前的 token 和 refresh_token
-    pub async fn before_request(client: &Mutex<ImRequestClient>) -> Self {
-        let old_tokens = capture_token_snapshot(client).await;
+    pub fn before_request(client: &ImRequestClient) -> Self {
+        let old_tokens = capture_token_snapshot(client);
         Self { old_tokens }
     }
 
-    /// 在 im_request 调用后检查 token 是否被刷新，如果是则持久化到数据库
-    pub async fn persist_if_refreshed(
-        &self,
-        client: &Mutex<ImRequestClient>,
-        db_conn: &RwLock<DatabaseConnection>,
-        uid: &str,
-    ) {
-        if uid.is_empty() {
-            return;
-        }
-
-        let (new_token, new_refresh_token, token_changed) = {
-            let c = client.lock().await;
-            let changed = self.old_tokens.token != c.token
-                || self.old_tokens.refresh_token != c.refresh_token;
-            (c.token.clone(), c.refresh_token.clone(), changed)
-        };
+    /// 在 im_request 调用后检查 token 是否被刷新，如果是则持久化到凭证存储
+    pub fn persist_if_refreshed(&self, client: &ImRequestClient, uid: &str) {
+        persist_token_if_refreshed(&self.old_tokens, client, uid)
+    }
+}
 
-        if token_changed {
-            if let (Some(token), Some(refresh_token)) = (new_token, new_refresh_token) {
-                match im_user_repository::save_user_tokens(
-                    &*db_conn.read().await,
-                    uid,
-                    &token,
-                    &refresh_token,
-                )
-                .await
-                {
-                    Ok(_) => {
-                        info!(
-                            "[TOKEN_PERSIST] SUCCESS: tokens saved for uid: {}, token_len: {}, refresh_len: {}",
-                            uid,
-                            token.len(),
-                            refresh_token.len()
-                        );
-                    }
-                    Err(e) => {
-                        warn!(
-                            "[TOKEN_PERSIST] FAILED: error saving tokens for uid {}: {}",
-                            uid, e
-                        );
-                    }
-                }
-            }
-        }
+/// 获取当前的 token 快照用于后续比较
+pub fn capture_token_snapshot(client: &ImRequestClient) -> TokenSnapshot {
+    TokenSnapshot {
+        token: client.token(),
+        refresh_token: client.refresh_token(),
     }
 }
 
-/// 便捷函数：在 im_request 后检查并持久化刷新的 token
+/// 在 im_request 后检查并持久化刷新的 token
+///
+/// 客户端绑定了会话时刷新 token 会自动写入凭证存储，这里兜底处理请求期间会话尚未绑定的情况。
+/// 客户端的所有克隆共享登录状态，并发请求中任意一个触发的刷新都会在这里被检测到，重复写入同样的值是安全的
 ///
 /// # Arguments
 /// * `old_tokens` - 请求前的 token 快照
-/// * `client` - ImRequestClient 的锁
-/// * `db_conn` - 数据库连接的锁
+/// * `client` - 请求客户端
 /// * `uid` - 用户 ID
-pub async fn persist_token_if_refreshed(
-    old_tokens: &TokenSnapshot,
-    client: &Mutex<ImRequestClient>,
-    db_conn: &RwLock<DatabaseConnection>,
-    uid: &str,
-) {
+pub fn persist_token_if_refreshed(old_tokens: &TokenSnapshot, client: &ImRequestClient, uid: &str) {
     if uid.is_empty() {
         return;
     }
 
-    let (new_token, new_refresh_token, token_changed) = {
-        let c = client.lock().await;
-        let changed = old_tokens.token != c.token || old_tokens.refresh_token != c.refresh_token;
-        (c.token.clone(), c.refresh_token.clone(), changed)
+    let current = capture_token_snapshot(client);
+    let token_changed =
+        old_tokens.token != current.token || old_tokens.refresh_token != current.refresh_token;
+    if !token_changed {
+        return;
+    }
+
+    let (Some(store), Some(token), Some(refresh_token)) = (
+        client.secret_store(),
+        current.token.as_deref(),
+        current.refresh_token.as_deref(),
+    ) else {
+        return;
     };
 
-    if token_changed {
-        if let (Some(token), Some(refresh_token)) = (new_token, new_refresh_token) {
-            match im_user_repository::save_user_tokens(
-                &*db_conn.read().await,
+    match secret_store::save_tokens(store.as_ref(), uid, token, refresh_token) {
+        Ok(_) => {
+            info!(
+                "[TOKEN_PERSIST] SUCCESS: tokens saved for uid: {}, token_len: {}, refresh_len: {}",
                 uid,
-                &token,
-                &refresh_token,
-            )
-            .await
-            {
-                Ok(_) => {
-                    info!(
-                        "[TOKEN_PERSIST] SUCCESS: tokens saved for uid: {}, token_len: {}, refresh_len: {}",
-                        uid,
-                        token.len(),
-                        refresh_token.len()
-                    );
-                }
-                Err(e) => {
-                    warn!(
-                        "[TOKEN_PERSIST] FAILED: error saving tokens for uid {}: {}",
-                        uid, e
-                    );
-                }
-            }
+                token.len(),
+                refresh_token.len()
+            );
+        }
+        Err(e) => {
+            warn!(
+                "[TOKEN_PERSIST] FAILED: error saving tokens for uid {}: {}",
+                uid, e
+            );
         }
     }
 }
 
-/// 便捷函数：获取当前的 token 快照用于后续比较
-pub async fn capture_token_snapshot(client: &Mutex<ImRequestClient>) -> TokenSnapshot {
-    let c = client.lock().await;
-    TokenSnapshot {
-        token: c.token.clone(),
-        refresh_token: c.refresh_token.clone(),
-    }
-}
-
-pub async fn capture_token_snapshot_arc(client: &Arc<Mutex<ImRequestClient>>) -> TokenSnapshot {
-    capture_token_snapshot(client.as_ref()).await
-}
-
-pub fn capture_token_snapshot_direct(client: &ImRequestClient) -> TokenSnapshot {
-    TokenSnapshot {
-        token: client.token.clone(),
-        refresh_token: client.refresh_token.clone(),
-    }
-}
-
-/// 便捷函数：获取当前的 refresh_token 用于后续比较
-pub async fn capture_refresh_token(client: &Mutex<ImRequestClient>) -> Option<String> {
-    client.lock().await.refresh_token.clone()
-}
-
-/// 便捷函数：使用 Arc 包装的类型
-pub async fn persist_token_if_refreshed_arc(
-    old_tokens: &TokenSnapshot,
-    client: &Arc<Mutex<ImRequestClient>>,
-    db_conn: &Arc<RwLock<DatabaseConnection>>,
-    uid: &str,
-) {
-    persist_token_if_refreshed(old_tokens, client.as_ref(), db_conn.as_ref(), uid).await
-}
-
-pub async fn capture_refresh_token_arc(client: &Arc<Mutex<ImRequestClient>>) -> Option<String> {
-    capture_refresh_token(client.as_ref()).await
-}
-
-/// 便捷函数：使用直接引用的 ImRequestClient（不需要 Mutex）
-pub async fn persist_token_if_refreshed_direct(
-    old_tokens: &TokenSnapshot,
-    client: &ImRequestClient,
+/// 把旧版本写在 im_user 表中的 token 迁移到凭证存储
+///
+/// 凭证存储中已有该用户的 token 时以凭证存储为准，数据库中的旧值直接清除；
+/// 写入凭证存储失败时保留数据库中的旧值，下次切换数据库时再迁移
+pub async fn migrate_legacy_tokens(
     db_conn: &DatabaseConnection,
+    store: &dyn SecretStore,
     uid: &str,
-) {
-    if uid.is_empty() {
-        return;
-    }
-
-    let token_changed =
-        old_tokens.token != client.token || old_tokens.refresh_token != client.refresh_token;
+) -> Result<(), CommonError> {
+    let Some((token, refresh_token)) =
+        im_user_repository::get_legacy_user_tokens(db_conn, uid).await?
+    else {
+        return Ok(());
+    };
 
-    if token_changed {
-        if let (Some(token), Some(refresh_token)) =
-            (client.token.clone(), client.refresh_token.clone())
-        {
-            match im_user_repository::save_user_tokens(db_conn, uid, &token, &refresh_token).await {
-                Ok(_) => {
-                    info!(
-                        "[TOKEN_PERSIST] SUCCESS: tokens saved for uid: {}, token_len: {}, refresh_len: {}",
-                        uid,
-                        token.len(),
-                        refresh_token.len()
-                    );
-                }
-                Err(e) => {
-                    warn!(
-                        "[TOKEN_PERSIST] FAILED: error saving tokens for uid {}: {}",
-                        uid, e
-                    );
-                }
-            }
-        }
+    if secret_store::load_tokens(store, uid)?.is_none() && !token.is_empty() {
+        secret_store::save_tokens(store, uid, &token, &refresh_token)?;
+        info!(
+            "[TOKEN_MIGRATE] tokens moved to secret store for uid: {}",
+            uid
+        );
     }
+    im_user_repository::clear_legacy_user_tokens(db_conn, uid).await
 }
@@ -1,27 +1,56 @@
+use crate::AppData;
+use crate::configuration::{HttpSettings, UploadSettings};
+use crate::repository::im_upload_task_repository;
+use crate::storage::{QiniuStorage, S3Storage, StorageBackend, StorageKind, StorageProvider};
+use crate::vo::storage::{OssTokenReq, StorageProviderReq};
 use bytes::Bytes;
-use futures_util::stream::try_unfold;
+use entity::im_upload_task;
+use futures_util::StreamExt;
+use futures_util::stream::{self, try_unfold};
 use md5::{Digest, Md5};
-use serde::Deserialize;
+use once_cell::sync::Lazy;
+use sea_orm::DatabaseConnection;
 use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::SeekFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, path::PathBuf};
 use tauri::{AppHandle, Manager, ipc::Channel, path::BaseDirectory};
-use tokio::{fs::File, io::AsyncReadExt};
+use tokio::sync::watch;
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tracing::{info, warn};
+
+/// 超过该大小的文件不做去重
+const QINIU_CHUNK_THRESHOLD: u64 = 4 * 1024 * 1024;
+/// 分片凭证过期前预留的时间（秒），临近过期的凭证不再复用
+const CTX_EXPIRE_MARGIN_SECS: i64 = 60 * 60;
+/// 计算文件指纹时首尾各读取的字节数
+const FINGERPRINT_SAMPLE_SIZE: u64 = 1024 * 1024;
+/// 请求体每次写入的字节数，带宽限制按该粒度生效
+const STREAM_CHUNK_SIZE: usize = 256 * 1024;
+/// 进度事件的最小发送间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+/// 计算上传速度的时间窗口
+const SPEED_WINDOW: Duration = Duration::from_secs(3);
+
+const STATUS_UPLOADING: &str = "uploading";
+const STATUS_PAUSED: &str = "paused";
+const STATUS_FAILED: &str = "failed";
 
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UploadProgressPayload {
     pub progress_total: u64,
     pub total: u64,
-}
-
-#[derive(Deserialize)]
-struct QiniuMkblkResponse {
-    ctx: String,
-}
-
-#[derive(Deserialize)]
-struct QiniuMkfileResponse {
-    key: Option<String>,
+    /// 最近一段时间的上传速度（字节/秒）
+    pub speed: u64,
+    /// 预计剩余时间（秒），速度未知时为空
+    pub eta: Option<u64>,
 }
 
 #[tauri::command]
@@ -34,9 +63,20 @@ pub async fn upload_file_put(
     on_progress: Channel<UploadProgressPayload>,
 ) -> Result<(), String> {
     let file_path = resolve_upload_path(&app_handle, &path, base_dir.as_deref())?;
-    upload_put(url, file_path, headers.unwrap_or_default(), on_progress).await
+    let client = upload_client(&app_handle).await?;
+    apply_upload_settings(&app_handle).await;
+    upload_put(
+        &client,
+        url,
+        file_path,
+        headers.unwrap_or_default(),
+        on_progress,
+    )
+    .await
 }
 
+/// 分片上传到七牛，上传进度会记录到本地数据库，中断后可以通过 [`resume_upload`] 继续
+/// `upload_id` 用于暂停、取消和恢复，未传入时自动生成
 #[tauri::command]
 pub async fn qiniu_upload_resumable(
     app_handle: AppHandle,
@@ -48,6 +88,7 @@ pub async fn qiniu_upload_resumable(
     account: Option<String>,
     storage_prefix: Option<String>,
     enable_deduplication: Option<bool>,
+    upload_id: Option<String>,
     on_progress: Channel<UploadProgressPayload>,
 ) -> Result<String, String> {
     let file_path = resolve_upload_path(&app_handle, &path, base_dir.as_deref())?;
@@ -57,18 +98,315 @@ pub async fn qiniu_upload_resumable(
         .ok_or_else(|| "Failed to determine file name".to_string())?
         .to_string();
 
-    qiniu_resumable_upload(
-        file_path,
-        token,
-        domain,
-        scene.unwrap_or_else(|| "chat".to_string()),
-        account,
-        storage_prefix,
-        enable_deduplication.unwrap_or(false),
-        file_name,
-        on_progress,
+    let (db, login_uid) = upload_task_store(&app_handle).await?;
+    let upload_id = upload_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    if im_upload_task_repository::get_task(&db, &upload_id, &login_uid)
+        .await?
+        .is_some()
+    {
+        return Err(format!(
+            "Upload {upload_id} already exists, use resume_upload"
+        ));
+    }
+
+    let client = upload_client(&app_handle).await?;
+    let storage = StorageBackend::Qiniu(QiniuStorage::new(client, &domain, token));
+    let task = create_upload_task(
+        upload_id,
+        login_uid,
+        &file_path,
+        &storage,
+        UploadKeyOptions {
+            scene: scene.unwrap_or_else(|| "chat".to_string()),
+            account,
+            storage_prefix,
+            enable_deduplication: enable_deduplication.unwrap_or(false),
+            file_name,
+        },
     )
-    .await
+    .await?;
+    im_upload_task_repository::insert_task(&db, task.clone()).await?;
+
+    let concurrency = apply_upload_settings(&app_handle).await.concurrency();
+    run_upload(&storage, &db, task, concurrency, on_progress).await
+}
+
+/// 对象存储上传结果
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageUploadResult {
+    pub key: String,
+    /// 下载地址，七牛由前端根据存储配置拼接，为空
+    pub download_url: Option<String>,
+    /// 实际使用的存储: qiniu 或 s3
+    pub provider: String,
+}
+
+/// 上传到服务端配置的对象存储，存储类型通过 `ImUrl::StorageProvider` 获取，获取失败时按本地配置选择
+/// 上传进度同样会记录到本地数据库，可以通过 [`resume_upload`] 继续
+#[tauri::command]
+pub async fn storage_upload(
+    app_handle: AppHandle,
+    path: String,
+    base_dir: Option<String>,
+    scene: Option<String>,
+    upload_id: Option<String>,
+    on_progress: Channel<UploadProgressPayload>,
+) -> Result<StorageUploadResult, String> {
+    let file_path = resolve_upload_path(&app_handle, &path, base_dir.as_deref())?;
+    let file_name = file_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| "Failed to determine file name".to_string())?
+        .to_string();
+    let scene = scene.unwrap_or_else(|| "chat".to_string());
+
+    let (db, login_uid) = upload_task_store(&app_handle).await?;
+    let upload_id = upload_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    if im_upload_task_repository::get_task(&db, &upload_id, &login_uid)
+        .await?
+        .is_some()
+    {
+        return Err(format!(
+            "Upload {upload_id} already exists, use resume_upload"
+        ));
+    }
+
+    let state = app_handle
+        .try_state::<AppData>()
+        .ok_or_else(|| "App state is not ready".to_string())?;
+    let client = upload_client(&app_handle).await?;
+    let kind = match state.rc.call(&StorageProviderReq::default()).await {
+        Ok(Some(resp)) => StorageKind::parse(&resp.provider),
+        Ok(None) => None,
+        Err(e) => {
+            warn!(
+                "Failed to query storage provider, falling back to config: {}",
+                e
+            );
+            None
+        }
+    };
+    let minio = state.config.lock().await.minio.clone();
+    let kind = kind.unwrap_or(if minio.is_some() {
+        StorageKind::S3
+    } else {
+        StorageKind::Qiniu
+    });
+
+    let (storage, storage_prefix) = match kind {
+        StorageKind::Qiniu => {
+            let credential = state
+                .rc
+                .call(&OssTokenReq {
+                    scene: scene.clone(),
+                    file_name: file_name.clone(),
+                })
+                .await
+                .map_err(|e| format!("Failed to get upload token: {e}"))?
+                .ok_or_else(|| "Upload token is empty".to_string())?;
+            let (Some(token), Some(domain)) = (credential.token, credential.domain) else {
+                return Err("Upload token response is missing token or domain".to_string());
+            };
+            (
+                StorageBackend::Qiniu(QiniuStorage::new(client, &domain, token)),
+                credential.storage_prefix,
+            )
+        }
+        StorageKind::S3 => {
+            let minio = minio.ok_or_else(|| "MinIO is not configured".to_string())?;
+            (StorageBackend::S3(S3Storage::new(client, &minio)), None)
+        }
+    };
+
+    let task = create_upload_task(
+        upload_id,
+        login_uid,
+        &file_path,
+        &storage,
+        UploadKeyOptions {
+            scene,
+            account: None,
+            storage_prefix,
+            enable_deduplication: false,
+            file_name,
+        },
+    )
+    .await?;
+    im_upload_task_repository::insert_task(&db, task.clone()).await?;
+
+    let concurrency = apply_upload_settings(&app_handle).await.concurrency();
+    let key = run_upload(&storage, &db, task, concurrency, on_progress).await?;
+    Ok(StorageUploadResult {
+        download_url: storage.download_url(&key),
+        provider: kind.as_str().to_string(),
+        key,
+    })
+}
+
+/// 恢复未完成的分片上传，跳过已上传且凭证未过期的分片
+/// 七牛上传凭证过期时可以传入新的 `token`
+#[tauri::command]
+pub async fn resume_upload(
+    app_handle: AppHandle,
+    upload_id: String,
+    token: Option<String>,
+    on_progress: Channel<UploadProgressPayload>,
+) -> Result<String, String> {
+    let (db, login_uid) = upload_task_store(&app_handle).await?;
+    let mut task = im_upload_task_repository::get_task(&db, &upload_id, &login_uid)
+        .await?
+        .ok_or_else(|| format!("Upload {upload_id} not found"))?;
+    if is_upload_active(&upload_id) {
+        return Err(format!("Upload {upload_id} is already in progress"));
+    }
+
+    // 文件被修改后已上传的块不再可用
+    let fingerprint = match File::open(&task.file_path).await {
+        Ok(mut file) => {
+            let total = file
+                .metadata()
+                .await
+                .map_err(|e| format!("Failed to read file metadata: {e}"))?
+                .len();
+            if total == task.file_size as u64 {
+                Some(file_fingerprint(&mut file, total).await?)
+            } else {
+                None
+            }
+        }
+        Err(_) => None,
+    };
+    if fingerprint.as_deref() != Some(task.fingerprint.as_str()) {
+        let err = "File has been changed or removed since the upload started".to_string();
+        im_upload_task_repository::update_status(
+            &db,
+            &upload_id,
+            STATUS_FAILED,
+            Some(err.clone()),
+            None,
+        )
+        .await?;
+        return Err(err);
+    }
+
+    if !contexts_usable(&task) {
+        warn!(
+            "Block contexts of upload {} have expired, restarting from the beginning",
+            upload_id
+        );
+    }
+
+    im_upload_task_repository::update_status(
+        &db,
+        &upload_id,
+        STATUS_UPLOADING,
+        None,
+        token.clone(),
+    )
+    .await?;
+    if let Some(token) = token {
+        task.token = token;
+    }
+
+    let storage = storage_for_task(&app_handle, &task).await?;
+    let concurrency = apply_upload_settings(&app_handle).await.concurrency();
+    run_upload(&storage, &db, task, concurrency, on_progress).await
+}
+
+/// 未完成的上传任务
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingUpload {
+    pub upload_id: String,
+    pub file_path: String,
+    pub total: u64,
+    pub uploaded: u64,
+    /// 任务状态: uploading, paused, failed
+    pub status: String,
+    /// 是否正在上传，应用异常退出后遗留的 uploading 任务为 false
+    pub active: bool,
+    /// 已上传的块是否已过期，过期后恢复上传需要从头开始
+    pub expired: bool,
+    pub last_error: Option<String>,
+    pub update_time: i64,
+}
+
+/// 获取当前用户未完成的上传任务
+#[tauri::command]
+pub async fn list_pending_uploads(app_handle: AppHandle) -> Result<Vec<PendingUpload>, String> {
+    let (db, login_uid) = upload_task_store(&app_handle).await?;
+    let tasks = im_upload_task_repository::list_tasks(&db, &login_uid).await?;
+    Ok(tasks
+        .into_iter()
+        .map(|task| PendingUpload {
+            active: is_upload_active(&task.id),
+            expired: !contexts_usable(&task),
+            upload_id: task.id,
+            file_path: task.file_path,
+            total: task.file_size as u64,
+            uploaded: task.uploaded as u64,
+            status: task.status,
+            last_error: task.last_error,
+            update_time: task.update_time,
+        })
+        .collect())
+}
+
+/// 暂停上传，已完成的块会保留，之后可以通过 [`resume_upload`] 继续
+#[tauri::command]
+pub async fn pause_upload(upload_id: String) -> Result<(), String> {
+    if !signal_upload(&upload_id, UploadSignal::Paused) {
+        return Err(format!("Upload {upload_id} is not in progress"));
+    }
+    Ok(())
+}
+
+/// 取消上传并删除任务记录
+#[tauri::command]
+pub async fn cancel_upload(app_handle: AppHandle, upload_id: String) -> Result<(), String> {
+    // 正在上传时由上传循环负责删除记录
+    if signal_upload(&upload_id, UploadSignal::Cancelled) {
+        return Ok(());
+    }
+    let (db, login_uid) = upload_task_store(&app_handle).await?;
+    if let Some(task) = im_upload_task_repository::get_task(&db, &upload_id, &login_uid).await? {
+        match storage_for_task(&app_handle, &task).await {
+            Ok(storage) => {
+                if let Err(e) = storage
+                    .abort(&task.object_key, task.session_id.as_deref())
+                    .await
+                {
+                    warn!("Failed to abort upload {}: {}", upload_id, e);
+                }
+            }
+            Err(e) => warn!("Failed to abort upload {}: {}", upload_id, e),
+        }
+        im_upload_task_repository::delete_task(&db, &upload_id, &login_uid).await?;
+    }
+    Ok(())
+}
+
+/// 上传使用的 HTTP 客户端，沿用配置中的代理和证书
+/// 上传耗时与文件大小相关，只限制连接超时，不设置整体请求超时
+async fn upload_client(app_handle: &AppHandle) -> Result<reqwest::Client, String> {
+    let http = match app_handle.try_state::<AppData>() {
+        Some(state) => state.config.lock().await.http.clone().unwrap_or_default(),
+        None => HttpSettings::default(),
+    };
+    http.client_builder()
+        .and_then(|builder| builder.build().map_err(anyhow::Error::from))
+        .map_err(|e| format!("Failed to create HTTP client: {e}"))
+}
+
+/// 读取上传配置，并把带宽上限应用到全局限速器
+async fn apply_upload_settings(app_handle: &AppHandle) -> UploadSettings {
+    let settings = match app_handle.try_state::<AppData>() {
+        Some(state) => state.config.lock().await.upload.clone().unwrap_or_default(),
+        None => UploadSettings::default(),
+    };
+    UPLOAD_BANDWIDTH.set_limit(settings.bandwidth_limit());
+    settings
 }
 
 fn resolve_upload_path(
@@ -101,7 +439,127 @@ fn resolve_upload_path(
         .map_err(|e| format!("Failed to resolve file path: {e}"))
 }
 
+/// 所有上传共享的带宽限制器
+static UPLOAD_BANDWIDTH: Lazy<BandwidthLimiter> = Lazy::new(BandwidthLimiter::default);
+
+/// 令牌桶限速，最多积累 1 秒的额度
+/// 令牌不足时记为欠额，后来的请求需要等待更久，多个上传之间按请求顺序分享带宽
+/// 使用 tokio 的时钟计时，与等待使用的 `sleep` 保持一致
+#[derive(Default)]
+struct BandwidthLimiter {
+    /// 带宽上限（字节/秒），0 表示不限速
+    limit: AtomicU64,
+    bucket: Mutex<Option<(f64, tokio::time::Instant)>>,
+}
+
+impl BandwidthLimiter {
+    fn set_limit(&self, limit: Option<u64>) {
+        let limit = limit.unwrap_or(0);
+        if self.limit.swap(limit, Ordering::Relaxed) != limit {
+            *self.bucket.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+    }
+
+    /// 发送 `bytes` 字节前调用，超出带宽上限时等待
+    async fn acquire(&self, bytes: u64) {
+        let limit = self.limit.load(Ordering::Relaxed);
+        if limit == 0 {
+            return;
+        }
+
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+            let now = tokio::time::Instant::now();
+            let rate = limit as f64;
+            let (available, last) = bucket.unwrap_or((rate, now));
+            let available = (available + now.duration_since(last).as_secs_f64() * rate).min(rate)
+                - bytes as f64;
+            *bucket = Some((available, now));
+            (available < 0.0).then(|| Duration::from_secs_f64(-available / rate))
+        };
+        if let Some(wait) = wait {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// 上传进度统计，按最近一段时间内发送的字节数计算速度和剩余时间
+struct ProgressTracker {
+    channel: Channel<UploadProgressPayload>,
+    total: u64,
+    /// 本次上传开始前已完成的字节数
+    base: u64,
+    /// 本次上传已发送的字节数
+    sent: Arc<AtomicU64>,
+    samples: VecDeque<(Instant, u64)>,
+    last_emit: Option<Instant>,
+}
+
+impl ProgressTracker {
+    fn new(channel: Channel<UploadProgressPayload>, total: u64, base: u64) -> Self {
+        Self {
+            channel,
+            total,
+            base,
+            sent: Arc::new(AtomicU64::new(0)),
+            samples: VecDeque::from([(Instant::now(), 0)]),
+            last_emit: None,
+        }
+    }
+
+    /// 发送数据时累加的计数器
+    fn counter(&self) -> Arc<AtomicU64> {
+        self.sent.clone()
+    }
+
+    /// 发送进度事件，`force` 为 false 时按 `PROGRESS_INTERVAL` 限制频率
+    fn emit(&mut self, force: bool) {
+        let now = Instant::now();
+        if !force
+            && self
+                .last_emit
+                .is_some_and(|last| now.duration_since(last) < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+        self.last_emit = Some(now);
+
+        let sent = self.sent.load(Ordering::Relaxed);
+        self.samples.push_back((now, sent));
+        while self.samples.len() > 2
+            && self
+                .samples
+                .get(1)
+                .is_some_and(|(time, _)| now.duration_since(*time) >= SPEED_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+
+        let speed = match self.samples.front() {
+            Some((time, bytes)) if now.duration_since(*time) > Duration::ZERO => {
+                ((sent - bytes) as f64 / now.duration_since(*time).as_secs_f64()) as u64
+            }
+            _ => 0,
+        };
+        let progress_total = self.base.saturating_add(sent).min(self.total);
+        let remaining = self.total - progress_total;
+        let eta = match (remaining, speed) {
+            (0, _) => Some(0),
+            (_, 0) => None,
+            (remaining, speed) => Some(remaining.div_ceil(speed)),
+        };
+
+        let _ = self.channel.send(UploadProgressPayload {
+            progress_total,
+            total: self.total,
+            speed,
+            eta,
+        });
+    }
+}
+
 async fn upload_put(
+    client: &reqwest::Client,
     url: String,
     file_path: PathBuf,
     headers: HashMap<String, String>,
@@ -116,30 +574,28 @@ async fn upload_put(
         .map_err(|e| format!("Failed to read file metadata: {e}"))?
         .len();
 
-    let chunk_size: usize = 4 * 1024 * 1024;
-    let stream = try_unfold(
-        (file, 0_u64, on_progress),
-        move |(mut file, mut transferred, on_progress)| async move {
-            let mut buf = vec![0u8; chunk_size];
+    let progress = ProgressTracker::new(on_progress, total, 0);
+    let sent = progress.counter();
+    let stream = try_unfold((file, progress), move |(mut file, mut progress)| {
+        let sent = sent.clone();
+        async move {
+            let mut buf = vec![0u8; STREAM_CHUNK_SIZE];
             let read = file.read(&mut buf).await?;
 
             if read == 0 {
+                progress.emit(true);
                 return Ok::<_, std::io::Error>(None);
             }
 
             buf.truncate(read);
-            transferred = transferred.saturating_add(read as u64);
+            UPLOAD_BANDWIDTH.acquire(read as u64).await;
+            sent.fetch_add(read as u64, Ordering::Relaxed);
+            progress.emit(false);
 
-            let _ = on_progress.send(UploadProgressPayload {
-                progress_total: transferred,
-                total,
-            });
-
-            Ok(Some((Bytes::from(buf), (file, transferred, on_progress))))
-        },
-    );
+            Ok(Some((Bytes::from(buf), (file, progress))))
+        }
+    });
 
-    let client = reqwest::Client::new();
     let mut request = client
         .put(url)
         .header(reqwest::header::CONTENT_LENGTH, total)
@@ -206,22 +662,187 @@ struct QiniuKeyOptions<'a> {
     md5_hex: Option<&'a str>,
 }
 
-async fn qiniu_resumable_upload(
-    file_path: PathBuf,
-    token: String,
-    domain: String,
+/// 计算文件指纹：文件大小、修改时间以及首尾各一段内容的 MD5
+/// 恢复上传前比对指纹，避免把修改后的文件与旧的块拼接在一起
+async fn file_fingerprint(file: &mut File, total: u64) -> Result<String, String> {
+    let modified = file
+        .metadata()
+        .await
+        .ok()
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mut hasher = Md5::new();
+    hasher.update(total.to_le_bytes());
+    hasher.update(modified.to_le_bytes());
+
+    let sample_len = std::cmp::min(FINGERPRINT_SAMPLE_SIZE, total);
+    for offset in [0, total - sample_len] {
+        let mut buf = vec![0u8; sample_len as usize];
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(|e| format!("Failed to seek file: {e}"))?;
+        file.read_exact(&mut buf)
+            .await
+            .map_err(|e| format!("Failed to read file: {e}"))?;
+        hasher.update(&buf);
+    }
+
+    Ok(hex_lower(hasher.finalize().as_ref()))
+}
+
+/// 上传任务的控制信号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UploadSignal {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+/// 正在进行中的分片上传，暂停和取消通过对应的信号通知上传循环
+static ACTIVE_UPLOADS: Lazy<Mutex<HashMap<String, watch::Sender<UploadSignal>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 上传期间持有，结束时从进行中的列表移除
+struct ActiveUpload {
+    id: String,
+    signal: watch::Receiver<UploadSignal>,
+}
+
+impl ActiveUpload {
+    fn register(id: &str) -> Result<Self, String> {
+        let mut uploads = ACTIVE_UPLOADS.lock().unwrap_or_else(|e| e.into_inner());
+        if uploads.contains_key(id) {
+            return Err(format!("Upload {id} is already in progress"));
+        }
+        let (tx, rx) = watch::channel(UploadSignal::Running);
+        uploads.insert(id.to_string(), tx);
+        Ok(Self {
+            id: id.to_string(),
+            signal: rx,
+        })
+    }
+
+    /// 等待暂停或取消信号
+    async fn stopped(&mut self) -> UploadSignal {
+        loop {
+            let signal = *self.signal.borrow_and_update();
+            if signal != UploadSignal::Running {
+                return signal;
+            }
+            if self.signal.changed().await.is_err() {
+                return std::future::pending().await;
+            }
+        }
+    }
+}
+
+impl Drop for ActiveUpload {
+    fn drop(&mut self) {
+        ACTIVE_UPLOADS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+    }
+}
+
+/// 向进行中的上传发送信号，任务不在进行中时返回 false
+fn signal_upload(id: &str, signal: UploadSignal) -> bool {
+    let uploads = ACTIVE_UPLOADS.lock().unwrap_or_else(|e| e.into_inner());
+    match uploads.get(id) {
+        Some(tx) => tx.send(signal).is_ok(),
+        None => false,
+    }
+}
+
+fn is_upload_active(id: &str) -> bool {
+    ACTIVE_UPLOADS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains_key(id)
+}
+
+/// 上传任务中途停止的原因
+enum UploadStop {
+    Paused,
+    Cancelled,
+    Failed(String),
+}
+
+impl From<String> for UploadStop {
+    fn from(err: String) -> Self {
+        UploadStop::Failed(err)
+    }
+}
+
+/// 获取上传任务所在的数据库连接和当前登录用户
+async fn upload_task_store(app_handle: &AppHandle) -> Result<(DatabaseConnection, String), String> {
+    let state = app_handle
+        .try_state::<AppData>()
+        .ok_or_else(|| "App state not ready".to_string())?;
+    let login_uid = state.user_info.lock().await.uid.clone();
+    let db = state.db_conn.read().await.clone();
+    Ok((db, login_uid))
+}
+
+fn now_secs() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// 已上传的分片凭证是否仍可用于合并
+fn contexts_usable(task: &im_upload_task::Model) -> bool {
+    task.ctx_expire_at
+        .is_none_or(|expire_at| expire_at > now_secs() + CTX_EXPIRE_MARGIN_SECS)
+}
+
+/// 根据任务记录还原上传时使用的对象存储，S3 的密钥来自本地配置
+async fn storage_for_task(
+    app_handle: &AppHandle,
+    task: &im_upload_task::Model,
+) -> Result<StorageBackend, String> {
+    let client = upload_client(app_handle).await?;
+    match StorageKind::parse(&task.provider) {
+        Some(StorageKind::Qiniu) => Ok(StorageBackend::Qiniu(QiniuStorage::new(
+            client,
+            &task.domain,
+            task.token.clone(),
+        ))),
+        Some(StorageKind::S3) => {
+            let state = app_handle
+                .try_state::<AppData>()
+                .ok_or_else(|| "App state is not ready".to_string())?;
+            let minio = state
+                .config
+                .lock()
+                .await
+                .minio
+                .clone()
+                .ok_or_else(|| "MinIO is not configured".to_string())?;
+            Ok(StorageBackend::S3(S3Storage::new(client, &minio)))
+        }
+        None => Err(format!("Unknown storage provider: {}", task.provider)),
+    }
+}
+
+/// 生成对象 key 所需的上传参数
+struct UploadKeyOptions {
     scene: String,
     account: Option<String>,
     storage_prefix: Option<String>,
     enable_deduplication: bool,
     file_name: String,
-    on_progress: Channel<UploadProgressPayload>,
-) -> Result<String, String> {
-    use base64::Engine;
-    use base64::engine::general_purpose::STANDARD;
+}
 
-    let domain = domain.trim_end_matches('/').to_string();
-    let mut file = File::open(&file_path)
+async fn create_upload_task(
+    upload_id: String,
+    login_uid: String,
+    file_path: &std::path::Path,
+    storage: &StorageBackend,
+    options: UploadKeyOptions,
+) -> Result<im_upload_task::Model, String> {
+    let mut file = File::open(file_path)
         .await
         .map_err(|e| format!("Failed to open file: {e}"))?;
     let total = file
@@ -229,100 +850,527 @@ async fn qiniu_resumable_upload(
         .await
         .map_err(|e| format!("Failed to read file metadata: {e}"))?
         .len();
+    let fingerprint = file_fingerprint(&mut file, total).await?;
 
-    let chunk_size: u64 = 4 * 1024 * 1024;
-    let chunk_threshold: u64 = 4 * 1024 * 1024;
     let timestamp_ms = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .map(|d| d.as_millis())
         .unwrap_or(0);
 
+    // 去重只针对单块的小文件，key 需要在上传前确定才能在恢复时保持一致
     let should_hash =
-        enable_deduplication && total <= chunk_threshold && account.as_deref().is_some();
-    let mut hasher = should_hash.then(Md5::new);
-    let mut contexts: Vec<String> = Vec::new();
+        options.enable_deduplication && total <= QINIU_CHUNK_THRESHOLD && options.account.is_some();
+    let md5_hex = if should_hash {
+        let mut buf = Vec::with_capacity(total as usize);
+        file.seek(SeekFrom::Start(0))
+            .await
+            .map_err(|e| format!("Failed to seek file: {e}"))?;
+        file.read_to_end(&mut buf)
+            .await
+            .map_err(|e| format!("Failed to read file: {e}"))?;
+        Some(hex_lower(Md5::digest(&buf).as_ref()))
+    } else {
+        None
+    };
 
-    let client = reqwest::Client::new();
-    let mut transferred: u64 = 0;
+    let object_key = build_qiniu_key(QiniuKeyOptions {
+        scene: &options.scene,
+        account: options.account.as_deref(),
+        storage_prefix: options.storage_prefix.as_deref(),
+        enable_deduplication: options.enable_deduplication,
+        total_size: total,
+        chunk_threshold: QINIU_CHUNK_THRESHOLD,
+        file_name: &options.file_name,
+        timestamp_ms,
+        md5_hex: md5_hex.as_deref(),
+    });
 
-    while transferred < total {
-        let remaining = total.saturating_sub(transferred);
-        let len = std::cmp::min(chunk_size, remaining) as usize;
-        let mut buf = vec![0u8; len];
-        file.read_exact(&mut buf)
+    let now = chrono::Utc::now().timestamp_millis();
+    Ok(im_upload_task::Model {
+        id: upload_id,
+        login_uid,
+        file_path: file_path.to_string_lossy().to_string(),
+        file_size: total as i64,
+        provider: storage.kind().as_str().to_string(),
+        fingerprint,
+        domain: storage.endpoint().to_string(),
+        token: storage.upload_token().to_string(),
+        session_id: None,
+        object_key,
+        block_size: storage.part_size() as i64,
+        uploaded: 0,
+        contexts: "[]".to_string(),
+        ctx_expire_at: None,
+        status: STATUS_UPLOADING.to_string(),
+        last_error: None,
+        create_time: now,
+        update_time: now,
+    })
+}
+
+/// 执行上传任务并根据结果更新任务记录：完成或取消后删除，暂停或失败时保留以便恢复
+async fn run_upload<P: StorageProvider>(
+    storage: &P,
+    db: &DatabaseConnection,
+    task: im_upload_task::Model,
+    concurrency: usize,
+    on_progress: Channel<UploadProgressPayload>,
+) -> Result<String, String> {
+    let mut active = ActiveUpload::register(&task.id)?;
+    let upload_id = task.id.clone();
+    let login_uid = task.login_uid.clone();
+    let object_key = task.object_key.clone();
+
+    match resumable_upload(storage, db, task, concurrency, &mut active, on_progress).await {
+        Ok(key) => {
+            im_upload_task_repository::delete_task(db, &upload_id, &login_uid).await?;
+            Ok(key)
+        }
+        Err(UploadStop::Paused) => {
+            im_upload_task_repository::update_status(db, &upload_id, STATUS_PAUSED, None, None)
+                .await?;
+            info!("Upload {} paused", upload_id);
+            Err("Upload paused".to_string())
+        }
+        Err(UploadStop::Cancelled) => {
+            // 上传会话可能在本次上传中才创建，以数据库中的最新记录为准
+            let session_id = im_upload_task_repository::get_task(db, &upload_id, &login_uid)
+                .await?
+                .and_then(|task| task.session_id);
+            if let Err(e) = storage.abort(&object_key, session_id.as_deref()).await {
+                warn!("Failed to abort upload {}: {}", upload_id, e);
+            }
+            im_upload_task_repository::delete_task(db, &upload_id, &login_uid).await?;
+            info!("Upload {} cancelled", upload_id);
+            Err("Upload cancelled".to_string())
+        }
+        Err(UploadStop::Failed(err)) => {
+            if let Err(e) = im_upload_task_repository::update_status(
+                db,
+                &upload_id,
+                STATUS_FAILED,
+                Some(err.clone()),
+                None,
+            )
             .await
-            .map_err(|e| format!("Failed to read file: {e}"))?;
+            {
+                warn!("Failed to record upload {} failure: {}", upload_id, e);
+            }
+            Err(err)
+        }
+    }
+}
 
-        if let Some(ref mut h) = hasher {
-            h.update(&buf);
+/// 读取一个分片作为请求体，按 `STREAM_CHUNK_SIZE` 分段写入以便限速和统计进度
+async fn part_body(
+    file_path: &str,
+    offset: u64,
+    len: u64,
+    sent: Arc<AtomicU64>,
+) -> Result<reqwest::Body, String> {
+    let mut file = File::open(file_path)
+        .await
+        .map_err(|e| format!("Failed to open file: {e}"))?;
+    file.seek(SeekFrom::Start(offset))
+        .await
+        .map_err(|e| format!("Failed to seek file: {e}"))?;
+    let mut buf = vec![0u8; len as usize];
+    file.read_exact(&mut buf)
+        .await
+        .map_err(|e| format!("Failed to read file: {e}"))?;
+
+    let data = Bytes::from(buf);
+    let body = stream::unfold(0_usize, move |pos| {
+        let data = data.clone();
+        let sent = sent.clone();
+        async move {
+            if pos >= data.len() {
+                return None;
+            }
+            let end = std::cmp::min(pos + STREAM_CHUNK_SIZE, data.len());
+            UPLOAD_BANDWIDTH.acquire((end - pos) as u64).await;
+            sent.fetch_add((end - pos) as u64, Ordering::Relaxed);
+            Some((Ok::<_, std::io::Error>(data.slice(pos..end)), end))
         }
+    });
+    Ok(reqwest::Body::wrap_stream(body))
+}
 
-        let mkblk_url = format!("{domain}/mkblk/{len}");
-        let response = client
-            .post(mkblk_url)
-            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
-            .header(reqwest::header::AUTHORIZATION, format!("UpToken {token}"))
-            .body(Bytes::from(buf))
-            .send()
+async fn resumable_upload<P: StorageProvider>(
+    storage: &P,
+    db: &DatabaseConnection,
+    mut task: im_upload_task::Model,
+    concurrency: usize,
+    active: &mut ActiveUpload,
+    on_progress: Channel<UploadProgressPayload>,
+) -> Result<String, UploadStop> {
+    let total = task.file_size as u64;
+    let block_size = task.block_size as u64;
+    let mut contexts: Vec<String> = serde_json::from_str(&task.contexts).unwrap_or_default();
+    let mut transferred = task.uploaded as u64;
+
+    // 记录与已上传的分片数对不上时从头上传
+    let expected_blocks = transferred.div_ceil(block_size) as usize;
+    if contexts.len() != expected_blocks || transferred > total || !contexts_usable(&task) {
+        contexts.clear();
+        transferred = 0;
+        task.ctx_expire_at = None;
+    }
+
+    if task.session_id.is_none() {
+        let content_type = mime_guess::from_path(&task.file_path)
+            .first_or_octet_stream()
+            .to_string();
+        task.session_id = storage
+            .begin(&task.object_key, &content_type)
+            .await
+            .map_err(|e| e.to_string())?;
+        im_upload_task_repository::update_progress(db, &task)
             .await
-            .map_err(|e| format!("Upload request failed: {e}"))?;
+            .map_err(|e| e.to_string())?;
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(format!("mkblk failed with status {status}: {body}"));
-        }
+    if transferred > 0 {
+        info!(
+            "Resuming upload {} from {}/{} bytes",
+            task.id, transferred, total
+        );
+    }
+    let mut progress = ProgressTracker::new(on_progress, total, transferred);
+    progress.emit(true);
+
+    // 多个分片并发上传，`buffered` 按分片顺序返回结果，只有连续完成的前缀才会记录为已上传
+    let first_index = contexts.len();
+    let offsets = (transferred..total).step_by(block_size as usize);
+    let (object_key, session_id, file_path) = (
+        task.object_key.clone(),
+        task.session_id.clone(),
+        task.file_path.clone(),
+    );
+    let sent = progress.counter();
+    let mut blocks = stream::iter(offsets.enumerate())
+        .map(|(i, offset)| {
+            let len = std::cmp::min(block_size, total - offset);
+            let (object_key, session_id, file_path, sent) =
+                (&object_key, &session_id, &file_path, sent.clone());
+            async move {
+                let body = part_body(file_path, offset, len, sent).await?;
+                let part = storage
+                    .upload_part(
+                        object_key,
+                        session_id.as_deref(),
+                        first_index + i,
+                        len,
+                        body,
+                    )
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok::<_, String>((len, part))
+            }
+        })
+        .buffered(concurrency);
+    let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+
+    loop {
+        // 暂停或取消时放弃正在上传的分片，恢复后从第一个未完成的分片继续
+        let (len, part) = tokio::select! {
+            block = blocks.next() => match block {
+                Some(block) => block?,
+                None => break,
+            },
+            signal = active.stopped() => {
+                return Err(match signal {
+                    UploadSignal::Cancelled => UploadStop::Cancelled,
+                    _ => UploadStop::Paused,
+                });
+            }
+            _ = ticker.tick() => {
+                progress.emit(false);
+                continue;
+            }
+        };
 
-        let payload = response
-            .json::<QiniuMkblkResponse>()
+        contexts.push(part.token);
+        transferred = transferred.saturating_add(len);
+
+        task.uploaded = transferred as i64;
+        task.contexts = serde_json::to_string(&contexts)
+            .map_err(|e| format!("Failed to serialize block contexts: {e}"))?;
+        if let Some(expire_at) = part.expire_at {
+            task.ctx_expire_at = Some(task.ctx_expire_at.map_or(expire_at, |t| t.min(expire_at)));
+        }
+        im_upload_task_repository::update_progress(db, &task)
             .await
-            .map_err(|e| format!("Failed to parse mkblk response: {e}"))?;
+            .map_err(|e| e.to_string())?;
+    }
+    progress.emit(true);
 
-        contexts.push(payload.ctx);
-        transferred = transferred.saturating_add(len as u64);
-        let _ = on_progress.send(UploadProgressPayload {
-            progress_total: transferred,
+    let key = storage
+        .complete(
+            &task.object_key,
+            task.session_id.as_deref(),
             total,
+            &contexts,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CommonError;
+    use crate::storage::UploadedPart;
+    use sea_orm::{ConnectionTrait, Database, Schema};
+    use tauri::ipc::InvokeResponseBody;
+
+    /// 记录上传分片和合并结果的对象存储
+    #[derive(Default)]
+    struct MockStorage {
+        parts: Mutex<Vec<usize>>,
+        completed: Mutex<Vec<String>>,
+    }
+
+    impl StorageProvider for MockStorage {
+        fn kind(&self) -> StorageKind {
+            StorageKind::Qiniu
+        }
+
+        fn part_size(&self) -> u64 {
+            4
+        }
+
+        async fn begin(
+            &self,
+            _key: &str,
+            _content_type: &str,
+        ) -> Result<Option<String>, CommonError> {
+            Ok(Some("session".to_string()))
+        }
+
+        async fn upload_part(
+            &self,
+            _key: &str,
+            _session: Option<&str>,
+            index: usize,
+            _len: u64,
+            _body: reqwest::Body,
+        ) -> Result<UploadedPart, CommonError> {
+            self.parts.lock().unwrap().push(index);
+            Ok(UploadedPart {
+                token: format!("ctx{index}"),
+                expire_at: Some(now_secs() + 24 * 60 * 60),
+            })
+        }
+
+        async fn complete(
+            &self,
+            key: &str,
+            _session: Option<&str>,
+            _total: u64,
+            parts: &[String],
+        ) -> Result<String, CommonError> {
+            *self.completed.lock().unwrap() = parts.to_vec();
+            Ok(key.to_string())
+        }
+
+        async fn abort(&self, _key: &str, _session: Option<&str>) -> Result<(), CommonError> {
+            Ok(())
+        }
+
+        fn download_url(&self, _key: &str) -> Option<String> {
+            None
+        }
+    }
+
+    /// 收集进度事件的通道
+    fn progress_channel() -> (
+        Channel<UploadProgressPayload>,
+        Arc<Mutex<Vec<serde_json::Value>>>,
+    ) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let channel = Channel::new(move |body| {
+            if let InvokeResponseBody::Json(json) = body {
+                sink.lock()
+                    .unwrap()
+                    .push(serde_json::from_str(&json).unwrap());
+            }
+            Ok(())
         });
+        (channel, events)
     }
 
-    let md5_hex = hasher.map(|h| {
-        let digest = h.finalize();
-        hex_lower(digest.as_ref())
-    });
-    let key = build_qiniu_key(QiniuKeyOptions {
-        scene: &scene,
-        account: account.as_deref(),
-        storage_prefix: storage_prefix.as_deref(),
-        enable_deduplication,
-        total_size: total,
-        chunk_threshold,
-        file_name: &file_name,
-        timestamp_ms,
-        md5_hex: md5_hex.as_deref(),
-    });
-    let encoded_key = STANDARD.encode(&key);
-
-    let mkfile_url = format!("{domain}/mkfile/{total}/key/{encoded_key}");
-    let response = client
-        .post(mkfile_url)
-        .header(reqwest::header::CONTENT_TYPE, "text/plain")
-        .header(reqwest::header::AUTHORIZATION, format!("UpToken {token}"))
-        .body(contexts.join(","))
-        .send()
+    /// 10 字节的文件按 4 字节分片，第一个分片已在上次上传完成
+    async fn saved_task(
+        id: &str,
+        ctx_expire_at: i64,
+    ) -> (DatabaseConnection, im_upload_task::Model) {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let backend = db.get_database_backend();
+        db.execute(
+            backend.build(&Schema::new(backend).create_table_from_entity(im_upload_task::Entity)),
+        )
         .await
-        .map_err(|e| format!("Finalize request failed: {e}"))?;
+        .unwrap();
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        return Err(format!("mkfile failed with status {status}: {body}"));
+        let file_path = std::env::temp_dir().join(format!("hula-upload-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&file_path, b"0123456789").unwrap();
+        let task = im_upload_task::Model {
+            id: id.to_string(),
+            login_uid: "u".to_string(),
+            file_path: file_path.to_string_lossy().to_string(),
+            file_size: 10,
+            provider: "qiniu".to_string(),
+            fingerprint: String::new(),
+            domain: String::new(),
+            token: String::new(),
+            session_id: Some("session".to_string()),
+            object_key: "chat/file.bin".to_string(),
+            block_size: 4,
+            uploaded: 4,
+            contexts: r#"["saved0"]"#.to_string(),
+            ctx_expire_at: Some(ctx_expire_at),
+            status: STATUS_PAUSED.to_string(),
+            last_error: None,
+            create_time: 0,
+            update_time: 0,
+        };
+        im_upload_task_repository::insert_task(&db, task.clone())
+            .await
+            .unwrap();
+        (db, task)
     }
 
-    let payload = response
-        .json::<QiniuMkfileResponse>()
-        .await
-        .map_err(|e| format!("Failed to parse mkfile response: {e}"))?;
+    #[tokio::test]
+    async fn test_resume_from_saved_task() {
+        let (db, task) = saved_task("resume", now_secs() + 24 * 60 * 60).await;
+        let file_path = task.file_path.clone();
+        let storage = MockStorage::default();
+        let (channel, events) = progress_channel();
 
-    Ok(payload.key.unwrap_or(key))
+        let key = run_upload(&storage, &db, task, 2, channel).await.unwrap();
+        assert_eq!(key, "chat/file.bin");
+        // 只上传剩余的分片，并发上传的完成顺序不固定，合并时按分片顺序带上之前保存的凭证
+        let mut parts = storage.parts.lock().unwrap().clone();
+        parts.sort();
+        assert_eq!(parts, [1, 2]);
+        assert_eq!(
+            *storage.completed.lock().unwrap(),
+            ["saved0", "ctx1", "ctx2"]
+        );
+        assert_eq!(events.lock().unwrap()[0]["progressTotal"], 4);
+        assert!(
+            im_upload_task_repository::get_task(&db, "resume", "u")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let _ = std::fs::remove_file(file_path);
+    }
+
+    #[tokio::test]
+    async fn test_expired_context_restarts_upload() {
+        // 凭证在预留时间内过期，不能再用于合并
+        let (db, task) = saved_task("expired", now_secs() + 60).await;
+        let file_path = task.file_path.clone();
+        let storage = MockStorage::default();
+        let (channel, events) = progress_channel();
+
+        run_upload(&storage, &db, task, 1, channel).await.unwrap();
+        assert_eq!(*storage.parts.lock().unwrap(), [0, 1, 2]);
+        assert_eq!(*storage.completed.lock().unwrap(), ["ctx0", "ctx1", "ctx2"]);
+        assert_eq!(events.lock().unwrap()[0]["progressTotal"], 0);
+        let _ = std::fs::remove_file(file_path);
+    }
+
+    #[test]
+    fn test_build_qiniu_key() {
+        let options = |total_size, storage_prefix, md5_hex| QiniuKeyOptions {
+            scene: "chat",
+            account: Some("10001"),
+            storage_prefix,
+            enable_deduplication: true,
+            total_size,
+            chunk_threshold: QINIU_CHUNK_THRESHOLD,
+            file_name: "report.final.pdf",
+            timestamp_ms: 1700000000000,
+            md5_hex,
+        };
+
+        // 小文件按内容去重，后缀取最后一段
+        assert_eq!(
+            build_qiniu_key(options(1024, None, Some("abc"))),
+            "chat/10001/abc.pdf"
+        );
+        // 大文件不做去重，优先使用服务端下发的前缀
+        assert_eq!(
+            build_qiniu_key(options(
+                QINIU_CHUNK_THRESHOLD + 1,
+                Some("tenant/chat"),
+                Some("abc")
+            )),
+            "tenant/chat/1700000000000_report.final.pdf"
+        );
+        assert_eq!(
+            build_qiniu_key(options(QINIU_CHUNK_THRESHOLD + 1, Some(""), None)),
+            "chat/1700000000000_report.final.pdf"
+        );
+        // 没有 MD5 时退回时间戳命名
+        assert_eq!(
+            build_qiniu_key(options(1024, None, None)),
+            "chat/1700000000000_report.final.pdf"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bandwidth_limiter_caps_throughput() {
+        let limiter = BandwidthLimiter::default();
+        let start = tokio::time::Instant::now();
+        limiter.acquire(10_000).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // 两个上传共享 1000 字节/秒，共 10000 字节，首秒额度用完后按上限发送
+        limiter.set_limit(Some(1000));
+        let upload = || async {
+            for _ in 0..10 {
+                limiter.acquire(500).await;
+            }
+        };
+        tokio::join!(upload(), upload());
+        let elapsed = start.elapsed().as_secs_f64();
+        assert!((9.0..9.01).contains(&elapsed), "elapsed {elapsed}s");
+    }
+
+    #[test]
+    fn test_progress_tracker() {
+        let (channel, events) = progress_channel();
+        let mut progress = ProgressTracker::new(channel, 100, 40);
+        let sent = progress.counter();
+
+        progress.emit(true);
+        sent.fetch_add(30, Ordering::Relaxed);
+        // 发送间隔内的非强制事件被忽略
+        progress.emit(false);
+        std::thread::sleep(Duration::from_millis(10));
+        progress.emit(true);
+        sent.fetch_add(100, Ordering::Relaxed);
+        progress.emit(true);
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0]["progressTotal"], 40);
+        assert_eq!(events[0]["speed"], 0);
+        assert!(events[0]["eta"].is_null());
+
+        assert_eq!(events[1]["progressTotal"], 70);
+        assert!(events[1]["speed"].as_u64().unwrap() > 0);
+        assert!(events[1]["eta"].as_u64().unwrap() > 0);
+
+        // 已发送的字节数超过剩余大小时按总大小计算
+        assert_eq!(events[2]["progressTotal"], 100);
+        assert_eq!(events[2]["total"], 100);
+        assert_eq!(events[2]["eta"], 0);
+    }
 }
@@ -10,7 +10,7 @@ use sea_orm::IntoActiveModel;
 use sea_orm::QueryFilter;
 use serde::{Deserialize, Serialize};
 use tauri::State;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -121,10 +121,7 @@ pub async fn get_user_tokens(state: State<'_, AppData>) -> Result<TokenResponse,
 pub async fn remove_tokens(state: State<'_, AppData>) -> Result<(), String> {
     info!("Removing user token info");
 
-    let mut rc = state.rc.lock().await;
-
-    rc.token = None;
-    rc.refresh_token = None;
+    state.rc.clear_tokens();
 
     info!("Successfully removed user token info");
     Ok(())
@@ -152,20 +149,16 @@ pub async fn update_token(
         user_info.token = req.token.clone();
         user_info.refresh_token = refresh_token.clone();
     }
-    {
-        let mut rc = state.rc.lock().await;
-        rc.token = Some(req.token.clone());
-        if !refresh_token.is_empty() {
-            rc.refresh_token = Some(refresh_token.clone());
-        }
+    im_user_repository::ensure_user(&*state.db_conn.read().await, &req.uid)
+        .await
+        .map_err(|e| e.to_string())?;
+    state
+        .rc
+        .set_tokens(req.token.clone(), Some(refresh_token.clone()));
+    state.rc.bind_session(&req.uid);
+    // 凭证保存失败不影响当前会话，只是下次启动需要重新登录
+    if let Err(e) = state.rc.persist_tokens() {
+        warn!("Failed to persist tokens for {}: {}", req.uid, e);
     }
-    im_user_repository::save_user_tokens(
-        &*state.db_conn.read().await,
-        &req.uid,
-        &req.token,
-        &refresh_token,
-    )
-    .await
-    .map_err(|e| e.to_string())?;
     Ok(())
 }
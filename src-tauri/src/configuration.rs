@@ -1,10 +1,11 @@
 use crate::error::CommonError;
-use sea_orm::{ConnectOptions, Database, DatabaseConnection};
-use std::io::Read;
-use std::path::PathBuf;
+use crate::timeout_config::TimeoutConfig;
+use crate::utils::db_crypto;
+use sea_orm::{ConnectOptions, Database, DatabaseConnection, DbErr};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tauri::{AppHandle, Manager};
-use tracing::info;
+use tracing::{error, info, warn};
 
 // 应用程序设置结构体
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
@@ -15,6 +16,10 @@ pub struct Settings {
     pub tencent: Option<Tencent>,
     pub minio: Option<MinioSettings>,
     pub ice_server: Option<IceServer>,
+    pub http: Option<HttpSettings>,
+    pub upload: Option<UploadSettings>,
+    pub download: Option<DownloadSettings>,
+    pub ai: Option<AiSettings>,
 }
 
 // 数据库配置设置
@@ -60,6 +65,224 @@ pub struct MinioSettings {
     pub download_domain: String,
 }
 
+// HTTP 客户端配置，用于代理、自定义证书和超时重试
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
+pub struct HttpSettings {
+    pub proxy: Option<ProxySettings>,
+    /// 额外信任的根证书（PEM 文件路径）
+    pub ca_certs: Option<Vec<String>>,
+    pub client_cert: Option<ClientCertSettings>,
+    /// 请求超时（秒），默认 `TimeoutConfig::HTTP_REQUEST_TIMEOUT`
+    pub request_timeout_secs: Option<u64>,
+    /// 连接超时（秒），默认 `TimeoutConfig::HTTP_CONNECT_TIMEOUT`
+    pub connect_timeout_secs: Option<u64>,
+    /// 幂等请求遇到网络错误时的最大重试次数
+    pub max_retries: Option<u32>,
+}
+
+// 代理配置，支持 http/https/socks5 协议
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct ProxySettings {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// 不走代理的地址，逗号分隔，例如 `localhost,127.0.0.1,.internal.com`
+    pub no_proxy: Option<String>,
+}
+
+// 客户端证书配置（双向 TLS）
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct ClientCertSettings {
+    /// PEM 格式证书路径
+    pub cert_path: String,
+    /// PEM 格式私钥路径，证书文件中已包含私钥时可省略
+    pub key_path: Option<String>,
+}
+
+// 文件上传配置
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
+pub struct UploadSettings {
+    /// 分片上传时同时上传的块数，默认 `DEFAULT_UPLOAD_CONCURRENCY`
+    pub concurrency: Option<usize>,
+    /// 所有上传共享的带宽上限（字节/秒），为空或 0 时不限速
+    pub bandwidth_limit: Option<u64>,
+}
+
+// 媒体下载缓存配置
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
+pub struct DownloadSettings {
+    /// 下载缓存的容量上限（字节），默认 `DEFAULT_DOWNLOAD_CACHE_SIZE`
+    pub cache_max_size: Option<u64>,
+}
+
+// AI 对话配置
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
+pub struct AiSettings {
+    /// 对话服务: hula（默认，经 HuLa 服务端）或 openai（自建的 OpenAI 兼容接口）
+    pub provider: Option<String>,
+    pub openai: Option<OpenAiSettings>,
+    /// 每个用户同时进行的流式回答上限，默认 `DEFAULT_AI_MAX_CONCURRENT_STREAMS`
+    pub max_concurrent_streams: Option<usize>,
+    /// 连续多久没有收到数据视为超时（秒），默认 `DEFAULT_AI_IDLE_TIMEOUT_SECS`
+    pub idle_timeout_secs: Option<u64>,
+    /// 单次回答的最长时间（秒），默认 `DEFAULT_AI_TOTAL_TIMEOUT_SECS`
+    pub total_timeout_secs: Option<u64>,
+}
+
+// OpenAI 兼容接口配置，适用于 llama.cpp、vLLM、Ollama 等
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct OpenAiSettings {
+    /// 接口地址，包含版本路径，如 `http://127.0.0.1:8080/v1`
+    pub base_url: String,
+    pub model: String,
+    pub system_prompt: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    /// 携带的历史消息条数上限，按完整的问答对保留，默认 `DEFAULT_AI_CONTEXT_MESSAGES`
+    pub context_messages: Option<usize>,
+}
+
+/// 幂等请求网络错误的默认重试次数
+const DEFAULT_HTTP_MAX_RETRIES: u32 = 2;
+
+impl HttpSettings {
+    pub fn request_timeout(&self) -> Duration {
+        self.request_timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(TimeoutConfig::HTTP_REQUEST_TIMEOUT)
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(TimeoutConfig::HTTP_CONNECT_TIMEOUT)
+    }
+
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries.unwrap_or(DEFAULT_HTTP_MAX_RETRIES)
+    }
+
+    /// 创建应用了代理、证书和连接超时的 `ClientBuilder`
+    ///
+    /// 不设置整体请求超时，上传、流式响应等长连接由调用方按需设置
+    pub fn client_builder(&self) -> Result<reqwest::ClientBuilder, anyhow::Error> {
+        let mut builder = reqwest::Client::builder().connect_timeout(self.connect_timeout());
+
+        if let Some(proxy_settings) = &self.proxy {
+            let mut proxy = reqwest::Proxy::all(&proxy_settings.url)
+                .map_err(|e| anyhow::anyhow!("Invalid proxy url: {}", e))?;
+            if let Some(username) = &proxy_settings.username {
+                let password = proxy_settings.password.as_deref().unwrap_or_default();
+                proxy = proxy.basic_auth(username, password);
+            }
+            if let Some(no_proxy) = &proxy_settings.no_proxy {
+                proxy = proxy.no_proxy(reqwest::NoProxy::from_string(no_proxy));
+            }
+            builder = builder.proxy(proxy);
+        }
+
+        for ca_path in self.ca_certs.iter().flatten() {
+            let pem = std::fs::read(ca_path)
+                .map_err(|e| anyhow::anyhow!("Failed to read CA certificate {}: {}", ca_path, e))?;
+            let certs = reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|e| anyhow::anyhow!("Invalid CA certificate {}: {}", ca_path, e))?;
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        if let Some(client_cert) = &self.client_cert {
+            let mut pem = std::fs::read(&client_cert.cert_path).map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to read client certificate {}: {}",
+                    client_cert.cert_path,
+                    e
+                )
+            })?;
+            if let Some(key_path) = &client_cert.key_path {
+                let key = std::fs::read(key_path).map_err(|e| {
+                    anyhow::anyhow!("Failed to read client key {}: {}", key_path, e)
+                })?;
+                pem.push(b'\n');
+                pem.extend_from_slice(&key);
+            }
+            let identity = reqwest::Identity::from_pem(&pem)
+                .map_err(|e| anyhow::anyhow!("Invalid client certificate: {}", e))?;
+            builder = builder.identity(identity);
+        }
+
+        Ok(builder)
+    }
+}
+
+/// 分片上传默认并发块数
+const DEFAULT_UPLOAD_CONCURRENCY: usize = 3;
+/// 分片上传最大并发块数，每个块会占用 4MB 内存
+const MAX_UPLOAD_CONCURRENCY: usize = 8;
+
+impl UploadSettings {
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+            .unwrap_or(DEFAULT_UPLOAD_CONCURRENCY)
+            .clamp(1, MAX_UPLOAD_CONCURRENCY)
+    }
+
+    pub fn bandwidth_limit(&self) -> Option<u64> {
+        self.bandwidth_limit.filter(|limit| *limit > 0)
+    }
+}
+
+/// 下载缓存默认容量 2GB
+const DEFAULT_DOWNLOAD_CACHE_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+impl DownloadSettings {
+    pub fn cache_max_size(&self) -> u64 {
+        self.cache_max_size
+            .filter(|size| *size > 0)
+            .unwrap_or(DEFAULT_DOWNLOAD_CACHE_SIZE)
+    }
+}
+
+/// 默认每个用户最多同时进行 3 个流式回答
+const DEFAULT_AI_MAX_CONCURRENT_STREAMS: usize = 3;
+/// 默认 60 秒没有收到数据视为超时
+const DEFAULT_AI_IDLE_TIMEOUT_SECS: u64 = 60;
+/// 默认单次回答最长 10 分钟
+const DEFAULT_AI_TOTAL_TIMEOUT_SECS: u64 = 600;
+
+impl AiSettings {
+    pub fn max_concurrent_streams(&self) -> usize {
+        self.max_concurrent_streams
+            .filter(|max| *max > 0)
+            .unwrap_or(DEFAULT_AI_MAX_CONCURRENT_STREAMS)
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.idle_timeout_secs
+                .filter(|secs| *secs > 0)
+                .unwrap_or(DEFAULT_AI_IDLE_TIMEOUT_SECS),
+        )
+    }
+
+    pub fn total_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.total_timeout_secs
+                .filter(|secs| *secs > 0)
+                .unwrap_or(DEFAULT_AI_TOTAL_TIMEOUT_SECS),
+        )
+    }
+}
+
+/// 默认携带最近 10 条历史消息
+const DEFAULT_AI_CONTEXT_MESSAGES: usize = 10;
+
+impl OpenAiSettings {
+    pub fn context_messages(&self) -> usize {
+        self.context_messages.unwrap_or(DEFAULT_AI_CONTEXT_MESSAGES)
+    }
+}
+
 // 应用程序运行环境枚举
 #[derive(Debug)]
 pub enum Environment {
@@ -78,22 +301,12 @@ impl DatabaseSettings {
         }
     }
 
-    /// 创建数据库连接
-    /// 根据不同的运行环境（桌面开发、移动端、桌面生产）选择合适的数据库路径
-    /// 并配置数据库连接选项，返回数据库连接实例
-    ///
-    /// # 参数
-    /// * `app_handle` - Tauri应用句柄，用于获取应用路径
-    /// * `uid` - 可选的用户ID，用于生成用户专属的数据库文件
-    ///
-    /// # 返回值
-    /// * `Ok(DatabaseConnection)` - 成功时返回数据库连接
-    /// * `Err(CommonError)` - 失败时返回错误信息
-    pub async fn connection_string(
-        &self,
+    /// 获取用户数据库文件路径
+    /// 桌面端开发环境使用项目根目录（src-tauri），其他环境使用 app_data_dir
+    pub fn database_path(
         app_handle: &AppHandle,
         uid: Option<&str>,
-    ) -> Result<DatabaseConnection, CommonError> {
+    ) -> Result<PathBuf, CommonError> {
         let db_filename = Self::get_db_filename(uid);
         info!("Database filename: {}", db_filename);
 
@@ -116,29 +329,97 @@ impl DatabaseSettings {
                 Err(e) => {
                     let error_msg = format!("Failed to get app_data_dir: {}", e);
                     tracing::error!("{}", error_msg);
-                    return Err(CommonError::RequestError(error_msg).into());
+                    return Err(CommonError::RequestError(error_msg));
                 }
             }
         };
         info!("Database path: {:?}", db_path);
+        Ok(db_path)
+    }
+
+    /// 创建数据库连接
+    /// 根据不同的运行环境（桌面开发、移动端、桌面生产）选择合适的数据库路径
+    /// 并配置数据库连接选项，返回数据库连接实例
+    ///
+    /// # 参数
+    /// * `app_handle` - Tauri应用句柄，用于获取应用路径
+    /// * `uid` - 可选的用户ID，用于生成用户专属的数据库文件
+    ///
+    /// # 返回值
+    /// * `Ok(DatabaseConnection)` - 成功时返回数据库连接
+    /// * `Err(CommonError)` - 失败时返回错误信息
+    pub async fn connection_string(
+        &self,
+        app_handle: &AppHandle,
+        uid: Option<&str>,
+    ) -> Result<DatabaseConnection, CommonError> {
+        let db_path = Self::database_path(app_handle, uid)?;
+
+        // 明文数据库会在这里迁移为加密数据库
+        let keys = db_crypto::prepare_database(&db_path, uid).await?;
 
-        if db_path.exists() {
-            let mut header = [0u8; 16];
-            let need_repair = match std::fs::File::open(&db_path) {
-                Ok(mut f) => {
-                    let _ = f.read(&mut header);
-                    header != *b"SQLite format 3\0"
+        match Self::connect_encrypted(&db_path, &keys.current).await {
+            Ok(db) => Ok(db),
+            Err(e) => {
+                let msg = e.to_string();
+                if !(msg.contains("file is not a database") || msg.contains("code: 26")) {
+                    return Err(anyhow::anyhow!("Database connection failed: {}", e).into());
+                }
+
+                // 上次重新加密中途退出时，数据库可能已经使用了新密钥
+                if let Some(pending) = keys.pending.as_ref()
+                    && let Ok(db) = Self::connect_encrypted(&db_path, pending).await
+                {
+                    let store = db_crypto::DbKeyStore::for_database(&db_path);
+                    store.commit_rekey(uid)?;
+                    return Ok(db);
                 }
-                Err(_) => false,
-            };
-            if need_repair {
-                let backup = db_path.with_extension("corrupted");
-                let _ =
-                    std::fs::rename(&db_path, &backup).or_else(|_| std::fs::remove_file(&db_path));
+
+                // 数据库损坏：改名保留原文件后重建，由消息同步重新拉取数据
+                let backup = db_crypto::quarantine_database(&db_path)?;
+                warn!(
+                    "Database {:?} cannot be opened with the stored key ({}), moved to {:?}",
+                    db_path, e, backup
+                );
+                Self::connect_encrypted(&db_path, &keys.current)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Database connection failed: {}", e).into())
             }
         }
+    }
 
+    /// 打开数据库，失败时（例如系统钥匙串不可用）改用临时数据库，保证应用可以继续运行
+    /// 临时数据库使用不保存的随机密钥，退出后数据丢失
+    pub async fn connect_or_fallback(
+        &self,
+        app_handle: &AppHandle,
+        uid: Option<&str>,
+    ) -> Result<DatabaseConnection, CommonError> {
+        match self.connection_string(app_handle, uid).await {
+            Ok(db) => Ok(db),
+            Err(e) => {
+                error!(
+                    "Failed to open database for {:?}, using a temporary database: {}",
+                    uid, e
+                );
+                let path =
+                    std::env::temp_dir().join(format!("hula-temp-{}.sqlite", uuid::Uuid::new_v4()));
+                Self::connect_encrypted(&path, &db_crypto::DatabaseKey::ephemeral())
+                    .await
+                    .map_err(|e| {
+                        anyhow::anyhow!("Temporary database connection failed: {}", e).into()
+                    })
+            }
+        }
+    }
+
+    /// 使用指定密钥打开加密数据库，并通过一次读取校验密钥是否正确
+    pub async fn connect_encrypted(
+        db_path: &Path,
+        key: &db_crypto::DatabaseKey,
+    ) -> Result<DatabaseConnection, DbErr> {
         let db_url = format!("sqlite:{}?mode=rwc", db_path.display());
+        let key = key.pragma_value().to_string();
 
         // 配置数据库连接选项
         let mut opt = ConnectOptions::new(db_url);
@@ -150,33 +431,16 @@ impl DatabaseSettings {
             .max_lifetime(Duration::from_secs(1800)) // 30分钟连接生命周期，避免频繁重建
             // 启用 SQL 日志记录，但只在 debug 模式下
             .sqlx_logging(cfg!(debug_assertions))
-            .sqlx_logging_level(tracing::log::LevelFilter::Info);
-
-        match Database::connect(opt).await {
-            Ok(db) => Ok(db),
-            Err(e) => {
-                let msg = e.to_string();
-                if msg.contains("file is not a database") || msg.contains("code: 26") {
-                    let _ = std::fs::remove_file(&db_path);
-                    let mut opt2 =
-                        ConnectOptions::new(format!("sqlite:{}?mode=rwc", db_path.display()));
-                    opt2.max_connections(20)
-                        .min_connections(2)
-                        .connect_timeout(Duration::from_secs(30))
-                        .acquire_timeout(Duration::from_secs(30))
-                        .idle_timeout(Duration::from_secs(600))
-                        .max_lifetime(Duration::from_secs(1800))
-                        .sqlx_logging(cfg!(debug_assertions))
-                        .sqlx_logging_level(tracing::log::LevelFilter::Info);
-                    let db = Database::connect(opt2)
-                        .await
-                        .map_err(|e| anyhow::anyhow!("Database connection failed: {}", e))?;
-                    Ok(db)
-                } else {
-                    Err(anyhow::anyhow!("Database connection failed: {}", e).into())
-                }
-            }
+            .sqlx_logging_level(tracing::log::LevelFilter::Info)
+            // 每个连接建立后首先执行 PRAGMA key
+            .map_sqlx_sqlite_opts(move |o| o.pragma("key", key.clone()));
+
+        let db = Database::connect(opt).await?;
+        if let Err(e) = db_crypto::verify_key(&db).await {
+            let _ = db.close().await;
+            return Err(e);
         }
+        Ok(db)
     }
 }
 
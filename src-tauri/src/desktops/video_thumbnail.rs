@@ -13,7 +13,8 @@ pub struct VideoThumbnailInfo {
     pub thumbnail_base64: String,
     pub width: u32,
     pub height: u32,
-    #[cfg(target_os = "macos")]
+    /// 视频时长（秒），无法获取时为 0
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
     pub duration: f64,
 }
 
@@ -200,7 +201,6 @@ async fn generate_thumbnail_macos(
         thumbnail_base64: base64_string,
         width,
         height,
-        #[cfg(target_os = "macos")]
         duration,
     })
 }
@@ -440,16 +440,356 @@ async fn generate_thumbnail_windows(
     })
 }
 
+/// Linux 依次尝试 ffmpeg、ffmpegthumbnailer 和桌面环境已生成的缩略图缓存
 #[cfg(target_os = "linux")]
 async fn generate_thumbnail_linux(
-    _video_path: &str,
-    _target_time: Option<f64>,
+    video_path: &str,
+    target_time: Option<f64>,
 ) -> TauriResult<VideoThumbnailInfo> {
-    // Linux 实现可以使用 gstreamer 或其他方案
-    Err(tauri::Error::Io(std::io::Error::new(
-        std::io::ErrorKind::Unsupported,
-        "Linux 平台暂不支持视频缩略图生成",
-    )))
+    let video_path = video_path.to_string();
+    tokio::task::spawn_blocking(move || linux::generate_thumbnail(&video_path, target_time))
+        .await
+        .map_err(|e| {
+            tauri::Error::Io(std::io::Error::other(format!("生成视频缩略图失败: {}", e)))
+        })?
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::VideoThumbnailInfo;
+    use base64::{Engine as _, engine::general_purpose};
+    use image::{ImageFormat, ImageReader};
+    use md5::{Digest, Md5};
+    use std::fs::File;
+    use std::io::{Read, Seek, SeekFrom};
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, Stdio};
+    use tauri::Result as TauriResult;
+    use tracing::debug;
+
+    /// 缩略图最长边，与其他平台保持一致
+    const THUMBNAIL_SIZE: u32 = 300;
+    /// 未指定截取时间时默认截取的位置（秒），跳过片头常见的黑屏
+    const DEFAULT_SEEK_SECS: f64 = 1.0;
+
+    pub(super) fn generate_thumbnail(
+        video_path: &str,
+        target_time: Option<f64>,
+    ) -> TauriResult<VideoThumbnailInfo> {
+        let duration = probe_duration(video_path)
+            .or_else(|| mp4_duration(Path::new(video_path)))
+            .unwrap_or(0.0);
+        let seek = seek_time(target_time, duration);
+
+        let mut errors = Vec::new();
+        let mut frame = None;
+        for (name, extract) in [
+            (
+                "ffmpeg",
+                extract_with_ffmpeg as fn(&str, f64) -> Result<Vec<u8>, String>,
+            ),
+            ("ffmpegthumbnailer", extract_with_ffmpegthumbnailer),
+            ("thumbnail cache", cached_thumbnail),
+        ] {
+            match extract(video_path, seek) {
+                Ok(data) => {
+                    frame = Some(data);
+                    break;
+                }
+                Err(e) => {
+                    debug!("Video thumbnail via {} failed: {}", name, e);
+                    errors.push(format!("{}: {}", name, e));
+                }
+            }
+        }
+        let frame = frame.ok_or_else(|| {
+            io_error(format!(
+                "生成视频缩略图失败，请安装 ffmpeg 后重试 ({})",
+                errors.join("; ")
+            ))
+        })?;
+
+        let img = ImageReader::new(std::io::Cursor::new(&frame))
+            .with_guessed_format()
+            .map_err(|e| io_error(format!("读取图像格式失败: {}", e)))?
+            .decode()
+            .map_err(|e| io_error(format!("解码图像失败: {}", e)))?;
+        // 缩略图缓存中的图片可能大于目标尺寸
+        let img = if img.width() > THUMBNAIL_SIZE || img.height() > THUMBNAIL_SIZE {
+            img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        } else {
+            img
+        };
+
+        let mut jpeg_data = Vec::new();
+        image::DynamicImage::ImageRgb8(img.to_rgb8())
+            .write_to(&mut std::io::Cursor::new(&mut jpeg_data), ImageFormat::Jpeg)
+            .map_err(|e| io_error(format!("转换为 JPEG 失败: {}", e)))?;
+
+        Ok(VideoThumbnailInfo {
+            thumbnail_base64: general_purpose::STANDARD.encode(&jpeg_data),
+            width: img.width(),
+            height: img.height(),
+            duration,
+        })
+    }
+
+    fn io_error(message: String) -> tauri::Error {
+        tauri::Error::Io(std::io::Error::other(message))
+    }
+
+    /// 截取时间超出视频时长时改为截取中间的画面
+    fn seek_time(target_time: Option<f64>, duration: f64) -> f64 {
+        let time = target_time
+            .filter(|t| t.is_finite())
+            .unwrap_or(DEFAULT_SEEK_SECS)
+            .max(0.0);
+        if duration > 0.0 && time >= duration {
+            duration / 2.0
+        } else {
+            time
+        }
+    }
+
+    fn run(command: &mut Command) -> Result<Vec<u8>, String> {
+        let output = command
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(output.stdout)
+    }
+
+    fn probe_duration(video_path: &str) -> Option<f64> {
+        let output = run(Command::new("ffprobe").args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+            video_path,
+        ]))
+        .ok()?;
+        String::from_utf8_lossy(&output)
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|d| d.is_finite() && *d > 0.0)
+    }
+
+    fn extract_with_ffmpeg(video_path: &str, seek: f64) -> Result<Vec<u8>, String> {
+        let scale = format!(
+            "scale={size}:{size}:force_original_aspect_ratio=decrease",
+            size = THUMBNAIL_SIZE
+        );
+        let extract = |seek: f64| {
+            run(Command::new("ffmpeg").args([
+                "-v",
+                "error",
+                "-ss",
+                &format!("{seek:.3}"),
+                "-i",
+                video_path,
+                "-frames:v",
+                "1",
+                "-vf",
+                &scale,
+                "-f",
+                "image2pipe",
+                "-vcodec",
+                "png",
+                "-",
+            ]))
+        };
+
+        let frame = extract(seek)?;
+        // 时长未知时截取位置可能超出视频末尾，此时改为截取第一帧
+        if frame.is_empty() && seek > 0.0 {
+            return extract(0.0).and_then(non_empty);
+        }
+        non_empty(frame)
+    }
+
+    fn extract_with_ffmpegthumbnailer(video_path: &str, seek: f64) -> Result<Vec<u8>, String> {
+        let output =
+            std::env::temp_dir().join(format!("hula-video-thumb-{}.png", uuid::Uuid::new_v4()));
+        let result = run(Command::new("ffmpegthumbnailer").args([
+            "-i",
+            video_path,
+            "-o",
+            &output.to_string_lossy(),
+            "-c",
+            "png",
+            "-s",
+            &THUMBNAIL_SIZE.to_string(),
+            "-t",
+            &format_timestamp(seek),
+        ]))
+        .and_then(|_| std::fs::read(&output).map_err(|e| e.to_string()));
+        let _ = std::fs::remove_file(&output);
+        result.and_then(non_empty)
+    }
+
+    /// 查找文件管理器按 freedesktop 缩略图规范生成的缩略图
+    fn cached_thumbnail(video_path: &str, _seek: f64) -> Result<Vec<u8>, String> {
+        let path = std::fs::canonicalize(video_path).map_err(|e| e.to_string())?;
+        let name = format!(
+            "{}.png",
+            hex::encode(Md5::digest(file_uri(&path).as_bytes()))
+        );
+        let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .ok_or_else(|| "cache directory not found".to_string())?;
+
+        ["large", "normal", "x-large", "xx-large"]
+            .iter()
+            .map(|size| cache_dir.join("thumbnails").join(size).join(&name))
+            .find_map(|candidate| std::fs::read(candidate).ok())
+            .ok_or_else(|| "no cached thumbnail".to_string())
+    }
+
+    fn non_empty(data: Vec<u8>) -> Result<Vec<u8>, String> {
+        if data.is_empty() {
+            Err("no frame extracted".to_string())
+        } else {
+            Ok(data)
+        }
+    }
+
+    fn format_timestamp(seconds: f64) -> String {
+        let total = seconds as u64;
+        format!(
+            "{:02}:{:02}:{:02}",
+            total / 3600,
+            total / 60 % 60,
+            total % 60
+        )
+    }
+
+    /// 缩略图规范要求的文件 URI，路径中除保留字符外都需要百分号编码
+    fn file_uri(path: &Path) -> String {
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut uri = String::from("file://");
+        for &byte in path.as_os_str().as_bytes() {
+            if byte.is_ascii_alphanumeric() || b"-._~/!$&'()*+,;=:@".contains(&byte) {
+                uri.push(byte as char);
+            } else {
+                uri.push_str(&format!("%{byte:02X}"));
+            }
+        }
+        uri
+    }
+
+    /// 没有 ffprobe 时从 MP4/MOV 的 mvhd 中读取时长
+    fn mp4_duration(path: &Path) -> Option<f64> {
+        let mut file = File::open(path).ok()?;
+        let len = file.metadata().ok()?.len();
+        let (moov_start, moov_end) = find_box(&mut file, 0, len, b"moov")?;
+        let (mvhd_start, mvhd_end) = find_box(&mut file, moov_start, moov_end, b"mvhd")?;
+
+        let mut data = vec![0u8; (mvhd_end - mvhd_start).min(32) as usize];
+        file.seek(SeekFrom::Start(mvhd_start)).ok()?;
+        file.read_exact(&mut data).ok()?;
+        parse_mvhd(&data)
+    }
+
+    /// 在 `[start, end)` 范围内查找指定类型的 box，返回 box 内容的范围
+    fn find_box<R: Read + Seek>(
+        reader: &mut R,
+        start: u64,
+        end: u64,
+        box_type: &[u8; 4],
+    ) -> Option<(u64, u64)> {
+        let mut pos = start;
+        while pos + 8 <= end {
+            reader.seek(SeekFrom::Start(pos)).ok()?;
+            let mut header = [0u8; 8];
+            reader.read_exact(&mut header).ok()?;
+            let mut header_len = 8;
+            let mut size = u32::from_be_bytes(header[..4].try_into().ok()?) as u64;
+            if size == 1 {
+                let mut large = [0u8; 8];
+                reader.read_exact(&mut large).ok()?;
+                size = u64::from_be_bytes(large);
+                header_len = 16;
+            } else if size == 0 {
+                size = end - pos;
+            }
+            if size < header_len || pos + size > end {
+                return None;
+            }
+            if &header[4..8] == box_type {
+                return Some((pos + header_len, pos + size));
+            }
+            pos += size;
+        }
+        None
+    }
+
+    fn parse_mvhd(data: &[u8]) -> Option<f64> {
+        let (timescale, duration) = match *data.first()? {
+            0 => (
+                u32::from_be_bytes(data.get(12..16)?.try_into().ok()?),
+                u32::from_be_bytes(data.get(16..20)?.try_into().ok()?) as u64,
+            ),
+            1 => (
+                u32::from_be_bytes(data.get(20..24)?.try_into().ok()?),
+                u64::from_be_bytes(data.get(24..32)?.try_into().ok()?),
+            ),
+            _ => return None,
+        };
+        if timescale == 0 {
+            return None;
+        }
+        Some(duration as f64 / timescale as f64)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn mp4_box(box_type: &[u8; 4], content: &[u8]) -> Vec<u8> {
+            let mut data = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+            data.extend_from_slice(box_type);
+            data.extend_from_slice(content);
+            data
+        }
+
+        #[test]
+        fn test_mp4_duration_from_mvhd() {
+            let mut mvhd = vec![0u8; 100];
+            mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+            mvhd[16..20].copy_from_slice(&12_500u32.to_be_bytes());
+            let mut file = mp4_box(b"ftyp", b"isom");
+            file.extend(mp4_box(b"free", &[0u8; 16]));
+            file.extend(mp4_box(
+                b"moov",
+                &[mp4_box(b"trak", &[0u8; 8]), mp4_box(b"mvhd", &mvhd)].concat(),
+            ));
+
+            let path = std::env::temp_dir().join(format!("hula-mvhd-{}.mp4", uuid::Uuid::new_v4()));
+            std::fs::write(&path, file).unwrap();
+            assert_eq!(mp4_duration(&path), Some(12.5));
+            let _ = std::fs::remove_file(path);
+        }
+
+        #[test]
+        fn test_seek_time_and_file_uri() {
+            assert_eq!(seek_time(None, 10.0), DEFAULT_SEEK_SECS);
+            assert_eq!(seek_time(Some(30.0), 10.0), 5.0);
+            assert_eq!(seek_time(Some(-1.0), 0.0), 0.0);
+            assert_eq!(
+                file_uri(Path::new("/home/user/视频 1.mp4")),
+                "file:///home/user/%E8%A7%86%E9%A2%91%201.mp4"
+            );
+        }
+    }
 }
 
 /// Tauri 命令：生成视频缩略图
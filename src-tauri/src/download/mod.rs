@@ -0,0 +1,626 @@
+//! 消息媒体下载缓存
+//!
+//! 文件按内容的 SHA-256 保存在 `objects` 目录，不同地址下载到相同内容时共用一个文件；
+//! `urls` 目录按地址的哈希记录对应的内容哈希。缓存总大小超过上限时按最近访问时间淘汰，
+//! 访问时间记录在文件的修改时间上，重启后据此重建淘汰顺序。
+//! 下载中的文件保存在 `tmp` 目录，中断后通过 HTTP Range 请求继续下载。
+
+use crate::error::CommonError;
+use futures_util::StreamExt;
+use moka::future::Cache;
+use moka::notification::RemovalCause;
+use moka::policy::EvictionPolicy;
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
+
+const OBJECTS_DIR: &str = "objects";
+const URLS_DIR: &str = "urls";
+const TMP_DIR: &str = "tmp";
+/// 续传时重新计算已下载部分哈希的读取块大小
+const HASH_READ_SIZE: usize = 256 * 1024;
+
+/// 已缓存的媒体文件
+#[derive(Debug, Clone)]
+pub struct CachedMedia {
+    pub path: PathBuf,
+    /// 文件内容的 SHA-256
+    pub hash: String,
+    pub size: u64,
+}
+
+/// 下载进度，`total` 在服务端未返回长度时为空
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadProgress {
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+#[derive(Clone)]
+struct CacheEntry {
+    path: PathBuf,
+    size: u64,
+}
+
+/// 按内容哈希去重的媒体缓存
+pub struct MediaCache {
+    root: PathBuf,
+    max_size: u64,
+    entries: Cache<String, CacheEntry>,
+}
+
+impl MediaCache {
+    /// 打开缓存目录并按文件修改时间重建淘汰顺序，超出 `max_size` 的最旧文件会被删除
+    pub async fn open(root: PathBuf, max_size: u64) -> Result<Self, CommonError> {
+        for dir in [OBJECTS_DIR, URLS_DIR, TMP_DIR] {
+            fs::create_dir_all(root.join(dir))
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to create cache directory: {}", e))?;
+        }
+
+        let entries = Cache::builder()
+            .max_capacity(max_size)
+            .weigher(|_hash: &String, entry: &CacheEntry| entry.size.try_into().unwrap_or(u32::MAX))
+            .eviction_policy(EvictionPolicy::lru())
+            .eviction_listener(|hash: Arc<String>, entry: CacheEntry, cause| {
+                // 替换和主动移除由调用方处理文件
+                if cause == RemovalCause::Size {
+                    debug!("Evicting cached media {}", hash);
+                    if let Err(e) = std::fs::remove_file(&entry.path) {
+                        warn!("Failed to remove evicted media {:?}: {}", entry.path, e);
+                    }
+                }
+            })
+            .build();
+
+        let cache = Self {
+            root,
+            max_size,
+            entries,
+        };
+        cache.load_entries().await?;
+        Ok(cache)
+    }
+
+    async fn load_entries(&self) -> Result<(), CommonError> {
+        let mut files = Vec::new();
+        let mut dir = fs::read_dir(self.root.join(OBJECTS_DIR))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read cache directory: {}", e))?;
+        while let Ok(Some(item)) = dir.next_entry().await {
+            let path = item.path();
+            let Some(hash) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(str::to_string)
+            else {
+                continue;
+            };
+            let Ok(metadata) = item.metadata().await else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            let accessed = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((accessed, hash, path, metadata.len()));
+        }
+
+        // 先插入最久未访问的文件，使其最先被淘汰
+        files.sort_by_key(|(accessed, ..)| *accessed);
+        for (_, hash, path, size) in files {
+            self.entries.insert(hash, CacheEntry { path, size }).await;
+        }
+        self.entries.run_pending_tasks().await;
+        info!(
+            "Media cache loaded, {} files, {} bytes",
+            self.entries.entry_count(),
+            self.entries.weighted_size()
+        );
+        Ok(())
+    }
+
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// 已缓存的文件数量和总大小
+    pub async fn usage(&self) -> (u64, u64) {
+        self.entries.run_pending_tasks().await;
+        (self.entries.entry_count(), self.entries.weighted_size())
+    }
+
+    /// 查找地址对应的缓存文件，命中时刷新访问时间
+    pub async fn lookup(&self, url: &str) -> Option<CachedMedia> {
+        let url_path = self.url_path(url);
+        let hash = fs::read_to_string(&url_path).await.ok()?;
+        let hash = hash.trim().to_string();
+
+        let Some(entry) = self.entries.get(&hash).await else {
+            // 内容已被淘汰
+            let _ = fs::remove_file(&url_path).await;
+            return None;
+        };
+        if fs::metadata(&entry.path).await.is_err() {
+            self.entries.invalidate(&hash).await;
+            let _ = fs::remove_file(&url_path).await;
+            return None;
+        }
+
+        touch(&entry.path).await;
+        Some(CachedMedia {
+            path: entry.path,
+            hash,
+            size: entry.size,
+        })
+    }
+
+    /// 把下载完成的临时文件加入缓存，相同内容已存在时直接复用
+    async fn insert(
+        &self,
+        url: &str,
+        temp_path: &Path,
+        hash: String,
+    ) -> Result<CachedMedia, CommonError> {
+        let entry = match self.entries.get(&hash).await {
+            Some(entry) if fs::metadata(&entry.path).await.is_ok() => {
+                let _ = fs::remove_file(temp_path).await;
+                touch(&entry.path).await;
+                entry
+            }
+            _ => {
+                let path = self
+                    .root
+                    .join(OBJECTS_DIR)
+                    .join(object_file_name(&hash, url));
+                fs::rename(temp_path, &path)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to move downloaded file: {}", e))?;
+                let size = fs::metadata(&path)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to read downloaded file: {}", e))?
+                    .len();
+                let entry = CacheEntry { path, size };
+                self.entries.insert(hash.clone(), entry.clone()).await;
+                entry
+            }
+        };
+
+        fs::write(self.url_path(url), &hash)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to write cache index: {}", e))?;
+        Ok(CachedMedia {
+            path: entry.path,
+            hash,
+            size: entry.size,
+        })
+    }
+
+    /// 删除所有缓存文件和未完成的下载
+    pub async fn clear(&self) -> Result<(), CommonError> {
+        self.entries.invalidate_all();
+        self.entries.run_pending_tasks().await;
+        for dir in [OBJECTS_DIR, URLS_DIR, TMP_DIR] {
+            let path = self.root.join(dir);
+            if let Err(e) = fs::remove_dir_all(&path).await {
+                warn!("Failed to remove cache directory {:?}: {}", path, e);
+            }
+            fs::create_dir_all(&path)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to create cache directory: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn url_path(&self, url: &str) -> PathBuf {
+        self.root.join(URLS_DIR).join(url_key(url))
+    }
+
+    fn temp_path(&self, url: &str, suffix: &str) -> PathBuf {
+        self.root
+            .join(TMP_DIR)
+            .join(format!("{}.{}", url_key(url), suffix))
+    }
+}
+
+/// 同一地址同时只允许一个下载，后到的请求等待前一个完成后直接命中缓存
+static URL_LOCKS: Lazy<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 下载地址对应的文件，已缓存时直接返回
+///
+/// 返回值中的 bool 表示是否命中缓存
+pub async fn fetch(
+    client: &reqwest::Client,
+    cache: &MediaCache,
+    url: &str,
+    mut on_progress: impl FnMut(DownloadProgress) + Send,
+) -> Result<(CachedMedia, bool), CommonError> {
+    if let Some(media) = cache.lookup(url).await {
+        return Ok((media, true));
+    }
+
+    let lock = URL_LOCKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(url.to_string())
+        .or_default()
+        .clone();
+    let result = {
+        let _guard = lock.lock().await;
+        match cache.lookup(url).await {
+            Some(media) => Ok((media, true)),
+            None => download(client, cache, url, &mut on_progress)
+                .await
+                .map(|media| (media, false)),
+        }
+    };
+
+    let mut locks = URL_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    // 只剩映射表和当前请求持有时说明没有其他等待者
+    if Arc::strong_count(&lock) <= 2 {
+        locks.remove(url);
+    }
+    result
+}
+
+async fn download(
+    client: &reqwest::Client,
+    cache: &MediaCache,
+    url: &str,
+    on_progress: &mut (impl FnMut(DownloadProgress) + Send),
+) -> Result<CachedMedia, CommonError> {
+    let part_path = cache.temp_path(url, "part");
+    let validator_path = cache.temp_path(url, "validator");
+
+    let mut downloaded = fs::metadata(&part_path).await.map(|m| m.len()).unwrap_or(0);
+    let validator = fs::read_to_string(&validator_path).await.ok();
+
+    let mut request = client.get(url);
+    // 没有校验值时无法确认服务端文件未变化，从头下载
+    if let Some(validator) = validator.as_deref().filter(|_| downloaded > 0) {
+        // 文件已变化时服务端会返回完整内容而不是 206
+        request = request
+            .header(reqwest::header::RANGE, format!("bytes={downloaded}-"))
+            .header(reqwest::header::IF_RANGE, validator);
+    } else {
+        downloaded = 0;
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("Download request failed: {}", e))?;
+
+    let status = response.status();
+    let resumed = downloaded > 0
+        && status == reqwest::StatusCode::PARTIAL_CONTENT
+        && content_range_start(&response) == Some(downloaded);
+    if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // 已下载部分与服务端文件不一致，丢弃后由下次请求重新下载
+        discard_partial(&part_path, &validator_path).await;
+        return Err(anyhow::anyhow!("Range not satisfiable, partial download discarded").into());
+    }
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow::anyhow!("Download failed with status {}: {}", status, body).into());
+    }
+
+    let mut hasher = Sha256::new();
+    let mut file = if resumed {
+        hash_file(&part_path, &mut hasher).await?;
+        debug!("Resuming download of {} from {} bytes", url, downloaded);
+        OpenOptions::new()
+            .append(true)
+            .open(&part_path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to open partial file: {}", e))?
+    } else {
+        downloaded = 0;
+        File::create(&part_path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to create partial file: {}", e))?
+    };
+
+    match response_validator(&response) {
+        Some(validator) => {
+            let _ = fs::write(&validator_path, validator).await;
+        }
+        None => {
+            let _ = fs::remove_file(&validator_path).await;
+        }
+    }
+
+    let total = response.content_length().map(|len| len + downloaded);
+    // 超过缓存上限的文件加入缓存后会被立即淘汰
+    if let Some(total) = total.filter(|total| *total > cache.max_size) {
+        drop(file);
+        discard_partial(&part_path, &validator_path).await;
+        return Err(too_large(total, cache.max_size));
+    }
+    on_progress(DownloadProgress { downloaded, total });
+
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| anyhow::anyhow!("Download interrupted: {}", e))?;
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to write downloaded data: {}", e))?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+        if downloaded > cache.max_size {
+            drop(file);
+            discard_partial(&part_path, &validator_path).await;
+            return Err(too_large(downloaded, cache.max_size));
+        }
+        on_progress(DownloadProgress { downloaded, total });
+    }
+    file.flush()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to write downloaded data: {}", e))?;
+    drop(file);
+
+    if let Some(total) = total
+        && downloaded != total
+    {
+        return Err(anyhow::anyhow!("Download incomplete: {}/{} bytes", downloaded, total).into());
+    }
+
+    let _ = fs::remove_file(&validator_path).await;
+    let hash = hex::encode(hasher.finalize());
+    cache.insert(url, &part_path, hash).await
+}
+
+async fn discard_partial(part_path: &Path, validator_path: &Path) {
+    let _ = fs::remove_file(part_path).await;
+    let _ = fs::remove_file(validator_path).await;
+}
+
+fn too_large(size: u64, max_size: u64) -> CommonError {
+    anyhow::anyhow!(
+        "File of {} bytes exceeds media cache size {}",
+        size,
+        max_size
+    )
+    .into()
+}
+
+async fn hash_file(path: &Path, hasher: &mut Sha256) -> Result<(), CommonError> {
+    let mut file = File::open(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to open partial file: {}", e))?;
+    let mut buf = vec![0u8; HASH_READ_SIZE];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read partial file: {}", e))?;
+        if n == 0 {
+            return Ok(());
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+/// 解析 `Content-Range: bytes start-end/total` 中的起始位置
+fn content_range_start(response: &reqwest::Response) -> Option<u64> {
+    let value = response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?;
+    let range = value.strip_prefix("bytes ")?;
+    range.split('-').next()?.trim().parse().ok()
+}
+
+/// 续传时用于 If-Range 的校验值，优先使用强 ETag
+fn response_validator(response: &reqwest::Response) -> Option<String> {
+    let headers = response.headers();
+    headers
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| {
+            headers
+                .get(reqwest::header::LAST_MODIFIED)
+                .and_then(|v| v.to_str().ok())
+        })
+        .map(str::to_string)
+}
+
+/// 刷新文件的修改时间作为最近访问时间
+async fn touch(path: &Path) {
+    let path = path.to_path_buf();
+    let _ = tokio::task::spawn_blocking(move || {
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+    })
+    .await;
+}
+
+fn url_key(url: &str) -> String {
+    hex::encode(Sha256::digest(url.as_bytes()))
+}
+
+/// 缓存文件名保留地址中的扩展名，便于系统按类型打开
+fn object_file_name(hash: &str, url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let extension = path
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, ext)| ext)
+        .filter(|ext| {
+            !ext.is_empty() && ext.len() <= 8 && ext.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    match extension {
+        Some(ext) => format!("{hash}.{}", ext.to_ascii_lowercase()),
+        None => hash.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "hula-media-cache-{}-{}",
+            name,
+            uuid::Uuid::new_v4()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    /// 支持 Range 请求的静态文件服务，记录每次请求的 Range 头
+    async fn serve(content: &'static [u8], ranges: Arc<Mutex<Vec<Option<String>>>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap();
+                let head = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let range = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("range: bytes="))
+                    .map(|r| r.trim_end_matches('-').to_string());
+                ranges.lock().unwrap().push(range.clone());
+
+                let start: usize = range.and_then(|r| r.parse().ok()).unwrap_or(0);
+                let body = &content[start..];
+                let status = if start > 0 {
+                    format!(
+                        "206 Partial Content\r\nContent-Range: bytes {}-{}/{}",
+                        start,
+                        content.len() - 1,
+                        content.len()
+                    )
+                } else {
+                    "200 OK".to_string()
+                };
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+                socket.write_all(body).await.unwrap();
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_fetch_resumes_and_deduplicates() {
+        let root = temp_root("fetch");
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let base = serve(b"hello media cache", ranges.clone()).await;
+        let cache = MediaCache::open(root.clone(), 1024).await.unwrap();
+        let client = reqwest::Client::new();
+
+        // 模拟上次中断时已下载的前 6 个字节
+        let url = format!("{base}/a/photo.PNG?token=1");
+        fs::write(cache.temp_path(&url, "part"), b"hello ")
+            .await
+            .unwrap();
+        fs::write(cache.temp_path(&url, "validator"), "\"v1\"")
+            .await
+            .unwrap();
+        let mut last = None;
+        let (media, cached) = fetch(&client, &cache, &url, |p| last = Some(p))
+            .await
+            .unwrap();
+        assert!(!cached);
+        assert_eq!(fs::read(&media.path).await.unwrap(), b"hello media cache");
+        assert_eq!(
+            media.hash,
+            hex::encode(Sha256::digest(b"hello media cache"))
+        );
+        assert!(media.path.to_string_lossy().ends_with(".png"));
+        assert_eq!(last.map(|p| (p.downloaded, p.total)), Some((17, Some(17))));
+
+        let (again, cached) = fetch(&client, &cache, &url, |_| {}).await.unwrap();
+        assert!(cached);
+        assert_eq!(again.path, media.path);
+
+        // 不同地址下载到相同内容时共用一个文件
+        let (other, cached) = fetch(&client, &cache, &format!("{base}/b"), |_| {})
+            .await
+            .unwrap();
+        assert!(!cached);
+        assert_eq!(other.path, media.path);
+        assert_eq!(cache.usage().await, (1, 17));
+
+        assert_eq!(*ranges.lock().unwrap(), vec![Some("6".to_string()), None]);
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_restarts_without_validator() {
+        let root = temp_root("restart");
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let base = serve(b"hello media cache", ranges.clone()).await;
+        let cache = MediaCache::open(root.clone(), 1024).await.unwrap();
+
+        // 没有校验值的残留文件无法续传
+        let url = format!("{base}/c");
+        fs::write(cache.temp_path(&url, "part"), b"stale!")
+            .await
+            .unwrap();
+        let (media, _) = fetch(&reqwest::Client::new(), &cache, &url, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&media.path).await.unwrap(), b"hello media cache");
+        assert_eq!(*ranges.lock().unwrap(), vec![None]);
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_files_larger_than_cache() {
+        let root = temp_root("too-large");
+        let base = serve(b"hello media cache", Arc::default()).await;
+        let cache = MediaCache::open(root.clone(), 10).await.unwrap();
+
+        let url = format!("{base}/d");
+        let result = fetch(&reqwest::Client::new(), &cache, &url, |_| {}).await;
+        assert!(result.is_err());
+        assert!(!cache.temp_path(&url, "part").exists());
+        assert_eq!(cache.usage().await, (0, 0));
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used() {
+        let root = temp_root("evict");
+        let objects = root.join(OBJECTS_DIR);
+        std::fs::create_dir_all(&objects).unwrap();
+        for (name, age) in [("old", 30), ("mid", 20), ("new", 10)] {
+            let path = objects.join(name);
+            std::fs::write(&path, [0u8; 40]).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(SystemTime::now() - std::time::Duration::from_secs(age))
+                .unwrap();
+        }
+
+        let cache = MediaCache::open(root.clone(), 100).await.unwrap();
+        assert_eq!(cache.usage().await, (2, 80));
+        assert!(!objects.join("old").exists());
+        assert!(objects.join("mid").exists());
+        assert!(objects.join("new").exists());
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
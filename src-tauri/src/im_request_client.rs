@@ -1,25 +1,64 @@
 use std::str::FromStr;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
 
-use base64::{Engine, prelude::BASE64_STANDARD};
+use base64::{
+    Engine,
+    prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD},
+};
+use rand::Rng;
 use reqwest::header;
 use serde_json::json;
-use tracing::error;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
 
 use crate::{
+    configuration::HttpSettings,
     pojo::common::ApiResult,
+    secret_store::{self, SecretStore},
+    timeout_config::TimeoutConfig,
     vo::vo::{LoginReq, LoginResp},
 };
 
-#[derive(Debug)]
+/// IM 后端请求客户端
+///
+/// 克隆的开销很小，所有克隆共享连接池和登录状态，请求之间互不阻塞
+#[derive(Debug, Clone)]
 pub struct ImRequestClient {
     client: reqwest::Client,
-    base_url: String,
-    pub token: Option<String>,
-    pub refresh_token: Option<String>,
+    base_url: Arc<RwLock<String>>,
+    /// 普通请求的整体超时，流式请求不受此限制
+    request_timeout: Duration,
+    /// 幂等请求遇到网络错误时的最大重试次数
+    max_retries: u32,
+    /// 登录状态，持有锁期间不会 await
+    session: Arc<RwLock<Session>>,
+    /// 登录凭证存储，token 刷新后自动写入
+    secret_store: Option<Arc<dyn SecretStore>>,
+    /// 保证同一时间只有一个 token 刷新请求
+    refresh_lock: Arc<Mutex<()>>,
+}
+
+#[derive(Debug, Default)]
+struct Session {
+    token: Option<String>,
+    refresh_token: Option<String>,
+    /// 当前 token 所属的用户
+    uid: Option<String>,
+    /// 登录或刷新接口返回的 token 过期时间
+    expiry: Option<TokenExpiry>,
+}
+
+/// 接口返回的过期时间只对签发时的 token 有效，token 被外部替换后失效
+#[derive(Debug)]
+struct TokenExpiry {
+    token: String,
+    /// 过期时间（毫秒时间戳）
+    expires_at: i64,
 }
 
 impl ImRequestClient {
-    pub fn new(base_url: String) -> Result<Self, anyhow::Error> {
+    pub fn new(base_url: String, http: &HttpSettings) -> Result<Self, anyhow::Error> {
         let mut headers = header::HeaderMap::new();
         headers.insert(
             header::CONTENT_TYPE,
@@ -30,21 +69,201 @@ impl ImRequestClient {
             .map_err(|e| anyhow::anyhow!("Failed to create HTTP client: {}", e))?;
         headers.insert(header::AUTHORIZATION, basic_auth_value);
 
-        let client = reqwest::Client::builder()
+        let client = http
+            .client_builder()?
             .default_headers(headers)
             .build()
             .map_err(|e| anyhow::anyhow!("Failed to create HTTP client: {}", e))?;
 
         Ok(Self {
             client: client,
-            base_url: base_url,
-            token: None,
-            refresh_token: None,
+            base_url: Arc::new(RwLock::new(base_url)),
+            request_timeout: http.request_timeout(),
+            max_retries: http.max_retries(),
+            session: Arc::new(RwLock::new(Session::default())),
+            secret_store: None,
+            refresh_lock: Arc::new(Mutex::new(())),
         })
     }
 
-    pub fn set_base_url(&mut self, base_url: String) {
-        self.base_url = base_url;
+    fn session(&self) -> RwLockReadGuard<'_, Session> {
+        self.session.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn session_mut(&self) -> RwLockWriteGuard<'_, Session> {
+        self.session.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn url(&self, path: &str) -> String {
+        let base_url = self.base_url.read().unwrap_or_else(|e| e.into_inner());
+        format!("{}/{}", base_url, path)
+    }
+
+    pub fn token(&self) -> Option<String> {
+        self.session().token.clone()
+    }
+
+    pub fn refresh_token(&self) -> Option<String> {
+        self.session().refresh_token.clone()
+    }
+
+    /// 替换当前 token，`refresh_token` 为 None 时保留原有的 refresh_token
+    pub fn set_tokens(&self, token: String, refresh_token: Option<String>) {
+        let mut session = self.session_mut();
+        session.token = Some(token);
+        if let Some(refresh_token) = refresh_token.filter(|t| !t.is_empty()) {
+            session.refresh_token = Some(refresh_token);
+        }
+    }
+
+    /// 清空内存中的 token，不影响凭证存储
+    pub fn clear_tokens(&self) {
+        let mut session = self.session_mut();
+        session.token = None;
+        session.refresh_token = None;
+        session.expiry = None;
+    }
+
+    pub fn set_secret_store(&mut self, secret_store: Arc<dyn SecretStore>) {
+        self.secret_store = Some(secret_store);
+    }
+
+    pub fn secret_store(&self) -> Option<Arc<dyn SecretStore>> {
+        self.secret_store.clone()
+    }
+
+    /// 绑定当前 token 所属的用户，之后刷新得到的 token 会写入该用户的凭证
+    pub fn bind_session(&self, uid: &str) {
+        self.session_mut().uid = Some(uid.to_string()).filter(|uid| !uid.is_empty());
+    }
+
+    /// 从凭证存储中恢复用户的 token 并绑定会话
+    pub fn restore_session(
+        &self,
+        uid: &str,
+    ) -> Result<Option<secret_store::AuthTokens>, anyhow::Error> {
+        let Some(store) = self.secret_store.as_deref() else {
+            return Ok(None);
+        };
+        let tokens = secret_store::load_tokens(store, uid)?;
+        let mut session = self.session_mut();
+        if let Some(tokens) = &tokens {
+            session.token = Some(tokens.token.clone()).filter(|t| !t.is_empty());
+            session.refresh_token = Some(tokens.refresh_token.clone()).filter(|t| !t.is_empty());
+        }
+        session.uid = Some(uid.to_string()).filter(|uid| !uid.is_empty());
+        Ok(tokens)
+    }
+
+    /// 把当前 token 写入绑定用户的凭证存储
+    pub fn persist_tokens(&self) -> Result<(), anyhow::Error> {
+        let session = self.session();
+        let (Some(store), Some(uid), Some(token)) = (
+            self.secret_store.as_deref(),
+            session.uid.as_deref(),
+            session.token.as_deref(),
+        ) else {
+            return Ok(());
+        };
+        let refresh_token = session.refresh_token.as_deref().unwrap_or_default();
+        secret_store::save_tokens(store, uid, token, refresh_token)?;
+        Ok(())
+    }
+
+    /// 退出登录：清空内存中的 token 并删除绑定用户的凭证
+    pub fn clear_session(&self) -> Result<(), anyhow::Error> {
+        let uid = {
+            let mut session = self.session_mut();
+            session.token = None;
+            session.refresh_token = None;
+            session.expiry = None;
+            session.uid.take()
+        };
+        if let (Some(store), Some(uid)) = (self.secret_store.as_deref(), uid) {
+            secret_store::wipe_tokens(store, &uid)?;
+        }
+        Ok(())
+    }
+
+    /// 保存登录或刷新得到的 token，`expire` 为接口返回的过期时间
+    fn store_issued_tokens(&self, token: String, refresh_token: String, expire: Option<&str>) {
+        let expiry = expire
+            .and_then(|expire| parse_expire(expire, chrono::Utc::now().timestamp_millis()))
+            .map(|expires_at| TokenExpiry {
+                token: token.clone(),
+                expires_at,
+            });
+        let mut session = self.session_mut();
+        session.expiry = expiry;
+        session.token = Some(token);
+        session.refresh_token = Some(refresh_token);
+    }
+
+    /// 当前 token 的过期时间（毫秒时间戳）
+    ///
+    /// 优先使用接口返回的过期时间，否则尝试解析 JWT 中的 `exp`，都无法获取时返回 None
+    pub fn token_expires_at(&self) -> Option<i64> {
+        let session = self.session();
+        let token = session.token.as_deref()?;
+        match &session.expiry {
+            Some(expiry) if expiry.token == token => Some(expiry.expires_at),
+            _ => decode_jwt_expiry(token),
+        }
+    }
+
+    /// token 即将过期且持有可用的 refresh_token
+    pub fn token_expiring(&self) -> bool {
+        let can_refresh = self
+            .session()
+            .refresh_token
+            .as_deref()
+            .is_some_and(|t| !t.is_empty());
+        let ahead = TimeoutConfig::TOKEN_REFRESH_AHEAD.as_millis() as i64;
+        can_refresh
+            && self
+                .token_expires_at()
+                .is_some_and(|at| at - chrono::Utc::now().timestamp_millis() <= ahead)
+    }
+
+    /// 距离下一次需要主动刷新 token 的时长，最长不超过检查间隔以便感知重新登录
+    pub fn next_refresh_in(&self) -> Duration {
+        let Some(expires_at) = self.token_expires_at() else {
+            return TimeoutConfig::TOKEN_CHECK_INTERVAL;
+        };
+        let ahead = TimeoutConfig::TOKEN_REFRESH_AHEAD.as_millis() as i64;
+        let wait_ms = expires_at - ahead - chrono::Utc::now().timestamp_millis();
+        Duration::from_millis(wait_ms.max(0) as u64).min(TimeoutConfig::TOKEN_CHECK_INTERVAL)
+    }
+
+    /// 单飞刷新：`stale_token` 为调用方发现过期的 token
+    ///
+    /// 并发的刷新请求排队执行，轮到时如果 token 已经被其他请求或定时任务刷新过则直接返回 false，
+    /// 避免用同一个 refresh_token 重复刷新
+    pub async fn refresh_token_once(
+        &self,
+        stale_token: Option<&str>,
+    ) -> Result<bool, anyhow::Error> {
+        let _guard = self.refresh_lock.lock().await;
+        if self.token().as_deref() != stale_token {
+            return Ok(false);
+        }
+        self.refresh().await?;
+        Ok(true)
+    }
+
+    /// 请求前 token 即将过期时提前刷新，刷新失败不影响本次请求，由服务端返回的 406 兜底
+    async fn refresh_if_expiring(&self) {
+        if !self.token_expiring() {
+            return;
+        }
+        let stale_token = self.token();
+        if let Err(e) = self.refresh_token_once(stale_token.as_deref()).await {
+            warn!("token 即将过期，提前刷新失败: {}", e);
+        }
+    }
+
+    pub fn set_base_url(&self, base_url: String) {
+        *self.base_url.write().unwrap_or_else(|e| e.into_inner()) = base_url;
     }
 
     /// 构建请求的公共方法（不发送请求）
@@ -65,11 +284,11 @@ impl ImRequestClient {
         params: &Option<C>,
         extra_headers: Option<Vec<(&str, &str)>>,
     ) -> reqwest::RequestBuilder {
-        let url = format!("{}/{}", self.base_url, path);
+        let url = self.url(path);
         let mut request_builder = self.client.request(method, &url);
 
         // 设置 token 请求头
-        if let Some(token) = &self.token {
+        if let Some(token) = self.token() {
             request_builder = request_builder.header("token", token);
         }
 
@@ -100,31 +319,56 @@ impl ImRequestClient {
         B: serde::Serialize,
         C: serde::Serialize,
     >(
-        &mut self,
-        method: http::Method,
-        path: &str,
+        &self,
+        im_url: &ImUrl,
         body: Option<B>,
         params: Option<C>,
     ) -> Result<ApiResult<T>, anyhow::Error> {
         let mut retry_count = 0;
         const MAX_RETRY_COUNT: u8 = 2;
-        let is_logout = path == ImUrl::Logout.get_url().1;
+        let (method, path) = im_url.get_url();
+        let is_logout = matches!(im_url, ImUrl::Logout);
+        let is_idempotent = im_url.is_idempotent();
+        let mut network_retry_count = 0;
+
+        if !is_logout {
+            self.refresh_if_expiring().await;
+        }
+
+        let url = self.url(path);
 
         loop {
+            let sent_token = self.token();
             // 使用 build_request 构建请求
-            let request_builder = self.build_request(method.clone(), path, &body, &params, None);
+            let request_builder = self
+                .build_request(method.clone(), path, &body, &params, None)
+                .timeout(self.request_timeout);
 
             // 发送请求，区分网络错误和业务错误
-            let response = request_builder
-                .send()
-                .await
-                .map_err(|e| anyhow::anyhow!("network_error: {}", e))?;
-            let result: ApiResult<T> = response
-                .json()
-                .await
-                .map_err(|e| anyhow::anyhow!("network_error: {}", e))?;
-
-            let url = format!("{}/{}", self.base_url, path);
+            let sent = match request_builder.send().await {
+                Ok(response) => response.json::<ApiResult<T>>().await,
+                Err(e) => Err(e),
+            };
+            let result = match sent {
+                Ok(result) => result,
+                Err(e) => {
+                    // 幂等请求遇到网络错误时退避重试，非幂等请求直接返回避免重复提交
+                    if is_idempotent
+                        && is_network_error(&e)
+                        && network_retry_count < self.max_retries
+                    {
+                        let delay = retry_delay(network_retry_count);
+                        network_retry_count += 1;
+                        warn!(
+                            "{}; 方法: {}; 网络错误，{:?} 后第 {} 次重试: {}",
+                            &url, method, delay, network_retry_count, e
+                        );
+                        tokio::time::sleep(delay).await;
+                        continue;
+                    }
+                    return Err(anyhow::anyhow!("network_error: {}", e));
+                }
+            };
 
             match result.code {
                 Some(406) => {
@@ -134,7 +378,7 @@ impl ImRequestClient {
                     if retry_count >= MAX_RETRY_COUNT {
                         return Err(anyhow::anyhow!("token过期，刷新token失败"));
                     }
-                    self.start_refresh_token().await?;
+                    self.refresh_token_once(sent_token.as_deref()).await?;
                     retry_count += 1;
                     continue;
                 }
@@ -174,42 +418,90 @@ impl ImRequestClient {
     /// 与 `request` 方法的区别：
     /// 1. 添加 `Accept: text/event-stream` 请求头
     /// 2. 返回 `reqwest::Response` 而不是解析 JSON
-    /// 3. 不支持自动 token 刷新重试（因为流式响应无法中断重试）
+    /// 3. 响应开始后无法重试，只在建立连接时遇到 token 过期刷新后重试一次
     ///
     /// # 参数
     /// - `method`: HTTP 方法
     /// - `path`: API 路径
     /// - `body`: 请求体（可选）
     /// - `params`: 查询参数（可选）
+    /// - `last_event_id`: 断线重连时携带的 `Last-Event-ID`（可选）
     ///
     /// # 返回
     /// - `Ok(Response)`: 成功返回响应对象，可用于读取流式数据
     /// - `Err`: 请求失败或状态码非 2xx
     pub async fn request_stream<B: serde::Serialize, C: serde::Serialize>(
-        &mut self,
+        &self,
         method: http::Method,
         path: &str,
         body: Option<B>,
         params: Option<C>,
+        last_event_id: Option<&str>,
     ) -> Result<reqwest::Response, anyhow::Error> {
-        // 添加流式请求头
-        let extra_headers = Some(vec![("Accept", "text/event-stream")]);
+        let url = self.url(path);
+        let mut refreshed = false;
 
-        // 使用 build_request 构建请求
-        let request_builder =
-            self.build_request(method.clone(), path, &body, &params, extra_headers);
+        self.refresh_if_expiring().await;
 
-        // 发送请求
-        let response = request_builder.send().await?;
+        loop {
+            let sent_token = self.token();
+            // 添加流式请求头
+            let mut extra_headers = vec![("Accept", "text/event-stream")];
+            if let Some(id) = last_event_id {
+                extra_headers.push(("Last-Event-ID", id));
+            }
 
-        // 检查响应状态（但不解析 JSON）
-        let status = response.status();
-        if !status.is_success() {
-            let url = format!("{}/{}", self.base_url, path);
-            error!("流式请求失败，URL: {}, 状态码: {}", url, status);
+            // 使用 build_request 构建请求
+            let request_builder =
+                self.build_request(method.clone(), path, &body, &params, Some(extra_headers));
+
+            // 发送请求
+            let response = request_builder.send().await?;
+
+            // 网关以 HTTP 状态码返回错误，业务层以 JSON 响应中的 code 返回错误
+            let status = response.status();
+            let code = if !status.is_success() {
+                error!("流式请求失败，URL: {}, 状态码: {}", url, status);
+                status.as_u16() as i32
+            } else if is_json_response(&response) {
+                let result: ApiResult<serde_json::Value> = response.json().await?;
+                match result.code {
+                    Some(200) | None => {
+                        return Err(anyhow::anyhow!(
+                            "{}",
+                            result
+                                .msg
+                                .unwrap_or_else(|| "流式请求返回了非流式响应".to_string())
+                        ));
+                    }
+                    Some(code) => {
+                        error!(
+                            "流式请求失败，URL: {}, 失败信息: {}",
+                            url,
+                            result.msg.clone().unwrap_or_default()
+                        );
+                        if code != 406 && code != 401 {
+                            return Err(anyhow::anyhow!("{}", result.msg.unwrap_or_default()));
+                        }
+                        code
+                    }
+                }
+            } else {
+                return Ok(response);
+            };
 
             // 根据状态码返回不同的错误信息
-            match status.as_u16() {
+            match code {
+                406 if !refreshed => {
+                    warn!("Token expired in stream request, refreshing and retrying once");
+                    self.refresh_token_once(sent_token.as_deref())
+                        .await
+                        .map_err(|e| {
+                            error!("流式请求刷新token失败: {}", e);
+                            anyhow::anyhow!("token过期，请刷新后重试")
+                        })?;
+                    refreshed = true;
+                }
                 406 => {
                     error!("Token expired in stream request");
                     return Err(anyhow::anyhow!("token过期，请刷新后重试"));
@@ -223,14 +515,19 @@ impl ImRequestClient {
                 }
             }
         }
+    }
 
-        Ok(response)
+    /// 使用 refresh_token 换取新的 token
+    pub async fn start_refresh_token(&self) -> Result<(), anyhow::Error> {
+        let _guard = self.refresh_lock.lock().await;
+        self.refresh().await
     }
 
-    pub async fn start_refresh_token(&mut self) -> Result<(), anyhow::Error> {
-        let url = format!("{}/{}", self.base_url, ImUrl::RefreshToken.get_url().1);
+    /// 调用方需持有 `refresh_lock`
+    async fn refresh(&self) -> Result<(), anyhow::Error> {
+        let url = self.url(ImUrl::RefreshToken.get_url().1);
 
-        let refresh_token = match self.refresh_token.clone() {
+        let refresh_token = match self.refresh_token() {
             Some(token) if !token.is_empty() => token,
             _ => return Err(anyhow::anyhow!("请重新登录")),
         };
@@ -240,7 +537,10 @@ impl ImRequestClient {
           "refreshToken": refresh_token
         });
 
-        let request_builder = self.client.request(http::Method::POST, &url);
+        let request_builder = self
+            .client
+            .request(http::Method::POST, &url)
+            .timeout(TimeoutConfig::TOKEN_REFRESH_TIMEOUT);
         let response = request_builder
             .json(&body)
             .send()
@@ -265,19 +565,23 @@ impl ImRequestClient {
         };
 
         let token = match data.get("token").and_then(|value| value.as_str()) {
-            Some(value) => value,
+            Some(value) => value.to_owned(),
             None => {
                 error!("刷新token失败: 响应缺少 token 字段");
                 return Err(anyhow::anyhow!("请重新登录"));
             }
         };
-        self.token = Some(token.to_owned());
 
         let refresh_token = match data.get("refreshToken").and_then(|value| value.as_str()) {
             Some(value) if !value.is_empty() => value.to_owned(),
             _ => old_refresh_token,
         };
-        self.refresh_token = Some(refresh_token);
+        let expire = data.get("expire").and_then(expire_as_string);
+        self.store_issued_tokens(token, refresh_token, expire.as_deref());
+
+        if let Err(e) = self.persist_tokens() {
+            error!("刷新token后保存凭证失败: {}", e);
+        }
 
         Ok(())
     }
@@ -287,26 +591,135 @@ impl ImRequestClient {
         B: serde::Serialize,
         C: serde::Serialize,
     >(
-        &mut self,
+        &self,
         url: ImUrl,
         body: Option<B>,
         params: Option<C>,
     ) -> Result<Option<T>, anyhow::Error> {
-        let (method, path) = url.get_url();
-        let result: ApiResult<T> = self.request(method, path, body, params).await?;
+        let result: ApiResult<T> = self.request(&url, body, params).await?;
         Ok(result.data)
     }
+
+    /// 调用强类型接口，请求参数和响应类型由 [`ImApi`] 决定
+    pub async fn call<A: ImApi>(&self, req: &A) -> Result<Option<A::Response>, anyhow::Error> {
+        if A::QUERY {
+            self.im_request(A::URL, None::<serde_json::Value>, Some(req))
+                .await
+        } else {
+            self.im_request(A::URL, Some(req), None::<serde_json::Value>)
+                .await
+        }
+    }
+}
+
+/// 连接失败、超时或读取响应体中断，响应内容无法解析不属于网络错误
+fn is_network_error(e: &reqwest::Error) -> bool {
+    !e.is_decode() && (e.is_connect() || e.is_timeout() || e.is_request() || e.is_body())
+}
+
+/// 指数退避加随机抖动：第 n 次重试等待 min(base * 2^n, max) 的一半到全部之间的随机时长
+fn retry_delay(attempt: u32) -> Duration {
+    const BASE_DELAY_MS: u64 = 300;
+    const MAX_DELAY_MS: u64 = 5_000;
+    let ceiling = BASE_DELAY_MS
+        .saturating_mul(1 << attempt.min(10))
+        .min(MAX_DELAY_MS);
+    Duration::from_millis(rand::thread_rng().gen_range(ceiling / 2..=ceiling))
+}
+
+fn is_json_response(response: &reqwest::Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"))
+}
+
+/// 刷新接口的 expire 可能以数字或字符串下发
+fn expire_as_string(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// 解析接口返回的过期时间，返回毫秒时间戳
+///
+/// 支持毫秒/秒时间戳、剩余有效秒数、`yyyy-MM-dd HH:mm:ss`（本地时间）和 RFC 3339
+fn parse_expire(expire: &str, now_ms: i64) -> Option<i64> {
+    let expire = expire.trim();
+    if let Ok(value) = expire.parse::<i64>() {
+        return match value {
+            value if value <= 0 => None,
+            value if value >= 100_000_000_000 => Some(value),
+            value if value >= 1_000_000_000 => Some(value * 1000),
+            value => Some(now_ms + value * 1000),
+        };
+    }
+    if let Ok(datetime) = chrono::NaiveDateTime::parse_from_str(expire, "%Y-%m-%d %H:%M:%S") {
+        return datetime
+            .and_local_timezone(chrono::Local)
+            .earliest()
+            .map(|datetime| datetime.timestamp_millis());
+    }
+    chrono::DateTime::parse_from_rfc3339(expire)
+        .ok()
+        .map(|datetime| datetime.timestamp_millis())
+}
+
+/// 解析 JWT 载荷中的 `exp`，不校验签名，仅用于判断何时刷新
+fn decode_jwt_expiry(token: &str) -> Option<i64> {
+    let mut parts = token.split('.');
+    let (Some(_), Some(payload), Some(_), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let payload = BASE64_URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    claims.get("exp")?.as_i64().map(|exp| exp * 1000)
+}
+
+/// 启动 token 定时刷新任务，在 token 过期前主动刷新，避免请求先失败再重试
+pub fn spawn_token_refresher(client: ImRequestClient) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(client.next_refresh_in()).await;
+
+            if !client.token_expiring() {
+                continue;
+            }
+            let stale_token = client.token();
+            match client.refresh_token_once(stale_token.as_deref()).await {
+                Ok(true) => info!("[TOKEN_REFRESH] token refreshed before expiry"),
+                Ok(false) => {}
+                Err(e) => {
+                    warn!(
+                        "[TOKEN_REFRESH] failed to refresh token before expiry: {}",
+                        e
+                    );
+                    tokio::time::sleep(TimeoutConfig::TOKEN_CHECK_INTERVAL).await;
+                }
+            }
+        }
+    });
 }
 
 impl ImRequest for ImRequestClient {
-    async fn login(&mut self, login_req: LoginReq) -> Result<Option<LoginResp>, anyhow::Error> {
+    async fn login(&self, login_req: LoginReq) -> Result<Option<LoginResp>, anyhow::Error> {
         let result: Option<LoginResp> = self
             .im_request(ImUrl::Login, Some(login_req), None::<serde_json::Value>)
             .await?;
 
         if let Some(data) = result.clone() {
-            self.token = Some(data.token.clone());
-            self.refresh_token = Some(data.refresh_token.clone());
+            self.store_issued_tokens(data.token, data.refresh_token, Some(data.expire.as_str()));
+            self.bind_session(&data.uid);
+            if let Err(e) = self.persist_tokens() {
+                warn!("登录后保存凭证失败: {}", e);
+            }
         }
 
         Ok(result)
@@ -854,6 +1267,135 @@ impl ImUrl {
         }
     }
 
+    /// 网络错误时是否可以安全重试，未列出的接口一律不重试
+    pub fn is_idempotent(&self) -> bool {
+        match self {
+            // 查询接口
+            ImUrl::GetQiniuToken
+            | ImUrl::InitConfig
+            | ImUrl::StorageProvider
+            | ImUrl::MapCoordTranslate
+            | ImUrl::MapReverseGeocode
+            | ImUrl::MapStatic
+            | ImUrl::GetAssistantModelList
+            | ImUrl::GetCaptcha
+            | ImUrl::Announcement
+            | ImUrl::GetAnnouncementList
+            | ImUrl::SearchGroup
+            | ImUrl::GroupList
+            | ImUrl::GroupListMember
+            | ImUrl::GroupDetail
+            | ImUrl::GroupInfo
+            | ImUrl::InviteList
+            | ImUrl::SessionDetailWithFriends
+            | ImUrl::SessionDetail
+            | ImUrl::GetMsgReadCount
+            | ImUrl::GetMsgReadList
+            | ImUrl::NoticeUnReadCount
+            | ImUrl::RequestNoticePage
+            | ImUrl::GetFriendPage
+            | ImUrl::GetContactList
+            | ImUrl::SearchFriend
+            | ImUrl::GetAllUserState
+            | ImUrl::GetEmoji
+            | ImUrl::GetUserInfoDetail
+            | ImUrl::GetBadgeList
+            | ImUrl::GenerateQRCode
+            | ImUrl::CheckQRStatus
+            | ImUrl::GetMsgPage
+            | ImUrl::GetMemberStatistic
+            | ImUrl::GetFeedPermission
+            | ImUrl::FeedDetail
+            | ImUrl::FeedLikeList
+            | ImUrl::FeedLikeCount
+            | ImUrl::FeedLikeHasLiked
+            | ImUrl::FeedCommentAll
+            | ImUrl::FeedCommentCount
+            | ImUrl::MessageListByConversationId
+            | ImUrl::MessagePage
+            | ImUrl::ConversationMyList
+            | ImUrl::ConversationGetMy
+            | ImUrl::ConversationPage
+            | ImUrl::ModelGet
+            | ImUrl::ModelRemainingUsage
+            | ImUrl::ModelPage
+            | ImUrl::ModelSimpleList
+            | ImUrl::ChatRoleMyPage
+            | ImUrl::ChatRoleGetMy
+            | ImUrl::ChatRoleCategoryList
+            | ImUrl::ChatRoleGet
+            | ImUrl::ChatRolePage
+            | ImUrl::ApiKeyGet
+            | ImUrl::ApiKeyPage
+            | ImUrl::ApiKeySimpleList
+            | ImUrl::ApiKeyBalance
+            | ImUrl::PlatformList
+            | ImUrl::ToolGet
+            | ImUrl::ToolPage
+            | ImUrl::ToolSimpleList
+            | ImUrl::ImageMyPage
+            | ImUrl::ImagePublicPage
+            | ImUrl::ImageGetMy
+            | ImUrl::ImageMyListByIds
+            | ImUrl::ImagePage
+            | ImUrl::VideoMyPage
+            | ImUrl::VideoGet
+            | ImUrl::VideoMyListByIds
+            | ImUrl::AudioMyPage
+            | ImUrl::AudioGetMy
+            | ImUrl::AudioMyListByIds
+            | ImUrl::AudioVoices
+            | ImUrl::KnowledgePage
+            | ImUrl::KnowledgeGet
+            | ImUrl::KnowledgeSimpleList
+            | ImUrl::KnowledgeDocumentPage
+            | ImUrl::KnowledgeDocumentGet
+            | ImUrl::KnowledgeSegmentGet
+            | ImUrl::KnowledgeSegmentPage
+            | ImUrl::KnowledgeSegmentSplit
+            | ImUrl::KnowledgeSegmentGetProcessList
+            | ImUrl::KnowledgeSegmentSearch
+            | ImUrl::MindMapPage
+            | ImUrl::MusicMyPage
+            | ImUrl::MusicGetMy
+            | ImUrl::MusicPage
+            | ImUrl::WorkflowGet
+            | ImUrl::WorkflowPage
+            | ImUrl::WritePage => true,
+
+            // 以 POST 提交的查询接口
+            ImUrl::CheckToken
+            | ImUrl::GetMsgList
+            | ImUrl::GetUserByIds
+            | ImUrl::GetBadgesBatch
+            | ImUrl::FeedList
+            | ImUrl::FeedCommentList => true,
+
+            // 覆盖式更新，重复提交结果相同
+            ImUrl::MarkMsgRead
+            | ImUrl::SetUserBadge
+            | ImUrl::ModifyUserInfo
+            | ImUrl::UpdateMyRoomInfo
+            | ImUrl::UpdateRoomInfo
+            | ImUrl::ConversationUpdateMy
+            | ImUrl::ModelUpdate
+            | ImUrl::ChatRoleUpdateMy
+            | ImUrl::ChatRoleUpdate
+            | ImUrl::ApiKeyUpdate
+            | ImUrl::ToolUpdate
+            | ImUrl::ImageUpdate
+            | ImUrl::KnowledgeUpdate
+            | ImUrl::KnowledgeDocumentUpdate
+            | ImUrl::KnowledgeDocumentUpdateStatus
+            | ImUrl::KnowledgeSegmentUpdate
+            | ImUrl::KnowledgeSegmentUpdateStatus
+            | ImUrl::MusicUpdate
+            | ImUrl::WorkflowUpdate => true,
+
+            _ => false,
+        }
+    }
+
     fn from_str(s: &str) -> Result<Self, anyhow::Error> {
         match s {
             // Token 相关
@@ -1147,5 +1689,143 @@ impl FromStr for ImUrl {
 }
 
 pub trait ImRequest {
-    async fn login(&mut self, login_req: LoginReq) -> Result<Option<LoginResp>, anyhow::Error>;
+    async fn login(&self, login_req: LoginReq) -> Result<Option<LoginResp>, anyhow::Error>;
+}
+
+/// 与 [`ImUrl`] 绑定的强类型接口
+///
+/// 实现该 trait 的类型即为接口的请求参数，通过 [`ImRequestClient::call`] 调用
+pub trait ImApi: serde::Serialize + Sync {
+    /// 接口地址
+    const URL: ImUrl;
+    /// 为 true 时参数作为查询字符串发送，否则作为 JSON 请求体发送
+    const QUERY: bool = false;
+    /// 响应中 `data` 字段的类型，不关心返回值时使用 [`serde::de::IgnoredAny`]
+    type Response: serde::de::DeserializeOwned;
+}
+
+/// 为请求类型实现 [`ImApi`]
+///
+/// ```ignore
+/// im_api!(GroupListMemberReq => GroupListMember, query, Vec<RoomMemberResponse>);
+/// im_api!(MyRoomInfoReq => UpdateMyRoomInfo, body, IgnoredAny);
+/// ```
+macro_rules! im_api {
+    ($req:ty => $url:ident, query, $resp:ty) => {
+        impl $crate::im_request_client::ImApi for $req {
+            const URL: $crate::im_request_client::ImUrl = $crate::im_request_client::ImUrl::$url;
+            const QUERY: bool = true;
+            type Response = $resp;
+        }
+    };
+    ($req:ty => $url:ident, body, $resp:ty) => {
+        impl $crate::im_request_client::ImApi for $req {
+            const URL: $crate::im_request_client::ImUrl = $crate::im_request_client::ImUrl::$url;
+            type Response = $resp;
+        }
+    };
+}
+pub(crate) use im_api;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_stays_within_backoff_window() {
+        for (attempt, ceiling) in [(0, 300), (1, 600), (3, 2_400), (4, 4_800)] {
+            for _ in 0..50 {
+                let delay = retry_delay(attempt).as_millis() as u64;
+                assert!(
+                    (ceiling / 2..=ceiling).contains(&delay),
+                    "{attempt}: {delay}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn retry_delay_is_capped() {
+        for attempt in [5, 10, 11, 64, u32::MAX] {
+            let delay = retry_delay(attempt).as_millis() as u64;
+            assert!((2_500..=5_000).contains(&delay), "{attempt}: {delay}");
+        }
+    }
+
+    #[test]
+    fn only_idempotent_endpoints_are_retried() {
+        assert!(ImUrl::GetMsgPage.is_idempotent());
+        assert!(ImUrl::GetMsgList.is_idempotent());
+        assert!(ImUrl::MarkMsgRead.is_idempotent());
+        assert!(!ImUrl::SendMsg.is_idempotent());
+        assert!(!ImUrl::RecallMsg.is_idempotent());
+        assert!(!ImUrl::ExitGroup.is_idempotent());
+        assert!(!ImUrl::DeleteFriend.is_idempotent());
+    }
+
+    fn jwt_with_payload(payload: &str) -> String {
+        format!(
+            "eyJhbGciOiJIUzI1NiJ9.{}.c2ln",
+            BASE64_URL_SAFE_NO_PAD.encode(payload)
+        )
+    }
+
+    #[test]
+    fn parse_expire_accepts_timestamps_and_remaining_seconds() {
+        let now = 1_700_000_000_000;
+        assert_eq!(parse_expire("1700003600000", now), Some(1_700_003_600_000));
+        assert_eq!(parse_expire("1700003600", now), Some(1_700_003_600_000));
+        assert_eq!(parse_expire(" 7200 ", now), Some(now + 7_200_000));
+        assert_eq!(parse_expire("0", now), None);
+        assert_eq!(parse_expire("-1", now), None);
+    }
+
+    #[test]
+    fn parse_expire_accepts_dates() {
+        let local = chrono::NaiveDate::from_ymd_opt(2024, 5, 1)
+            .unwrap()
+            .and_hms_opt(12, 30, 0)
+            .unwrap()
+            .and_local_timezone(chrono::Local)
+            .earliest()
+            .unwrap()
+            .timestamp_millis();
+        assert_eq!(parse_expire("2024-05-01 12:30:00", 0), Some(local));
+        assert_eq!(
+            parse_expire("2024-05-01T12:30:00+08:00", 0),
+            Some(1_714_537_800_000)
+        );
+        assert_eq!(
+            parse_expire("2024-05-01T04:30:00Z", 0),
+            Some(1_714_537_800_000)
+        );
+        assert_eq!(parse_expire("tomorrow", 0), None);
+        assert_eq!(parse_expire("", 0), None);
+    }
+
+    #[test]
+    fn decode_jwt_expiry_reads_exp_claim() {
+        let token = jwt_with_payload(r#"{"sub":"1","exp":1700003600}"#);
+        assert_eq!(decode_jwt_expiry(&token), Some(1_700_003_600_000));
+    }
+
+    #[test]
+    fn decode_jwt_expiry_without_exp_is_none() {
+        let token = jwt_with_payload(r#"{"sub":"1"}"#);
+        assert_eq!(decode_jwt_expiry(&token), None);
+        let token = jwt_with_payload(r#"{"exp":"soon"}"#);
+        assert_eq!(decode_jwt_expiry(&token), None);
+    }
+
+    #[test]
+    fn decode_jwt_expiry_rejects_malformed_tokens() {
+        assert_eq!(decode_jwt_expiry("eyJhbGciOiJIUzI1NiJ9.@@@.c2ln"), None);
+        assert_eq!(
+            decode_jwt_expiry("eyJhbGciOiJIUzI1NiJ9.bm90IGpzb24.c2ln"),
+            None
+        );
+        assert_eq!(decode_jwt_expiry("only.two"), None);
+        assert_eq!(decode_jwt_expiry("a.b.c.d"), None);
+        assert_eq!(decode_jwt_expiry("opaque-token"), None);
+    }
 }
@@ -49,13 +49,17 @@ use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use tauri_plugin_fs::FsExt;
+pub mod ai;
 pub mod command;
 pub mod common;
 pub mod configuration;
+pub mod download;
 pub mod error;
 mod im_request_client;
 pub mod pojo;
 pub mod repository;
+pub mod secret_store;
+pub mod storage;
 pub mod timeout_config;
 pub mod utils;
 mod vo;
@@ -73,8 +77,8 @@ use crate::command::room_member_command::update_my_room_info;
 use crate::command::setting_command::get_settings;
 use crate::command::setting_command::update_settings;
 use crate::command::user_command::remove_tokens;
-use crate::configuration::Settings;
 use crate::configuration::get_configuration;
+use crate::configuration::{HttpSettings, Settings};
 use crate::error::CommonError;
 use sea_orm::DatabaseConnection;
 use serde::Deserialize;
@@ -96,22 +100,23 @@ use mobiles::splash;
 pub struct AppData {
     db_conn: Arc<RwLock<DatabaseConnection>>,
     user_info: Arc<Mutex<UserInfo>>,
-    pub rc: Arc<Mutex<im_request_client::ImRequestClient>>,
+    pub rc: im_request_client::ImRequestClient,
     pub config: Arc<Mutex<Settings>>,
     frontend_task: Mutex<bool>,
     backend_task: Mutex<bool>,
     /// 限制对 SQLite 的写入并发，避免 database is locked
     pub write_lock: Arc<Mutex<()>>,
-    /// 记录正在进行的 AI 流式任务
-    pub stream_tasks: Arc<Mutex<std::collections::HashMap<String, tokio::task::JoinHandle<()>>>>,
+    /// 管理正在进行的 AI 流式任务
+    pub ai_streams: Arc<ai::AiStreamManager>,
 }
 
 pub(crate) static APP_STATE_READY: AtomicBool = AtomicBool::new(false);
 
 use crate::command::chat_history_command::query_chat_history;
+use crate::command::chat_history_command::search_all_messages;
 use crate::command::contact_command::hide_contact_command;
 use crate::command::contact_command::list_contacts_command;
-use crate::command::database_command::switch_user_database;
+use crate::command::database_command::{rekey_user_database, switch_user_database};
 use crate::command::file_manager_command::debug_message_stats;
 use crate::command::file_manager_command::get_navigation_items;
 use crate::command::file_manager_command::query_files;
@@ -123,6 +128,7 @@ use crate::command::message_command::send_msg;
 use crate::command::message_command::sync_messages;
 use crate::command::message_command::update_message_recall_status;
 use crate::command::message_mark_command::save_message_mark;
+use crate::command::message_retry_command::retry_failed_messages;
 use crate::command::oauth_command::OauthServerState;
 use crate::command::oauth_command::start_oauth_server;
 
@@ -187,7 +193,7 @@ async fn initialize_app_data(
     (
         Arc<RwLock<DatabaseConnection>>,
         Arc<Mutex<UserInfo>>,
-        Arc<Mutex<im_request_client::ImRequestClient>>,
+        im_request_client::ImRequestClient,
         Arc<Mutex<Settings>>,
     ),
     CommonError,
@@ -208,7 +214,7 @@ async fn initialize_app_data(
             .lock()
             .await
             .database
-            .connection_string(&app_handle, None)
+            .connect_or_fallback(&app_handle, None)
             .await?,
     ));
 
@@ -222,11 +228,22 @@ async fn initialize_app_data(
         }
     }
 
-    let rc: im_request_client::ImRequestClient = im_request_client::ImRequestClient::new(
-        configuration.lock().await.backend.base_url.clone(),
-    )
+    let mut rc: im_request_client::ImRequestClient = {
+        let configuration = configuration.lock().await;
+        im_request_client::ImRequestClient::new(
+            configuration.backend.base_url.clone(),
+            &configuration.http.clone().unwrap_or_default(),
+        )
+    }
     .map_err(|e| anyhow::anyhow!("Failed to create request client: {}", e))?;
 
+    // 登录凭证保存在应用数据目录下的加密文件中，钥匙串不可用时只保存在内存中
+    let secret_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow::anyhow!("Failed to get app_data_dir: {}", e))?;
+    rc.set_secret_store(secret_store::open_default(&secret_dir));
+
     // 创建用户信息
     let user_info = UserInfo {
         token: Default::default(),
@@ -235,7 +252,7 @@ async fn initialize_app_data(
     };
     let user_info = Arc::new(Mutex::new(user_info));
 
-    Ok((db, user_info, Arc::new(Mutex::new(rc)), configuration))
+    Ok((db, user_info, rc, configuration))
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -246,8 +263,14 @@ pub struct UserInfo {
     pub uid: String,
 }
 
-pub async fn build_request_client() -> Result<reqwest::Client, CommonError> {
-    let client = reqwest::Client::builder()
+pub async fn build_request_client(
+    http: Option<&HttpSettings>,
+) -> Result<reqwest::Client, CommonError> {
+    let default_settings = HttpSettings::default();
+    let http = http.unwrap_or(&default_settings);
+    let client = http
+        .client_builder()?
+        .timeout(http.request_timeout())
         .build()
         .map_err(|e| anyhow::anyhow!("Reqwest client error: {}", e))?;
     Ok(client)
@@ -329,6 +352,21 @@ pub async fn handle_logout_windows(app_handle: &tauri::AppHandle) {
         }
     }
 
+    // 清除内存和凭证存储中的登录凭证
+    if let Some(state) = app_handle.try_state::<AppData>() {
+        // 停止所有 AI 流式回答，已收到的内容由各任务保存
+        let stopped = state.ai_streams.cancel_all(ai::StopReason::Logout);
+        if stopped > 0 {
+            tracing::info!("[LOGOUT] Stopping {} AI streams", stopped);
+        }
+        if let Err(e) = state.rc.clear_session() {
+            tracing::warn!("[LOGOUT] Failed to wipe stored tokens: {}", e);
+        }
+        let mut user_info = state.user_info.lock().await;
+        user_info.token.clear();
+        user_info.refresh_token.clear();
+    }
+
     tracing::info!(
         "[LOGOUT] Logout completed - windows closed and capture/checkupdate windows preserved"
     );
@@ -395,6 +433,7 @@ fn common_setup(app_handle: AppHandle) -> Result<(), Box<dyn std::error::Error>>
     match tauri::async_runtime::block_on(initialize_app_data(app_handle.clone())) {
         Ok((db, user_info, rc, settings)) => {
             // 使用 manage 方法在运行时添加状态
+            im_request_client::spawn_token_refresher(rc.clone());
             app_handle.manage(AppData {
                 db_conn: db.clone(),
                 user_info: user_info.clone(),
@@ -404,7 +443,7 @@ fn common_setup(app_handle: AppHandle) -> Result<(), Box<dyn std::error::Error>>
                 // 后端任务默认完成
                 backend_task: Mutex::new(true),
                 write_lock: Arc::new(Mutex::new(())),
-                stream_tasks: Arc::new(Mutex::new(std::collections::HashMap::new())),
+                ai_streams: Arc::new(ai::AiStreamManager::new()),
             });
             app_handle.manage(OauthServerState::default());
             APP_STATE_READY.store(true, Ordering::SeqCst);
@@ -428,11 +467,29 @@ fn get_invoke_handlers() -> impl Fn(tauri::ipc::Invoke<tauri::Wry>) -> bool + Se
 {
     use crate::command::ai_command::ai_message_cancel_stream;
     use crate::command::ai_command::ai_message_send_stream;
+    use crate::command::ai_command::list_active_streams;
+    use crate::command::ai_command::list_local_ai_conversations;
+    use crate::command::ai_command::list_local_ai_messages;
+    use crate::command::ai_command::save_ai_api_key;
+    use crate::command::ai_command::search_local_ai_messages;
+    use crate::command::download_command::clear_media_cache;
+    use crate::command::download_command::download_media;
+    use crate::command::download_command::get_media_cache_usage;
+    use crate::command::image_command::process_outgoing_image;
+    use crate::command::knowledge_command::add_knowledge_source;
+    use crate::command::knowledge_command::list_knowledge_sources;
+    use crate::command::knowledge_command::remove_knowledge_source;
+    use crate::command::knowledge_command::search_knowledge;
     use crate::command::markdown_command::get_readme_html;
     use crate::command::markdown_command::parse_markdown;
     #[cfg(mobile)]
     use crate::command::set_complete;
+    use crate::command::upload_command::cancel_upload;
+    use crate::command::upload_command::list_pending_uploads;
+    use crate::command::upload_command::pause_upload;
     use crate::command::upload_command::qiniu_upload_resumable;
+    use crate::command::upload_command::resume_upload;
+    use crate::command::upload_command::storage_upload;
     use crate::command::upload_command::upload_file_put;
     use crate::command::user_command::get_user_tokens;
     use crate::command::user_command::save_user_info;
@@ -505,9 +562,11 @@ fn get_invoke_handlers() -> impl Fn(tauri::ipc::Invoke<tauri::Wry>) -> bool + Se
         delete_message,
         delete_room_messages,
         update_message_recall_status,
+        retry_failed_messages,
         save_message_mark,
         // 聊天历史相关命令
         query_chat_history,
+        search_all_messages,
         // 文件管理相关命令
         query_files,
         get_navigation_items,
@@ -530,6 +589,16 @@ fn get_invoke_handlers() -> impl Fn(tauri::ipc::Invoke<tauri::Wry>) -> bool + Se
         // AI 相关命令
         ai_message_send_stream,
         ai_message_cancel_stream,
+        list_active_streams,
+        list_local_ai_conversations,
+        list_local_ai_messages,
+        search_local_ai_messages,
+        save_ai_api_key,
+        // 本地知识库
+        add_knowledge_source,
+        remove_knowledge_source,
+        list_knowledge_sources,
+        search_knowledge,
         // OAuth
         start_oauth_server,
         // Markdown 相关命令
@@ -537,6 +606,15 @@ fn get_invoke_handlers() -> impl Fn(tauri::ipc::Invoke<tauri::Wry>) -> bool + Se
         get_readme_html,
         upload_file_put,
         qiniu_upload_resumable,
+        resume_upload,
+        storage_upload,
+        list_pending_uploads,
+        pause_upload,
+        cancel_upload,
+        download_media,
+        get_media_cache_usage,
+        clear_media_cache,
+        process_outgoing_image,
         #[cfg(mobile)]
         set_complete,
         #[cfg(mobile)]
@@ -551,5 +629,6 @@ fn get_invoke_handlers() -> impl Fn(tauri::ipc::Invoke<tauri::Wry>) -> bool + Se
         set_webview_keyboard_adjustment,
         is_app_state_ready,
         switch_user_database,
+        rekey_user_database,
     ]
 }
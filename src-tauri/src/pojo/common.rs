@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -60,3 +60,31 @@ pub struct LoginResp {
     pub refresh_token: String,
     pub client: String,
 }
+
+/// 兼容服务端以数字或字符串下发的ID
+pub fn deserialize_id<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Str(String),
+        Num(i64),
+    }
+
+    Ok(match Id::deserialize(deserializer)? {
+        Id::Str(s) => s,
+        Id::Num(n) => n.to_string(),
+    })
+}
+
+/// 服务端游标分页响应，最后一页的 cursor 可能为空
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CursorPage<T> {
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub is_last: bool,
+    pub list: Option<Vec<T>>,
+}
@@ -0,0 +1,474 @@
+use crate::error::CommonError;
+use crate::repository::im_message_repository::{
+    build_snippet, escape_like_pattern, split_search_terms,
+};
+use chrono::Utc;
+use entity::{im_ai_conversation, im_ai_message};
+use sea_orm::sea_query::{Expr, OnConflict, Value};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, FromQueryResult,
+    IntoActiveModel, QueryFilter, QueryOrder, Statement, TransactionTrait,
+};
+
+pub const STATUS_STREAMING: &str = "streaming";
+pub const STATUS_DONE: &str = "done";
+pub const STATUS_CANCELLED: &str = "cancelled";
+pub const STATUS_ERROR: &str = "error";
+/// 应用退出时仍在接收的回答，之后不会再更新
+pub const STATUS_INTERRUPTED: &str = "interrupted";
+
+/// 本地与服务端记录的时间误差，服务端消息的创建时间略晚于本地写入时间
+const SERVER_TIME_TOLERANCE_MS: i64 = 60_000;
+
+/// AI 消息搜索命中
+#[derive(Clone)]
+pub struct AiMessageSearchHit {
+    pub message: im_ai_message::Model,
+    pub conversation_title: Option<String>,
+    pub snippet: Option<String>,
+}
+
+/// 保存服务端返回的会话，已存在的会话覆盖标题、置顶等信息
+pub async fn save_conversations(
+    db: &DatabaseConnection,
+    conversations: Vec<im_ai_conversation::Model>,
+) -> Result<(), CommonError> {
+    if conversations.is_empty() {
+        return Ok(());
+    }
+    im_ai_conversation::Entity::insert_many(
+        conversations
+            .into_iter()
+            .map(IntoActiveModel::into_active_model),
+    )
+    .on_conflict(
+        OnConflict::column(im_ai_conversation::Column::Id)
+            .update_columns([
+                im_ai_conversation::Column::Title,
+                im_ai_conversation::Column::RoleId,
+                im_ai_conversation::Column::ModelId,
+                im_ai_conversation::Column::Pinned,
+                im_ai_conversation::Column::UpdateTime,
+            ])
+            .to_owned(),
+    )
+    .exec(db)
+    .await
+    .map_err(|e| anyhow::anyhow!("保存AI会话失败: {}", e))?;
+    Ok(())
+}
+
+/// 本地发起对话时确保会话存在并刷新最后对话时间，新建时以提问内容作为标题
+pub async fn touch_conversation(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    conversation_id: &str,
+    title: &str,
+) -> Result<(), CommonError> {
+    let now = Utc::now().timestamp_millis();
+    let stmt = Statement::from_sql_and_values(
+        db.get_database_backend(),
+        "INSERT INTO im_ai_conversation (id, login_uid, title, pinned, create_time, update_time)
+         VALUES (?, ?, ?, 0, ?, ?)
+         ON CONFLICT(id) DO UPDATE SET update_time = excluded.update_time",
+        [
+            conversation_id.into(),
+            login_uid.into(),
+            title.into(),
+            now.into(),
+            now.into(),
+        ],
+    );
+    db.execute(stmt)
+        .await
+        .map_err(|e| anyhow::anyhow!("更新AI会话失败: {}", e))?;
+    Ok(())
+}
+
+/// 获取本地缓存的会话列表，置顶优先，其余按最后对话时间倒序
+pub async fn list_conversations(
+    db: &DatabaseConnection,
+    login_uid: &str,
+) -> Result<Vec<im_ai_conversation::Model>, CommonError> {
+    let list = im_ai_conversation::Entity::find()
+        .filter(im_ai_conversation::Column::LoginUid.eq(login_uid))
+        .order_by_desc(im_ai_conversation::Column::Pinned)
+        .order_by_desc(im_ai_conversation::Column::UpdateTime)
+        .all(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("查询AI会话失败: {}", e))?;
+    Ok(list)
+}
+
+/// 删除会话及其全部消息
+pub async fn delete_conversations(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    conversation_ids: &[String],
+) -> Result<(), CommonError> {
+    if conversation_ids.is_empty() {
+        return Ok(());
+    }
+    let txn = db.begin().await?;
+    im_ai_message::Entity::delete_many()
+        .filter(im_ai_message::Column::LoginUid.eq(login_uid))
+        .filter(im_ai_message::Column::ConversationId.is_in(conversation_ids.iter().cloned()))
+        .exec(&txn)
+        .await?;
+    im_ai_conversation::Entity::delete_many()
+        .filter(im_ai_conversation::Column::LoginUid.eq(login_uid))
+        .filter(im_ai_conversation::Column::Id.is_in(conversation_ids.iter().cloned()))
+        .exec(&txn)
+        .await?;
+    txn.commit()
+        .await
+        .map_err(|e| anyhow::anyhow!("删除AI会话失败: {}", e))?;
+    Ok(())
+}
+
+/// 删除所有未置顶的会话及其消息
+pub async fn delete_unpinned_conversations(
+    db: &DatabaseConnection,
+    login_uid: &str,
+) -> Result<(), CommonError> {
+    let ids: Vec<String> = list_conversations(db, login_uid)
+        .await?
+        .into_iter()
+        .filter(|conversation| !conversation.pinned)
+        .map(|conversation| conversation.id)
+        .collect();
+    delete_conversations(db, login_uid, &ids).await
+}
+
+/// 只删除会话中的消息，保留会话本身
+pub async fn clear_conversation_messages(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    conversation_ids: &[String],
+) -> Result<(), CommonError> {
+    if conversation_ids.is_empty() {
+        return Ok(());
+    }
+    im_ai_message::Entity::delete_many()
+        .filter(im_ai_message::Column::LoginUid.eq(login_uid))
+        .filter(im_ai_message::Column::ConversationId.is_in(conversation_ids.iter().cloned()))
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("删除AI消息失败: {}", e))?;
+    Ok(())
+}
+
+/// 保存服务端返回的一个会话的消息
+/// 服务端返回的时间范围内已有完整记录，因此同时删除该范围内本地写入且已完成的消息；
+/// 范围之外的本地消息（例如其他服务提供的对话）以及被取消或出错的消息继续保留
+pub async fn save_server_messages(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    conversation_id: &str,
+    messages: Vec<im_ai_message::Model>,
+) -> Result<(), CommonError> {
+    let times = messages.iter().map(|message| message.create_time);
+    let (Some(first), Some(last)) = (times.clone().min(), times.max()) else {
+        return Ok(());
+    };
+
+    let txn = db.begin().await?;
+    im_ai_message::Entity::delete_many()
+        .filter(im_ai_message::Column::LoginUid.eq(login_uid))
+        .filter(im_ai_message::Column::ConversationId.eq(conversation_id))
+        .filter(im_ai_message::Column::Local.eq(true))
+        .filter(im_ai_message::Column::Status.eq(STATUS_DONE))
+        .filter(im_ai_message::Column::CreateTime.gte(first - SERVER_TIME_TOLERANCE_MS))
+        .filter(im_ai_message::Column::CreateTime.lte(last + SERVER_TIME_TOLERANCE_MS))
+        .exec(&txn)
+        .await?;
+    im_ai_message::Entity::insert_many(
+        messages.into_iter().map(IntoActiveModel::into_active_model),
+    )
+    .on_conflict(
+        OnConflict::column(im_ai_message::Column::Id)
+            .update_columns([
+                im_ai_message::Column::Content,
+                im_ai_message::Column::ReasoningContent,
+                im_ai_message::Column::Model,
+                im_ai_message::Column::Status,
+                im_ai_message::Column::UpdateTime,
+            ])
+            .to_owned(),
+    )
+    .exec(&txn)
+    .await?;
+    txn.commit()
+        .await
+        .map_err(|e| anyhow::anyhow!("保存AI消息失败: {}", e))?;
+    Ok(())
+}
+
+/// 新增一条本地消息
+pub async fn insert_message(
+    db: &DatabaseConnection,
+    message: im_ai_message::Model,
+) -> Result<(), CommonError> {
+    im_ai_message::Entity::insert(message.into_active_model())
+        .on_conflict(
+            OnConflict::column(im_ai_message::Column::Id)
+                .update_columns([
+                    im_ai_message::Column::Content,
+                    im_ai_message::Column::Status,
+                    im_ai_message::Column::UpdateTime,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("保存AI消息失败: {}", e))?;
+    Ok(())
+}
+
+/// 在消息内容末尾追加流式收到的片段
+pub async fn append_message_content(
+    db: &DatabaseConnection,
+    id: &str,
+    login_uid: &str,
+    delta: &str,
+) -> Result<(), CommonError> {
+    let stmt = Statement::from_sql_and_values(
+        db.get_database_backend(),
+        "UPDATE im_ai_message SET content = content || ?, update_time = ? WHERE id = ? AND login_uid = ?",
+        [
+            delta.into(),
+            Utc::now().timestamp_millis().into(),
+            id.into(),
+            login_uid.into(),
+        ],
+    );
+    db.execute(stmt)
+        .await
+        .map_err(|e| anyhow::anyhow!("写入AI消息内容失败: {}", e))?;
+    Ok(())
+}
+
+/// 更新仍在接收中的消息状态，已结束的消息不受影响
+pub async fn finish_streaming_message(
+    db: &DatabaseConnection,
+    id: &str,
+    login_uid: &str,
+    status: &str,
+) -> Result<(), CommonError> {
+    let stmt = Statement::from_sql_and_values(
+        db.get_database_backend(),
+        "UPDATE im_ai_message SET status = ?, update_time = ? WHERE id = ? AND login_uid = ? AND status = ?",
+        [
+            status.into(),
+            Utc::now().timestamp_millis().into(),
+            id.into(),
+            login_uid.into(),
+            STATUS_STREAMING.into(),
+        ],
+    );
+    db.execute(stmt)
+        .await
+        .map_err(|e| anyhow::anyhow!("更新AI消息状态失败: {}", e))?;
+    Ok(())
+}
+
+/// 把仍处于接收中的消息标记为已中断，`active_ids` 为当前仍在进行的请求
+pub async fn mark_interrupted_messages(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    active_ids: &[String],
+) -> Result<u64, CommonError> {
+    let result = im_ai_message::Entity::update_many()
+        .col_expr(
+            im_ai_message::Column::Status,
+            Expr::value(STATUS_INTERRUPTED),
+        )
+        .col_expr(
+            im_ai_message::Column::UpdateTime,
+            Expr::value(Utc::now().timestamp_millis()),
+        )
+        .filter(im_ai_message::Column::LoginUid.eq(login_uid))
+        .filter(im_ai_message::Column::Status.eq(STATUS_STREAMING))
+        .filter(im_ai_message::Column::Id.is_not_in(active_ids.iter().cloned()))
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("更新AI消息状态失败: {}", e))?;
+    Ok(result.rows_affected)
+}
+
+/// 获取会话的全部本地消息，按时间正序
+pub async fn list_messages(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    conversation_id: &str,
+) -> Result<Vec<im_ai_message::Model>, CommonError> {
+    let list = im_ai_message::Entity::find()
+        .filter(im_ai_message::Column::LoginUid.eq(login_uid))
+        .filter(im_ai_message::Column::ConversationId.eq(conversation_id))
+        .order_by_asc(im_ai_message::Column::CreateTime)
+        .order_by_asc(im_ai_message::Column::Id)
+        .all(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("查询AI消息失败: {}", e))?;
+    Ok(list)
+}
+
+/// 删除单条消息
+pub async fn delete_message(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    id: &str,
+) -> Result<(), CommonError> {
+    im_ai_message::Entity::delete_many()
+        .filter(im_ai_message::Column::LoginUid.eq(login_uid))
+        .filter(im_ai_message::Column::Id.eq(id))
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("删除AI消息失败: {}", e))?;
+    Ok(())
+}
+
+/// 按关键词搜索本地 AI 消息，多个关键词之间为 AND 关系，结果按时间倒序
+pub async fn search_messages(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    keyword: &str,
+    conversation_id: Option<&str>,
+    limit: u64,
+) -> Result<Vec<AiMessageSearchHit>, CommonError> {
+    let terms = split_search_terms(keyword);
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut sql = String::from(
+        "SELECT m.*, c.title AS conversation_title FROM im_ai_message m \
+         LEFT JOIN im_ai_conversation c ON c.id = m.conversation_id AND c.login_uid = m.login_uid \
+         WHERE m.login_uid = ?",
+    );
+    let mut values = vec![Value::from(login_uid)];
+    if let Some(conversation_id) = conversation_id {
+        sql.push_str(" AND m.conversation_id = ?");
+        values.push(Value::from(conversation_id));
+    }
+    for term in &terms {
+        sql.push_str(" AND m.content LIKE ? ESCAPE '\\'");
+        values.push(Value::from(escape_like_pattern(term)));
+    }
+    sql.push_str(" ORDER BY m.create_time DESC LIMIT ?");
+    values.push(Value::from(limit as i64));
+
+    let stmt = Statement::from_sql_and_values(db.get_database_backend(), sql, values);
+    let rows = db
+        .query_all(stmt)
+        .await
+        .map_err(|e| anyhow::anyhow!("搜索AI消息失败: {}", e))?;
+
+    let mut hits = Vec::with_capacity(rows.len());
+    for row in rows {
+        let message = im_ai_message::Model::from_query_result(&row, "")?;
+        let conversation_title: Option<String> = row.try_get("", "conversation_title")?;
+        let snippet = build_snippet(&message.content, &terms);
+        hits.push(AiMessageSearchHit {
+            message,
+            conversation_title,
+            snippet,
+        });
+    }
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::Database;
+
+    async fn memory_db() -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE im_ai_message (id TEXT PRIMARY KEY, login_uid TEXT NOT NULL, \
+             conversation_id TEXT NOT NULL, msg_type TEXT NOT NULL, content TEXT NOT NULL, \
+             reasoning_content TEXT, model TEXT, status TEXT NOT NULL, local BOOLEAN NOT NULL, \
+             create_time INTEGER NOT NULL, update_time INTEGER NOT NULL)",
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    fn message(id: &str, status: &str, local: bool, create_time: i64) -> im_ai_message::Model {
+        im_ai_message::Model {
+            id: id.to_string(),
+            login_uid: "u1".to_string(),
+            conversation_id: "c1".to_string(),
+            msg_type: "assistant".to_string(),
+            content: id.to_string(),
+            reasoning_content: None,
+            model: None,
+            status: status.to_string(),
+            local,
+            create_time,
+            update_time: create_time,
+        }
+    }
+
+    async fn ids(db: &DatabaseConnection) -> Vec<String> {
+        list_messages(db, "u1", "c1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|message| message.id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_server_messages_replace_local_rows_in_range() {
+        let db = memory_db().await;
+        let day = 86_400_000;
+        for local in [
+            message("old-local", STATUS_DONE, true, 1_000),
+            message("recent-local", STATUS_DONE, true, 10 * day),
+            message("recent-cancelled", STATUS_CANCELLED, true, 10 * day + 1),
+        ] {
+            insert_message(&db, local).await.unwrap();
+        }
+
+        // 空列表不会删除任何本地消息
+        save_server_messages(&db, "u1", "c1", Vec::new())
+            .await
+            .unwrap();
+        assert_eq!(ids(&db).await.len(), 3);
+
+        // 只有服务端返回的时间范围内已完成的本地消息被替换
+        let server = vec![message("s1", STATUS_DONE, false, 10 * day + 500)];
+        save_server_messages(&db, "u1", "c1", server).await.unwrap();
+        assert_eq!(ids(&db).await, ["old-local", "recent-cancelled", "s1"]);
+    }
+
+    #[tokio::test]
+    async fn test_mark_interrupted_skips_active_streams() {
+        let db = memory_db().await;
+        for row in [
+            message("left", STATUS_STREAMING, true, 1),
+            message("active", STATUS_STREAMING, true, 2),
+            message("done", STATUS_DONE, true, 3),
+        ] {
+            insert_message(&db, row).await.unwrap();
+        }
+
+        let marked = mark_interrupted_messages(&db, "u1", &["active".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(marked, 1);
+        let statuses: Vec<String> = list_messages(&db, "u1", "c1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|message| message.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            [STATUS_INTERRUPTED, STATUS_STREAMING, STATUS_DONE]
+        );
+    }
+}
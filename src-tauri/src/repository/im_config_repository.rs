@@ -1,8 +1,8 @@
 use crate::error::CommonError;
 use entity::im_config;
-use sea_orm::QueryFilter;
 use sea_orm::{ActiveModelTrait, ColumnTrait, IntoActiveModel, Set};
 use sea_orm::{DatabaseConnection, EntityTrait, TransactionTrait};
+use sea_orm::{QueryFilter, QuerySelect};
 
 /// 获取配置列表
 pub async fn list_config(
@@ -47,16 +47,23 @@ pub async fn save_or_update_config(
         config_active.config_value = Set(config_value);
         config_active.update(db).await?;
     } else {
-        // 创建新配置
+        // 创建新配置，表的主键为 (id, login_uid) 且 id 不是自增列，需要手动分配
+        let max_id: Option<Option<i64>> = im_config::Entity::find()
+            .select_only()
+            .column_as(im_config::Column::Id.max(), "max_id")
+            .filter(im_config::Column::LoginUid.eq(login_uid))
+            .into_tuple()
+            .one(db)
+            .await?;
         let new_config = im_config::Model {
-            id: 0, // 自增主键
+            id: max_id.flatten().unwrap_or(0) + 1,
             config_key: config_key.to_string(),
             config_value,
             login_uid: login_uid.to_string(),
         };
-        let mut config_active = new_config.into_active_model();
-        config_active.id = sea_orm::NotSet;
-        config_active.insert(db).await?;
+        im_config::Entity::insert(new_config.into_active_model())
+            .exec_without_returning(db)
+            .await?;
     }
     Ok(())
 }
@@ -118,3 +125,18 @@ pub async fn delete_config(
         .map_err(|e| anyhow::anyhow!("删除配置失败: {}", e))?;
     Ok(())
 }
+
+/// 按前缀删除配置
+pub async fn delete_config_by_prefix(
+    db: &DatabaseConnection,
+    prefix: &str,
+    login_uid: &str,
+) -> Result<u64, CommonError> {
+    let result = im_config::Entity::delete_many()
+        .filter(im_config::Column::ConfigKey.starts_with(prefix))
+        .filter(im_config::Column::LoginUid.eq(login_uid))
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("删除配置失败: {}", e))?;
+    Ok(result.rows_affected)
+}
@@ -1,9 +1,10 @@
 use crate::error::CommonError;
 
 use entity::im_contact;
+use sea_orm::prelude::Expr;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
-    Set, TransactionTrait,
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    IntoActiveModel, QueryFilter, Set, TransactionTrait,
 };
 use tracing::info;
 
@@ -95,3 +96,51 @@ pub async fn update_contact_hide(
 
     Ok(())
 }
+
+/// 更新会话名称和头像，传入 None 的字段保持不变
+pub async fn update_contact_info<C>(
+    db: &C,
+    room_id: &str,
+    name: Option<&str>,
+    avatar: Option<&str>,
+    login_uid: &str,
+) -> Result<(), CommonError>
+where
+    C: ConnectionTrait,
+{
+    if name.is_none() && avatar.is_none() {
+        return Ok(());
+    }
+
+    let mut update = im_contact::Entity::update_many()
+        .filter(im_contact::Column::RoomId.eq(room_id))
+        .filter(im_contact::Column::LoginUid.eq(login_uid));
+    if let Some(name) = name {
+        update = update.col_expr(im_contact::Column::ContactName, Expr::value(name));
+    }
+    if let Some(avatar) = avatar {
+        update = update.col_expr(im_contact::Column::Avatar, Expr::value(avatar));
+    }
+
+    update
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to update contact info: {}", e))?;
+
+    Ok(())
+}
+
+/// 删除会话
+pub async fn delete_contact<C>(db: &C, room_id: &str, login_uid: &str) -> Result<(), CommonError>
+where
+    C: ConnectionTrait,
+{
+    im_contact::Entity::delete_many()
+        .filter(im_contact::Column::RoomId.eq(room_id))
+        .filter(im_contact::Column::LoginUid.eq(login_uid))
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to delete contact: {}", e))?;
+
+    Ok(())
+}
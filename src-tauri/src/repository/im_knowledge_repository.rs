@@ -0,0 +1,428 @@
+use crate::ai::knowledge::DocumentChunk;
+use crate::error::CommonError;
+use crate::repository::im_message_repository::escape_like_pattern;
+use entity::{im_kb_chunk, im_kb_source};
+use sea_orm::sea_query::{OnConflict, Value};
+use sea_orm::{
+    ActiveValue::{NotSet, Set},
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, FromQueryResult,
+    IntoActiveModel, QueryFilter, QueryOrder, Statement, TransactionTrait,
+};
+
+/// 每批插入的片段数，避免超过 SQLite 的参数数量上限
+const CHUNK_INSERT_BATCH: usize = 200;
+
+/// 知识库检索命中
+#[derive(Debug, Clone)]
+pub struct KnowledgeHit {
+    pub chunk: im_kb_chunk::Model,
+    pub source_name: String,
+    pub source_path: String,
+    /// bm25 分数，LIKE 检索时为命中分数的相反数，越小越相关
+    pub rank: f64,
+}
+
+/// 按文件路径查找来源
+pub async fn find_source_by_path(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    path: &str,
+) -> Result<Option<im_kb_source::Model>, CommonError> {
+    let source = im_kb_source::Entity::find()
+        .filter(im_kb_source::Column::LoginUid.eq(login_uid))
+        .filter(im_kb_source::Column::Path.eq(path))
+        .one(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("查询知识库来源失败: {}", e))?;
+    Ok(source)
+}
+
+/// 获取用户的全部来源，最近建立索引的在前
+pub async fn list_sources(
+    db: &DatabaseConnection,
+    login_uid: &str,
+) -> Result<Vec<im_kb_source::Model>, CommonError> {
+    let sources = im_kb_source::Entity::find()
+        .filter(im_kb_source::Column::LoginUid.eq(login_uid))
+        .order_by_desc(im_kb_source::Column::UpdateTime)
+        .all(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("查询知识库来源失败: {}", e))?;
+    Ok(sources)
+}
+
+/// 保存来源并用新的片段替换旧的索引
+pub async fn save_source(
+    db: &DatabaseConnection,
+    source: im_kb_source::Model,
+    chunks: &[DocumentChunk],
+) -> Result<(), CommonError> {
+    let txn = db.begin().await?;
+    delete_source_chunks(&txn, &source.id).await?;
+
+    let source_id = source.id.clone();
+    let login_uid = source.login_uid.clone();
+    im_kb_source::Entity::insert(source.into_active_model())
+        .on_conflict(
+            OnConflict::column(im_kb_source::Column::Id)
+                .update_columns([
+                    im_kb_source::Column::Name,
+                    im_kb_source::Column::Kind,
+                    im_kb_source::Column::Size,
+                    im_kb_source::Column::ModifiedTime,
+                    im_kb_source::Column::ChunkCount,
+                    im_kb_source::Column::UpdateTime,
+                ])
+                .to_owned(),
+        )
+        .exec(&txn)
+        .await?;
+
+    for (batch_index, batch) in chunks.chunks(CHUNK_INSERT_BATCH).enumerate() {
+        let models = batch
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| im_kb_chunk::ActiveModel {
+                id: NotSet,
+                source_id: Set(source_id.clone()),
+                login_uid: Set(login_uid.clone()),
+                seq: Set((batch_index * CHUNK_INSERT_BATCH + index) as i32),
+                heading: Set(chunk.heading.clone()),
+                content: Set(chunk.content.clone()),
+                start_line: Set(chunk.start_line as i32),
+                end_line: Set(chunk.end_line as i32),
+            });
+        im_kb_chunk::Entity::insert_many(models).exec(&txn).await?;
+    }
+
+    // 全文索引的 rowid 与片段ID一致
+    txn.execute(Statement::from_sql_and_values(
+        txn.get_database_backend(),
+        "INSERT INTO im_kb_chunk_fts (rowid, content, heading, source_id, login_uid)
+         SELECT id, content, COALESCE(heading, ''), source_id, login_uid
+         FROM im_kb_chunk WHERE source_id = ?",
+        [source_id.into()],
+    ))
+    .await?;
+
+    txn.commit()
+        .await
+        .map_err(|e| anyhow::anyhow!("保存知识库索引失败: {}", e))?;
+    Ok(())
+}
+
+/// 删除来源及其索引，来源不存在时返回 false
+pub async fn delete_source(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    source_id: &str,
+) -> Result<bool, CommonError> {
+    let txn = db.begin().await?;
+    delete_source_chunks(&txn, source_id).await?;
+    let result = im_kb_source::Entity::delete_many()
+        .filter(im_kb_source::Column::LoginUid.eq(login_uid))
+        .filter(im_kb_source::Column::Id.eq(source_id))
+        .exec(&txn)
+        .await?;
+    txn.commit()
+        .await
+        .map_err(|e| anyhow::anyhow!("删除知识库来源失败: {}", e))?;
+    Ok(result.rows_affected > 0)
+}
+
+async fn delete_source_chunks<C: ConnectionTrait>(
+    conn: &C,
+    source_id: &str,
+) -> Result<(), CommonError> {
+    conn.execute(Statement::from_sql_and_values(
+        conn.get_database_backend(),
+        "DELETE FROM im_kb_chunk_fts WHERE rowid IN (SELECT id FROM im_kb_chunk WHERE source_id = ?)",
+        [source_id.into()],
+    ))
+    .await?;
+    im_kb_chunk::Entity::delete_many()
+        .filter(im_kb_chunk::Column::SourceId.eq(source_id))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// 按 bm25 检索最相关的片段，命中任意一个检索词即参与排序，标题的权重高于正文
+pub async fn search_chunks(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    terms: &[String],
+    limit: u64,
+) -> Result<Vec<KnowledgeHit>, CommonError> {
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let match_query = terms
+        .iter()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+
+    let stmt = Statement::from_sql_and_values(
+        db.get_database_backend(),
+        "SELECT c.*, s.name AS source_name, s.path AS source_path,
+             bm25(im_kb_chunk_fts, 1.0, 2.0) AS search_rank
+         FROM im_kb_chunk_fts
+         JOIN im_kb_chunk c ON c.id = im_kb_chunk_fts.rowid
+         JOIN im_kb_source s ON s.id = c.source_id
+         WHERE im_kb_chunk_fts MATCH ? AND im_kb_chunk_fts.login_uid = ?
+         ORDER BY search_rank ASC LIMIT ?",
+        [
+            Value::from(match_query),
+            Value::from(login_uid),
+            Value::from(limit as i64),
+        ],
+    );
+    let rows = db
+        .query_all(stmt)
+        .await
+        .map_err(|e| anyhow::anyhow!("检索知识库失败: {}", e))?;
+
+    let mut hits = Vec::with_capacity(rows.len());
+    for row in rows {
+        hits.push(KnowledgeHit {
+            chunk: im_kb_chunk::Model::from_query_result(&row, "")?,
+            source_name: row.try_get("", "source_name")?,
+            source_path: row.try_get("", "source_path")?,
+            rank: row.try_get("", "search_rank")?,
+        });
+    }
+    Ok(hits)
+}
+
+/// trigram 无法匹配 1~2 个字符的词，退化为 LIKE 检索，标题命中计 2 分、正文命中计 1 分
+pub async fn search_chunks_like(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    terms: &[String],
+    limit: u64,
+) -> Result<Vec<KnowledgeHit>, CommonError> {
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let score = terms
+        .iter()
+        .map(|_| {
+            "CASE WHEN c.heading LIKE ? ESCAPE '\\' THEN 2.0 ELSE 0.0 END \
+             + CASE WHEN c.content LIKE ? ESCAPE '\\' THEN 1.0 ELSE 0.0 END"
+        })
+        .collect::<Vec<_>>()
+        .join(" + ");
+    let mut values = Vec::with_capacity(terms.len() * 2 + 2);
+    for term in terms {
+        let pattern = escape_like_pattern(term);
+        values.push(Value::from(pattern.clone()));
+        values.push(Value::from(pattern));
+    }
+    values.push(Value::from(login_uid));
+    values.push(Value::from(limit as i64));
+
+    let stmt = Statement::from_sql_and_values(
+        db.get_database_backend(),
+        format!(
+            "SELECT * FROM (
+                 SELECT c.*, s.name AS source_name, s.path AS source_path,
+                     -({score}) AS search_rank
+                 FROM im_kb_chunk c
+                 JOIN im_kb_source s ON s.id = c.source_id
+                 WHERE c.login_uid = ?
+             )
+             WHERE search_rank < 0
+             ORDER BY search_rank ASC, id ASC LIMIT ?"
+        ),
+        values,
+    );
+    let rows = db
+        .query_all(stmt)
+        .await
+        .map_err(|e| anyhow::anyhow!("检索知识库失败: {}", e))?;
+
+    let mut hits = Vec::with_capacity(rows.len());
+    for row in rows {
+        hits.push(KnowledgeHit {
+            chunk: im_kb_chunk::Model::from_query_result(&row, "")?,
+            source_name: row.try_get("", "source_name")?,
+            source_path: row.try_get("", "source_path")?,
+            rank: row.try_get("", "search_rank")?,
+        });
+    }
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::Database;
+
+    async fn memory_db() -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        for sql in [
+            "CREATE TABLE im_kb_source (id TEXT PRIMARY KEY, login_uid TEXT NOT NULL, \
+             path TEXT NOT NULL, name TEXT NOT NULL, kind TEXT NOT NULL, size INTEGER NOT NULL, \
+             modified_time INTEGER, chunk_count INTEGER NOT NULL, create_time INTEGER NOT NULL, \
+             update_time INTEGER NOT NULL)",
+            "CREATE TABLE im_kb_chunk (id INTEGER PRIMARY KEY AUTOINCREMENT, \
+             source_id TEXT NOT NULL, login_uid TEXT NOT NULL, seq INTEGER NOT NULL, heading TEXT, \
+             content TEXT NOT NULL, start_line INTEGER NOT NULL, end_line INTEGER NOT NULL)",
+            "CREATE VIRTUAL TABLE im_kb_chunk_fts USING fts5(content, heading, \
+             source_id UNINDEXED, login_uid UNINDEXED, tokenize = 'trigram')",
+        ] {
+            db.execute_unprepared(sql).await.unwrap();
+        }
+        db
+    }
+
+    fn source(id: &str, login_uid: &str) -> im_kb_source::Model {
+        im_kb_source::Model {
+            id: id.to_string(),
+            login_uid: login_uid.to_string(),
+            path: format!("/docs/{}.md", id),
+            name: format!("{}.md", id),
+            kind: "markdown".to_string(),
+            size: 1,
+            modified_time: None,
+            chunk_count: 0,
+            create_time: 1,
+            update_time: 1,
+        }
+    }
+
+    fn chunk(heading: Option<&str>, content: &str) -> DocumentChunk {
+        DocumentChunk {
+            heading: heading.map(str::to_string),
+            content: content.to_string(),
+            start_line: 1,
+            end_line: 1,
+        }
+    }
+
+    fn terms(terms: &[&str]) -> Vec<String> {
+        terms.iter().map(|term| term.to_string()).collect()
+    }
+
+    async fn count(db: &DatabaseConnection, table: &str) -> i64 {
+        db.query_one(Statement::from_string(
+            db.get_database_backend(),
+            format!("SELECT COUNT(*) AS n FROM {}", table),
+        ))
+        .await
+        .unwrap()
+        .unwrap()
+        .try_get("", "n")
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_search_chunks_ranks_by_bm25_and_filters_login_uid() {
+        let db = memory_db().await;
+        let chunks = [
+            chunk(Some("主题"), "切换深色模式后，也可以在网络页填写代理地址"),
+            chunk(Some("代理地址"), "在设置页面填写代理地址"),
+            chunk(Some("通知"), "关闭消息提醒"),
+        ];
+        save_source(&db, source("guide", "u1"), &chunks)
+            .await
+            .unwrap();
+        save_source(&db, source("other", "u2"), &[chunk(None, "代理地址")])
+            .await
+            .unwrap();
+
+        // 标题和正文都命中的片段排在只有正文命中的片段之前
+        let hits = search_chunks(&db, "u1", &terms(&["代理地址"]), 10)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].chunk.heading.as_deref(), Some("代理地址"));
+        assert_eq!(hits[0].source_name, "guide.md");
+        assert!(hits[0].rank < hits[1].rank);
+        assert!(hits.iter().all(|hit| hit.chunk.login_uid == "u1"));
+
+        let hits = search_chunks(&db, "u2", &terms(&["代理地址"]), 10)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk.source_id, "other");
+    }
+
+    #[tokio::test]
+    async fn test_reindex_and_delete_clean_up_fts_rows() {
+        let db = memory_db().await;
+        save_source(
+            &db,
+            source("guide", "u1"),
+            &[chunk(None, "旧的代理说明"), chunk(None, "旧的主题说明")],
+        )
+        .await
+        .unwrap();
+        save_source(&db, source("guide", "u1"), &[chunk(None, "新的代理说明")])
+            .await
+            .unwrap();
+        assert_eq!(count(&db, "im_kb_chunk").await, 1);
+        assert_eq!(count(&db, "im_kb_chunk_fts").await, 1);
+        assert!(
+            search_chunks(&db, "u1", &terms(&["旧的主题"]), 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let hits = search_chunks(&db, "u1", &terms(&["代理说明"]), 10)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk.content, "新的代理说明");
+
+        // 其他用户无法删除该来源
+        assert!(!delete_source(&db, "u2", "guide").await.unwrap());
+        assert!(delete_source(&db, "u1", "guide").await.unwrap());
+        assert!(!delete_source(&db, "u1", "guide").await.unwrap());
+        assert_eq!(count(&db, "im_kb_chunk").await, 0);
+        assert_eq!(count(&db, "im_kb_chunk_fts").await, 0);
+        assert!(list_sources(&db, "u1").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_chunks_like_matches_short_terms() {
+        let db = memory_db().await;
+        let chunks = [
+            chunk(None, "网络代理配置"),
+            chunk(Some("代理"), "填写地址"),
+            chunk(None, "进度 100%"),
+        ];
+        save_source(&db, source("guide", "u1"), &chunks)
+            .await
+            .unwrap();
+
+        // trigram 无法匹配两个字的词
+        assert!(
+            search_chunks(&db, "u1", &terms(&["代理"]), 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let hits = search_chunks_like(&db, "u1", &terms(&["代理"]), 10)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].chunk.heading.as_deref(), Some("代理"));
+        assert_eq!(hits[0].rank, -2.0);
+        assert_eq!(hits[1].rank, -1.0);
+
+        // % 按字面匹配
+        let hits = search_chunks_like(&db, "u1", &terms(&["0%"]), 10)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk.content, "进度 100%");
+        assert!(
+            search_chunks_like(&db, "u2", &terms(&["代理"]), 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
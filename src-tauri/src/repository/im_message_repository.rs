@@ -1,5 +1,6 @@
 use crate::error::CommonError;
 use crate::pojo::common::{CursorPageParam, CursorPageResp};
+use crate::repository::im_config_repository;
 use chrono::Utc;
 use entity::im_message;
 use lazy_static::lazy_static;
@@ -7,23 +8,34 @@ use sea_orm::prelude::Expr;
 use sea_orm::sea_query::{Alias, Value};
 use sea_orm::{
     ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DatabaseTransaction, EntityTrait,
-    IntoActiveModel, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set, Statement,
-    TryIntoModel,
+    FromQueryResult, IntoActiveModel, PaginatorTrait, QueryFilter, QueryOrder, QueryResult,
+    QuerySelect, Set, Statement, TransactionTrait, TryIntoModel,
 };
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 lazy_static! {
     static ref DELETED_TABLE_INITIALIZED: AtomicBool = AtomicBool::new(false);
     static ref ROOM_CLEAR_TABLE_INITIALIZED: AtomicBool = AtomicBool::new(false);
+    static ref SEARCH_INDEX_STATE: AtomicU8 = AtomicU8::new(SEARCH_INDEX_UNKNOWN);
 }
 
+const SEARCH_INDEX_UNKNOWN: u8 = 0;
+const SEARCH_INDEX_READY: u8 = 1;
+const SEARCH_INDEX_MISSING: u8 = 2;
+
+/// trigram 分词器无法匹配少于 3 个字符的查询词，此时退化为 LIKE 扫描索引内容
+const MIN_MATCH_TERM_CHARS: usize = 3;
+/// 本地生成摘要时保留的字符数
+const SNIPPET_CHARS: usize = 32;
+
 /// 重置表初始化标志，在切换数据库时调用
 pub fn reset_table_initialization_flags() {
     DELETED_TABLE_INITIALIZED.store(false, Ordering::SeqCst);
     ROOM_CLEAR_TABLE_INITIALIZED.store(false, Ordering::SeqCst);
+    SEARCH_INDEX_STATE.store(SEARCH_INDEX_UNKNOWN, Ordering::SeqCst);
     info!("Table initialization flags have been reset");
 }
 
@@ -31,6 +43,8 @@ pub fn reset_table_initialization_flags() {
 pub struct MessageWithThumbnail {
     pub message: im_message::Model,
     pub thumbnail_path: Option<String>,
+    /// 消息中媒体文件下载到本地缓存后的路径
+    pub local_path: Option<String>,
 }
 
 impl MessageWithThumbnail {
@@ -38,9 +52,15 @@ impl MessageWithThumbnail {
         Self {
             message,
             thumbnail_path,
+            local_path: None,
         }
     }
 
+    pub fn with_local_path(mut self, local_path: Option<String>) -> Self {
+        self.local_path = local_path;
+        self
+    }
+
     pub fn key(&self) -> (String, String) {
         (self.message.id.clone(), self.message.login_uid.clone())
     }
@@ -51,10 +71,21 @@ impl From<im_message::Model> for MessageWithThumbnail {
         Self {
             message,
             thumbnail_path: None,
+            local_path: None,
         }
     }
 }
 
+/// 关键词搜索命中的消息
+#[derive(Clone)]
+pub struct MessageSearchHit {
+    pub record: MessageWithThumbnail,
+    /// 命中片段，关键词使用 `<mark>` 标签包裹
+    pub snippet: Option<String>,
+    /// BM25 相关度得分，越大越相关；LIKE 退化查询时为空
+    pub score: Option<f64>,
+}
+
 fn parse_message_id(id: &str) -> Option<i64> {
     id.parse::<i64>().ok()
 }
@@ -168,16 +199,24 @@ fn collect_message_keys(messages: &[MessageWithThumbnail]) -> Vec<(String, Strin
         .collect()
 }
 
-async fn fetch_thumbnail_map<C: ConnectionTrait>(
+/// 消息在本地保存的文件路径，不随服务端消息同步
+#[derive(Clone, Default)]
+struct LocalPaths {
+    thumbnail_path: Option<String>,
+    local_path: Option<String>,
+}
+
+async fn fetch_local_paths_map<C: ConnectionTrait>(
     conn: &C,
     keys: &[(String, String)],
-) -> Result<HashMap<(String, String), String>, CommonError> {
+) -> Result<HashMap<(String, String), LocalPaths>, CommonError> {
     if keys.is_empty() {
         return Ok(HashMap::new());
     }
 
     let backend = conn.get_database_backend();
-    let mut sql = String::from("SELECT id, login_uid, thumbnail_path FROM im_message WHERE ");
+    let mut sql =
+        String::from("SELECT id, login_uid, thumbnail_path, local_path FROM im_message WHERE ");
     let mut conditions = Vec::with_capacity(keys.len());
     let mut values = Vec::with_capacity(keys.len() * 2);
 
@@ -196,24 +235,31 @@ async fn fetch_thumbnail_map<C: ConnectionTrait>(
     for row in rows {
         let id: String = row.try_get("", "id")?;
         let login_uid: String = row.try_get("", "login_uid")?;
-        let path: Option<String> = row.try_get("", "thumbnail_path")?;
-        if let Some(path) = path {
-            map.insert((id, login_uid), path);
+        let thumbnail_path: Option<String> = row.try_get("", "thumbnail_path")?;
+        let local_path: Option<String> = row.try_get("", "local_path")?;
+        if thumbnail_path.is_some() || local_path.is_some() {
+            map.insert(
+                (id, login_uid),
+                LocalPaths {
+                    thumbnail_path,
+                    local_path,
+                },
+            );
         }
     }
 
     Ok(map)
 }
 
-async fn fetch_thumbnail_path<C: ConnectionTrait>(
+async fn fetch_local_paths<C: ConnectionTrait>(
     conn: &C,
     id: &str,
     login_uid: &str,
-) -> Result<Option<String>, CommonError> {
+) -> Result<LocalPaths, CommonError> {
     let backend = conn.get_database_backend();
     let stmt = Statement::from_sql_and_values(
         backend,
-        "SELECT thumbnail_path FROM im_message WHERE id = ? AND login_uid = ? LIMIT 1",
+        "SELECT thumbnail_path, local_path FROM im_message WHERE id = ? AND login_uid = ? LIMIT 1",
         vec![
             Value::from(id.to_string()),
             Value::from(login_uid.to_string()),
@@ -221,35 +267,97 @@ async fn fetch_thumbnail_path<C: ConnectionTrait>(
     );
 
     if let Some(row) = conn.query_one(stmt).await? {
-        let path: Option<String> = row.try_get("", "thumbnail_path")?;
-        Ok(path)
+        Ok(LocalPaths {
+            thumbnail_path: row.try_get("", "thumbnail_path")?,
+            local_path: row.try_get("", "local_path")?,
+        })
     } else {
-        Ok(None)
+        Ok(LocalPaths::default())
     }
 }
 
-async fn update_thumbnail_path<C: ConnectionTrait>(
+async fn update_local_paths<C: ConnectionTrait>(
     conn: &C,
-    key: &(String, String),
-    path: Option<&str>,
+    record: &MessageWithThumbnail,
 ) -> Result<(), CommonError> {
     let backend = conn.get_database_backend();
-    let value = match path {
-        Some(p) => Value::from(p.to_string()),
+    let to_value = |path: Option<&String>| match path {
+        Some(p) => Value::from(p.clone()),
         None => Value::String(None),
     };
 
     let stmt = Statement::from_sql_and_values(
         backend,
+        "UPDATE im_message SET thumbnail_path = ?, local_path = ? WHERE id = ? AND login_uid = ?",
+        vec![
+            to_value(record.thumbnail_path.as_ref()),
+            to_value(record.local_path.as_ref()),
+            Value::from(record.message.id.clone()),
+            Value::from(record.message.login_uid.clone()),
+        ],
+    );
+
+    conn.execute(stmt).await?;
+    Ok(())
+}
+
+/// 记录消息图片生成的本地缩略图路径
+pub async fn update_message_thumbnail_path(
+    db: &DatabaseConnection,
+    id: &str,
+    login_uid: &str,
+    path: Option<&str>,
+) -> Result<(), CommonError> {
+    let value = match path {
+        Some(p) => Value::from(p.to_string()),
+        None => Value::String(None),
+    };
+    let stmt = Statement::from_sql_and_values(
+        db.get_database_backend(),
         "UPDATE im_message SET thumbnail_path = ? WHERE id = ? AND login_uid = ?",
         vec![
             value,
-            Value::from(key.0.clone()),
-            Value::from(key.1.clone()),
+            Value::from(id.to_string()),
+            Value::from(login_uid.to_string()),
         ],
     );
+    db.execute(stmt)
+        .await
+        .map_err(|e| anyhow::anyhow!("更新消息缩略图路径失败: {}", e))?;
+    Ok(())
+}
 
-    conn.execute(stmt).await?;
+/// 记录消息媒体文件下载到本地的路径
+pub async fn update_message_local_path(
+    db: &DatabaseConnection,
+    id: &str,
+    login_uid: &str,
+    path: Option<&str>,
+) -> Result<(), CommonError> {
+    let value = match path {
+        Some(p) => Value::from(p.to_string()),
+        None => Value::String(None),
+    };
+    let stmt = Statement::from_sql_and_values(
+        db.get_database_backend(),
+        "UPDATE im_message SET local_path = ? WHERE id = ? AND login_uid = ?",
+        vec![
+            value,
+            Value::from(id.to_string()),
+            Value::from(login_uid.to_string()),
+        ],
+    );
+    db.execute(stmt)
+        .await
+        .map_err(|e| anyhow::anyhow!("更新消息本地文件路径失败: {}", e))?;
+    Ok(())
+}
+
+/// 清除所有账号消息记录的本地文件路径，下载缓存由所有账号共用，清空缓存后调用
+pub async fn clear_message_local_paths(db: &DatabaseConnection) -> Result<(), CommonError> {
+    db.execute_unprepared("UPDATE im_message SET local_path = NULL WHERE local_path IS NOT NULL")
+        .await
+        .map_err(|e| anyhow::anyhow!("清除消息本地文件路径失败: {}", e))?;
     Ok(())
 }
 
@@ -266,19 +374,358 @@ async fn enrich_models_with_thumbnails<C: ConnectionTrait>(
         .map(|msg| (msg.id.clone(), msg.login_uid.clone()))
         .collect();
 
-    let thumbnail_map = fetch_thumbnail_map(conn, &keys).await?;
+    let mut paths_map = fetch_local_paths_map(conn, &keys).await?;
     let enriched = messages
         .into_iter()
         .map(|message| {
             let key = (message.id.clone(), message.login_uid.clone());
-            let path = thumbnail_map.get(&key).cloned();
-            MessageWithThumbnail::new(message, path)
+            let paths = paths_map.remove(&key).unwrap_or_default();
+            // 下载缓存中的文件可能已被淘汰或清空
+            let local_path = paths
+                .local_path
+                .filter(|path| std::path::Path::new(path).is_file());
+            MessageWithThumbnail::new(message, paths.thumbnail_path).with_local_path(local_path)
         })
         .collect();
 
     Ok(enriched)
 }
 
+/// 检查全文索引表是否可用，结果在切换数据库前一直有效
+async fn search_index_available<C: ConnectionTrait>(conn: &C) -> Result<bool, CommonError> {
+    match SEARCH_INDEX_STATE.load(Ordering::SeqCst) {
+        SEARCH_INDEX_READY => return Ok(true),
+        SEARCH_INDEX_MISSING => return Ok(false),
+        _ => {}
+    }
+
+    let backend = conn.get_database_backend();
+    let stmt = Statement::from_string(
+        backend,
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'im_message_fts' LIMIT 1"
+            .to_string(),
+    );
+    let available = conn.query_one(stmt).await?.is_some();
+    if !available {
+        warn!("Full-text index table im_message_fts is missing, falling back to LIKE search");
+    }
+
+    SEARCH_INDEX_STATE.store(
+        if available {
+            SEARCH_INDEX_READY
+        } else {
+            SEARCH_INDEX_MISSING
+        },
+        Ordering::SeqCst,
+    );
+    Ok(available)
+}
+
+/// 提取消息中可检索的文本：正文、文件名、回复内容以及合并消息标题
+pub(crate) fn extract_search_text(message: &im_message::Model) -> Option<String> {
+    // 撤回消息不参与检索
+    if message.message_type == Some(2) {
+        return None;
+    }
+
+    let body = message.body.as_deref()?;
+    let json = match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(json) => json,
+        Err(_) => {
+            let trimmed = body.trim();
+            return (!trimmed.is_empty()).then(|| trimmed.to_string());
+        }
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+
+    // 普通消息的 content 为字符串，合并消息的 content 为标题数组
+    match json.get("content") {
+        Some(serde_json::Value::String(content)) => parts.push(content),
+        Some(serde_json::Value::Array(items)) => {
+            parts.extend(items.iter().filter_map(|item| item.as_str()))
+        }
+        _ => {}
+    }
+
+    if let Some(file_name) = ["fileName", "filename", "file_name"]
+        .iter()
+        .find_map(|key| json.get(*key).and_then(|value| value.as_str()))
+    {
+        parts.push(file_name);
+    }
+
+    match json.pointer("/reply/body") {
+        Some(serde_json::Value::String(reply)) => parts.push(reply),
+        Some(reply) => {
+            if let Some(content) = reply.get("content").and_then(|value| value.as_str()) {
+                parts.push(content);
+            }
+        }
+        None => {}
+    }
+
+    let text = parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    (!text.is_empty()).then_some(text)
+}
+
+/// 删除消息对应的全文索引记录，需要在删除 im_message 行之前调用
+async fn remove_search_entries<C: ConnectionTrait>(
+    conn: &C,
+    keys: &[(String, String)],
+) -> Result<(), CommonError> {
+    if keys.is_empty() || !search_index_available(conn).await? {
+        return Ok(());
+    }
+
+    let backend = conn.get_database_backend();
+    let mut conditions = Vec::with_capacity(keys.len());
+    let mut values = Vec::with_capacity(keys.len() * 2);
+    for (id, login_uid) in keys {
+        conditions.push("(id = ? AND login_uid = ?)");
+        values.push(Value::from(id.clone()));
+        values.push(Value::from(login_uid.clone()));
+    }
+
+    let sql = format!(
+        "DELETE FROM im_message_fts WHERE rowid IN (SELECT rowid FROM im_message WHERE {})",
+        conditions.join(" OR ")
+    );
+    conn.execute(Statement::from_sql_and_values(backend, sql, values))
+        .await?;
+    Ok(())
+}
+
+/// 删除整个房间的全文索引记录，需要在删除 im_message 行之前调用
+async fn remove_room_search_entries<C: ConnectionTrait>(
+    conn: &C,
+    room_id: &str,
+    login_uid: &str,
+) -> Result<(), CommonError> {
+    if !search_index_available(conn).await? {
+        return Ok(());
+    }
+
+    let backend = conn.get_database_backend();
+    let stmt = Statement::from_sql_and_values(
+        backend,
+        "DELETE FROM im_message_fts WHERE rowid IN (SELECT rowid FROM im_message WHERE room_id = ? AND login_uid = ?)",
+        vec![
+            Value::from(room_id.to_string()),
+            Value::from(login_uid.to_string()),
+        ],
+    );
+    conn.execute(stmt).await?;
+    Ok(())
+}
+
+/// 按消息当前内容重建全文索引记录，需要在写入 im_message 行之后调用
+async fn index_messages<C: ConnectionTrait>(
+    conn: &C,
+    messages: &[&im_message::Model],
+) -> Result<(), CommonError> {
+    if messages.is_empty() || !search_index_available(conn).await? {
+        return Ok(());
+    }
+
+    let backend = conn.get_database_backend();
+    for message in messages {
+        let key_values = vec![
+            Value::from(message.id.clone()),
+            Value::from(message.login_uid.clone()),
+        ];
+
+        let stmt = match extract_search_text(message) {
+            Some(text) => {
+                let mut values = vec![Value::from(text)];
+                values.extend(key_values);
+                Statement::from_sql_and_values(
+                    backend,
+                    "INSERT OR REPLACE INTO im_message_fts (rowid, content, id, login_uid, room_id)
+                     SELECT rowid, ?, id, login_uid, room_id FROM im_message WHERE id = ? AND login_uid = ?",
+                    values,
+                )
+            }
+            None => Statement::from_sql_and_values(
+                backend,
+                "DELETE FROM im_message_fts WHERE rowid IN (SELECT rowid FROM im_message WHERE id = ? AND login_uid = ?)",
+                key_values,
+            ),
+        };
+        conn.execute(stmt).await?;
+    }
+
+    Ok(())
+}
+
+/// 已有消息的全文索引回填完成标记，保存在 im_config 中
+const SEARCH_INDEX_BACKFILL_KEY: &str = "searchIndexBackfilled";
+/// 回填全文索引时每批处理的消息数量
+const SEARCH_INDEX_BACKFILL_BATCH: u64 = 500;
+
+/// 为建立全文索引之前已有的消息分批回填索引，提取规则与写入时的 extract_search_text 相同
+/// `after_id` 为上一批最后一条消息的ID，返回本批最后一条消息的ID，全部完成后返回 None 并记录完成标记
+pub async fn backfill_search_index(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    after_id: Option<&str>,
+) -> Result<Option<String>, CommonError> {
+    if !search_index_available(db).await?
+        || im_config_repository::get_config_by_key(db, SEARCH_INDEX_BACKFILL_KEY, login_uid)
+            .await?
+            .is_some()
+    {
+        return Ok(None);
+    }
+
+    let mut query = im_message::Entity::find()
+        .filter(im_message::Column::LoginUid.eq(login_uid))
+        .order_by_asc(im_message::Column::Id)
+        .limit(SEARCH_INDEX_BACKFILL_BATCH);
+    if let Some(after_id) = after_id {
+        query = query.filter(im_message::Column::Id.gt(after_id));
+    }
+    let messages = query.all(db).await?;
+
+    let Some(last) = messages.last().map(|message| message.id.clone()) else {
+        im_config_repository::save_or_update_config(
+            db,
+            SEARCH_INDEX_BACKFILL_KEY,
+            Some("1".to_string()),
+            login_uid,
+        )
+        .await?;
+        return Ok(None);
+    };
+
+    let txn = db.begin().await?;
+    index_messages(&txn, &messages.iter().collect::<Vec<_>>()).await?;
+    txn.commit().await?;
+    Ok(Some(last))
+}
+
+/// 全文索引与消息表的关联子句，消息表别名为 m
+const FTS_JOIN_CLAUSE: &str = "FROM im_message_fts \
+     JOIN im_message m ON m.rowid = im_message_fts.rowid \
+         AND m.id = im_message_fts.id AND m.login_uid = im_message_fts.login_uid";
+
+/// 消息可检索文本的 SQL 表达式（消息表别名为 m），全文索引不可用时使用，规则与 extract_search_text 一致
+const SEARCH_TEXT_SQL: &str = "TRIM(CASE WHEN json_valid(m.body) THEN \
+     COALESCE((SELECT GROUP_CONCAT(j.value, ' ') FROM json_each(m.body, '$.content') j \
+         WHERE j.type = 'text'), '') || ' ' || \
+     COALESCE(json_extract(m.body, '$.fileName'), json_extract(m.body, '$.filename'), \
+         json_extract(m.body, '$.file_name'), '') || ' ' || \
+     COALESCE(CASE json_type(m.body, '$.reply.body') \
+         WHEN 'text' THEN json_extract(m.body, '$.reply.body') \
+         ELSE json_extract(m.body, '$.reply.body.content') END, '') \
+     ELSE m.body END)";
+
+/// 追加全文索引的关键词过滤条件
+fn push_fts_keyword_filter(sql: &mut String, values: &mut Vec<Value>, terms: &[String]) {
+    if can_use_match(terms) {
+        sql.push_str(" AND im_message_fts MATCH ?");
+        values.push(Value::from(build_match_query(terms)));
+    } else {
+        for term in terms {
+            sql.push_str(" AND im_message_fts.content LIKE ? ESCAPE '\\'");
+            values.push(Value::from(escape_like_pattern(term)));
+        }
+    }
+}
+
+/// 按空白拆分搜索关键词并去重
+pub(crate) fn split_search_terms(keyword: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for term in keyword.split_whitespace() {
+        if !terms.iter().any(|existing| existing == term) {
+            terms.push(term.to_string());
+        }
+    }
+    terms
+}
+
+/// 所有查询词都足够长时才能使用 FTS5 MATCH
+fn can_use_match(terms: &[String]) -> bool {
+    !terms.is_empty()
+        && terms
+            .iter()
+            .all(|term| term.chars().count() >= MIN_MATCH_TERM_CHARS)
+}
+
+/// 构建 FTS5 MATCH 表达式，每个词作为短语处理，多个词之间为 AND 关系
+fn build_match_query(terms: &[String]) -> String {
+    terms
+        .iter()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub(crate) fn escape_like_pattern(term: &str) -> String {
+    let escaped = term
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// 在 Rust 侧生成命中摘要，用于 LIKE 退化查询
+pub(crate) fn build_snippet(text: &str, terms: &[String]) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| fold_char(*c)).collect();
+    let terms: Vec<Vec<char>> = terms
+        .iter()
+        .map(|term| term.chars().map(fold_char).collect::<Vec<char>>())
+        .filter(|term| !term.is_empty())
+        .collect();
+
+    let match_len_at = |pos: usize| -> Option<usize> {
+        terms
+            .iter()
+            .filter(|term| lower[pos..].starts_with(term))
+            .map(|term| term.len())
+            .max()
+    };
+
+    let first = (0..chars.len()).find(|&pos| match_len_at(pos).is_some())?;
+    let start = first.saturating_sub(SNIPPET_CHARS / 4);
+    let end = (start + SNIPPET_CHARS).min(chars.len());
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push_str("...");
+    }
+
+    let mut pos = start;
+    while pos < end {
+        if let Some(len) = match_len_at(pos) {
+            let match_end = (pos + len).min(chars.len());
+            snippet.push_str("<mark>");
+            snippet.extend(&chars[pos..match_end]);
+            snippet.push_str("</mark>");
+            pos = match_end;
+        } else {
+            snippet.push(chars[pos]);
+            pos += 1;
+        }
+    }
+
+    if pos < chars.len() {
+        snippet.push_str("...");
+    }
+    Some(snippet)
+}
+
+fn fold_char(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
 pub async fn save_all<C>(db: &C, messages: Vec<MessageWithThumbnail>) -> Result<(), CommonError>
 where
     C: ConnectionTrait,
@@ -377,12 +824,15 @@ where
     let mut messages = filtered_messages;
 
     let message_keys = collect_message_keys(&messages);
-    let existing_thumbnail_map = fetch_thumbnail_map(db, &message_keys).await?;
+    let existing_paths_map = fetch_local_paths_map(db, &message_keys).await?;
 
     for message in &mut messages {
-        if message.thumbnail_path.is_none() {
-            if let Some(path) = existing_thumbnail_map.get(&message.key()) {
-                message.thumbnail_path = Some(path.clone());
+        if let Some(paths) = existing_paths_map.get(&message.key()) {
+            if message.thumbnail_path.is_none() {
+                message.thumbnail_path = paths.thumbnail_path.clone();
+            }
+            if message.local_path.is_none() {
+                message.local_path = paths.local_path.clone();
             }
         }
     }
@@ -404,6 +854,21 @@ where
         .map_err(|e| anyhow::anyhow!("Failed to query existing messages: {}", e))?;
 
     if !existing_messages.is_empty() {
+        // 删除重建会丢失重发状态，从已有记录中保留
+        for message in &mut messages {
+            if let Some(existing) = existing_messages.iter().find(|existing| {
+                existing.id == message.message.id && existing.login_uid == message.message.login_uid
+            }) {
+                keep_send_retry(&mut message.message, existing);
+            }
+        }
+
+        let existing_keys: Vec<(String, String)> = existing_messages
+            .iter()
+            .map(|msg| (msg.id.clone(), msg.login_uid.clone()))
+            .collect();
+        remove_search_entries(db, &existing_keys).await?;
+
         let mut delete_condition = sea_orm::Condition::any();
         for msg in &existing_messages {
             delete_condition = delete_condition.add(
@@ -434,12 +899,22 @@ where
         .map_err(|e| anyhow::anyhow!("Failed to batch insert messages: {}", e))?;
 
     for message in &messages {
-        update_thumbnail_path(db, &message.key(), message.thumbnail_path.as_deref()).await?;
+        update_local_paths(db, message).await?;
     }
 
+    let models: Vec<&im_message::Model> = messages.iter().map(|msg| &msg.message).collect();
+    index_messages(db, &models).await?;
+
     Ok(())
 }
 
+/// 保留已有记录的重发次数、下一次重发时间与最近一次发送时间
+fn keep_send_retry(message: &mut im_message::Model, existing: &im_message::Model) {
+    message.send_attempts = existing.send_attempts;
+    message.next_retry_time = existing.next_retry_time;
+    message.last_attempt_time = existing.last_attempt_time;
+}
+
 /// 根据房间ID进行游标分页查询消息（包含消息标记）
 pub async fn cursor_page_messages(
     db: &DatabaseConnection,
@@ -532,13 +1007,16 @@ pub async fn save_message(
     .await
     .map_err(|e| anyhow::anyhow!("Failed to find message: {}", e))?;
 
-    if record.thumbnail_path.is_none() {
-        record.thumbnail_path =
-            fetch_thumbnail_path(db, &record.message.id, &record.message.login_uid).await?;
+    if record.thumbnail_path.is_none() || record.local_path.is_none() {
+        let paths = fetch_local_paths(db, &record.message.id, &record.message.login_uid).await?;
+        record.thumbnail_path = record.thumbnail_path.or(paths.thumbnail_path);
+        record.local_path = record.local_path.or(paths.local_path);
     }
 
-    // 如果已存在，则先删除
-    if existing_message.is_some() {
+    // 如果已存在，则保留重发状态后先删除
+    if let Some(existing) = existing_message {
+        keep_send_retry(&mut record.message, &existing);
+        remove_search_entries(db, &[record.key()]).await?;
         im_message::Entity::delete_by_id((
             record.message.id.clone(),
             record.message.login_uid.clone(),
@@ -565,7 +1043,8 @@ pub async fn save_message(
 
     let active_model = record.message.clone().into_active_model();
     im_message::Entity::insert(active_model).exec(db).await?;
-    update_thumbnail_path(db, &record.key(), record.thumbnail_path.as_deref()).await?;
+    update_local_paths(db, &record).await?;
+    index_messages(db, &[&record.message]).await?;
     Ok(record)
 }
 
@@ -574,6 +1053,8 @@ pub async fn delete_message_by_id(
     message_id: &str,
     login_uid: &str,
 ) -> Result<u64, CommonError> {
+    remove_search_entries(db, &[(message_id.to_string(), login_uid.to_string())]).await?;
+
     let result = im_message::Entity::delete_many()
         .filter(im_message::Column::Id.eq(message_id))
         .filter(im_message::Column::LoginUid.eq(login_uid))
@@ -588,6 +1069,8 @@ pub async fn delete_messages_by_room(
     room_id: &str,
     login_uid: &str,
 ) -> Result<u64, CommonError> {
+    remove_room_search_entries(db, room_id, login_uid).await?;
+
     let result = im_message::Entity::delete_many()
         .filter(im_message::Column::RoomId.eq(room_id))
         .filter(im_message::Column::LoginUid.eq(login_uid))
@@ -620,6 +1103,91 @@ pub async fn get_room_max_message_id(
     }
 }
 
+/// 统计房间内消息ID位于 `[min_id, max_id]` 区间的本地消息数量
+pub async fn count_room_messages_in_id_range(
+    db: &DatabaseConnection,
+    room_id: &str,
+    login_uid: &str,
+    min_id: i64,
+    max_id: i64,
+) -> Result<u64, CommonError> {
+    let stmt = Statement::from_sql_and_values(
+        db.get_database_backend(),
+        "SELECT COUNT(*) as count FROM im_message \
+         WHERE room_id = ? AND login_uid = ? AND CAST(id AS INTEGER) BETWEEN ? AND ?",
+        vec![
+            Value::from(room_id.to_string()),
+            Value::from(login_uid.to_string()),
+            Value::from(min_id),
+            Value::from(max_id),
+        ],
+    );
+
+    let count: i64 = match db.query_one(stmt).await? {
+        Some(row) => row.try_get("", "count")?,
+        None => 0,
+    };
+    Ok(count as u64)
+}
+
+/// 找出本地缺失的消息ID，`messages` 为消息ID与发送时间
+/// 已在本地删除或清空聊天记录之前的消息不会再写入，不算缺失
+pub async fn find_missing_message_ids(
+    db: &DatabaseConnection,
+    room_id: &str,
+    login_uid: &str,
+    messages: &[(String, Option<i64>)],
+) -> Result<Vec<String>, CommonError> {
+    if messages.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let existing: Vec<String> = im_message::Entity::find()
+        .select_only()
+        .column(im_message::Column::Id)
+        .filter(im_message::Column::LoginUid.eq(login_uid))
+        .filter(im_message::Column::Id.is_in(messages.iter().map(|(id, _)| id.clone())))
+        .into_tuple()
+        .all(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to query existing messages: {}", e))?;
+
+    let mut missing = Vec::new();
+    for (id, send_time) in messages {
+        if existing.contains(id) {
+            continue;
+        }
+        if !should_skip_message_insert(db, id, room_id, login_uid, *send_time).await? {
+            missing.push(id.clone());
+        }
+    }
+    Ok(missing)
+}
+
+/// 获取房间内最新一条消息的发送时间
+pub async fn get_room_latest_send_time<C>(
+    db: &C,
+    room_id: &str,
+    login_uid: &str,
+) -> Result<Option<i64>, CommonError>
+where
+    C: ConnectionTrait,
+{
+    let send_time = im_message::Entity::find()
+        .filter(im_message::Column::RoomId.eq(room_id))
+        .filter(im_message::Column::LoginUid.eq(login_uid))
+        .order_by_desc(im_message::Column::SendTime)
+        .select_only()
+        .column(im_message::Column::SendTime)
+        .into_tuple::<Option<i64>>()
+        .one(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to query last send_time: {}", e))?
+        .flatten();
+
+    Ok(send_time)
+}
+
 pub async fn get_room_id_by_message_id(
     db: &DatabaseConnection,
     message_id: &str,
@@ -779,18 +1347,174 @@ pub async fn update_message_status(
 
     let updated_model = active_model.try_into_model()?;
     record.message = updated_model;
-    update_thumbnail_path(db, &record.key(), record.thumbnail_path.as_deref()).await?;
+    update_local_paths(db, &record).await?;
+    // 发送成功后消息ID会变化，需要同步刷新索引中的ID
+    index_messages(db, &[&record.message]).await?;
     Ok(record)
 }
 
-/// 更新消息撤回状态
-pub async fn update_message_recall_status(
+/// 获取消息已自动重发的次数
+pub async fn get_send_attempts(
     db: &DatabaseConnection,
     message_id: &str,
-    message_type: u8,
-    message_body: &str,
     login_uid: &str,
+) -> Result<i32, CommonError> {
+    let backend = db.get_database_backend();
+    let stmt = Statement::from_sql_and_values(
+        backend,
+        "SELECT send_attempts FROM im_message WHERE id = ? AND login_uid = ? LIMIT 1",
+        vec![
+            Value::from(message_id.to_string()),
+            Value::from(login_uid.to_string()),
+        ],
+    );
+
+    if let Some(row) = db.query_one(stmt).await? {
+        let attempts: Option<i32> = row.try_get("", "send_attempts")?;
+        Ok(attempts.unwrap_or(0))
+    } else {
+        Ok(0)
+    }
+}
+
+/// 更新消息的重发次数与下一次重发时间
+pub async fn update_send_retry(
+    db: &DatabaseConnection,
+    message_id: &str,
+    login_uid: &str,
+    attempts: i32,
+    next_retry_time: Option<i64>,
 ) -> Result<(), CommonError> {
+    let backend = db.get_database_backend();
+    let stmt = Statement::from_sql_and_values(
+        backend,
+        "UPDATE im_message SET send_attempts = ?, next_retry_time = ? WHERE id = ? AND login_uid = ?",
+        vec![
+            Value::from(attempts),
+            match next_retry_time {
+                Some(time) => Value::from(time),
+                None => Value::BigInt(None),
+            },
+            Value::from(message_id.to_string()),
+            Value::from(login_uid.to_string()),
+        ],
+    );
+    db.execute(stmt).await?;
+    Ok(())
+}
+
+/// 记录消息开始发送的时间
+pub async fn mark_send_started(
+    db: &DatabaseConnection,
+    message_id: &str,
+    login_uid: &str,
+    started_at: i64,
+) -> Result<(), CommonError> {
+    let backend = db.get_database_backend();
+    let stmt = Statement::from_sql_and_values(
+        backend,
+        "UPDATE im_message SET last_attempt_time = ? WHERE id = ? AND login_uid = ?",
+        vec![
+            Value::from(started_at),
+            Value::from(message_id.to_string()),
+            Value::from(login_uid.to_string()),
+        ],
+    );
+    db.execute(stmt).await?;
+    Ok(())
+}
+
+/// 查询需要自动重发的消息：已到重发时间的失败消息，以及发送中断的 pending 消息
+/// pending 消息以最近一次开始发送的时间判断，从未开始发送的以消息发送时间判断
+/// 返回消息及其已重发次数，按发送时间升序排列以保证重发顺序
+pub async fn list_retryable_messages(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    now: i64,
+    stale_pending_before: i64,
+    max_attempts: i32,
+    limit: u64,
+) -> Result<Vec<(MessageWithThumbnail, i32)>, CommonError> {
+    let backend = db.get_database_backend();
+    let stmt = Statement::from_sql_and_values(
+        backend,
+        "SELECT * FROM im_message
+         WHERE login_uid = ?
+           AND COALESCE(send_attempts, 0) < ?
+           AND (
+               (send_status = 'fail' AND COALESCE(next_retry_time, 0) <= ?)
+               OR (send_status = 'pending' AND COALESCE(last_attempt_time, send_time, 0) <= ?)
+           )
+         ORDER BY send_time ASC
+         LIMIT ?",
+        vec![
+            Value::from(login_uid.to_string()),
+            Value::from(max_attempts),
+            Value::from(now),
+            Value::from(stale_pending_before),
+            Value::from(limit as i64),
+        ],
+    );
+
+    let rows = db
+        .query_all(stmt)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to query retryable messages: {}", e))?;
+
+    let mut messages = Vec::with_capacity(rows.len());
+    for row in rows {
+        let message = im_message::Model::from_query_result(&row, "")?;
+        let thumbnail_path: Option<String> = row.try_get("", "thumbnail_path")?;
+        let local_path: Option<String> = row.try_get("", "local_path")?;
+        let attempts = message.send_attempts;
+        messages.push((
+            MessageWithThumbnail::new(message, thumbnail_path).with_local_path(local_path),
+            attempts,
+        ));
+    }
+
+    Ok(messages)
+}
+
+/// 重置未发送成功消息的重发次数，未指定消息ID时重置全部
+pub async fn reset_send_attempts(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    message_ids: Option<&[String]>,
+) -> Result<u64, CommonError> {
+    let backend = db.get_database_backend();
+    let mut sql = String::from(
+        "UPDATE im_message SET send_attempts = 0, next_retry_time = NULL
+         WHERE login_uid = ? AND send_status IN ('fail', 'pending')",
+    );
+    let mut values = vec![Value::from(login_uid.to_string())];
+
+    if let Some(ids) = message_ids {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        sql.push_str(&format!(" AND id IN ({})", placeholders));
+        values.extend(ids.iter().map(|id| Value::from(id.clone())));
+    }
+
+    let result = db
+        .execute(Statement::from_sql_and_values(backend, sql, values))
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// 更新消息撤回状态
+pub async fn update_message_recall_status<C>(
+    db: &C,
+    message_id: &str,
+    message_type: u8,
+    message_body: &str,
+    login_uid: &str,
+) -> Result<(), CommonError>
+where
+    C: ConnectionTrait,
+{
     info!(
         "[RECALL] Updating message recall status in database, message_id: {}",
         message_id
@@ -816,11 +1540,14 @@ pub async fn update_message_recall_status(
     active_model.update_time = Set(Some(chrono::Utc::now().timestamp_millis()));
 
     // 执行更新
-    im_message::Entity::update(active_model)
+    let updated_message = im_message::Entity::update(active_model)
         .exec(db)
         .await
         .map_err(|e| anyhow::anyhow!("Failed to update message recall status: {}", e))?;
 
+    // 撤回后的消息不再参与检索
+    index_messages(db, &[&updated_message]).await?;
+
     info!(
         "[RECALL] Successfully updated message recall status in database, message_id: {}",
         message_id
@@ -829,15 +1556,26 @@ pub async fn update_message_recall_status(
 }
 
 /// 支持消息类型筛选、关键词搜索、日期排序和分页
+/// 带关键词时优先走全文索引，返回命中摘要与相关度
 pub async fn query_chat_history(
     db: &DatabaseConnection,
     condition: crate::command::chat_history_command::ChatHistoryQueryCondition,
-) -> Result<Vec<MessageWithThumbnail>, CommonError> {
+) -> Result<Vec<MessageSearchHit>, CommonError> {
     info!(
         "查询聊天历史记录 - 房间: {}, 类型: {:?}, 关键词: {:?}",
         condition.room_id, condition.message_type, condition.search_keyword
     );
 
+    let terms = condition
+        .search_keyword
+        .as_deref()
+        .map(split_search_terms)
+        .unwrap_or_default();
+
+    if !terms.is_empty() && search_index_available(db).await? {
+        return search_chat_history(db, &condition, &terms).await;
+    }
+
     // 构建基础查询条件
     let mut conditions = Condition::all()
         .add(im_message::Column::LoginUid.eq(&condition.login_uid))
@@ -853,7 +1591,7 @@ pub async fn query_chat_history(
         conditions = conditions.add(type_condition);
     }
 
-    // 关键词搜索（支持消息内容与文件名等字段）
+    // 关键词搜索（索引不可用时的兜底方案，支持消息内容与文件名等字段）
     if let Some(ref keyword) = condition.search_keyword {
         let trimmed = keyword.trim();
         if !trimmed.is_empty() {
@@ -880,15 +1618,9 @@ pub async fn query_chat_history(
     // 日期范围筛选
     if let Some(ref date_range) = condition.date_range {
         if let Some(start_time) = date_range.start_time {
-            chrono::DateTime::from_timestamp_millis(start_time)
-                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
-                .unwrap_or_else(|| "无效时间".to_string());
             conditions = conditions.add(im_message::Column::SendTime.gte(start_time));
         }
         if let Some(end_time) = date_range.end_time {
-            chrono::DateTime::from_timestamp_millis(end_time)
-                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
-                .unwrap_or_else(|| "无效时间".to_string());
             conditions = conditions.add(im_message::Column::SendTime.lte(end_time));
         }
     }
@@ -896,12 +1628,13 @@ pub async fn query_chat_history(
     // 构建分页查询
     let mut query = im_message::Entity::find().filter(conditions);
 
-    // 应用排序
+    // 应用排序，无索引时相关度排序退化为时间降序
     query = match condition.sort_order {
         crate::command::chat_history_command::SortOrder::Asc => {
             query.order_by_asc(im_message::Column::SendTime)
         }
-        crate::command::chat_history_command::SortOrder::Desc => {
+        crate::command::chat_history_command::SortOrder::Desc
+        | crate::command::chat_history_command::SortOrder::Relevance => {
             query.order_by_desc(im_message::Column::SendTime)
         }
     };
@@ -918,7 +1651,331 @@ pub async fn query_chat_history(
         .await
         .map_err(|e| anyhow::anyhow!("查询聊天历史记录失败: {}", e))?;
 
-    enrich_models_with_thumbnails(db, messages).await
+    let enriched = enrich_models_with_thumbnails(db, messages).await?;
+    Ok(enriched
+        .into_iter()
+        .map(|record| {
+            let snippet = if terms.is_empty() {
+                None
+            } else {
+                extract_search_text(&record.message).and_then(|text| build_snippet(&text, &terms))
+            };
+            MessageSearchHit {
+                record,
+                snippet,
+                score: None,
+            }
+        })
+        .collect())
+}
+
+/// 基于 im_message_fts 的关键词检索
+async fn search_chat_history(
+    db: &DatabaseConnection,
+    condition: &crate::command::chat_history_command::ChatHistoryQueryCondition,
+    terms: &[String],
+) -> Result<Vec<MessageSearchHit>, CommonError> {
+    let use_match = can_use_match(terms);
+
+    let mut sql = String::from("SELECT m.*, im_message_fts.content AS search_content, ");
+    if use_match {
+        sql.push_str(
+            "snippet(im_message_fts, 0, '<mark>', '</mark>', '...', 24) AS search_snippet, \
+             bm25(im_message_fts) AS search_rank ",
+        );
+    } else {
+        sql.push_str("NULL AS search_snippet, NULL AS search_rank ");
+    }
+    sql.push_str(FTS_JOIN_CLAUSE);
+    sql.push_str(" WHERE m.login_uid = ? AND m.room_id = ?");
+
+    let mut values = vec![
+        Value::from(condition.login_uid.clone()),
+        Value::from(condition.room_id.clone()),
+    ];
+    push_fts_keyword_filter(&mut sql, &mut values, terms);
+
+    if let Some(ref message_types) = condition.message_type
+        && !message_types.is_empty()
+    {
+        let placeholders = vec!["?"; message_types.len()].join(", ");
+        sql.push_str(&format!(" AND m.message_type IN ({})", placeholders));
+        values.extend(message_types.iter().map(|t| Value::from(*t)));
+    }
+
+    if let Some(ref date_range) = condition.date_range {
+        if let Some(start_time) = date_range.start_time {
+            sql.push_str(" AND m.send_time >= ?");
+            values.push(Value::from(start_time));
+        }
+        if let Some(end_time) = date_range.end_time {
+            sql.push_str(" AND m.send_time <= ?");
+            values.push(Value::from(end_time));
+        }
+    }
+
+    // bm25() 越小越相关
+    sql.push_str(match condition.sort_order {
+        crate::command::chat_history_command::SortOrder::Relevance if use_match => {
+            " ORDER BY search_rank ASC, m.send_time DESC"
+        }
+        crate::command::chat_history_command::SortOrder::Asc => " ORDER BY m.send_time ASC",
+        _ => " ORDER BY m.send_time DESC",
+    });
+
+    let offset = (condition.pagination.page.saturating_sub(1)) * condition.pagination.page_size;
+    sql.push_str(" LIMIT ? OFFSET ?");
+    values.push(Value::from(condition.pagination.page_size as i64));
+    values.push(Value::from(offset as i64));
+
+    let stmt = Statement::from_sql_and_values(db.get_database_backend(), sql, values);
+    let rows = db
+        .query_all(stmt)
+        .await
+        .map_err(|e| anyhow::anyhow!("全文检索聊天历史记录失败: {}", e))?;
+
+    rows.iter()
+        .map(|row| search_hit_from_row(row, terms))
+        .collect()
+}
+
+/// 解析检索结果行，行中需包含 search_content、search_snippet 和 search_rank 列
+fn search_hit_from_row(
+    row: &QueryResult,
+    terms: &[String],
+) -> Result<MessageSearchHit, CommonError> {
+    let message = im_message::Model::from_query_result(row, "")?;
+    let thumbnail_path: Option<String> = row.try_get("", "thumbnail_path")?;
+    let local_path: Option<String> = row.try_get("", "local_path")?;
+    let snippet: Option<String> = row.try_get("", "search_snippet")?;
+    let content: Option<String> = row.try_get("", "search_content")?;
+    let rank: Option<f64> = row.try_get("", "search_rank")?;
+
+    Ok(MessageSearchHit {
+        record: MessageWithThumbnail::new(message, thumbnail_path).with_local_path(local_path),
+        snippet: snippet.or_else(|| content.and_then(|text| build_snippet(&text, terms))),
+        score: rank.map(|rank| -rank),
+    })
+}
+
+/// 跨房间搜索结果中的单个房间分组
+#[derive(Clone)]
+pub struct RoomSearchGroup {
+    pub room_id: String,
+    /// 会话备注或名称，缺失时使用房间名称
+    pub room_name: Option<String>,
+    pub avatar: Option<String>,
+    pub hit_count: u64,
+    pub last_hit_time: i64,
+    /// 房间内相关度最高的若干条命中消息
+    pub hits: Vec<MessageSearchHit>,
+}
+
+/// 跨房间搜索当前用户的全部消息，并按房间分组统计命中数
+/// 分组按最近命中时间降序排列，游标格式为 `{last_hit_time}_{room_id}`
+pub async fn search_all_messages(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    keyword: &str,
+    message_types: Option<&[u8]>,
+    hits_per_room: u32,
+    cursor_page_param: CursorPageParam,
+) -> Result<CursorPageResp<Vec<RoomSearchGroup>>, CommonError> {
+    let terms = split_search_terms(keyword);
+    if terms.is_empty() {
+        return Ok(CursorPageResp {
+            cursor: String::new(),
+            is_last: true,
+            list: Some(vec![]),
+            total: 0,
+        });
+    }
+
+    // 命中消息的查询条件，分组统计和房间内命中列表共用，保证两者一致
+    let use_fts = search_index_available(db).await?;
+    let use_match = use_fts && can_use_match(&terms);
+    let mut hit_from = String::new();
+    let mut hit_values = vec![Value::from(login_uid.to_string())];
+    if use_fts {
+        hit_from.push_str(FTS_JOIN_CLAUSE);
+        hit_from.push_str(" WHERE m.login_uid = ?");
+        push_fts_keyword_filter(&mut hit_from, &mut hit_values, &terms);
+    } else {
+        hit_from.push_str(
+            "FROM im_message m WHERE m.login_uid = ? AND COALESCE(m.message_type, 0) <> 2",
+        );
+        for term in &terms {
+            hit_from.push_str(&format!(
+                " AND LOWER({}) LIKE ? ESCAPE '\\'",
+                SEARCH_TEXT_SQL
+            ));
+            hit_values.push(Value::from(escape_like_pattern(&term.to_lowercase())));
+        }
+    }
+
+    if let Some(message_types) = message_types
+        && !message_types.is_empty()
+    {
+        let placeholders = vec!["?"; message_types.len()].join(", ");
+        hit_from.push_str(&format!(" AND m.message_type IN ({})", placeholders));
+        hit_values.extend(message_types.iter().map(|t| Value::from(*t)));
+    }
+    let hit_sql = format!(
+        "SELECT m.room_id AS room_id, m.send_time AS send_time {}",
+        hit_from
+    );
+
+    let backend = db.get_database_backend();
+
+    // 统计命中的房间总数
+    let total_stmt = Statement::from_sql_and_values(
+        backend,
+        format!(
+            "WITH hits AS ({}) SELECT COUNT(DISTINCT room_id) AS total FROM hits",
+            hit_sql
+        ),
+        hit_values.clone(),
+    );
+    let total = match db.query_one(total_stmt).await? {
+        Some(row) => row.try_get::<i64>("", "total")? as u64,
+        None => 0,
+    };
+
+    // 按房间分组，并关联会话与房间信息获取展示名称
+    let mut group_sql = format!(
+        "WITH hits AS ({}), \
+         room_hits AS ( \
+             SELECT room_id, COUNT(*) AS hit_count, MAX(COALESCE(send_time, 0)) AS last_hit_time \
+             FROM hits GROUP BY room_id \
+         ) \
+         SELECT g.room_id, g.hit_count, g.last_hit_time, \
+             (SELECT COALESCE(NULLIF(c.remark, ''), NULLIF(c.contact_name, '')) FROM im_contact c \
+                 WHERE c.room_id = g.room_id AND c.login_uid = ? LIMIT 1) AS contact_name, \
+             (SELECT c.avatar FROM im_contact c \
+                 WHERE c.room_id = g.room_id AND c.login_uid = ? LIMIT 1) AS contact_avatar, \
+             (SELECT r.room_name FROM im_room r \
+                 WHERE r.room_id = g.room_id AND r.login_uid = ? LIMIT 1) AS room_name, \
+             (SELECT r.avatar FROM im_room r \
+                 WHERE r.room_id = g.room_id AND r.login_uid = ? LIMIT 1) AS room_avatar \
+         FROM room_hits g",
+        hit_sql
+    );
+    let mut group_values = hit_values.clone();
+    for _ in 0..4 {
+        group_values.push(Value::from(login_uid.to_string()));
+    }
+
+    if let Some((time, room_id)) = cursor_page_param.cursor.split_once('_')
+        && let Ok(time) = time.parse::<i64>()
+    {
+        group_sql.push_str(" WHERE g.last_hit_time < ? OR (g.last_hit_time = ? AND g.room_id < ?)");
+        group_values.push(Value::from(time));
+        group_values.push(Value::from(time));
+        group_values.push(Value::from(room_id.to_string()));
+    }
+
+    // 多取一条用于判断是否还有下一页
+    let page_size = cursor_page_param.page_size as usize;
+    group_sql.push_str(" ORDER BY g.last_hit_time DESC, g.room_id DESC LIMIT ?");
+    group_values.push(Value::from(page_size as i64 + 1));
+
+    let mut rows = db
+        .query_all(Statement::from_sql_and_values(
+            backend,
+            group_sql,
+            group_values,
+        ))
+        .await
+        .map_err(|e| anyhow::anyhow!("跨房间搜索消息失败: {}", e))?;
+    let is_last = rows.len() <= page_size;
+    rows.truncate(page_size);
+
+    let mut groups = Vec::with_capacity(rows.len());
+    for row in rows {
+        let room_id: String = row.try_get("", "room_id")?;
+        let hit_count: i64 = row.try_get("", "hit_count")?;
+        let last_hit_time: i64 = row.try_get("", "last_hit_time")?;
+        let contact_name: Option<String> = row.try_get("", "contact_name")?;
+        let contact_avatar: Option<String> = row.try_get("", "contact_avatar")?;
+        let room_name: Option<String> = row.try_get("", "room_name")?;
+        let room_avatar: Option<String> = row.try_get("", "room_avatar")?;
+
+        groups.push(RoomSearchGroup {
+            room_id,
+            room_name: contact_name.or(room_name),
+            avatar: contact_avatar.or(room_avatar),
+            hit_count: hit_count as u64,
+            last_hit_time,
+            hits: Vec::new(),
+        });
+    }
+
+    // 一次查询取出本页所有房间中相关度最高的若干条命中消息
+    if hits_per_room > 0 && !groups.is_empty() {
+        let columns = if use_match {
+            "im_message_fts.content AS search_content, \
+             snippet(im_message_fts, 0, '<mark>', '</mark>', '...', 24) AS search_snippet, \
+             bm25(im_message_fts) AS search_rank"
+                .to_string()
+        } else if use_fts {
+            "im_message_fts.content AS search_content, NULL AS search_snippet, NULL AS search_rank"
+                .to_string()
+        } else {
+            format!(
+                "{} AS search_content, NULL AS search_snippet, NULL AS search_rank",
+                SEARCH_TEXT_SQL
+            )
+        };
+        let placeholders = vec!["?"; groups.len()].join(", ");
+        let sql = format!(
+            "SELECT * FROM ( \
+                 SELECT h.*, ROW_NUMBER() OVER ( \
+                     PARTITION BY h.room_id ORDER BY h.search_rank ASC, h.send_time DESC \
+                 ) AS search_row \
+                 FROM (SELECT m.*, {} {} AND m.room_id IN ({})) h \
+             ) WHERE search_row <= ? ORDER BY room_id, search_row",
+            columns, hit_from, placeholders
+        );
+        let mut values = hit_values;
+        values.extend(
+            groups
+                .iter()
+                .map(|group| Value::from(group.room_id.clone())),
+        );
+        values.push(Value::from(hits_per_room as i64));
+
+        let rows = db
+            .query_all(Statement::from_sql_and_values(backend, sql, values))
+            .await
+            .map_err(|e| anyhow::anyhow!("跨房间搜索消息失败: {}", e))?;
+        let mut hits_by_room: HashMap<String, Vec<MessageSearchHit>> = HashMap::new();
+        for row in &rows {
+            let hit = search_hit_from_row(row, &terms)?;
+            hits_by_room
+                .entry(hit.record.message.room_id.clone())
+                .or_default()
+                .push(hit);
+        }
+        for group in &mut groups {
+            group.hits = hits_by_room.remove(&group.room_id).unwrap_or_default();
+        }
+    }
+
+    let cursor = if is_last {
+        String::new()
+    } else {
+        groups
+            .last()
+            .map(|group| format!("{}_{}", group.last_hit_time, group.room_id))
+            .unwrap_or_default()
+    };
+
+    Ok(CursorPageResp {
+        cursor,
+        is_last,
+        list: Some(groups),
+        total,
+    })
 }
 
 /// 专门用于文件管理的查询函数，支持跨房间查询文件类型消息
@@ -949,13 +2006,35 @@ pub async fn query_file_messages(
         conditions = conditions.add(type_condition);
     }
 
-    // 关键词搜索（搜索文件名、来源等关键信息）
+    // 关键词搜索（优先使用全文索引，索引不可用时搜索文件名、来源等关键信息）
     if let Some(keyword) = search_keyword {
-        let trimmed_keyword = keyword.trim();
-        if !trimmed_keyword.is_empty() {
-            let keyword_lower = trimmed_keyword.to_lowercase();
+        let terms = split_search_terms(keyword);
+        if !terms.is_empty() && search_index_available(db).await? {
+            // 同时按 id 和 login_uid 关联，避免 rowid 被复用时命中其他消息
+            if can_use_match(&terms) {
+                conditions = conditions.add(Expr::cust_with_values(
+                    "(rowid, id) IN (SELECT rowid, id FROM im_message_fts \
+                     WHERE im_message_fts MATCH ? AND login_uid = ?)",
+                    [
+                        Value::from(build_match_query(&terms)),
+                        Value::from(login_uid.to_string()),
+                    ],
+                ));
+            } else {
+                for term in &terms {
+                    conditions = conditions.add(Expr::cust_with_values(
+                        "(rowid, id) IN (SELECT rowid, id FROM im_message_fts \
+                         WHERE login_uid = ? AND content LIKE ? ESCAPE '\\')",
+                        [
+                            Value::from(login_uid.to_string()),
+                            Value::from(escape_like_pattern(term)),
+                        ],
+                    ));
+                }
+            }
+        } else if !terms.is_empty() {
+            let keyword_lower = keyword.trim().to_lowercase();
             let keyword_pattern = format!("%{}%", keyword_lower);
-            use sea_orm::sea_query::Value;
 
             let json_paths = [
                 "$.fileName",
@@ -1009,3 +2088,417 @@ pub async fn query_file_messages(
 
     enrich_models_with_thumbnails(db, messages).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(message_type: u8, body: &str) -> im_message::Model {
+        im_message::Model {
+            id: "1".to_string(),
+            uid: "10001".to_string(),
+            nickname: None,
+            room_id: "1".to_string(),
+            send_time: Some(1),
+            message_type: Some(message_type),
+            body: Some(body.to_string()),
+            message_marks: None,
+            create_time: None,
+            update_time: None,
+            login_uid: "10001".to_string(),
+            send_status: "success".to_string(),
+            time_block: None,
+            send_attempts: 0,
+            next_retry_time: None,
+            last_attempt_time: None,
+        }
+    }
+
+    fn terms(keyword: &str) -> Vec<String> {
+        split_search_terms(keyword)
+    }
+
+    #[test]
+    fn test_extract_search_text_by_body_type() {
+        let cases = [
+            (1, r#"{"content":"你好 HuLa"}"#, Some("你好 HuLa")),
+            (1, "  纯文本消息  ", Some("纯文本消息")),
+            (
+                4,
+                r#"{"fileName":"季度报告.pdf","size":1024,"url":"https://x/a.pdf"}"#,
+                Some("季度报告.pdf"),
+            ),
+            (6, r#"{"file_name":"demo.mp4"}"#, Some("demo.mp4")),
+            (
+                1,
+                r#"{"content":"收到","reply":{"body":"明天开会"}}"#,
+                Some("收到 明天开会"),
+            ),
+            (
+                1,
+                r#"{"content":"好的","reply":{"body":{"content":"发一下文档"}}}"#,
+                Some("好的 发一下文档"),
+            ),
+            (
+                12,
+                r#"{"content":["张三：早上好","李四：[图片]"],"messageList":[]}"#,
+                Some("张三：早上好 李四：[图片]"),
+            ),
+            (3, r#"{"url":"https://x/a.png","width":10}"#, None),
+            (2, r#"{"content":"已撤回的内容"}"#, None),
+        ];
+        for (message_type, body, expected) in cases {
+            assert_eq!(
+                extract_search_text(&message(message_type, body)).as_deref(),
+                expected,
+                "body: {}",
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn test_match_or_like_by_term_length() {
+        assert!(can_use_match(&terms("hula 会议纪要")));
+        assert_eq!(
+            build_match_query(&terms("hula \"q\"a")),
+            "\"hula\" \"\"\"q\"\"a\""
+        );
+        // trigram 无法匹配 1~2 个字符的词，只要有一个短词就整体退化为 LIKE
+        assert!(!can_use_match(&terms("会议")));
+        assert!(!can_use_match(&terms("hula 会议")));
+        assert!(!can_use_match(&terms("go")));
+        assert!(!can_use_match(&[]));
+        assert_eq!(escape_like_pattern("50%_a\\b"), "%50\\%\\_a\\\\b%");
+    }
+
+    #[test]
+    fn test_snippet_cuts_on_char_boundaries() {
+        let text = format!("{}会议纪要{}", "前".repeat(20), "后".repeat(40));
+        let snippet = build_snippet(&text, &terms("会议")).unwrap();
+        assert_eq!(
+            snippet,
+            format!(
+                "...{}<mark>会议</mark>纪要{}...",
+                "前".repeat(8),
+                "后".repeat(20)
+            )
+        );
+
+        assert_eq!(
+            build_snippet("开会了 HuLa", &terms("hula 开会")).unwrap(),
+            "<mark>开会</mark>了 <mark>HuLa</mark>"
+        );
+        assert!(build_snippet("没有命中", &terms("会议")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_local_path_dropped_when_file_missing() {
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE im_message (id TEXT NOT NULL, login_uid TEXT NOT NULL, \
+             thumbnail_path TEXT, local_path TEXT, PRIMARY KEY (id, login_uid))",
+        )
+        .await
+        .unwrap();
+        let file = std::env::temp_dir().join(format!("hula-local-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&file, b"media").unwrap();
+        let existing = file.to_string_lossy().to_string();
+        for (id, path) in [
+            ("1", existing.as_str()),
+            ("2", "/nonexistent/hula/evicted.png"),
+        ] {
+            db.execute(Statement::from_sql_and_values(
+                db.get_database_backend(),
+                "INSERT INTO im_message (id, login_uid, local_path) VALUES (?, '10001', ?)",
+                [id.into(), path.into()],
+            ))
+            .await
+            .unwrap();
+        }
+
+        let mut second = message(1, "{}");
+        second.id = "2".to_string();
+        let enriched = enrich_models_with_thumbnails(&db, vec![message(1, "{}"), second])
+            .await
+            .unwrap();
+        assert_eq!(enriched[0].local_path.as_deref(), Some(existing.as_str()));
+        assert_eq!(enriched[1].local_path, None);
+        let _ = std::fs::remove_file(file);
+    }
+
+    async fn search_page(
+        db: &DatabaseConnection,
+        keyword: &str,
+        hits_per_room: u32,
+        page_size: u32,
+        cursor: &str,
+    ) -> CursorPageResp<Vec<RoomSearchGroup>> {
+        search_all_messages(
+            db,
+            "u",
+            keyword,
+            None,
+            hits_per_room,
+            CursorPageParam {
+                page_size,
+                cursor: cursor.to_string(),
+                create_id: None,
+                create_time: None,
+                update_time: None,
+            },
+        )
+        .await
+        .unwrap()
+    }
+
+    /// 全文索引是否可用的标记是全局的，依赖它的测试需要串行执行
+    static SEARCH_INDEX_TEST_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    #[tokio::test]
+    async fn test_search_all_messages() {
+        let _guard = SEARCH_INDEX_TEST_LOCK.lock().await;
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        for sql in [
+            "CREATE TABLE im_message (id TEXT NOT NULL, uid TEXT NOT NULL, nickname TEXT, \
+             room_id TEXT NOT NULL, send_time INTEGER, message_type INTEGER, body TEXT, \
+             message_marks TEXT, create_time INTEGER, update_time INTEGER, \
+             login_uid TEXT NOT NULL, send_status TEXT NOT NULL, time_block INTEGER, \
+             send_attempts INTEGER NOT NULL DEFAULT 0, next_retry_time INTEGER, \
+             last_attempt_time INTEGER, thumbnail_path TEXT, local_path TEXT, \
+             PRIMARY KEY (id, login_uid))",
+            "CREATE TABLE im_contact (room_id TEXT, login_uid TEXT, remark TEXT, \
+             contact_name TEXT, avatar TEXT)",
+            "CREATE TABLE im_room (room_id TEXT, login_uid TEXT, room_name TEXT, avatar TEXT)",
+            "INSERT INTO im_contact VALUES ('3', 'u', '', '张三', 'a.png')",
+            "INSERT INTO im_room VALUES ('3', 'u', '群聊', 'b.png')",
+        ] {
+            db.execute_unprepared(sql).await.unwrap();
+        }
+
+        // 前三个房间的最近命中时间相同，房间ID包含下划线时游标也要能正确拆分
+        let rows = [
+            ("1", "1", 100, r#"{"content":"周会纪要"}"#),
+            ("2", "2_x", 100, r#"{"content":"周会改期"}"#),
+            ("3", "2_x", 90, r#"{"content":"周会地点"}"#),
+            ("4", "3", 100, r#"{"content":"周会"}"#),
+            ("5", "9", 50, r#"{"content":"周会"}"#),
+            ("6", "8", 200, r#"{"content":"无关消息"}"#),
+            // 关键词只出现在不参与检索的字段中
+            ("7", "7", 300, r#"{"content":"图片","url":"周会.png"}"#),
+        ];
+        for (id, room_id, send_time, body) in rows {
+            db.execute(Statement::from_sql_and_values(
+                db.get_database_backend(),
+                "INSERT INTO im_message (id, uid, room_id, send_time, message_type, body, \
+                 login_uid, send_status) VALUES (?, 'u', ?, ?, 1, ?, 'u', 'success')",
+                [
+                    id.into(),
+                    room_id.into(),
+                    Value::from(send_time as i64),
+                    body.into(),
+                ],
+            ))
+            .await
+            .unwrap();
+        }
+        reset_table_initialization_flags();
+
+        let mut cursor = String::new();
+        let mut pages = Vec::new();
+        loop {
+            let page = search_page(&db, "周会", 0, 2, &cursor).await;
+            assert_eq!(page.total, 4);
+            pages.push(
+                page.list
+                    .unwrap()
+                    .into_iter()
+                    .map(|group| (group.room_id, group.last_hit_time, group.hit_count))
+                    .collect::<Vec<_>>(),
+            );
+            if page.is_last {
+                break;
+            }
+            cursor = page.cursor;
+        }
+
+        // 最后一页刚好填满时不会再多出一个空页
+        assert_eq!(
+            pages,
+            vec![
+                vec![("3".to_string(), 100, 1), ("2_x".to_string(), 100, 2)],
+                vec![("1".to_string(), 100, 1), ("9".to_string(), 50, 1)],
+            ]
+        );
+
+        let page = search_page(&db, "周会", 1, 10, "").await;
+        let groups = page.list.unwrap();
+        assert!(page.is_last);
+        assert_eq!(groups[0].room_name.as_deref(), Some("张三"));
+        assert!(groups.iter().all(|group| group.hits.len() == 1));
+        assert_eq!(groups[1].hits[0].record.message.id, "2");
+        assert_eq!(
+            groups[1].hits[0].snippet.as_deref(),
+            Some("<mark>周会</mark>改期")
+        );
+
+        // 建立全文索引后走 FTS 检索，房间内命中按相关度排序并返回索引生成的摘要
+        for sql in [
+            "CREATE VIRTUAL TABLE im_message_fts USING fts5(content, id UNINDEXED, \
+             login_uid UNINDEXED, room_id UNINDEXED, tokenize = 'trigram')",
+            "INSERT INTO im_message_fts (rowid, content, id, login_uid, room_id) \
+             SELECT rowid, json_extract(body, '$.content'), id, login_uid, room_id FROM im_message",
+        ] {
+            db.execute_unprepared(sql).await.unwrap();
+        }
+        reset_table_initialization_flags();
+
+        let page = search_page(&db, "周会", 2, 10, "").await;
+        assert_eq!(page.total, 4);
+        let groups = page.list.unwrap();
+        assert!(
+            groups
+                .iter()
+                .all(|group| group.hits.len() as u64 == group.hit_count)
+        );
+
+        let page = search_page(&db, "周会改期", 2, 10, "").await;
+        let groups = page.list.unwrap();
+        assert_eq!(groups.len(), 1);
+        let hit = &groups[0].hits[0];
+        assert_eq!(hit.record.message.id, "2");
+        assert!(hit.score.is_some());
+        assert_eq!(hit.snippet.as_deref(), Some("<mark>周会改期</mark>"));
+    }
+
+    #[tokio::test]
+    async fn test_backfill_search_index_and_file_search() {
+        let _guard = SEARCH_INDEX_TEST_LOCK.lock().await;
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        for sql in [
+            "CREATE TABLE im_message (id TEXT NOT NULL, uid TEXT NOT NULL, nickname TEXT, \
+             room_id TEXT NOT NULL, send_time INTEGER, message_type INTEGER, body TEXT, \
+             message_marks TEXT, create_time INTEGER, update_time INTEGER, \
+             login_uid TEXT NOT NULL, send_status TEXT NOT NULL, time_block INTEGER, \
+             send_attempts INTEGER NOT NULL DEFAULT 0, next_retry_time INTEGER, \
+             last_attempt_time INTEGER, thumbnail_path TEXT, local_path TEXT, \
+             PRIMARY KEY (id, login_uid))",
+            "CREATE TABLE im_config (id INTEGER NOT NULL, \
+             config_key TEXT NOT NULL, config_value TEXT, login_uid TEXT NOT NULL, \
+             PRIMARY KEY (id, login_uid))",
+            "CREATE TABLE im_message_deleted (id TEXT, room_id TEXT, login_uid TEXT)",
+            "CREATE VIRTUAL TABLE im_message_fts USING fts5(content, id UNINDEXED, \
+             login_uid UNINDEXED, room_id UNINDEXED, tokenize = 'trigram')",
+        ] {
+            db.execute_unprepared(sql).await.unwrap();
+        }
+        let rows = [
+            ("1", "u", 4, r#"{"fileName":"季度周报.pdf"}"#),
+            ("2", "u", 4, r#"{"fileName":"合同.docx"}"#),
+            ("3", "u", 2, r#"{"content":"已撤回的周报"}"#),
+            ("4", "other", 4, r#"{"fileName":"季度周报.pdf"}"#),
+        ];
+        for (id, login_uid, message_type, body) in rows {
+            db.execute(Statement::from_sql_and_values(
+                db.get_database_backend(),
+                "INSERT INTO im_message (id, uid, room_id, send_time, message_type, body, \
+                 login_uid, send_status) VALUES (?, 'u', 'r', 1, ?, ?, ?, 'success')",
+                [
+                    id.into(),
+                    Value::from(message_type),
+                    body.into(),
+                    login_uid.into(),
+                ],
+            ))
+            .await
+            .unwrap();
+        }
+        reset_table_initialization_flags();
+
+        let mut after_id = None;
+        while let Some(last) = backfill_search_index(&db, "u", after_id.as_deref())
+            .await
+            .unwrap()
+        {
+            after_id = Some(last);
+        }
+        let indexed: Vec<String> = db
+            .query_all(Statement::from_string(
+                db.get_database_backend(),
+                "SELECT id FROM im_message_fts ORDER BY id",
+            ))
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.try_get("", "id").unwrap())
+            .collect();
+        assert_eq!(indexed, ["1", "2"]);
+        // 完成后不再重复回填
+        assert!(
+            backfill_search_index(&db, "u", None)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // 残留的索引行指向同一 rowid 但消息ID不同，不能命中
+        db.execute_unprepared(
+            "UPDATE im_message_fts SET content = '过期周报.pdf', id = 'stale' WHERE id = '2'",
+        )
+        .await
+        .unwrap();
+        let files = query_file_messages(&db, "u", None, Some(&[4]), Some("周报"), 1, 10)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = files
+            .iter()
+            .map(|record| record.message.id.as_str())
+            .collect();
+        assert_eq!(ids, ["1"]);
+    }
+
+    #[tokio::test]
+    async fn test_retryable_pending_keyed_on_attempt_time() {
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE im_message (id TEXT NOT NULL, uid TEXT NOT NULL, nickname TEXT, \
+             room_id TEXT NOT NULL, send_time INTEGER, message_type INTEGER, body TEXT, \
+             message_marks TEXT, create_time INTEGER, update_time INTEGER, \
+             login_uid TEXT NOT NULL, send_status TEXT NOT NULL, time_block INTEGER, \
+             send_attempts INTEGER NOT NULL DEFAULT 0, next_retry_time INTEGER, \
+             last_attempt_time INTEGER, thumbnail_path TEXT, local_path TEXT, \
+             PRIMARY KEY (id, login_uid))",
+        )
+        .await
+        .unwrap();
+        // 1: 很早创建但刚开始发送；2: 发送已中断；3: 从未开始发送；4: 失败且已到重发时间
+        db.execute_unprepared(
+            "INSERT INTO im_message (id, uid, room_id, send_time, login_uid, send_status, \
+             next_retry_time, last_attempt_time) VALUES \
+             ('1', 'u', 'r', 100, 'u', 'pending', NULL, 950), \
+             ('2', 'u', 'r', 200, 'u', 'pending', NULL, 300), \
+             ('3', 'u', 'r', 300, 'u', 'pending', NULL, NULL), \
+             ('4', 'u', 'r', 400, 'u', 'fail', 900, 400)",
+        )
+        .await
+        .unwrap();
+
+        let ids = |messages: Vec<(MessageWithThumbnail, i32)>| -> Vec<String> {
+            messages
+                .into_iter()
+                .map(|(record, _)| record.message.id)
+                .collect()
+        };
+        let messages = list_retryable_messages(&db, "u", 1000, 500, 5, 10)
+            .await
+            .unwrap();
+        assert_eq!(ids(messages), ["2", "3", "4"]);
+
+        // 重新开始发送后不再视为中断
+        mark_send_started(&db, "2", "u", 990).await.unwrap();
+        let messages = list_retryable_messages(&db, "u", 1000, 500, 5, 10)
+            .await
+            .unwrap();
+        assert_eq!(ids(messages), ["3", "4"]);
+    }
+}
@@ -5,8 +5,9 @@ use sea_orm::IntoActiveModel;
 use sea_orm::PaginatorTrait;
 use sea_orm::QuerySelect;
 use sea_orm::TransactionTrait;
+use sea_orm::prelude::Expr;
 use sea_orm::{ActiveModelTrait, Set};
-use sea_orm::{ColumnTrait, DatabaseConnection, QueryFilter, QueryOrder};
+use sea_orm::{ColumnTrait, ConnectionTrait, DatabaseConnection, QueryFilter, QueryOrder};
 use tracing::{debug, info};
 
 use crate::pojo::common::{CursorPageParam, CursorPageResp};
@@ -310,3 +311,122 @@ pub async fn update_my_room_info(
         Ok(())
     }
 }
+
+/// 新增或覆盖房间成员，已存在的成员按 uid 替换
+pub async fn upsert_room_members<C>(
+    db: &C,
+    room_id: &str,
+    members: Vec<im_room_member::Model>,
+    login_uid: &str,
+) -> Result<(), CommonError>
+where
+    C: ConnectionTrait,
+{
+    let uids: Vec<String> = members.iter().filter_map(|m| m.uid.clone()).collect();
+    if uids.is_empty() {
+        return Ok(());
+    }
+    remove_room_members(db, room_id, &uids, login_uid).await?;
+
+    let active_models: Vec<im_room_member::ActiveModel> = members
+        .into_iter()
+        .filter(|member| member.uid.is_some())
+        .map(|mut member| {
+            if member.id.is_empty() {
+                // 与 update_my_room_info 保持一致，使用 room_id + uid 作为主键
+                member.id = format!("{}_{}", room_id, member.uid.as_deref().unwrap_or_default());
+            }
+            member.room_id = Some(room_id.to_string());
+            member.login_uid = login_uid.to_string();
+            member.into_active_model()
+        })
+        .collect();
+
+    im_room_member::Entity::insert_many(active_models)
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to insert room members: {}", e))?;
+
+    debug!("Upserted {} members for room_id: {}", uids.len(), room_id);
+    Ok(())
+}
+
+/// 移除房间中的指定成员
+pub async fn remove_room_members<C>(
+    db: &C,
+    room_id: &str,
+    uids: &[String],
+    login_uid: &str,
+) -> Result<u64, CommonError>
+where
+    C: ConnectionTrait,
+{
+    if uids.is_empty() {
+        return Ok(0);
+    }
+
+    let result = im_room_member::Entity::delete_many()
+        .filter(im_room_member::Column::RoomId.eq(room_id))
+        .filter(im_room_member::Column::LoginUid.eq(login_uid))
+        .filter(im_room_member::Column::Uid.is_in(uids.iter().cloned()))
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to remove room members: {}", e))?;
+
+    Ok(result.rows_affected)
+}
+
+/// 删除房间及其全部成员，用于群聊解散或本人退出群聊
+pub async fn delete_room<C>(db: &C, room_id: &str, login_uid: &str) -> Result<(), CommonError>
+where
+    C: ConnectionTrait,
+{
+    im_room_member::Entity::delete_many()
+        .filter(im_room_member::Column::RoomId.eq(room_id))
+        .filter(im_room_member::Column::LoginUid.eq(login_uid))
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to delete room members: {}", e))?;
+
+    im_room::Entity::delete_many()
+        .filter(im_room::Column::RoomId.eq(room_id))
+        .filter(im_room::Column::LoginUid.eq(login_uid))
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to delete room record: {}", e))?;
+
+    Ok(())
+}
+
+/// 更新房间名称和头像，传入 None 的字段保持不变
+pub async fn update_room_info<C>(
+    db: &C,
+    room_id: &str,
+    room_name: Option<&str>,
+    avatar: Option<&str>,
+    login_uid: &str,
+) -> Result<(), CommonError>
+where
+    C: ConnectionTrait,
+{
+    if room_name.is_none() && avatar.is_none() {
+        return Ok(());
+    }
+
+    let mut update = im_room::Entity::update_many()
+        .filter(im_room::Column::RoomId.eq(room_id))
+        .filter(im_room::Column::LoginUid.eq(login_uid));
+    if let Some(room_name) = room_name {
+        update = update.col_expr(im_room::Column::RoomName, Expr::value(room_name));
+    }
+    if let Some(avatar) = avatar {
+        update = update.col_expr(im_room::Column::Avatar, Expr::value(avatar));
+    }
+
+    update
+        .exec(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to update room info: {}", e))?;
+
+    Ok(())
+}
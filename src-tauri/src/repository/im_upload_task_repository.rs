@@ -0,0 +1,100 @@
+use crate::error::CommonError;
+use chrono::Utc;
+use entity::im_upload_task;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
+    QueryOrder, Set,
+};
+
+/// 新建上传任务
+pub async fn insert_task(
+    db: &DatabaseConnection,
+    task: im_upload_task::Model,
+) -> Result<(), CommonError> {
+    task.into_active_model()
+        .insert(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("保存上传任务失败: {}", e))?;
+    Ok(())
+}
+
+/// 根据任务ID获取上传任务
+pub async fn get_task(
+    db: &DatabaseConnection,
+    id: &str,
+    login_uid: &str,
+) -> Result<Option<im_upload_task::Model>, CommonError> {
+    let task = im_upload_task::Entity::find_by_id(id.to_string())
+        .filter(im_upload_task::Column::LoginUid.eq(login_uid))
+        .one(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("查询上传任务失败: {}", e))?;
+    Ok(task)
+}
+
+/// 按创建时间获取当前用户未完成的上传任务
+pub async fn list_tasks(
+    db: &DatabaseConnection,
+    login_uid: &str,
+) -> Result<Vec<im_upload_task::Model>, CommonError> {
+    let list = im_upload_task::Entity::find()
+        .filter(im_upload_task::Column::LoginUid.eq(login_uid))
+        .order_by_asc(im_upload_task::Column::CreateTime)
+        .all(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("查询上传任务失败: {}", e))?;
+    Ok(list)
+}
+
+/// 记录一个分片上传完成后的进度
+pub async fn update_progress(
+    db: &DatabaseConnection,
+    task: &im_upload_task::Model,
+) -> Result<(), CommonError> {
+    let mut active = im_upload_task::ActiveModel {
+        id: Set(task.id.clone()),
+        ..Default::default()
+    };
+    active.session_id = Set(task.session_id.clone());
+    active.uploaded = Set(task.uploaded);
+    active.contexts = Set(task.contexts.clone());
+    active.ctx_expire_at = Set(task.ctx_expire_at);
+    active.update_time = Set(Utc::now().timestamp_millis());
+    active.update(db).await?;
+    Ok(())
+}
+
+/// 更新任务状态，恢复上传时可同时替换上传凭证
+pub async fn update_status(
+    db: &DatabaseConnection,
+    id: &str,
+    status: &str,
+    last_error: Option<String>,
+    token: Option<String>,
+) -> Result<(), CommonError> {
+    let mut active = im_upload_task::ActiveModel {
+        id: Set(id.to_string()),
+        ..Default::default()
+    };
+    active.status = Set(status.to_string());
+    active.last_error = Set(last_error);
+    if let Some(token) = token {
+        active.token = Set(token);
+    }
+    active.update_time = Set(Utc::now().timestamp_millis());
+    active.update(db).await?;
+    Ok(())
+}
+
+/// 上传完成或取消后删除任务
+pub async fn delete_task(
+    db: &DatabaseConnection,
+    id: &str,
+    login_uid: &str,
+) -> Result<(), CommonError> {
+    im_upload_task::Entity::delete_by_id(id.to_string())
+        .filter(im_upload_task::Column::LoginUid.eq(login_uid))
+        .exec(db)
+        .await?;
+    Ok(())
+}
@@ -31,92 +31,42 @@ where
     }
 }
 
-/// 保存或更新用户的 token 信息
-pub async fn save_user_tokens<C>(
-    db: &C,
-    login_uid: &str,
-    token: &str,
-    refresh_token: &str,
-) -> Result<(), CommonError>
+/// 确保用户记录存在，新用户默认需要初始化（全量同步消息）
+pub async fn ensure_user<C>(db: &C, login_uid: &str) -> Result<(), CommonError>
 where
     C: ConnectionTrait,
 {
-    // 检查用户是否已存在
     let existing_user = ImUserEntity::find()
         .filter(im_user::Column::Id.eq(login_uid))
         .one(db)
         .await
         .map_err(|e| {
-            error!("Failed to query user for token update: {:?}", e);
+            error!("Failed to query user: {:?}", e);
             CommonError::DatabaseError(e)
         })?;
+    if existing_user.is_some() {
+        return Ok(());
+    }
 
-    let refresh_token_to_save = if refresh_token.is_empty() {
-        if let Some(user) = &existing_user {
-            if let Some(saved_refresh) = user.refresh_token.clone() {
-                if !saved_refresh.is_empty() {
-                    saved_refresh
-                } else {
-                    "".to_string()
-                }
-            } else {
-                "".to_string()
-            }
-        } else {
-            "".to_string()
-        }
-    } else {
-        refresh_token.to_string()
-    };
-
-    let user_update = if existing_user.is_some() {
-        // 用户存在，更新 token 信息
-        im_user::ActiveModel {
-            id: Set(login_uid.to_string()),
-            token: Set(Some(token.to_string())),
-            refresh_token: Set(Some(refresh_token_to_save.clone())),
-            ..Default::default()
-        }
-    } else {
-        // 用户不存在，创建新用户并设置 token 信息
-        im_user::ActiveModel {
-            id: Set(login_uid.to_string()),
-            token: Set(Some(token.to_string())),
-            refresh_token: Set(Some(refresh_token_to_save.clone())),
-            is_init: Set(true), // 新用户默认未初始化
-            ..Default::default()
-        }
+    let new_user = im_user::ActiveModel {
+        id: Set(login_uid.to_string()),
+        is_init: Set(true), // 新用户默认未初始化
+        ..Default::default()
     };
-
-    if existing_user.is_some() {
-        // 更新现有用户
-        match ImUserEntity::update(user_update).exec(db).await {
-            Ok(_) => {
-                info!("User {} token info updated successfully", login_uid);
-                Ok(())
-            }
-            Err(e) => {
-                error!("Failed to update user token info: {:?}", e);
-                Err(e.into())
-            }
+    match ImUserEntity::insert(new_user).exec(db).await {
+        Ok(_) => {
+            info!("New user {} created", login_uid);
+            Ok(())
         }
-    } else {
-        // 插入新用户
-        match ImUserEntity::insert(user_update).exec(db).await {
-            Ok(_) => {
-                info!("New user {} created with token info", login_uid);
-                Ok(())
-            }
-            Err(e) => {
-                error!("Failed to create user with token info: {:?}", e);
-                Err(e.into())
-            }
+        Err(e) => {
+            error!("Failed to create user: {:?}", e);
+            Err(e.into())
         }
     }
 }
 
-/// 获取用户的 token 信息
-pub async fn get_user_tokens<C>(
+/// 读取旧版本保存在 im_user 表中的 token
+pub async fn get_legacy_user_tokens<C>(
     db: &C,
     login_uid: &str,
 ) -> Result<Option<(String, String)>, CommonError>
@@ -132,14 +82,31 @@ where
             CommonError::DatabaseError(e)
         })?;
 
-    match user {
-        Some(user) => {
-            if let (Some(token), Some(refresh_token)) = (user.token, user.refresh_token) {
-                Ok(Some((token, refresh_token)))
-            } else {
-                Ok(None)
-            }
-        }
-        None => Ok(None),
+    let Some(user) = user else {
+        return Ok(None);
+    };
+    if user.token.is_none() && user.refresh_token.is_none() {
+        return Ok(None);
     }
+
+    Ok(Some((
+        user.token.unwrap_or_default(),
+        user.refresh_token.unwrap_or_default(),
+    )))
+}
+
+/// 清空 im_user 表中旧版本保存的 token
+/// 凭证已迁移到 SecretStore，数据库中不再保留明文 token
+pub async fn clear_legacy_user_tokens<C>(db: &C, login_uid: &str) -> Result<(), CommonError>
+where
+    C: ConnectionTrait,
+{
+    let user_update = im_user::ActiveModel {
+        id: Set(login_uid.to_string()),
+        token: Set(None),
+        refresh_token: Set(None),
+        ..Default::default()
+    };
+    ImUserEntity::update(user_update).exec(db).await?;
+    Ok(())
 }
@@ -0,0 +1,123 @@
+use crate::error::CommonError;
+use chrono::Utc;
+use entity::im_ws_outbox;
+use sea_orm::sea_query::Value;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, Statement,
+};
+
+/// 写入发件箱，同一 client_id 重复写入时只刷新内容与过期时间，保留原有投递顺序
+/// 返回因超出容量而被丢弃的最旧消息数量
+pub async fn enqueue_message(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    client_id: &str,
+    payload: &str,
+    expire_time: i64,
+    max_size: u64,
+) -> Result<u64, CommonError> {
+    let backend = db.get_database_backend();
+    let stmt = Statement::from_sql_and_values(
+        backend,
+        "INSERT INTO im_ws_outbox (client_id, login_uid, payload, retry_count, create_time, expire_time)
+         VALUES (?, ?, ?, 0, ?, ?)
+         ON CONFLICT(client_id, login_uid) DO UPDATE SET payload = excluded.payload, expire_time = excluded.expire_time",
+        vec![
+            Value::from(client_id.to_string()),
+            Value::from(login_uid.to_string()),
+            Value::from(payload.to_string()),
+            Value::from(Utc::now().timestamp_millis()),
+            Value::from(expire_time),
+        ],
+    );
+    db.execute(stmt)
+        .await
+        .map_err(|e| anyhow::anyhow!("写入发件箱失败: {}", e))?;
+
+    // 限制队列长度，丢弃最旧的消息
+    let trim_stmt = Statement::from_sql_and_values(
+        backend,
+        "DELETE FROM im_ws_outbox WHERE login_uid = ? AND id NOT IN (
+             SELECT id FROM im_ws_outbox WHERE login_uid = ? ORDER BY id DESC LIMIT ?
+         )",
+        vec![
+            Value::from(login_uid.to_string()),
+            Value::from(login_uid.to_string()),
+            Value::from(max_size as i64),
+        ],
+    );
+    let result = db.execute(trim_stmt).await?;
+    Ok(result.rows_affected())
+}
+
+/// 按写入顺序获取待投递的消息
+pub async fn list_pending_messages(
+    db: &DatabaseConnection,
+    login_uid: &str,
+) -> Result<Vec<im_ws_outbox::Model>, CommonError> {
+    let list = im_ws_outbox::Entity::find()
+        .filter(im_ws_outbox::Column::LoginUid.eq(login_uid))
+        .order_by_asc(im_ws_outbox::Column::Id)
+        .all(db)
+        .await
+        .map_err(|e| anyhow::anyhow!("查询发件箱失败: {}", e))?;
+    Ok(list)
+}
+
+/// 统计待投递的消息数量
+pub async fn count_pending_messages(
+    db: &DatabaseConnection,
+    login_uid: &str,
+) -> Result<u64, CommonError> {
+    let count = im_ws_outbox::Entity::find()
+        .filter(im_ws_outbox::Column::LoginUid.eq(login_uid))
+        .count(db)
+        .await?;
+    Ok(count)
+}
+
+/// 投递成功或放弃投递后移除消息
+pub async fn remove_message(db: &DatabaseConnection, id: i64) -> Result<(), CommonError> {
+    im_ws_outbox::Entity::delete_by_id(id).exec(db).await?;
+    Ok(())
+}
+
+/// 记录一次投递失败，返回累计重试次数
+pub async fn record_failure(
+    db: &DatabaseConnection,
+    entry: &im_ws_outbox::Model,
+    error: &str,
+) -> Result<i32, CommonError> {
+    let backend = db.get_database_backend();
+    let stmt = Statement::from_sql_and_values(
+        backend,
+        "UPDATE im_ws_outbox SET retry_count = retry_count + 1, last_error = ? WHERE id = ?",
+        vec![Value::from(error.to_string()), Value::from(entry.id)],
+    );
+    db.execute(stmt).await?;
+    Ok(entry.retry_count + 1)
+}
+
+/// 移除已过期的消息，返回被移除的记录
+pub async fn remove_expired_messages(
+    db: &DatabaseConnection,
+    login_uid: &str,
+    now: i64,
+) -> Result<Vec<im_ws_outbox::Model>, CommonError> {
+    let expired = im_ws_outbox::Entity::find()
+        .filter(im_ws_outbox::Column::LoginUid.eq(login_uid))
+        .filter(im_ws_outbox::Column::ExpireTime.lte(now))
+        .all(db)
+        .await?;
+
+    if !expired.is_empty() {
+        im_ws_outbox::Entity::delete_many()
+            .filter(im_ws_outbox::Column::LoginUid.eq(login_uid))
+            .filter(im_ws_outbox::Column::ExpireTime.lte(now))
+            .exec(db)
+            .await?;
+    }
+
+    Ok(expired)
+}
@@ -1,5 +1,9 @@
+pub mod im_ai_chat_repository;
 pub mod im_config_repository;
 pub mod im_contact_repository;
+pub mod im_knowledge_repository;
 pub mod im_message_repository;
 pub mod im_room_member_repository;
+pub mod im_upload_task_repository;
 pub mod im_user_repository;
+pub mod im_ws_outbox_repository;
@@ -0,0 +1,414 @@
+//! 敏感信息存储
+//!
+//! 登录凭证等敏感信息不再写入业务数据库，统一通过 [`SecretStore`] 读写。
+//! 默认实现 [`FileSecretStore`] 把所有条目加密后保存在单个文件中，加密密钥保存在系统钥匙串，
+//! 测试时可以换成内存中的密钥存储，在没有 keyring 服务的 Linux 环境下同样可用。
+
+use crate::error::CommonError;
+use crate::utils::keychain::{self, KeyVault, SystemKeychain};
+use rand::RngCore;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const SECRET_FILENAME: &str = "secrets.enc";
+/// 钥匙串中加密密钥条目的名称前缀
+const SECRET_KEY_ENTRY: &str = "secret-store";
+const KEY_LEN: usize = 32;
+
+/// 敏感信息存储接口
+pub trait SecretStore: Send + Sync + std::fmt::Debug {
+    fn get(&self, key: &str) -> Result<Option<String>, CommonError>;
+
+    fn set(&self, key: &str, value: &str) -> Result<(), CommonError>;
+
+    fn delete(&self, key: &str) -> Result<(), CommonError>;
+
+    /// 删除所有以 `prefix` 开头的条目
+    fn delete_prefix(&self, prefix: &str) -> Result<(), CommonError>;
+}
+
+/// 用户登录凭证
+#[derive(Clone, PartialEq, Eq)]
+pub struct AuthTokens {
+    pub token: String,
+    pub refresh_token: String,
+}
+
+impl std::fmt::Debug for AuthTokens {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthTokens")
+            .field("token_len", &self.token.len())
+            .field("refresh_token_len", &self.refresh_token.len())
+            .finish()
+    }
+}
+
+fn auth_prefix(uid: &str) -> String {
+    format!("auth:{}:", uid)
+}
+
+/// 保存用户的 token，`refresh_token` 为空时保留已保存的 refresh_token
+pub fn save_tokens(
+    store: &dyn SecretStore,
+    uid: &str,
+    token: &str,
+    refresh_token: &str,
+) -> Result<(), CommonError> {
+    let prefix = auth_prefix(uid);
+    store.set(&format!("{}token", prefix), token)?;
+    if !refresh_token.is_empty() {
+        store.set(&format!("{}refresh_token", prefix), refresh_token)?;
+    }
+    Ok(())
+}
+
+/// 读取用户的 token
+pub fn load_tokens(store: &dyn SecretStore, uid: &str) -> Result<Option<AuthTokens>, CommonError> {
+    let prefix = auth_prefix(uid);
+    let token = store.get(&format!("{}token", prefix))?;
+    let refresh_token = store.get(&format!("{}refresh_token", prefix))?;
+    Ok(match (token, refresh_token) {
+        (Some(token), refresh_token) => Some(AuthTokens {
+            token,
+            refresh_token: refresh_token.unwrap_or_default(),
+        }),
+        (None, Some(refresh_token)) => Some(AuthTokens {
+            token: String::new(),
+            refresh_token,
+        }),
+        (None, None) => None,
+    })
+}
+
+/// 清除用户的所有登录凭证
+pub fn wipe_tokens(store: &dyn SecretStore, uid: &str) -> Result<(), CommonError> {
+    store.delete_prefix(&auth_prefix(uid))
+}
+
+/// OpenAI 兼容接口的 API Key，所有用户共用
+const AI_API_KEY_ENTRY: &str = "ai:openai:api_key";
+
+/// 保存 OpenAI 兼容接口的 API Key，为空时删除
+pub fn save_ai_api_key(store: &dyn SecretStore, api_key: Option<&str>) -> Result<(), CommonError> {
+    match api_key.filter(|key| !key.is_empty()) {
+        Some(key) => store.set(AI_API_KEY_ENTRY, key),
+        None => store.delete(AI_API_KEY_ENTRY),
+    }
+}
+
+/// 读取 OpenAI 兼容接口的 API Key
+pub fn load_ai_api_key(store: &dyn SecretStore) -> Result<Option<String>, CommonError> {
+    store.get(AI_API_KEY_ENTRY)
+}
+
+/// 打开默认的敏感信息存储
+/// 系统钥匙串不可用时退化为内存存储：当前会话照常使用，只是重启后需要重新登录
+pub fn open_default(dir: &Path) -> Arc<dyn SecretStore> {
+    let store = FileSecretStore::new(dir);
+    match store.vault.get(&store.key_entry) {
+        Ok(_) => Arc::new(store),
+        Err(e) => {
+            warn!(
+                "System keychain unavailable, credentials will not be saved: {}",
+                e
+            );
+            Arc::new(MemorySecretStore::default())
+        }
+    }
+}
+
+/// 内存中的敏感信息存储，退出后条目不会保留
+#[derive(Debug, Default)]
+pub struct MemorySecretStore {
+    entries: Mutex<HashMap<String, String>>,
+}
+
+impl SecretStore for MemorySecretStore {
+    fn get(&self, key: &str) -> Result<Option<String>, CommonError> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(entries.get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), CommonError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), CommonError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(key);
+        Ok(())
+    }
+
+    fn delete_prefix(&self, prefix: &str) -> Result<(), CommonError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|key, _| !key.starts_with(prefix));
+        Ok(())
+    }
+}
+
+/// 基于加密文件的敏感信息存储
+///
+/// 条目整体序列化后使用 AES-256-GCM 加密，文件格式为 `nonce || ciphertext || tag`。
+/// 加密密钥为随机生成的 32 字节，保存在系统钥匙串中，不与密文放在一起。
+#[derive(Debug)]
+pub struct FileSecretStore {
+    path: PathBuf,
+    key_entry: String,
+    vault: Box<dyn KeyVault>,
+    lock: Mutex<()>,
+}
+
+impl FileSecretStore {
+    pub fn new(dir: &Path) -> Self {
+        Self::with_vault(dir, Box::new(SystemKeychain::new(dir)))
+    }
+
+    pub fn with_vault(dir: &Path, vault: Box<dyn KeyVault>) -> Self {
+        Self {
+            path: dir.join(SECRET_FILENAME),
+            key_entry: keychain::entry_name(SECRET_KEY_ENTRY, dir),
+            vault,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// 读取加密密钥，首次使用时生成
+    /// 已有密文但找不到密钥时返回错误，不会生成新密钥覆盖无法解密的数据
+    fn load_or_create_key(&self) -> Result<LessSafeKey, CommonError> {
+        let key_bytes = match self.vault.get(&self.key_entry)? {
+            Some(bytes) => bytes,
+            None if self.path.exists() => {
+                return Err(anyhow::anyhow!(
+                    "Secret store key is missing, {:?} cannot be decrypted",
+                    self.path
+                )
+                .into());
+            }
+            None => {
+                let mut bytes = vec![0u8; KEY_LEN];
+                rand::thread_rng().fill_bytes(&mut bytes);
+                self.vault.set(&self.key_entry, &bytes)?;
+                bytes
+            }
+        };
+        if key_bytes.len() != KEY_LEN {
+            return Err(anyhow::anyhow!("Invalid secret store key").into());
+        }
+        let unbound = UnboundKey::new(&AES_256_GCM, &key_bytes)
+            .map_err(|_| anyhow::anyhow!("Invalid secret store key"))?;
+        Ok(LessSafeKey::new(unbound))
+    }
+
+    fn read_entries(&self, key: &LessSafeKey) -> Result<HashMap<String, String>, CommonError> {
+        let mut data = match std::fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => {
+                return Err(anyhow::anyhow!("Failed to read secret store: {}", e).into());
+            }
+        };
+        // 无法解密时返回错误，按空存储处理会在下次写入时覆盖掉全部条目
+        if data.len() < NONCE_LEN {
+            return Err(anyhow::anyhow!("Secret store file is truncated").into());
+        }
+
+        let mut ciphertext = data.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&data)
+            .map_err(|_| anyhow::anyhow!("Invalid secret store nonce"))?;
+        let plaintext = key
+            .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt secret store"))?;
+        let entries = serde_json::from_slice(plaintext)
+            .map_err(|e| anyhow::anyhow!("Failed to parse secret store: {}", e))?;
+        Ok(entries)
+    }
+
+    fn write_entries(
+        &self,
+        key: &LessSafeKey,
+        entries: &HashMap<String, String>,
+    ) -> Result<(), CommonError> {
+        let mut data = serde_json::to_vec(entries)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize secret store: {}", e))?;
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce_bytes),
+            Aad::empty(),
+            &mut data,
+        )
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt secret store"))?;
+
+        let mut output = Vec::with_capacity(NONCE_LEN + data.len());
+        output.extend_from_slice(&nonce_bytes);
+        output.extend_from_slice(&data);
+        write_private_file(&self.path, &output)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut HashMap<String, String>)) -> Result<(), CommonError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let key = self.load_or_create_key()?;
+        let mut entries = self.read_entries(&key)?;
+        f(&mut entries);
+        self.write_entries(&key, &entries)
+    }
+}
+
+/// 先写临时文件再替换，并在 Unix 上限制为仅当前用户可读写
+fn write_private_file(path: &Path, data: &[u8]) -> Result<(), CommonError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create secret store directory: {}", e))?;
+    }
+    // 临时文件名保留原扩展名，避免不同文件的临时文件互相覆盖
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    std::fs::write(&tmp_path, data)
+        .map_err(|e| anyhow::anyhow!("Failed to write secret store: {}", e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600));
+    }
+    std::fs::rename(&tmp_path, path)
+        .map_err(|e| anyhow::anyhow!("Failed to replace secret store: {}", e))?;
+    Ok(())
+}
+
+impl SecretStore for FileSecretStore {
+    fn get(&self, key: &str) -> Result<Option<String>, CommonError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let cipher = self.load_or_create_key()?;
+        Ok(self.read_entries(&cipher)?.remove(key))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), CommonError> {
+        self.update(|entries| {
+            entries.insert(key.to_string(), value.to_string());
+        })
+    }
+
+    fn delete(&self, key: &str) -> Result<(), CommonError> {
+        self.update(|entries| {
+            entries.remove(key);
+        })
+    }
+
+    fn delete_prefix(&self, prefix: &str) -> Result<(), CommonError> {
+        self.update(|entries| entries.retain(|key, _| !key.starts_with(prefix)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::keychain::MemoryKeyVault;
+
+    fn temp_store() -> (FileSecretStore, MemoryKeyVault, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("hula-secret-{}", uuid::Uuid::new_v4()));
+        let vault = MemoryKeyVault::default();
+        (open_store(&dir, &vault), vault, dir)
+    }
+
+    fn open_store(dir: &Path, vault: &MemoryKeyVault) -> FileSecretStore {
+        FileSecretStore::with_vault(dir, Box::new(vault.clone()))
+    }
+
+    #[test]
+    fn test_file_secret_store_round_trip() {
+        let (store, vault, dir) = temp_store();
+
+        save_tokens(&store, "10001", "token-a", "refresh-a").unwrap();
+        save_tokens(&store, "10002", "token-b", "refresh-b").unwrap();
+        // refresh_token 为空时保留旧值
+        save_tokens(&store, "10001", "token-a2", "").unwrap();
+
+        let tokens = load_tokens(&store, "10001").unwrap().unwrap();
+        assert_eq!(tokens.token, "token-a2");
+        assert_eq!(tokens.refresh_token, "refresh-a");
+
+        // 密文中不包含明文 token
+        let raw = std::fs::read(dir.join(SECRET_FILENAME)).unwrap();
+        assert!(!raw.windows(7).any(|w| w == b"token-b"));
+
+        // 重新打开后仍可读取
+        let reopened = open_store(&dir, &vault);
+        wipe_tokens(&reopened, "10001").unwrap();
+        assert!(load_tokens(&reopened, "10001").unwrap().is_none());
+        assert_eq!(
+            load_tokens(&reopened, "10002").unwrap().unwrap().token,
+            "token-b"
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_file_secret_store_tampered_file() {
+        let (store, _vault, dir) = temp_store();
+        store.set("auth:1:token", "value").unwrap();
+
+        let path = dir.join(SECRET_FILENAME);
+        let mut raw = std::fs::read(&path).unwrap();
+        let last = raw.len() - 1;
+        raw[last] ^= 0xff;
+        std::fs::write(&path, &raw).unwrap();
+
+        // 校验失败时返回错误，写入也会失败，不会覆盖原有的密文
+        assert!(store.get("auth:1:token").is_err());
+        assert!(store.set("auth:1:token", "new").is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), raw);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_file_secret_store_key_in_keychain() {
+        let (store, vault, dir) = temp_store();
+        store.set("auth:1:token", "value").unwrap();
+        assert!(vault.get(&store.key_entry).unwrap().is_some());
+        assert!(!dir.join(format!("{}.tmp", SECRET_FILENAME)).exists());
+
+        // 钥匙串中没有密钥时不能生成新密钥覆盖已有的密文
+        let empty = open_store(&dir, &MemoryKeyVault::default());
+        assert!(empty.get("auth:1:token").is_err());
+        assert!(empty.set("auth:1:token", "new").is_err());
+        assert_eq!(store.get("auth:1:token").unwrap().as_deref(), Some("value"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_memory_secret_store() {
+        let store = MemorySecretStore::default();
+        save_tokens(&store, "10001", "token-a", "refresh-a").unwrap();
+        save_tokens(&store, "10002", "token-b", "refresh-b").unwrap();
+
+        wipe_tokens(&store, "10001").unwrap();
+        assert!(load_tokens(&store, "10001").unwrap().is_none());
+        assert_eq!(
+            load_tokens(&store, "10002").unwrap().unwrap().token,
+            "token-b"
+        );
+
+        save_ai_api_key(&store, Some("sk-local")).unwrap();
+        assert_eq!(
+            load_ai_api_key(&store).unwrap().as_deref(),
+            Some("sk-local")
+        );
+        // 清除用户凭证不影响 API Key，传空时删除
+        wipe_tokens(&store, "10002").unwrap();
+        assert!(load_ai_api_key(&store).unwrap().is_some());
+        save_ai_api_key(&store, Some("")).unwrap();
+        assert!(load_ai_api_key(&store).unwrap().is_none());
+    }
+}
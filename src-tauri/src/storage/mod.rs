@@ -0,0 +1,198 @@
+//! 对象存储上传
+//!
+//! 文件按 [`StorageProvider::part_size`] 切分后并发上传，每个分片返回的凭证（七牛的 ctx、S3 的 ETag）
+//! 按分片顺序保存，全部完成后通过 [`StorageProvider::complete`] 合并为一个对象。
+//! 上传会话和分片凭证可以持久化，用于断点续传。
+
+use crate::error::CommonError;
+use std::future::Future;
+
+pub mod qiniu;
+pub mod s3;
+
+pub use qiniu::QiniuStorage;
+pub use s3::S3Storage;
+
+/// 对象存储类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    Qiniu,
+    S3,
+}
+
+impl StorageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageKind::Qiniu => "qiniu",
+            StorageKind::S3 => "s3",
+        }
+    }
+
+    /// 解析服务端或任务记录中的存储类型，MinIO 按 S3 兼容存储处理
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "qiniu" => Some(StorageKind::Qiniu),
+            "s3" | "minio" => Some(StorageKind::S3),
+            _ => None,
+        }
+    }
+}
+
+/// 已上传的分片
+#[derive(Debug, Clone)]
+pub struct UploadedPart {
+    /// 合并时使用的分片凭证
+    pub token: String,
+    /// 分片凭证的过期时间（秒），为空表示不会过期
+    pub expire_at: Option<i64>,
+}
+
+/// 对象存储上传接口
+pub trait StorageProvider: Send + Sync {
+    fn kind(&self) -> StorageKind;
+
+    /// 分片大小，除最后一个分片外都按这个大小切分
+    fn part_size(&self) -> u64;
+
+    /// 开始分片上传，返回上传会话ID，不需要会话的存储返回 None
+    fn begin(
+        &self,
+        key: &str,
+        content_type: &str,
+    ) -> impl Future<Output = Result<Option<String>, CommonError>> + Send;
+
+    /// 上传第 `index` 个分片（从 0 开始），`body` 的长度为 `len`
+    fn upload_part(
+        &self,
+        key: &str,
+        session: Option<&str>,
+        index: usize,
+        len: u64,
+        body: reqwest::Body,
+    ) -> impl Future<Output = Result<UploadedPart, CommonError>> + Send;
+
+    /// 按顺序合并所有分片，返回最终的对象 key
+    fn complete(
+        &self,
+        key: &str,
+        session: Option<&str>,
+        total: u64,
+        parts: &[String],
+    ) -> impl Future<Output = Result<String, CommonError>> + Send;
+
+    /// 放弃上传，清理服务端已上传的分片
+    fn abort(
+        &self,
+        key: &str,
+        session: Option<&str>,
+    ) -> impl Future<Output = Result<(), CommonError>> + Send;
+
+    /// 对象的下载地址，无法确定时返回 None
+    fn download_url(&self, key: &str) -> Option<String>;
+}
+
+/// 运行时选择的对象存储
+#[derive(Debug, Clone)]
+pub enum StorageBackend {
+    Qiniu(QiniuStorage),
+    S3(S3Storage),
+}
+
+impl StorageBackend {
+    /// 上传地址，记录在上传任务中
+    pub fn endpoint(&self) -> &str {
+        match self {
+            StorageBackend::Qiniu(storage) => storage.upload_host(),
+            StorageBackend::S3(storage) => storage.endpoint(),
+        }
+    }
+
+    /// 恢复上传时需要的凭证，S3 使用本地配置的密钥签名，不需要保存
+    pub fn upload_token(&self) -> &str {
+        match self {
+            StorageBackend::Qiniu(storage) => storage.token(),
+            StorageBackend::S3(_) => "",
+        }
+    }
+}
+
+impl StorageProvider for StorageBackend {
+    fn kind(&self) -> StorageKind {
+        match self {
+            StorageBackend::Qiniu(storage) => storage.kind(),
+            StorageBackend::S3(storage) => storage.kind(),
+        }
+    }
+
+    fn part_size(&self) -> u64 {
+        match self {
+            StorageBackend::Qiniu(storage) => storage.part_size(),
+            StorageBackend::S3(storage) => storage.part_size(),
+        }
+    }
+
+    async fn begin(&self, key: &str, content_type: &str) -> Result<Option<String>, CommonError> {
+        match self {
+            StorageBackend::Qiniu(storage) => storage.begin(key, content_type).await,
+            StorageBackend::S3(storage) => storage.begin(key, content_type).await,
+        }
+    }
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        session: Option<&str>,
+        index: usize,
+        len: u64,
+        body: reqwest::Body,
+    ) -> Result<UploadedPart, CommonError> {
+        match self {
+            StorageBackend::Qiniu(storage) => {
+                storage.upload_part(key, session, index, len, body).await
+            }
+            StorageBackend::S3(storage) => {
+                storage.upload_part(key, session, index, len, body).await
+            }
+        }
+    }
+
+    async fn complete(
+        &self,
+        key: &str,
+        session: Option<&str>,
+        total: u64,
+        parts: &[String],
+    ) -> Result<String, CommonError> {
+        match self {
+            StorageBackend::Qiniu(storage) => storage.complete(key, session, total, parts).await,
+            StorageBackend::S3(storage) => storage.complete(key, session, total, parts).await,
+        }
+    }
+
+    async fn abort(&self, key: &str, session: Option<&str>) -> Result<(), CommonError> {
+        match self {
+            StorageBackend::Qiniu(storage) => storage.abort(key, session).await,
+            StorageBackend::S3(storage) => storage.abort(key, session).await,
+        }
+    }
+
+    fn download_url(&self, key: &str) -> Option<String> {
+        match self {
+            StorageBackend::Qiniu(storage) => storage.download_url(key),
+            StorageBackend::S3(storage) => storage.download_url(key),
+        }
+    }
+}
+
+/// 检查响应状态，失败时把响应内容带入错误信息
+pub(crate) async fn check_response(
+    response: reqwest::Response,
+    action: &str,
+) -> Result<reqwest::Response, CommonError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    Err(anyhow::anyhow!("{} failed with status {}: {}", action, status, body).into())
+}
@@ -0,0 +1,135 @@
+//! 七牛云分片上传（mkblk / mkfile）
+
+use super::{StorageKind, StorageProvider, UploadedPart, check_response};
+use crate::error::CommonError;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE;
+use serde::Deserialize;
+
+/// 七牛分片上传的块大小，mkblk 要求固定为 4MB
+pub const QINIU_BLOCK_SIZE: u64 = 4 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct QiniuMkblkResponse {
+    ctx: String,
+    /// ctx 过期时间（秒）
+    expired_at: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct QiniuMkfileResponse {
+    key: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QiniuStorage {
+    client: reqwest::Client,
+    upload_host: String,
+    token: String,
+}
+
+impl QiniuStorage {
+    pub fn new(client: reqwest::Client, upload_host: &str, token: String) -> Self {
+        Self {
+            client,
+            upload_host: upload_host.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    pub fn upload_host(&self) -> &str {
+        &self.upload_host
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    fn authorization(&self) -> String {
+        format!("UpToken {}", self.token)
+    }
+}
+
+impl StorageProvider for QiniuStorage {
+    fn kind(&self) -> StorageKind {
+        StorageKind::Qiniu
+    }
+
+    fn part_size(&self) -> u64 {
+        QINIU_BLOCK_SIZE
+    }
+
+    /// mkblk 不需要上传会话
+    async fn begin(&self, _key: &str, _content_type: &str) -> Result<Option<String>, CommonError> {
+        Ok(None)
+    }
+
+    async fn upload_part(
+        &self,
+        _key: &str,
+        _session: Option<&str>,
+        _index: usize,
+        len: u64,
+        body: reqwest::Body,
+    ) -> Result<UploadedPart, CommonError> {
+        let response = self
+            .client
+            .post(format!("{}/mkblk/{len}", self.upload_host))
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .header(reqwest::header::CONTENT_LENGTH, len)
+            .header(reqwest::header::AUTHORIZATION, self.authorization())
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Upload request failed: {}", e))?;
+        let payload = check_response(response, "mkblk")
+            .await?
+            .json::<QiniuMkblkResponse>()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to parse mkblk response: {}", e))?;
+
+        Ok(UploadedPart {
+            token: payload.ctx,
+            expire_at: payload.expired_at,
+        })
+    }
+
+    async fn complete(
+        &self,
+        key: &str,
+        _session: Option<&str>,
+        total: u64,
+        parts: &[String],
+    ) -> Result<String, CommonError> {
+        let encoded_key = URL_SAFE.encode(key);
+        let response = self
+            .client
+            .post(format!(
+                "{}/mkfile/{total}/key/{encoded_key}",
+                self.upload_host
+            ))
+            .header(reqwest::header::CONTENT_TYPE, "text/plain")
+            .header(reqwest::header::AUTHORIZATION, self.authorization())
+            .body(parts.join(","))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Finalize request failed: {}", e))?;
+        let payload = check_response(response, "mkfile")
+            .await?
+            .json::<QiniuMkfileResponse>()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to parse mkfile response: {}", e))?;
+
+        Ok(payload.key.unwrap_or_else(|| key.to_string()))
+    }
+
+    /// 七牛未合并的块会在 ctx 过期后自动清理
+    async fn abort(&self, _key: &str, _session: Option<&str>) -> Result<(), CommonError> {
+        Ok(())
+    }
+
+    /// 七牛的下载域名由前端的存储配置决定
+    fn download_url(&self, _key: &str) -> Option<String> {
+        None
+    }
+}
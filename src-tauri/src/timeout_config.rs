@@ -27,6 +27,12 @@ impl TimeoutConfig {
 
     /// Token 刷新超时
     pub const TOKEN_REFRESH_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Token 过期前提前刷新的时间
+    pub const TOKEN_REFRESH_AHEAD: Duration = Duration::from_secs(5 * 60);
+
+    /// Token 定时刷新任务的检查间隔
+    pub const TOKEN_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
 }
 
 /// 为异步操作添加超时的辅助函数
@@ -0,0 +1,484 @@
+//! 本地数据库加密
+//!
+//! 用户数据库通过 SQLCipher 加密存储，每个用户的密钥由设备主密钥和用户盐值经 HMAC-SHA256 派生。
+//! 主密钥保存在系统钥匙串中，数据库目录下的 `db_keys.json` 只记录每个用户的盐值，
+//! 数据库文件和密钥文件都不包含可以直接解密的信息。
+
+use crate::error::CommonError;
+use crate::utils::keychain::{self, KeyVault, SystemKeychain};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use sea_orm::{ConnectOptions, ConnectionTrait, Database, DatabaseConnection};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+const KEY_STORE_FILENAME: &str = "db_keys.json";
+/// 钥匙串中主密钥条目的名称前缀
+const MASTER_SECRET_ENTRY: &str = "db-master";
+const MASTER_SECRET_LEN: usize = 32;
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+/// 未登录时使用的默认数据库对应的密钥标识
+const DEFAULT_KEY_ID: &str = "default";
+
+/// 密钥文件的读写需要串行，避免并发切换用户时互相覆盖
+static KEY_STORE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// SQLCipher 原始密钥，格式为 `"x'<hex>'"`，可直接作为 `PRAGMA key` 的值
+#[derive(Clone, PartialEq, Eq)]
+pub struct DatabaseKey(String);
+
+impl DatabaseKey {
+    fn derive(master_secret: &[u8], key_id: &str, salt: &[u8]) -> Result<Self, CommonError> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(master_secret)
+            .map_err(|e| anyhow::anyhow!("Invalid database master secret: {}", e))?;
+        mac.update(b"hula-db:");
+        mac.update(key_id.as_bytes());
+        mac.update(b":");
+        mac.update(salt);
+        let key = mac.finalize().into_bytes();
+        Ok(Self(format!("\"x'{}'\"", hex::encode(key))))
+    }
+
+    /// 不保存的随机密钥，用于无法打开用户数据库时的临时数据库
+    pub fn ephemeral() -> Self {
+        Self(format!("\"x'{}'\"", random_hex(32)))
+    }
+
+    /// `PRAGMA key` / `ATTACH ... KEY` 使用的值
+    pub fn pragma_value(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for DatabaseKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DatabaseKey(***)")
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct KeyStoreFile {
+    #[serde(default)]
+    users: HashMap<String, UserKeyEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct UserKeyEntry {
+    salt: String,
+    /// 重新加密过程中的新盐值，加密完成后替换 `salt`；进程中途退出时用于尝试打开数据库
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pending_salt: Option<String>,
+}
+
+/// 用户数据库当前可用的密钥
+#[derive(Debug, Clone)]
+pub struct DatabaseKeys {
+    pub current: DatabaseKey,
+    /// 上次重新加密未完成时留下的新密钥
+    pub pending: Option<DatabaseKey>,
+}
+
+/// 数据库密钥存储
+pub struct DbKeyStore {
+    dir: PathBuf,
+    path: PathBuf,
+    vault: Box<dyn KeyVault>,
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+fn random_hex(len: usize) -> String {
+    hex::encode(random_bytes(len))
+}
+
+fn key_id(uid: Option<&str>) -> &str {
+    match uid {
+        Some(id) if !id.is_empty() => id,
+        _ => DEFAULT_KEY_ID,
+    }
+}
+
+impl DbKeyStore {
+    /// 密钥文件与数据库文件放在同一目录，主密钥保存在系统钥匙串
+    pub fn for_database(db_path: &Path) -> Self {
+        let dir = db_path.parent().unwrap_or_else(|| Path::new("."));
+        Self::with_vault(dir, Box::new(SystemKeychain::new(dir)))
+    }
+
+    fn with_vault(dir: &Path, vault: Box<dyn KeyVault>) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            path: dir.join(KEY_STORE_FILENAME),
+            vault,
+        }
+    }
+
+    /// 读取密钥文件和主密钥
+    ///
+    /// 目录中已有加密数据库时，密钥文件或主密钥丢失都直接返回错误：
+    /// 重新生成的密钥无法打开这些数据库，继续下去只会让它们被当作损坏的文件处理
+    fn load(&self) -> Result<(KeyStoreFile, Vec<u8>), CommonError> {
+        let store = match std::fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str::<KeyStoreFile>(&content)
+                .map_err(|e| anyhow::anyhow!("Failed to parse database key store: {}", e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                if let Some(db_path) = self.find_encrypted_database() {
+                    return Err(anyhow::anyhow!(
+                        "Database key store {:?} is missing, encrypted database {:?} cannot be opened",
+                        self.path,
+                        db_path
+                    )
+                    .into());
+                }
+                KeyStoreFile::default()
+            }
+            Err(e) => {
+                return Err(anyhow::anyhow!("Failed to read database key store: {}", e).into());
+            }
+        };
+        let master = self.master_secret()?;
+        Ok((store, master))
+    }
+
+    /// 从系统钥匙串读取主密钥，首次使用时生成
+    fn master_secret(&self) -> Result<Vec<u8>, CommonError> {
+        let entry = keychain::entry_name(MASTER_SECRET_ENTRY, &self.dir);
+        if let Some(secret) = self.vault.get(&entry)? {
+            return Ok(secret);
+        }
+        if let Some(db_path) = self.find_encrypted_database() {
+            return Err(anyhow::anyhow!(
+                "Database master secret is missing from the system keychain, encrypted database {:?} cannot be opened",
+                db_path
+            )
+            .into());
+        }
+
+        let secret = random_bytes(MASTER_SECRET_LEN);
+        self.vault.set(&entry, &secret)?;
+        Ok(secret)
+    }
+
+    /// 查找目录中已加密的数据库文件
+    fn find_encrypted_database(&self) -> Option<PathBuf> {
+        std::fs::read_dir(&self.dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .find(|path| {
+                path.extension().is_some_and(|ext| ext == "sqlite") && is_encrypted_database(path)
+            })
+    }
+
+    fn save(&self, store: &KeyStoreFile) -> Result<(), CommonError> {
+        let content = serde_json::to_string_pretty(store)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize database key store: {}", e))?;
+        // 先写临时文件再替换，避免写到一半时密钥文件损坏
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content)
+            .map_err(|e| anyhow::anyhow!("Failed to write database key store: {}", e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600));
+        }
+        std::fs::rename(&tmp_path, &self.path)
+            .map_err(|e| anyhow::anyhow!("Failed to replace database key store: {}", e))?;
+        Ok(())
+    }
+
+    fn derive(master: &[u8], key_id: &str, salt: &str) -> Result<DatabaseKey, CommonError> {
+        let salt =
+            hex::decode(salt).map_err(|e| anyhow::anyhow!("Invalid database salt: {}", e))?;
+        DatabaseKey::derive(master, key_id, &salt)
+    }
+
+    /// 获取用户数据库密钥，首次使用时生成盐值
+    pub fn keys(&self, uid: Option<&str>) -> Result<DatabaseKeys, CommonError> {
+        self.load_keys(uid, true)
+    }
+
+    /// 获取已存在的用户数据库密钥，没有记录时返回错误而不是生成新的盐值
+    pub fn existing_keys(&self, uid: Option<&str>) -> Result<DatabaseKeys, CommonError> {
+        self.load_keys(uid, false)
+    }
+
+    fn load_keys(&self, uid: Option<&str>, create: bool) -> Result<DatabaseKeys, CommonError> {
+        let _guard = KEY_STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let key_id = key_id(uid);
+        let (mut store, master) = self.load()?;
+
+        let entry = match store.users.get(key_id) {
+            Some(entry) => entry.clone(),
+            None if !create => {
+                return Err(anyhow::anyhow!("No database key found for {}", key_id).into());
+            }
+            None => {
+                let entry = UserKeyEntry {
+                    salt: random_hex(16),
+                    pending_salt: None,
+                };
+                store.users.insert(key_id.to_string(), entry.clone());
+                self.save(&store)?;
+                entry
+            }
+        };
+
+        let current = Self::derive(&master, key_id, &entry.salt)?;
+        let pending = match entry.pending_salt.as_deref() {
+            Some(salt) => Some(Self::derive(&master, key_id, salt)?),
+            None => None,
+        };
+        Ok(DatabaseKeys { current, pending })
+    }
+
+    /// 生成新的盐值并记录为待生效，返回新密钥
+    pub fn begin_rekey(&self, uid: Option<&str>) -> Result<DatabaseKey, CommonError> {
+        let _guard = KEY_STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let key_id = key_id(uid);
+        let (mut store, master) = self.load()?;
+        let salt = random_hex(16);
+        let entry = store
+            .users
+            .get_mut(key_id)
+            .ok_or_else(|| anyhow::anyhow!("No database key found for {}", key_id))?;
+        entry.pending_salt = Some(salt.clone());
+        self.save(&store)?;
+        Self::derive(&master, key_id, &salt)
+    }
+
+    /// 数据库已使用新密钥加密，让待生效的盐值成为当前盐值
+    pub fn commit_rekey(&self, uid: Option<&str>) -> Result<(), CommonError> {
+        self.update_entry(uid, |entry| {
+            if let Some(salt) = entry.pending_salt.take() {
+                entry.salt = salt;
+            }
+        })
+    }
+
+    /// 放弃未完成的重新加密
+    pub fn abort_rekey(&self, uid: Option<&str>) -> Result<(), CommonError> {
+        self.update_entry(uid, |entry| entry.pending_salt = None)
+    }
+
+    fn update_entry(
+        &self,
+        uid: Option<&str>,
+        f: impl FnOnce(&mut UserKeyEntry),
+    ) -> Result<(), CommonError> {
+        let _guard = KEY_STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let (mut store, _) = self.load()?;
+        if let Some(entry) = store.users.get_mut(key_id(uid)) {
+            f(entry);
+            self.save(&store)?;
+        }
+        Ok(())
+    }
+}
+
+/// 判断数据库文件是否为未加密的 SQLite 文件
+pub fn is_plaintext_database(db_path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    match std::fs::File::open(db_path) {
+        Ok(mut f) => f.read_exact(&mut header).is_ok() && header == *SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+/// 判断数据库文件是否已加密：文件非空且不是明文 SQLite 文件
+pub fn is_encrypted_database(db_path: &Path) -> bool {
+    std::fs::metadata(db_path).is_ok_and(|metadata| metadata.is_file() && metadata.len() > 0)
+        && !is_plaintext_database(db_path)
+}
+
+fn sidecar_path(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut name = db_path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn remove_sidecar_files(db_path: &Path) {
+    for suffix in ["-wal", "-shm", "-journal"] {
+        let path = sidecar_path(db_path, suffix);
+        if path.exists() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// 把无法打开的数据库连同 WAL 等附属文件改名保留，返回备份文件路径
+///
+/// SQLCipher 无法区分密钥错误和文件损坏，原文件不删除，找回密钥后仍可恢复
+pub fn quarantine_database(db_path: &Path) -> Result<PathBuf, CommonError> {
+    let backup = db_path.with_extension(format!(
+        "corrupted-{}",
+        chrono::Utc::now().timestamp_millis()
+    ));
+    std::fs::rename(db_path, &backup)
+        .map_err(|e| anyhow::anyhow!("Failed to move unreadable database aside: {}", e))?;
+    for suffix in ["-wal", "-shm", "-journal"] {
+        let path = sidecar_path(db_path, suffix);
+        if path.exists() {
+            let _ = std::fs::rename(&path, sidecar_path(&backup, suffix));
+        }
+    }
+    Ok(backup)
+}
+
+/// 使用 `sqlcipher_export` 把数据库导出为新密钥加密的副本，并替换原文件
+///
+/// `source_key` 为 None 表示源文件是明文数据库。调用方需保证源数据库没有其他连接。
+pub async fn export_database(
+    db_path: &Path,
+    source_key: Option<&DatabaseKey>,
+    target_key: &DatabaseKey,
+) -> Result<(), CommonError> {
+    let tmp_path = db_path.with_extension("encrypting");
+    if tmp_path.exists() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+
+    // 附加的数据库继承主连接的打开模式，需要 rwc 才能创建导出文件
+    let mut opt = ConnectOptions::new(format!("sqlite:{}?mode=rwc", db_path.display()));
+    opt.max_connections(1)
+        .min_connections(1)
+        .sqlx_logging(false);
+    if let Some(key) = source_key {
+        let key = key.pragma_value().to_string();
+        opt.map_sqlx_sqlite_opts(move |o| o.pragma("key", key.clone()));
+    }
+    let db = Database::connect(opt)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to open database for export: {}", e))?;
+
+    let attach = format!(
+        "ATTACH DATABASE '{}' AS encrypted KEY {}",
+        tmp_path.display().to_string().replace('\'', "''"),
+        target_key.pragma_value()
+    );
+    let exported = async {
+        db.execute_unprepared(&attach).await?;
+        db.execute_unprepared("SELECT sqlcipher_export('encrypted')")
+            .await?;
+        db.execute_unprepared("DETACH DATABASE encrypted").await?;
+        Ok::<_, sea_orm::DbErr>(())
+    }
+    .await;
+    let _ = db.close().await;
+
+    if let Err(e) = exported {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(anyhow::anyhow!("Failed to export encrypted database: {}", e).into());
+    }
+
+    remove_sidecar_files(db_path);
+    std::fs::rename(&tmp_path, db_path)
+        .map_err(|e| anyhow::anyhow!("Failed to replace database file: {}", e))?;
+    Ok(())
+}
+
+/// 打开数据库前的准备：取出用户密钥，如果数据库仍是明文则先迁移为加密数据库
+pub async fn prepare_database(
+    db_path: &Path,
+    uid: Option<&str>,
+) -> Result<DatabaseKeys, CommonError> {
+    let store = DbKeyStore::for_database(db_path);
+    // 已加密的数据库只能使用已有的密钥打开
+    let keys = if is_encrypted_database(db_path) {
+        store.existing_keys(uid)?
+    } else {
+        store.keys(uid)?
+    };
+
+    if db_path.exists() && is_plaintext_database(db_path) {
+        info!("Migrating plaintext database to encrypted: {:?}", db_path);
+        if let Err(e) = export_database(db_path, None, &keys.current).await {
+            // 迁移失败时保留明文数据库，下次启动再尝试，避免丢失本地数据
+            warn!("Failed to encrypt plaintext database {:?}: {}", db_path, e);
+            return Err(e);
+        }
+        info!("Plaintext database encrypted: {:?}", db_path);
+    }
+
+    Ok(keys)
+}
+
+/// 验证密钥是否能打开数据库，密钥错误时 SQLCipher 会在第一次读取时报 `file is not a database`
+pub async fn verify_key(db: &DatabaseConnection) -> Result<(), sea_orm::DbErr> {
+    db.execute_unprepared("SELECT count(*) FROM sqlite_master")
+        .await
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::keychain::MemoryKeyVault;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hula-db-keys-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_master_secret_kept_in_keychain() {
+        let dir = temp_dir();
+        let vault = MemoryKeyVault::default();
+
+        let store = DbKeyStore::with_vault(&dir, Box::new(vault.clone()));
+        let keys = store.keys(Some("10001")).unwrap();
+        let content = std::fs::read_to_string(dir.join(KEY_STORE_FILENAME)).unwrap();
+        assert!(!content.contains("masterSecret"));
+
+        // 重新打开后从钥匙串读取同一个主密钥，派生的密钥不变
+        let store = DbKeyStore::with_vault(&dir, Box::new(vault));
+        assert!(store.existing_keys(Some("10001")).unwrap().current == keys.current);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_quarantine_keeps_database_and_wal() {
+        let dir = temp_dir();
+        let db_path = dir.join("db_10001.sqlite");
+        std::fs::write(&db_path, b"broken").unwrap();
+        std::fs::write(sidecar_path(&db_path, "-wal"), b"wal").unwrap();
+
+        let backup = quarantine_database(&db_path).unwrap();
+        assert!(!db_path.exists());
+        assert!(!sidecar_path(&db_path, "-wal").exists());
+        assert_eq!(std::fs::read(&backup).unwrap(), b"broken");
+        assert_eq!(
+            std::fs::read(sidecar_path(&backup, "-wal")).unwrap(),
+            b"wal"
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_missing_key_store_with_encrypted_database() {
+        let dir = temp_dir();
+        std::fs::write(dir.join("db_10001.sqlite"), random_bytes(4096)).unwrap();
+
+        let store = DbKeyStore::with_vault(&dir, Box::new(MemoryKeyVault::default()));
+        assert!(store.keys(Some("10001")).is_err());
+        assert!(store.keys(Some("10002")).is_err());
+        assert!(!dir.join(KEY_STORE_FILENAME).exists());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
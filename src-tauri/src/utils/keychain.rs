@@ -0,0 +1,134 @@
+//! 系统钥匙串
+//!
+//! 本地加密使用的主密钥保存在系统提供的安全存储中：Windows 凭据管理器（DPAPI）、
+//! macOS/iOS 钥匙串以及 Linux 的 Secret Service。
+//!
+//! 注意：Android 目前没有接入 Android Keystore，主密钥以十六进制明文保存在应用私有数据目录
+//! （权限 0600）。这只能防止其他应用读取，无法防御 root 后的设备或整机备份提取，
+//! 在这类场景下本地加密数据库和令牌文件并不比明文更安全。
+
+use crate::error::CommonError;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// 钥匙串条目所属的服务名
+#[cfg(not(target_os = "android"))]
+const KEYCHAIN_SERVICE: &str = "com.hula.pc";
+
+/// 密钥存储接口
+pub trait KeyVault: Send + Sync + std::fmt::Debug {
+    /// 读取密钥，不存在时返回 None
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, CommonError>;
+
+    fn set(&self, name: &str, key: &[u8]) -> Result<(), CommonError>;
+}
+
+/// 生成与数据目录绑定的条目名，开发环境和正式环境的数据目录不同，密钥互不影响
+pub fn entry_name(kind: &str, dir: &Path) -> String {
+    let digest = Sha256::digest(dir.to_string_lossy().as_bytes());
+    format!("{}:{}", kind, &hex::encode(digest)[..16])
+}
+
+/// 系统钥匙串，Android 上退化为私有目录中的明文密钥文件（见模块说明）
+#[derive(Debug)]
+pub struct SystemKeychain {
+    /// Android 上保存密钥的私有目录
+    #[cfg(target_os = "android")]
+    dir: std::path::PathBuf,
+}
+
+impl SystemKeychain {
+    #[cfg_attr(not(target_os = "android"), allow(unused_variables))]
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            #[cfg(target_os = "android")]
+            dir: dir.to_path_buf(),
+        }
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+impl KeyVault for SystemKeychain {
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, CommonError> {
+        let entry = keyring::Entry::new(KEYCHAIN_SERVICE, name)
+            .map_err(|e| anyhow::anyhow!("Failed to open system keychain: {}", e))?;
+        match entry.get_password() {
+            Ok(value) => {
+                let key = hex::decode(value)
+                    .map_err(|e| anyhow::anyhow!("Invalid key in system keychain: {}", e))?;
+                Ok(Some(key))
+            }
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Failed to read system keychain: {}", e).into()),
+        }
+    }
+
+    fn set(&self, name: &str, key: &[u8]) -> Result<(), CommonError> {
+        let entry = keyring::Entry::new(KEYCHAIN_SERVICE, name)
+            .map_err(|e| anyhow::anyhow!("Failed to open system keychain: {}", e))?;
+        entry
+            .set_password(&hex::encode(key))
+            .map_err(|e| anyhow::anyhow!("Failed to write system keychain: {}", e).into())
+    }
+}
+
+#[cfg(target_os = "android")]
+impl KeyVault for SystemKeychain {
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, CommonError> {
+        match std::fs::read_to_string(self.key_path(name)) {
+            Ok(value) => {
+                let key = hex::decode(value.trim())
+                    .map_err(|e| anyhow::anyhow!("Invalid key file: {}", e))?;
+                Ok(Some(key))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Failed to read key file: {}", e).into()),
+        }
+    }
+
+    fn set(&self, name: &str, key: &[u8]) -> Result<(), CommonError> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create key directory: {}", e))?;
+        let path = self.key_path(name);
+        let tmp_path = path.with_extension("key.tmp");
+        std::fs::write(&tmp_path, hex::encode(key))
+            .map_err(|e| anyhow::anyhow!("Failed to write key file: {}", e))?;
+        std::fs::set_permissions(
+            &tmp_path,
+            std::os::unix::fs::PermissionsExt::from_mode(0o600),
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to restrict key file: {}", e))?;
+        std::fs::rename(&tmp_path, &path)
+            .map_err(|e| anyhow::anyhow!("Failed to replace key file: {}", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "android")]
+impl SystemKeychain {
+    fn key_path(&self, name: &str) -> std::path::PathBuf {
+        self.dir.join(format!("{}.key", name.replace(':', "-")))
+    }
+}
+
+/// 测试使用的内存密钥存储，克隆后共享同一份数据
+#[cfg(test)]
+#[derive(Debug, Default, Clone)]
+pub struct MemoryKeyVault(
+    std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, Vec<u8>>>>,
+);
+
+#[cfg(test)]
+impl KeyVault for MemoryKeyVault {
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, CommonError> {
+        Ok(self.0.lock().unwrap().get(name).cloned())
+    }
+
+    fn set(&self, name: &str, key: &[u8]) -> Result<(), CommonError> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), key.to_vec());
+        Ok(())
+    }
+}
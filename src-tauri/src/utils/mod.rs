@@ -1,7 +1,10 @@
+pub mod db_crypto;
+pub mod keychain;
 #[cfg(target_os = "linux")]
 pub mod linux_runtime_guard;
 #[cfg(target_os = "macos")]
 pub mod macos_runtime_guard;
 pub mod sql_debug;
+pub mod sse;
 #[cfg(target_os = "windows")]
 pub mod win_runtime_guard;
@@ -0,0 +1,253 @@
+//! 按 WHATWG 规范解析 `text/event-stream`
+//!
+//! 解码器按字节缓存网络数据，只在拿到完整的行之后才做 UTF-8 解码，
+//! 因此多字节字符被拆到两次读取中也不会丢失。
+
+use std::time::Duration;
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// 解析出的一条 SSE 事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    /// `event` 字段，未指定时为 "message"
+    pub event: String,
+    /// 多行 `data` 以 "\n" 拼接后的内容
+    pub data: String,
+    /// 派发该事件时的 last event id
+    pub id: Option<String>,
+}
+
+/// 增量 SSE 解码器，一个连接（及其重连）使用一个实例
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buf: Vec<u8>,
+    /// 是否已经越过流开头可能出现的 BOM
+    started: bool,
+    /// 上一行以 `\r` 结束，下一个字节若为 `\n` 需要跳过
+    skip_lf: bool,
+    event: String,
+    data: String,
+    last_event_id: String,
+    retry: Option<Duration>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从上次连接记录的 id 继续，重连时需把它作为 `Last-Event-ID` 请求头发送
+    pub fn with_last_event_id(id: impl Into<String>) -> Self {
+        Self {
+            last_event_id: id.into(),
+            ..Self::default()
+        }
+    }
+
+    /// 最近一次收到的事件 id，没有时为空
+    pub fn last_event_id(&self) -> Option<&str> {
+        (!self.last_event_id.is_empty()).then_some(self.last_event_id.as_str())
+    }
+
+    /// 服务端通过 `retry` 字段指定的重连间隔
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    /// 写入一段网络数据，返回其中已完整的事件
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buf.extend_from_slice(chunk);
+
+        if !self.started {
+            if self.buf.len() < BOM.len() && BOM.starts_with(&self.buf) {
+                return Vec::new();
+            }
+            if self.buf.starts_with(BOM) {
+                self.buf.drain(..BOM.len());
+            }
+            self.started = true;
+        }
+
+        let mut events = Vec::new();
+        let mut start = 0;
+        while start < self.buf.len() {
+            if self.skip_lf {
+                self.skip_lf = false;
+                if self.buf[start] == b'\n' {
+                    start += 1;
+                    continue;
+                }
+            }
+            let Some(offset) = self.buf[start..]
+                .iter()
+                .position(|b| *b == b'\n' || *b == b'\r')
+            else {
+                break;
+            };
+            let end = start + offset;
+            self.skip_lf = self.buf[end] == b'\r';
+            let line = String::from_utf8_lossy(&self.buf[start..end]).into_owned();
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+            start = end + 1;
+        }
+        self.buf.drain(..start);
+        events
+    }
+
+    /// 连接断开时调用，丢弃未以空行结束的事件，保留 last event id 和 retry
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.started = false;
+        self.skip_lf = false;
+        self.event.clear();
+        self.data.clear();
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = value.to_string(),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(ms) = value.parse() {
+                    self.retry = Some(Duration::from_millis(ms));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = std::mem::take(&mut self.event);
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut data = std::mem::take(&mut self.data);
+        data.pop();
+        Some(SseEvent {
+            event: if event.is_empty() {
+                "message".to_string()
+            } else {
+                event
+            },
+            data,
+            id: self.last_event_id().map(str::to_string),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(data: &str) -> SseEvent {
+        SseEvent {
+            event: "message".to_string(),
+            data: data.to_string(),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn parses_fields_and_multiline_data() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.feed(
+            b": keep-alive\nevent: delta\nid: 7\nretry: 3000\ndata: first\ndata:second\ndata\n\n",
+        );
+        assert_eq!(
+            events,
+            vec![SseEvent {
+                event: "delta".to_string(),
+                data: "first\nsecond\n".to_string(),
+                id: Some("7".to_string()),
+            }]
+        );
+        assert_eq!(decoder.last_event_id(), Some("7"));
+        assert_eq!(decoder.retry(), Some(Duration::from_millis(3000)));
+
+        // 事件类型不跨事件保留，id 保留
+        let events = decoder.feed(b"data: next\n\n");
+        assert_eq!(events[0].event, "message");
+        assert_eq!(events[0].id.as_deref(), Some("7"));
+    }
+
+    #[test]
+    fn handles_all_line_endings() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.feed(b"data: a\r\n\r\ndata: b\r\rdata: c\n\n");
+        assert_eq!(events, vec![message("a"), message("b"), message("c")]);
+    }
+
+    #[test]
+    fn handles_crlf_split_across_chunks() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.feed(b"data: a\r").is_empty());
+        assert!(decoder.feed(b"\n").is_empty());
+        assert_eq!(decoder.feed(b"\r\n"), vec![message("a")]);
+    }
+
+    #[test]
+    fn keeps_utf8_split_across_chunks() {
+        let bytes = "data: 你好\n\n".as_bytes();
+        let mut decoder = SseDecoder::new();
+        let mut events = Vec::new();
+        for byte in bytes {
+            events.extend(decoder.feed(std::slice::from_ref(byte)));
+        }
+        assert_eq!(events, vec![message("你好")]);
+    }
+
+    #[test]
+    fn strips_leading_bom() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.feed(&BOM[..2]).is_empty());
+        let mut rest = BOM[2..].to_vec();
+        rest.extend_from_slice(b"data: x\n\n");
+        assert_eq!(decoder.feed(&rest), vec![message("x")]);
+    }
+
+    #[test]
+    fn ignores_invalid_id_retry_and_empty_events() {
+        let mut decoder = SseDecoder::with_last_event_id("1");
+        let events = decoder.feed(b"id: a\0b\nretry: 1s\nevent: ping\n\ndata:\n\n");
+        assert_eq!(
+            events,
+            vec![SseEvent {
+                event: "message".to_string(),
+                data: String::new(),
+                id: Some("1".to_string()),
+            }]
+        );
+        assert_eq!(decoder.retry(), None);
+
+        // 空 id 会清除 last event id
+        decoder.feed(b"id\n");
+        assert_eq!(decoder.last_event_id(), None);
+    }
+
+    #[test]
+    fn reset_drops_pending_event() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.feed(b"id: 3\ndata: partial\n").is_empty());
+        decoder.reset();
+        assert_eq!(decoder.feed(b"data: full\n\n")[0].data, "full");
+        assert_eq!(decoder.last_event_id(), Some("3"));
+    }
+}
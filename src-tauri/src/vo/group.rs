@@ -0,0 +1,53 @@
+use entity::im_room;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+
+use crate::command::room_member_command::RoomMemberResponse;
+use crate::im_request_client::im_api;
+use crate::pojo::common::Page;
+use crate::vo::vo::MyRoomInfoReq;
+
+/// 分页查询我加入的群聊
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupListReq {
+    pub current: u32,
+    pub size: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupListMemberReq {
+    pub room_id: String,
+}
+
+im_api!(GroupListReq => GroupList, query, Page<im_room::Model>);
+im_api!(GroupListMemberReq => GroupListMember, query, Vec<RoomMemberResponse>);
+im_api!(MyRoomInfoReq => UpdateMyRoomInfo, body, IgnoredAny);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_group_models_serde_round_trip() {
+        let req = GroupListReq {
+            current: 1,
+            size: 20,
+        };
+        let value = serde_json::to_value(&req).unwrap();
+        assert_eq!(value, json!({ "current": 1, "size": 20 }));
+        assert_eq!(serde_json::from_value::<GroupListReq>(value).unwrap(), req);
+
+        let req = GroupListMemberReq {
+            room_id: "10".to_string(),
+        };
+        let value = serde_json::to_value(&req).unwrap();
+        assert_eq!(value, json!({ "roomId": "10" }));
+        assert_eq!(
+            serde_json::from_value::<GroupListMemberReq>(value).unwrap(),
+            req
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+use crate::command::message_command::MessageResp;
+use crate::im_request_client::im_api;
+use crate::pojo::common::CursorPage;
+use crate::vo::vo::ChatMessageReq;
+
+/// 按房间游标分页拉取消息
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgPageReq {
+    pub room_id: String,
+    pub page_size: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// 拉取消息列表，`msg_ids` 为空时拉取全部离线消息
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgListReq {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub msg_ids: Option<Vec<String>>,
+    #[serde(rename = "async", default, skip_serializing_if = "Option::is_none")]
+    pub async_data: Option<bool>,
+}
+
+im_api!(MsgPageReq => GetMsgPage, query, CursorPage<MessageResp>);
+im_api!(MsgListReq => GetMsgList, body, Vec<MessageResp>);
+im_api!(ChatMessageReq => SendMsg, body, MessageResp);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_message_models_serde_round_trip() {
+        let req = MsgPageReq {
+            room_id: "1".to_string(),
+            page_size: 20,
+            cursor: None,
+        };
+        let value = serde_json::to_value(&req).unwrap();
+        assert_eq!(value, json!({ "roomId": "1", "pageSize": 20 }));
+        assert_eq!(serde_json::from_value::<MsgPageReq>(value).unwrap(), req);
+
+        let req = MsgListReq {
+            msg_ids: Some(vec!["1".to_string(), "2".to_string()]),
+            async_data: Some(true),
+        };
+        let value = serde_json::to_value(&req).unwrap();
+        assert_eq!(value, json!({ "msgIds": ["1", "2"], "async": true }));
+        assert_eq!(serde_json::from_value::<MsgListReq>(value).unwrap(), req);
+        assert_eq!(
+            serde_json::to_value(MsgListReq::default()).unwrap(),
+            json!({})
+        );
+
+        // 最后一页的 cursor 为空且可能缺少 isLast
+        let page: CursorPage<String> =
+            serde_json::from_value(json!({ "cursor": null, "list": ["a"] })).unwrap();
+        assert!(!page.is_last);
+        let value = serde_json::to_value(&page).unwrap();
+        assert_eq!(
+            value,
+            json!({ "cursor": null, "isLast": false, "list": ["a"] })
+        );
+    }
+}
@@ -1,2 +1,5 @@
+pub mod group;
+pub mod message;
+pub mod storage;
 pub mod user_info;
 pub mod vo;
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::im_request_client::im_api;
+
+/// 查询服务端默认的对象存储
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct StorageProviderReq {}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StorageProviderResp {
+    /// qiniu 或 minio
+    pub provider: String,
+}
+
+/// 获取上传凭证
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OssTokenReq {
+    pub scene: String,
+    pub file_name: String,
+}
+
+/// 七牛上传凭证
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OssTokenResp {
+    pub token: Option<String>,
+    pub domain: Option<String>,
+    pub storage_prefix: Option<String>,
+}
+
+im_api!(StorageProviderReq => StorageProvider, query, StorageProviderResp);
+im_api!(OssTokenReq => GetQiniuToken, query, OssTokenResp);
@@ -1,7 +1,9 @@
 use crate::AppData;
 use crate::command::message_command::{SyncMessagesParam, sync_messages};
-use crate::websocket::commands::get_websocket_client_container;
+use crate::command::message_retry_command::start_retry_worker;
+use crate::repository::im_ws_outbox_repository;
 
+use super::message::dispatch_inbound_message;
 use super::types::*;
 use anyhow::Result;
 use chrono::Utc;
@@ -18,6 +20,13 @@ use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 use tracing::{debug, error, info, warn};
 use url::Url;
 
+/// 发件箱容量上限，超出后丢弃最旧的消息
+const OUTBOX_MAX_SIZE: u64 = 100;
+/// 单条消息最大投递尝试次数
+const OUTBOX_MAX_RETRIES: i32 = 5;
+/// 发件箱消息默认有效期：24 小时
+const OUTBOX_DEFAULT_TTL_MS: u64 = 24 * 60 * 60 * 1000;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AckMessage {
@@ -52,7 +61,6 @@ pub struct WebSocketClient {
 
     // 消息队列
     message_sender: Arc<RwLock<Option<mpsc::UnboundedSender<Message>>>>,
-    pending_messages: Arc<RwLock<Vec<serde_json::Value>>>,
 
     // 连接控制
     should_stop: Arc<AtomicBool>,
@@ -87,7 +95,6 @@ impl WebSocketClient {
             reconnect_attempts: Arc::new(AtomicU32::new(0)),
             is_reconnecting: Arc::new(AtomicBool::new(false)),
             message_sender: Arc::new(RwLock::new(None)),
-            pending_messages: Arc::new(RwLock::new(Vec::new())),
             should_stop: Arc::new(AtomicBool::new(false)),
             is_app_in_background: Arc::new(AtomicBool::new(false)),
             last_foreground_time: Arc::new(AtomicU64::new(
@@ -174,63 +181,105 @@ impl WebSocketClient {
 
     /// 发送消息
     pub async fn send_message(&self, data: serde_json::Value) -> Result<()> {
+        self.send_message_with_options(data, None, None)
+            .await
+            .map(|_| ())
+    }
+
+    /// 发送消息，连接未就绪时写入发件箱
+    /// `client_id` 用于发件箱去重，`ttl_ms` 为发件箱中的有效期
+    /// 返回 `Sent` 表示已立即发出，`Queued` 表示已写入发件箱等待重连后投递
+    pub async fn send_message_with_options(
+        &self,
+        data: serde_json::Value,
+        client_id: Option<String>,
+        ttl_ms: Option<u64>,
+    ) -> Result<OutboxStatus> {
         // 首先检查连接状态
         let current_state = self.get_state().await;
 
-        match current_state {
-            ConnectionState::Connected => {
-                let sender = self.message_sender.read().await;
-
-                if let Some(sender) = sender.as_ref() {
-                    let message = Message::Text(data.to_string().into());
-                    sender.send(message.clone()).map_err(|e| {
-                        anyhow::anyhow!("Failed to queue message for sending: {}", e)
-                    })?;
-                    info!("Message sent {:?}", message);
-                    Ok(())
-                } else {
-                    warn!("Connection state is Connected but sender not ready, message queued");
-                    // 连接未完全建立，将消息加入待发队列
-                    let mut pending = self.pending_messages.write().await;
-                    pending.push(data);
-
-                    // 限制队列长度
-                    if pending.len() > 100 {
-                        pending.remove(0);
-                        warn!("Pending queue full, dropping oldest message");
+        if current_state == ConnectionState::Connected {
+            let sender = self.message_sender.read().await;
+            if let Some(sender) = sender.as_ref() {
+                let message = Message::Text(data.to_string().into());
+                match sender.send(message.clone()) {
+                    Ok(_) => {
+                        info!("Message sent {:?}", message);
+                        return Ok(OutboxStatus::Sent);
                     }
-
-                    // 返回错误，让上层知道消息没有立即发送
-                    Err(anyhow::anyhow!(
-                        "Connection not fully established, message queued"
-                    ))
+                    Err(e) => warn!("Failed to send message: {}, queueing to outbox", e),
                 }
+            } else {
+                warn!("Connection state is Connected but sender not ready, queueing to outbox");
             }
-            ConnectionState::Connecting | ConnectionState::Reconnecting => {
-                // 连接中，将消息加入待发队列
-                let mut pending = self.pending_messages.write().await;
-                pending.push(data);
-                warn!(
-                    "正在连接中，消息已加入待发队列 (队列长度: {})",
-                    pending.len()
-                );
+        } else {
+            warn!(
+                "WebSocket 未连接 (状态: {:?})，消息写入发件箱",
+                current_state
+            );
+        }
 
-                // 限制队列长度
-                if pending.len() > 100 {
-                    pending.remove(0);
-                    warn!("Pending queue full, dropping oldest message");
-                }
+        // 连接未就绪，将消息写入发件箱等待重连后发送
+        if self.queue_to_outbox(data, client_id, ttl_ms).await? {
+            Ok(OutboxStatus::Queued)
+        } else {
+            Err(anyhow::anyhow!(
+                "WebSocket not connected (state: {:?}), message dropped",
+                current_state
+            ))
+        }
+    }
 
-                Err(anyhow::anyhow!("WebSocket is connecting, message queued"))
-            }
-            _ => {
-                warn!("WebSocket 未连接 (状态: {:?})，无法发送消息", current_state);
-                Err(anyhow::anyhow!(
-                    "WebSocket not connected (state: {:?})",
-                    current_state
-                ))
-            }
+    /// 将消息写入持久化发件箱，返回是否已入队，心跳消息不入队
+    async fn queue_to_outbox(
+        &self,
+        data: serde_json::Value,
+        client_id: Option<String>,
+        ttl_ms: Option<u64>,
+    ) -> Result<bool> {
+        if data.get("type").and_then(|t| t.as_str()) == Some("2") {
+            return Ok(false);
         }
+
+        let Some(state) = self.app_handle.try_state::<AppData>() else {
+            return Err(anyhow::anyhow!("App state not ready, message dropped"));
+        };
+        let login_uid = state.user_info.lock().await.uid.clone();
+        let db = state.db_conn.read().await.clone();
+
+        // 优先使用调用方提供的 client_id，其次使用消息体中的 clientId
+        let client_id = client_id
+            .or_else(|| {
+                data.get("clientId")
+                    .and_then(|id| id.as_str())
+                    .map(str::to_string)
+            })
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let ttl_ms = ttl_ms.unwrap_or(OUTBOX_DEFAULT_TTL_MS);
+        let expire_time = Utc::now().timestamp_millis() + ttl_ms as i64;
+
+        let dropped = im_ws_outbox_repository::enqueue_message(
+            &db,
+            &login_uid,
+            &client_id,
+            &data.to_string(),
+            expire_time,
+            OUTBOX_MAX_SIZE,
+        )
+        .await?;
+        if dropped > 0 {
+            warn!("Outbox full, dropped {} oldest messages", dropped);
+        }
+
+        let remaining = im_ws_outbox_repository::count_pending_messages(&db, &login_uid).await?;
+        self.emit_event(WebSocketEvent::OutboxProgress {
+            client_id,
+            status: OutboxStatus::Queued,
+            retry_count: 0,
+            remaining,
+        })
+        .await;
+        Ok(true)
     }
 
     /// 获取连接健康状态
@@ -429,8 +478,10 @@ impl WebSocketClient {
         // 标记为已连接
         self.is_ws_connected.store(true, Ordering::SeqCst);
 
-        // 发送待发消息
-        self.send_pending_messages().await?;
+        // 投递发件箱中的消息，失败的消息留待下次连接
+        if let Err(e) = self.send_pending_messages().await {
+            warn!("Failed to flush outbox: {}", e);
+        }
 
         // 启动心跳
         self.start_heartbeat().await;
@@ -584,8 +635,8 @@ impl WebSocketClient {
 
         // 处理业务消息
         if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&text) {
-            // 处理具体的业务消息类型
-            Self::process_business_message(&json_value, app_handle).await;
+            // 解析为业务事件并分发给已注册的处理器
+            dispatch_inbound_message(&json_value, app_handle).await;
 
             // 同时发送原始消息事件（保持兼容性）
             let _ = app_handle.emit(
@@ -652,208 +703,6 @@ impl WebSocketClient {
         Err(anyhow::anyhow!("Failed to send ACK after all retries"))
     }
 
-    /// 处理业务消息类型
-    async fn process_business_message(message: &serde_json::Value, app_handle: &AppHandle) {
-        // 提取消息类型
-        let message_type = message.get("type").and_then(|t| t.as_str()).unwrap_or("");
-
-        // 提取消息数据
-        let data = message.get("data");
-
-        debug!("Processing business message type: {}", message_type);
-
-        // 根据消息类型进行处理并发送对应的事件
-        match message_type {
-            // 登录相关
-            "loginQrCode" => {
-                info!("Getting login QR code");
-                let _ = app_handle.emit("ws-login-qr-code", data);
-            }
-            "waitingAuthorize" => {
-                info!("Waiting for authorization");
-                let _ = app_handle.emit("ws-waiting-authorize", data);
-            }
-            "loginSuccess" => {
-                info!("Login successful");
-                let _ = app_handle.emit_to("home", "ws-login-success", data);
-            }
-
-            // 消息相关 TODO 暂时只实现聊天消息的ack
-            "receiveMessage" => {
-                info!("Received message");
-
-                let client_container = get_websocket_client_container();
-                let client_guard = client_container.read().await;
-
-                if let Some(data_obj) = data {
-                    if let Some(message_id) = data_obj
-                        .get("message")
-                        .and_then(|m| m.get("id"))
-                        .and_then(|id| id.as_str())
-                    {
-                        info!("回执 ACK: {}", message_id);
-
-                        if let Some(client) = client_guard.as_ref() {
-                            match client.send_ack(message_id).await {
-                                Ok(_) => {
-                                    info!("ACK sent successfully for message {}", message_id);
-                                }
-                                Err(e) => {
-                                    error!(" Failed to send ACK for message {}: {}", message_id, e);
-                                }
-                            };
-                        } else {
-                            error!(" 回执失败");
-                        }
-                    }
-                }
-
-                let _ = app_handle.emit_to("home", "ws-receive-message", data);
-            }
-            "msgRecall" => {
-                info!("Message recalled");
-                let _ = app_handle.emit_to("home", "ws-msg-recall", data);
-            }
-            "msgMarkItem" => {
-                info!("Message liked/disliked");
-                let _ = app_handle.emit_to("home", "ws-msg-mark-item", data);
-            }
-
-            // 用户状态相关
-            "online" => {
-                info!("User online");
-                let _ = app_handle.emit_to("home", "ws-online", data);
-            }
-            "offline" => {
-                info!("User offline");
-                let _ = app_handle.emit_to("home", "ws-offline", data);
-            }
-            "userStateChange" => {
-                info!("User state changed");
-                let _ = app_handle.emit_to("home", "ws-user-state-change", data);
-            }
-            // 通知总线
-            "notifyEvent" => {
-                info!("新的notifyEvent");
-                let _ = app_handle.emit_to("home", "ws-request-notify-event", data);
-            }
-            "groupSetAdmin" => {
-                let _ = app_handle.emit_to("home", "ws-group-set-admin-success", data);
-            }
-            // 好友相关
-            "newApply" => {
-                info!("New apply request");
-                let _ = app_handle.emit_to("home", "ws-request-new-apply", data);
-            }
-            "requestApprovalFriend" => {
-                info!("Friend request approved");
-                let _ = app_handle.emit_to("home", "ws-request-approval-friend", data);
-            }
-            "memberChange" => {
-                info!("Member change");
-                let _ = app_handle.emit_to("home", "ws-member-change", data);
-            }
-
-            // 房间/群聊相关
-            "roomInfoChange" => {
-                info!("Room info changed");
-                let _ = app_handle.emit_to("home", "ws-room-info-change", data);
-            }
-            "myRoomInfoChange" => {
-                info!("My room info changed");
-                let _ = app_handle.emit_to("home", "ws-my-room-info-change", data);
-            }
-            "roomGroupNoticeMsg" => {
-                info!("Group notice published");
-                let _ = app_handle.emit_to("home", "ws-room-group-notice-msg", data);
-            }
-            "roomEditGroupNoticeMsg" => {
-                info!("✏️ Group notice edited");
-                let _ = app_handle.emit_to("home", "ws-room-edit-group-notice-msg", data);
-            }
-            "roomDissolution" => {
-                info!("Room dissolved");
-                let _ = app_handle.emit_to("home", "ws-room-dissolution", data);
-            }
-
-            // 视频通话相关
-            "VideoCallRequest" => {
-                info!("Received call request");
-                let _ = app_handle.emit("ws-video-call-request", data);
-            }
-            "CallAccepted" => {
-                info!("Call accepted");
-                let _ = app_handle.emit("ws-call-accepted", data);
-            }
-            "CallRejected" => {
-                info!(" Call rejected");
-                let _ = app_handle.emit("ws-call-rejected", data);
-            }
-            "RoomClosed" => {
-                info!("Room closed");
-                let _ = app_handle.emit("ws-room-closed", data);
-            }
-            "WEBRTC_SIGNAL" => {
-                info!("Signaling message");
-                let _ = app_handle.emit("ws-webrtc-signal", data);
-            }
-            "JoinVideo" => {
-                info!("User joined video");
-                let _ = app_handle.emit("ws-join-video", data);
-            }
-            "LeaveVideo" => {
-                info!("User left video");
-                let _ = app_handle.emit("ws-leave-video", data);
-            }
-            "DROPPED" => {
-                info!("Call dropped");
-                let _ = app_handle.emit("ws-dropped", data);
-            }
-
-            "CANCEL" => {
-                info!("Call cancelled");
-                let _ = app_handle.emit("ws-cancel", data);
-            }
-
-            "TIMEOUT" => {
-                info!("Call timeout");
-                let _ = app_handle.emit("ws-timeout", data);
-            }
-
-            // 系统相关
-            "tokenExpired" => {
-                warn!("Token expired");
-                let _ = app_handle.emit("ws-token-expired", data);
-            }
-            "invalidUser" => {
-                warn!("Invalid user");
-                let _ = app_handle.emit("ws-invalid-user", data);
-            }
-
-            "deleteFriend" => {
-                warn!("Delete Friend");
-                let _ = app_handle.emit("ws-delete-friend", data);
-            }
-
-            // 朋友圈相关
-            "feedSendMsg" => {
-                info!("Feed message received");
-                let _ = app_handle.emit_to("home", "ws-feed-send-msg", data);
-            }
-            "feedNotify" => {
-                info!("Feed notification received (like/comment)");
-                let _ = app_handle.emit_to("home", "ws-feed-notify", data);
-            }
-
-            // 未知消息类型
-            _ => {
-                warn!("Received unhandled message type: {}", message_type);
-                // 发送通用的未知消息事件
-                let _ = app_handle.emit("ws-unknown-message", message);
-            }
-        }
-    }
-
     /// 启动心跳机制
     async fn start_heartbeat(&self) {
         if self.heartbeat_active.swap(true, Ordering::SeqCst) {
@@ -951,53 +800,89 @@ impl WebSocketClient {
         handles.push(heartbeat_task);
     }
 
-    /// 发送待发消息
+    /// 按写入顺序投递发件箱中的消息
     async fn send_pending_messages(&self) -> Result<()> {
-        // 先取出所有待发消息
-        let messages_to_send = {
-            let mut pending = self.pending_messages.write().await;
-            if pending.is_empty() {
-                return Ok(());
-            }
-
-            info!("Preparing to send {} pending messages", pending.len());
-            pending.drain(..).collect::<Vec<_>>()
+        let Some(state) = self.app_handle.try_state::<AppData>() else {
+            return Ok(());
         };
+        let login_uid = state.user_info.lock().await.uid.clone();
+        let db = state.db_conn.read().await.clone();
+
+        // 先清理过期消息
+        let expired = im_ws_outbox_repository::remove_expired_messages(
+            &db,
+            &login_uid,
+            Utc::now().timestamp_millis(),
+        )
+        .await?;
+        let entries = im_ws_outbox_repository::list_pending_messages(&db, &login_uid).await?;
+        let mut remaining = entries.len() as u64;
+
+        for entry in expired {
+            warn!("Outbox message {} expired, dropping", entry.client_id);
+            self.emit_event(WebSocketEvent::OutboxProgress {
+                client_id: entry.client_id,
+                status: OutboxStatus::Expired,
+                retry_count: entry.retry_count,
+                remaining,
+            })
+            .await;
+        }
+
+        if entries.is_empty() {
+            return Ok(());
+        }
+        info!("Preparing to send {} pending messages", entries.len());
 
         // 获取发送器
         let sender = self.message_sender.read().await;
-        if let Some(sender) = sender.as_ref() {
-            let mut failed_messages = Vec::new();
+        let Some(sender) = sender.as_ref() else {
+            warn!("Sender not ready, messages kept in outbox");
+            return Err(anyhow::anyhow!("Message sender not ready"));
+        };
 
-            // 尝试发送每条消息
-            for message in messages_to_send {
-                let text_message = Message::Text(message.to_string().into());
-                if let Err(e) = sender.send(text_message) {
-                    error!(" Failed to send pending message: {}", e);
-                    failed_messages.push(message);
+        for entry in entries {
+            let text_message = Message::Text(entry.payload.clone().into());
+            match sender.send(text_message) {
+                Ok(_) => {
+                    im_ws_outbox_repository::remove_message(&db, entry.id).await?;
+                    remaining -= 1;
+                    self.emit_event(WebSocketEvent::OutboxProgress {
+                        client_id: entry.client_id,
+                        status: OutboxStatus::Sent,
+                        retry_count: entry.retry_count,
+                        remaining,
+                    })
+                    .await;
                 }
-            }
-
-            // 如果有失败的消息，重新加入队列
-            if !failed_messages.is_empty() {
-                let mut pending = self.pending_messages.write().await;
-                for msg in failed_messages.into_iter().rev() {
-                    pending.insert(0, msg); // 插入到队列前面
+                Err(e) => {
+                    error!(" Failed to send pending message: {}", e);
+                    let retry_count =
+                        im_ws_outbox_repository::record_failure(&db, &entry, &e.to_string())
+                            .await?;
+
+                    let status = if retry_count >= OUTBOX_MAX_RETRIES {
+                        im_ws_outbox_repository::remove_message(&db, entry.id).await?;
+                        remaining -= 1;
+                        OutboxStatus::Failed
+                    } else {
+                        OutboxStatus::Retrying
+                    };
+                    self.emit_event(WebSocketEvent::OutboxProgress {
+                        client_id: entry.client_id,
+                        status,
+                        retry_count,
+                        remaining,
+                    })
+                    .await;
+
+                    // 保证投递顺序，后续消息等待下次连接
+                    return Err(anyhow::anyhow!("Some pending messages failed to send"));
                 }
-                return Err(anyhow::anyhow!("Some pending messages failed to send"));
             }
-
-            info!("All pending messages sent");
-        } else {
-            // 发送器未就绪，将消息重新加入队列
-            let mut pending = self.pending_messages.write().await;
-            for msg in messages_to_send.into_iter().rev() {
-                pending.insert(0, msg);
-            }
-            warn!("Sender not ready, messages re-queued");
-            return Err(anyhow::anyhow!("Message sender not ready"));
         }
 
+        info!("All pending messages sent");
         Ok(())
     }
 
@@ -1009,6 +894,10 @@ impl WebSocketClient {
             drop(state);
 
             info!("Connection state changed: {:?}", new_state);
+            if new_state == ConnectionState::Connected {
+                // 连接恢复后启动失败消息的自动重发
+                start_retry_worker(self.app_handle.clone());
+            }
             self.emit_event(WebSocketEvent::ConnectionStateChanged {
                 state: new_state,
                 is_reconnection,
@@ -27,8 +27,13 @@ pub struct InitWsParams {
 
 /// WebSocket 消息发送参数
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SendMessageParams {
     pub data: serde_json::Value,
+    /// 客户端消息ID，连接未就绪时用于发件箱去重
+    pub client_id: Option<String>,
+    /// 发件箱中的有效期（毫秒）
+    pub ttl_ms: Option<u64>,
 }
 
 /// WebSocket 配置更新参数
@@ -57,6 +62,14 @@ impl SuccessResponse {
     }
 }
 
+/// 发送消息响应，`status` 为 `SENT` 或 `QUEUED`（已写入发件箱）
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendMessageResponse {
+    pub success: bool,
+    pub status: OutboxStatus,
+}
+
 /// 初始化 WebSocket 连接
 #[tauri::command]
 pub async fn ws_init_connection(
@@ -67,12 +80,10 @@ pub async fn ws_init_connection(
     info!("Received WebSocket initialization request");
 
     let client_container = get_websocket_client_container();
-    let rc = state.rc.lock().await;
-
     let config = WebSocketConfig {
         server_url: state.config.lock().await.backend.ws_url.clone(),
         client_id: params.client_id,
-        token: rc.token.clone(),
+        token: state.rc.token(),
         ..Default::default()
     };
 
@@ -135,13 +146,19 @@ pub async fn ws_disconnect(_app_handle: AppHandle) -> Result<SuccessResponse, St
 pub async fn ws_send_message(
     _app_handle: AppHandle,
     params: SendMessageParams,
-) -> Result<SuccessResponse, String> {
+) -> Result<SendMessageResponse, String> {
     let client_container = get_websocket_client_container();
     let client_guard = client_container.read().await;
 
     if let Some(client) = client_guard.as_ref() {
-        match client.send_message(params.data).await {
-            Ok(_) => Ok(SuccessResponse::new()),
+        match client
+            .send_message_with_options(params.data, params.client_id, params.ttl_ms)
+            .await
+        {
+            Ok(status) => Ok(SendMessageResponse {
+                success: true,
+                status,
+            }),
             Err(e) => {
                 error!(" Failed to send message: {}", e);
                 Err(format!("发送失败: {}", e))
@@ -1,11 +1,41 @@
+use crate::command::message_command::{apply_message_recall, save_received_message};
+use crate::command::message_mark_command::apply_message_mark_event;
+use crate::command::room_member_command::{
+    apply_member_change, apply_room_dissolution, apply_room_info_change,
+};
+
+use super::commands::get_websocket_client_container;
+use super::types::{InboundEvent, InboundEventDiagnostic};
+use futures::future::BoxFuture;
 use serde_json::Value;
 use std::collections::HashMap;
-use tracing::debug;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::RwLock;
+use tracing::{debug, error, info, warn};
+
+/// 订阅全部事件类型时使用的通配类型
+pub const ALL_EVENTS: &str = "*";
+
+/// 业务事件处理器
+pub type EventHandler = Arc<
+    dyn Fn(AppHandle, Arc<InboundEvent>) -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync,
+>;
+
+/// 全局消息处理器，其他模块可以通过它订阅服务端推送的事件
+static MESSAGE_PROCESSOR: OnceLock<RwLock<MessageProcessor>> = OnceLock::new();
+
+/// 获取全局消息处理器
+pub fn get_message_processor() -> &'static RwLock<MessageProcessor> {
+    MESSAGE_PROCESSOR.get_or_init(|| RwLock::new(MessageProcessor::default()))
+}
 
 /// 消息处理器
-/// 负责处理不同类型的 WebSocket 消息
+/// 负责把 WebSocket 消息解析为 [`InboundEvent`] 并分发给已注册的处理器
 pub struct MessageProcessor {
-    message_handlers: HashMap<String, Box<dyn Fn(&Value) + Send + Sync>>,
+    /// 事件类型 -> (处理器名称, 处理器)，按注册顺序执行
+    message_handlers: HashMap<String, Vec<(String, EventHandler)>>,
 }
 
 impl MessageProcessor {
@@ -15,44 +45,48 @@ impl MessageProcessor {
         }
     }
 
-    /// 注册消息处理器
-    pub fn register_handler<F>(&mut self, message_type: String, handler: F)
-    where
-        F: Fn(&Value) + Send + Sync + 'static,
+    /// 注册事件处理器
+    /// `event_type` 为服务端的 `type` 字段，传入 [`ALL_EVENTS`] 订阅全部事件；
+    /// 同一事件类型下名称相同的处理器会被替换
+    pub fn register_handler<F, Fut>(
+        &mut self,
+        event_type: impl Into<String>,
+        name: impl Into<String>,
+        handler: F,
+    ) where
+        F: Fn(AppHandle, Arc<InboundEvent>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
     {
-        self.message_handlers
-            .insert(message_type, Box::new(handler));
-    }
-
-    /// 处理接收到的消息
-    pub fn process_message(&self, message: &Value) -> ProcessResult {
-        // 尝试解析消息类型
-        if let Some(msg_type) = self.extract_message_type(message) {
-            debug!("Processing message type: {}", msg_type);
+        let name = name.into();
+        let handler: EventHandler = Arc::new(move |app_handle, event| {
+            Box::pin(handler(app_handle, event)) as BoxFuture<'static, anyhow::Result<()>>
+        });
 
-            // 查找对应的处理器
-            if let Some(handler) = self.message_handlers.get(&msg_type) {
-                handler(message);
-                return ProcessResult::Handled;
-            } else {
-                debug!("No handler found for message type {}", msg_type);
-            }
+        let handlers = self.message_handlers.entry(event_type.into()).or_default();
+        match handlers.iter_mut().find(|(n, _)| *n == name) {
+            Some(entry) => entry.1 = handler,
+            None => handlers.push((name, handler)),
         }
+    }
 
-        ProcessResult::Unhandled
+    /// 按名称移除事件处理器，返回是否有处理器被移除
+    pub fn unregister_handler(&mut self, event_type: &str, name: &str) -> bool {
+        let Some(handlers) = self.message_handlers.get_mut(event_type) else {
+            return false;
+        };
+        let before = handlers.len();
+        handlers.retain(|(n, _)| n != name);
+        before != handlers.len()
     }
 
-    /// 提取消息类型
-    fn extract_message_type(&self, message: &Value) -> Option<String> {
-        message.get("type").and_then(|t| {
-            if let Some(s) = t.as_str() {
-                Some(s.to_string())
-            } else if let Some(n) = t.as_u64() {
-                Some(n.to_string())
-            } else {
-                None
-            }
-        })
+    /// 获取事件的处理器：先执行该类型的处理器，再执行订阅全部事件的处理器
+    pub fn handlers_for(&self, event_type: &str) -> Vec<(String, EventHandler)> {
+        [event_type, ALL_EVENTS]
+            .iter()
+            .filter_map(|t| self.message_handlers.get(*t))
+            .flatten()
+            .cloned()
+            .collect()
     }
 
     /// 验证消息格式
@@ -96,43 +130,151 @@ impl Default for MessageProcessor {
 impl MessageProcessor {
     /// 注册默认的消息处理器
     fn register_default_handlers(&mut self) {
-        // 登录相关消息
-        self.register_handler("1".to_string(), |msg| {
-            debug!("Processing login-related message: {:?}", msg);
+        // 以下事件先写入本地数据库，保证前端窗口未打开时本地数据也不会落后
+        self.register_handler(
+            "receiveMessage",
+            "persist",
+            |app_handle, event| async move {
+                match event.data() {
+                    Some(data) => save_received_message(&app_handle, data).await,
+                    None => Ok(()),
+                }
+                .map_err(|e| anyhow::anyhow!(e))
+            },
+        );
+        self.register_handler("msgRecall", "persist", |app_handle, event| async move {
+            match event.data() {
+                Some(data) => apply_message_recall(&app_handle, data).await,
+                None => Ok(()),
+            }
+            .map_err(|e| anyhow::anyhow!(e))
         });
-
-        // 心跳消息
-        self.register_handler("2".to_string(), |_msg| {
-            debug!("Received heartbeat message");
+        self.register_handler("msgMarkItem", "persist", |app_handle, event| async move {
+            match event.data() {
+                Some(data) => apply_message_mark_event(&app_handle, data).await,
+                None => Ok(()),
+            }
+            .map_err(|e| anyhow::anyhow!(e))
         });
-
-        self.register_handler("3".to_string(), |_msg| {
-            debug!("Received heartbeat response");
+        self.register_handler("memberChange", "persist", |app_handle, event| async move {
+            match event.data() {
+                Some(data) => apply_member_change(&app_handle, data).await,
+                None => Ok(()),
+            }
+            .map_err(|e| anyhow::anyhow!(e))
         });
+        self.register_handler(
+            "roomInfoChange",
+            "persist",
+            |app_handle, event| async move {
+                match event.data() {
+                    Some(data) => apply_room_info_change(&app_handle, data).await,
+                    None => Ok(()),
+                }
+                .map_err(|e| anyhow::anyhow!(e))
+            },
+        );
+        self.register_handler(
+            "roomDissolution",
+            "persist",
+            |app_handle, event| async move {
+                match event.data() {
+                    Some(data) => apply_room_dissolution(&app_handle, data).await,
+                    None => Ok(()),
+                }
+                .map_err(|e| anyhow::anyhow!(e))
+            },
+        );
 
-        // 普通聊天消息
-        self.register_handler("RECEIVE_MESSAGE".to_string(), |msg| {
-            debug!("Received chat message: {:?}", msg);
-        });
+        // 收到聊天消息后回执 ACK TODO 暂时只实现聊天消息的ack
+        self.register_handler("receiveMessage", "ack", |_app_handle, event| async move {
+            let Some(message_id) = event
+                .data()
+                .and_then(|data| data.get("message"))
+                .and_then(|m| m.get("id"))
+                .and_then(|id| id.as_str())
+            else {
+                return Ok(());
+            };
 
-        // 用户状态变化
-        self.register_handler("USER_STATE_CHANGE".to_string(), |msg| {
-            debug!("User status changed: {:?}", msg);
+            info!("回执 ACK: {}", message_id);
+            let client_guard = get_websocket_client_container().read().await;
+            let Some(client) = client_guard.as_ref() else {
+                error!(" 回执失败");
+                return Ok(());
+            };
+            client.send_ack(message_id).await?;
+            info!("ACK sent successfully for message {}", message_id);
+            Ok(())
         });
 
-        // 视频通话相关
-        self.register_handler("VideoCallRequest".to_string(), |msg| {
-            debug!("Received video call request: {:?}", msg);
+        // 所有事件转发到前端，最后执行，保证前端收到时其他处理器已完成
+        self.register_handler(ALL_EVENTS, "frontend", |app_handle, event| async move {
+            let (name, home_only) = event.frontend_event();
+            if home_only {
+                app_handle.emit_to("home", name, event.data())?;
+            } else {
+                app_handle.emit(name, event.data())?;
+            }
+            Ok(())
         });
+    }
+}
 
-        self.register_handler("CallAccepted".to_string(), |msg| {
-            debug!("Call accepted: {:?}", msg);
-        });
+/// 解析并分发服务端推送的业务消息
+pub async fn dispatch_inbound_message(message: &Value, app_handle: &AppHandle) -> ProcessResult {
+    let event = match InboundEvent::from_value(message) {
+        Ok(event) => Arc::new(event),
+        Err(diagnostic) => {
+            let processor = get_message_processor().read().await;
+            let diagnostic = InboundEventDiagnostic {
+                message: processor.sanitize_message(diagnostic.message),
+                ..diagnostic
+            };
+            warn!(
+                "Received unrecognized message: reason={:?}, type={:?}, fields={:?}, detail={}",
+                diagnostic.reason, diagnostic.message_type, diagnostic.fields, diagnostic.detail
+            );
+            let _ = app_handle.emit("ws-unknown-message", &diagnostic);
+            return ProcessResult::Unrecognized(diagnostic);
+        }
+    };
 
-        self.register_handler("CallRejected".to_string(), |msg| {
-            debug!(" Call rejected: {:?}", msg);
-        });
+    let event_type = event.event_type();
+    debug!("Processing business message type: {}", event_type);
+
+    // 先复制处理器列表再执行，避免处理器执行期间持有锁
+    let handlers = get_message_processor()
+        .read()
+        .await
+        .handlers_for(event_type);
+    if handlers.is_empty() {
+        debug!("No handler found for message type {}", event_type);
+        return ProcessResult::Unhandled;
     }
+
+    let mut persisted = true;
+    for (name, handler) in handlers {
+        // 写入本地数据库失败时不回执 ACK，由服务端重新投递
+        if name == "ack" && !persisted {
+            warn!(
+                "Skip ACK for message type {} because it was not persisted",
+                event_type
+            );
+            continue;
+        }
+        if let Err(e) = handler(app_handle.clone(), event.clone()).await {
+            error!(
+                "Handler '{}' failed to process message type {}: {}",
+                name, event_type, e
+            );
+            if name == "persist" {
+                persisted = false;
+            }
+        }
+    }
+
+    ProcessResult::Handled
 }
 
 /// 消息处理结果
@@ -140,6 +282,8 @@ impl MessageProcessor {
 pub enum ProcessResult {
     Handled,
     Unhandled,
+    /// 无法识别的消息
+    Unrecognized(InboundEventDiagnostic),
 }
 
 /// 消息验证结果
@@ -152,6 +296,7 @@ pub enum ValidationResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::websocket::types::DiagnosticReason;
     use serde_json::json;
 
     #[test]
@@ -201,4 +346,62 @@ mod tests {
         assert_eq!(sanitized["token"], "***");
         assert_eq!(sanitized["data"], "normal data");
     }
+
+    #[test]
+    fn test_inbound_event_parsing() {
+        let event = InboundEvent::from_value(&json!({
+            "type": "receiveMessage",
+            "data": { "message": { "id": "1" } }
+        }))
+        .unwrap();
+        assert_eq!(event.event_type(), "receiveMessage");
+        assert_eq!(event.data().unwrap()["message"]["id"], "1");
+        assert_eq!(event.frontend_event(), ("ws-receive-message", true));
+
+        // 无负载的事件
+        let event = InboundEvent::from_value(&json!({ "type": "DROPPED" })).unwrap();
+        assert_eq!(event.event_type(), "DROPPED");
+        assert!(event.data().is_none());
+
+        let diagnostic = InboundEvent::from_value(&json!({
+            "type": "somethingNew",
+            "data": { "a": 1 }
+        }))
+        .unwrap_err();
+        assert_eq!(diagnostic.reason, DiagnosticReason::UnknownEvent);
+        assert_eq!(diagnostic.message_type.as_deref(), Some("somethingNew"));
+        assert!(diagnostic.fields.contains(&"data".to_string()));
+
+        let diagnostic = InboundEvent::from_value(&json!({ "type": 15 })).unwrap_err();
+        assert_eq!(diagnostic.reason, DiagnosticReason::InvalidField);
+
+        let diagnostic = InboundEvent::from_value(&json!({ "data": {} })).unwrap_err();
+        assert_eq!(diagnostic.reason, DiagnosticReason::MissingField);
+    }
+
+    #[test]
+    fn test_handler_registry() {
+        let mut processor = MessageProcessor::new();
+        processor.register_handler(ALL_EVENTS, "all", |_, _| async { Ok(()) });
+        processor.register_handler("msgRecall", "first", |_, _| async { Ok(()) });
+        processor.register_handler("msgRecall", "second", |_, _| async { Ok(()) });
+        // 同名处理器替换而不是追加
+        processor.register_handler("msgRecall", "first", |_, _| async { Ok(()) });
+
+        let names = |p: &MessageProcessor, t: &str| {
+            p.handlers_for(t)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&processor, "msgRecall"),
+            vec!["first", "second", "all"]
+        );
+        assert_eq!(names(&processor, "online"), vec!["all"]);
+
+        assert!(processor.unregister_handler("msgRecall", "first"));
+        assert!(!processor.unregister_handler("msgRecall", "first"));
+        assert_eq!(names(&processor, "msgRecall"), vec!["second", "all"]);
+    }
 }
@@ -6,5 +6,5 @@ pub mod message;
 pub mod types;
 
 pub use client::WebSocketClient;
-pub use message::MessageProcessor;
+pub use message::{ALL_EVENTS, MessageProcessor, get_message_processor};
 pub use types::*;
@@ -92,6 +92,332 @@ pub enum WebSocketEvent {
         message: String,
         details: Option<HashMap<String, serde_json::Value>>,
     },
+    /// 离线发件箱中单条消息的投递进度
+    OutboxProgress {
+        client_id: String,
+        status: OutboxStatus,
+        retry_count: i32,
+        /// 发件箱中剩余待投递的消息数量
+        remaining: u64,
+    },
+}
+
+/// 离线发件箱消息状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OutboxStatus {
+    /// 已写入发件箱，等待连接恢复
+    Queued,
+    /// 已投递
+    Sent,
+    /// 投递失败，等待下次连接时重试
+    Retrying,
+    /// 超过最大重试次数，已放弃
+    Failed,
+    /// 超过有效期，已放弃
+    Expired,
+}
+
+/// 服务端推送的业务事件，按 `type` 字段区分，`data` 为事件负载
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "camelCase")]
+pub enum InboundEvent {
+    // 登录相关
+    /// 获取登录二维码
+    LoginQrCode(Option<serde_json::Value>),
+    /// 等待扫码授权
+    WaitingAuthorize(Option<serde_json::Value>),
+    /// 登录成功
+    LoginSuccess(Option<serde_json::Value>),
+
+    // 消息相关
+    /// 收到新消息
+    ReceiveMessage(Option<serde_json::Value>),
+    /// 消息撤回
+    MsgRecall(Option<serde_json::Value>),
+    /// 消息点赞/点踩
+    MsgMarkItem(Option<serde_json::Value>),
+
+    // 用户状态相关
+    /// 用户上线
+    Online(Option<serde_json::Value>),
+    /// 用户下线
+    Offline(Option<serde_json::Value>),
+    /// 用户状态变化
+    UserStateChange(Option<serde_json::Value>),
+
+    // 通知总线
+    /// 通知事件
+    NotifyEvent(Option<serde_json::Value>),
+    /// 设置群管理员
+    GroupSetAdmin(Option<serde_json::Value>),
+
+    // 好友相关
+    /// 新的好友/入群申请
+    NewApply(Option<serde_json::Value>),
+    /// 好友申请已通过
+    RequestApprovalFriend(Option<serde_json::Value>),
+    /// 群成员变动
+    MemberChange(Option<serde_json::Value>),
+    /// 删除好友
+    DeleteFriend(Option<serde_json::Value>),
+
+    // 房间/群聊相关
+    /// 房间信息变更
+    RoomInfoChange(Option<serde_json::Value>),
+    /// 我在房间内的信息变更
+    MyRoomInfoChange(Option<serde_json::Value>),
+    /// 发布群公告
+    RoomGroupNoticeMsg(Option<serde_json::Value>),
+    /// 编辑群公告
+    RoomEditGroupNoticeMsg(Option<serde_json::Value>),
+    /// 群聊解散
+    RoomDissolution(Option<serde_json::Value>),
+
+    // 视频通话相关
+    /// 通话请求
+    #[serde(rename = "VideoCallRequest")]
+    VideoCallRequest(Option<serde_json::Value>),
+    /// 通话已接听
+    #[serde(rename = "CallAccepted")]
+    CallAccepted(Option<serde_json::Value>),
+    /// 通话被拒绝
+    #[serde(rename = "CallRejected")]
+    CallRejected(Option<serde_json::Value>),
+    /// 通话房间关闭
+    #[serde(rename = "RoomClosed")]
+    RoomClosed(Option<serde_json::Value>),
+    /// 信令消息
+    #[serde(rename = "WEBRTC_SIGNAL")]
+    WebrtcSignal(Option<serde_json::Value>),
+    /// 用户加入通话
+    #[serde(rename = "JoinVideo")]
+    JoinVideo(Option<serde_json::Value>),
+    /// 用户离开通话
+    #[serde(rename = "LeaveVideo")]
+    LeaveVideo(Option<serde_json::Value>),
+    /// 通话中断
+    #[serde(rename = "DROPPED")]
+    Dropped(Option<serde_json::Value>),
+    /// 通话取消
+    #[serde(rename = "CANCEL")]
+    Cancel(Option<serde_json::Value>),
+    /// 通话超时
+    #[serde(rename = "TIMEOUT")]
+    Timeout(Option<serde_json::Value>),
+
+    // 系统相关
+    /// 登录凭证过期
+    TokenExpired(Option<serde_json::Value>),
+    /// 无效用户
+    InvalidUser(Option<serde_json::Value>),
+
+    // 朋友圈相关
+    /// 朋友圈新动态
+    FeedSendMsg(Option<serde_json::Value>),
+    /// 朋友圈点赞/评论通知
+    FeedNotify(Option<serde_json::Value>),
+}
+
+impl InboundEvent {
+    /// 从原始 JSON 消息解析业务事件，无法识别时返回诊断信息
+    pub fn from_value(message: &serde_json::Value) -> Result<Self, InboundEventDiagnostic> {
+        let message_type = match message.get("type") {
+            Some(serde_json::Value::String(t)) => t.clone(),
+            Some(other) => {
+                return Err(InboundEventDiagnostic::new(
+                    DiagnosticReason::InvalidField,
+                    Some(other.to_string()),
+                    message,
+                    format!("'type' must be a string, got {}", other),
+                ));
+            }
+            None => {
+                return Err(InboundEventDiagnostic::new(
+                    DiagnosticReason::MissingField,
+                    None,
+                    message,
+                    "message has no 'type' field".to_string(),
+                ));
+            }
+        };
+
+        // 负载统一为 Option<Value>，类型合法时解析失败只可能是未知的事件类型
+        Self::deserialize(message).map_err(|_| {
+            InboundEventDiagnostic::new(
+                DiagnosticReason::UnknownEvent,
+                Some(message_type.clone()),
+                message,
+                format!("unknown event type '{}'", message_type),
+            )
+        })
+    }
+
+    /// 事件类型，与服务端下发的 `type` 字段一致
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            Self::LoginQrCode(_) => "loginQrCode",
+            Self::WaitingAuthorize(_) => "waitingAuthorize",
+            Self::LoginSuccess(_) => "loginSuccess",
+            Self::ReceiveMessage(_) => "receiveMessage",
+            Self::MsgRecall(_) => "msgRecall",
+            Self::MsgMarkItem(_) => "msgMarkItem",
+            Self::Online(_) => "online",
+            Self::Offline(_) => "offline",
+            Self::UserStateChange(_) => "userStateChange",
+            Self::NotifyEvent(_) => "notifyEvent",
+            Self::GroupSetAdmin(_) => "groupSetAdmin",
+            Self::NewApply(_) => "newApply",
+            Self::RequestApprovalFriend(_) => "requestApprovalFriend",
+            Self::MemberChange(_) => "memberChange",
+            Self::DeleteFriend(_) => "deleteFriend",
+            Self::RoomInfoChange(_) => "roomInfoChange",
+            Self::MyRoomInfoChange(_) => "myRoomInfoChange",
+            Self::RoomGroupNoticeMsg(_) => "roomGroupNoticeMsg",
+            Self::RoomEditGroupNoticeMsg(_) => "roomEditGroupNoticeMsg",
+            Self::RoomDissolution(_) => "roomDissolution",
+            Self::VideoCallRequest(_) => "VideoCallRequest",
+            Self::CallAccepted(_) => "CallAccepted",
+            Self::CallRejected(_) => "CallRejected",
+            Self::RoomClosed(_) => "RoomClosed",
+            Self::WebrtcSignal(_) => "WEBRTC_SIGNAL",
+            Self::JoinVideo(_) => "JoinVideo",
+            Self::LeaveVideo(_) => "LeaveVideo",
+            Self::Dropped(_) => "DROPPED",
+            Self::Cancel(_) => "CANCEL",
+            Self::Timeout(_) => "TIMEOUT",
+            Self::TokenExpired(_) => "tokenExpired",
+            Self::InvalidUser(_) => "invalidUser",
+            Self::FeedSendMsg(_) => "feedSendMsg",
+            Self::FeedNotify(_) => "feedNotify",
+        }
+    }
+
+    /// 事件负载
+    pub fn data(&self) -> Option<&serde_json::Value> {
+        match self {
+            Self::LoginQrCode(data)
+            | Self::WaitingAuthorize(data)
+            | Self::LoginSuccess(data)
+            | Self::ReceiveMessage(data)
+            | Self::MsgRecall(data)
+            | Self::MsgMarkItem(data)
+            | Self::Online(data)
+            | Self::Offline(data)
+            | Self::UserStateChange(data)
+            | Self::NotifyEvent(data)
+            | Self::GroupSetAdmin(data)
+            | Self::NewApply(data)
+            | Self::RequestApprovalFriend(data)
+            | Self::MemberChange(data)
+            | Self::DeleteFriend(data)
+            | Self::RoomInfoChange(data)
+            | Self::MyRoomInfoChange(data)
+            | Self::RoomGroupNoticeMsg(data)
+            | Self::RoomEditGroupNoticeMsg(data)
+            | Self::RoomDissolution(data)
+            | Self::VideoCallRequest(data)
+            | Self::CallAccepted(data)
+            | Self::CallRejected(data)
+            | Self::RoomClosed(data)
+            | Self::WebrtcSignal(data)
+            | Self::JoinVideo(data)
+            | Self::LeaveVideo(data)
+            | Self::Dropped(data)
+            | Self::Cancel(data)
+            | Self::Timeout(data)
+            | Self::TokenExpired(data)
+            | Self::InvalidUser(data)
+            | Self::FeedSendMsg(data)
+            | Self::FeedNotify(data) => data.as_ref(),
+        }
+    }
+
+    /// 转发给前端的事件名以及是否只发送到 home 窗口
+    pub fn frontend_event(&self) -> (&'static str, bool) {
+        match self {
+            Self::LoginQrCode(_) => ("ws-login-qr-code", false),
+            Self::WaitingAuthorize(_) => ("ws-waiting-authorize", false),
+            Self::LoginSuccess(_) => ("ws-login-success", true),
+            Self::ReceiveMessage(_) => ("ws-receive-message", true),
+            Self::MsgRecall(_) => ("ws-msg-recall", true),
+            Self::MsgMarkItem(_) => ("ws-msg-mark-item", true),
+            Self::Online(_) => ("ws-online", true),
+            Self::Offline(_) => ("ws-offline", true),
+            Self::UserStateChange(_) => ("ws-user-state-change", true),
+            Self::NotifyEvent(_) => ("ws-request-notify-event", true),
+            Self::GroupSetAdmin(_) => ("ws-group-set-admin-success", true),
+            Self::NewApply(_) => ("ws-request-new-apply", true),
+            Self::RequestApprovalFriend(_) => ("ws-request-approval-friend", true),
+            Self::MemberChange(_) => ("ws-member-change", true),
+            Self::DeleteFriend(_) => ("ws-delete-friend", false),
+            Self::RoomInfoChange(_) => ("ws-room-info-change", true),
+            Self::MyRoomInfoChange(_) => ("ws-my-room-info-change", true),
+            Self::RoomGroupNoticeMsg(_) => ("ws-room-group-notice-msg", true),
+            Self::RoomEditGroupNoticeMsg(_) => ("ws-room-edit-group-notice-msg", true),
+            Self::RoomDissolution(_) => ("ws-room-dissolution", true),
+            Self::VideoCallRequest(_) => ("ws-video-call-request", false),
+            Self::CallAccepted(_) => ("ws-call-accepted", false),
+            Self::CallRejected(_) => ("ws-call-rejected", false),
+            Self::RoomClosed(_) => ("ws-room-closed", false),
+            Self::WebrtcSignal(_) => ("ws-webrtc-signal", false),
+            Self::JoinVideo(_) => ("ws-join-video", false),
+            Self::LeaveVideo(_) => ("ws-leave-video", false),
+            Self::Dropped(_) => ("ws-dropped", false),
+            Self::Cancel(_) => ("ws-cancel", false),
+            Self::Timeout(_) => ("ws-timeout", false),
+            Self::TokenExpired(_) => ("ws-token-expired", false),
+            Self::InvalidUser(_) => ("ws-invalid-user", false),
+            Self::FeedSendMsg(_) => ("ws-feed-send-msg", true),
+            Self::FeedNotify(_) => ("ws-feed-notify", true),
+        }
+    }
+}
+
+/// 无法识别的服务端消息的诊断原因
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DiagnosticReason {
+    /// 缺少 `type` 字段
+    MissingField,
+    /// `type` 字段不是字符串
+    InvalidField,
+    /// 未知的事件类型
+    UnknownEvent,
+}
+
+/// 无法识别的服务端消息诊断信息，通过 `ws-unknown-message` 事件发送到前端
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboundEventDiagnostic {
+    pub reason: DiagnosticReason,
+    pub message_type: Option<String>,
+    /// 消息中出现的顶层字段，便于排查协议变更
+    pub fields: Vec<String>,
+    pub detail: String,
+    pub message: serde_json::Value,
+}
+
+impl InboundEventDiagnostic {
+    fn new(
+        reason: DiagnosticReason,
+        message_type: Option<String>,
+        message: &serde_json::Value,
+        detail: String,
+    ) -> Self {
+        let fields = message
+            .as_object()
+            .map(|obj| obj.keys().cloned().collect())
+            .unwrap_or_default();
+
+        Self {
+            reason,
+            message_type,
+            fields,
+            detail,
+            message: message.clone(),
+        }
+    }
 }
 
 /// WebSocket 请求消息
@@ -135,13 +135,21 @@ class RustWebSocketClient {
   }
 
   /**
-   * 发送消息
+   * 发送消息，连接未就绪时消息会写入发件箱，返回 QUEUED
+   * @param data 消息体
+   * @param options.clientId 客户端消息ID，用于发件箱去重，默认取消息体中的 clientId
+   * @param options.ttlMs 发件箱中的有效期（毫秒）
    */
-  async sendMessage(data: any): Promise<void> {
+  async sendMessage(data: any, options?: { clientId?: string; ttlMs?: number }): Promise<'SENT' | 'QUEUED'> {
     try {
-      await invoke('ws_send_message', {
-        params: { data }
-      })
+      const res = await invoke<{ success: boolean; status: 'SENT' | 'QUEUED' }>('ws_send_message', {
+        params: {
+          data,
+          clientId: options?.clientId ?? data?.clientId ?? data?.data?.clientId,
+          ttlMs: options?.ttlMs
+        }
+      })
+      return res.status
     } catch (err: any) {
       error(`[RustWS] 发送消息失败: ${err}`)
       throw err
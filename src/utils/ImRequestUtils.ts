@@ -798,10 +798,30 @@ export async function feedCommentAll(params: { feedId: string }) {
  * SSE 流式数据事件类型
  */
 interface SseStreamEvent {
-  eventType: 'chunk' | 'done' | 'error'
+  eventType: 'chunk' | 'done' | 'error' | 'cancelled' | 'usage'
+  /** 服务端 SSE 的 event 字段，默认 message 事件为空 */
+  event?: string
+  /** 服务端 SSE 的事件 id */
+  id?: string
   data?: string
   error?: string
   requestId: string
+  /** 用量统计，仅 usage 事件携带 */
+  usage?: SseStreamUsage
+}
+
+/**
+ * 流式回答结束时的用量统计，作为最后一个事件发送
+ */
+export interface SseStreamUsage {
+  status: 'done' | 'cancelled' | 'error'
+  stopReason?: 'cancelled' | 'logout' | 'idleTimeout' | 'totalTimeout'
+  chunks: number
+  chars: number
+  durationMs: number
+  promptTokens?: number
+  completionTokens?: number
+  totalTokens?: number
 }
 
 /**
@@ -811,7 +831,10 @@ export interface StreamCallbacks {
   onChunk?: (chunk: string) => void
   onDone?: (fullContent: string) => void
   onError?: (error: string) => void
+  /** 回答被取消，参数为已收到的部分内容 */
+  onCancel?: (partialContent: string) => void
   onStart?: (requestId: string) => void
+  onUsage?: (usage: SseStreamUsage) => void
 }
 
 /**
@@ -823,7 +846,15 @@ export interface StreamCallbacks {
  * @returns Promise，在流结束后 resolve 完整内容
  */
 export async function messageSendStream(
-  body: { conversationId: string; content: string; useContext?: boolean; reasoningEnabled?: boolean },
+  body: {
+    conversationId: string
+    content: string
+    useContext?: boolean
+    reasoningEnabled?: boolean
+    /** 检索本地知识库作为参考资料 */
+    useKnowledge?: boolean
+    knowledgeTopK?: number
+  },
   callbacks?: StreamCallbacks
 ): Promise<string> {
   const { invoke, Channel } = await import('@tauri-apps/api/core')
@@ -871,6 +902,21 @@ export async function messageSendStream(
             reject(new Error(errorMsg))
           }
           break
+
+        case 'cancelled':
+          if (!isResolved) {
+            isResolved = true
+            const partialContent = data || fullContent
+            callbacks?.onCancel?.(partialContent)
+            resolve(partialContent)
+          }
+          break
+
+        case 'usage':
+          if (event.usage) {
+            callbacks?.onUsage?.(event.usage)
+          }
+          break
       }
     }
 
@@ -901,6 +947,14 @@ export async function messageCancelStream(requestId: string): Promise<void> {
   await invoke('ai_message_cancel_stream', { requestId })
 }
 
+/**
+ * 保存 OpenAI 兼容接口的 API Key，传空时清除
+ */
+export async function saveAiApiKey(apiKey?: string): Promise<void> {
+  const { invoke } = await import('@tauri-apps/api/core')
+  await invoke('save_ai_api_key', { apiKey: apiKey || null })
+}
+
 // 获得指定对话的消息列表
 export async function messageListByConversationId(params: {
   conversationId: string